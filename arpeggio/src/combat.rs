@@ -10,13 +10,6 @@ use crate::{
   types::*,
 };
 
-use std::marker::PhantomData;
-
-/// This is set to 1.5 so that it's greater than sqrt(2) -- meaning that creatures can attack
-/// diagonally!
-pub const MELEE_RANGE: u32units::Length =
-  u32units::Length { dimension: PhantomData, units: PhantomData, value: 150 };
-
 impl<'game> DynamicCombat<'game> {
   pub fn remove_from_combat(&self, cid: CreatureID) -> Result<Option<Combat>, GameError> {
     self.combat.remove_from_combat(cid)
@@ -30,8 +23,7 @@ impl<'game> DynamicCombat<'game> {
       }
       CombatLog::EndTurn { ref creature_id } => {
         assert_eq!(*creature_id, new.current_creature_id());
-        new.creatures.next_circular();
-        new.movement_used = Zero::zero();
+        new.next_turn();
       }
       CombatLog::RerollInitiative { ref combatants } => {
         if new.creatures.get_cursor() != 0 {
@@ -47,12 +39,13 @@ impl<'game> DynamicCombat<'game> {
         new.creatures = creatures_with_inits;
         new.creatures.set_cursor(cursor);
       }
-      CombatLog::ForceNextTurn => {
-        new.movement_used = Zero::zero();
-        new.creatures.next_circular();
-      }
+      CombatLog::ForceNextTurn => new.next_turn(),
       CombatLog::ForcePrevTurn => {
         new.movement_used = Zero::zero();
+        new.movement_trail.clear();
+        if new.creatures.get_cursor() == 0 {
+          new.round = new.round.saturating_sub(1).max(1);
+        }
         new.creatures.prev_circular();
       }
     }
@@ -71,7 +64,9 @@ impl<'game> DynamicCombat<'game> {
     Ok(self.game.tile_system.get_all_accessible(
       self.current_pos()?,
       &self.scene.terrain,
+      &self.scene.terrain_costs,
       Volume::AABB(current.creature.size),
+      current.creature.movement_mode,
       current_speed,
     ))
   }
@@ -88,6 +83,31 @@ impl<'game> DynamicCombat<'game> {
     }
   }
 
+  /// How far the current creature has moved this turn, and how much further it can move.
+  pub fn movement(&self) -> Result<CombatMovement, GameError> {
+    let movement_used = self.combat.movement_used;
+    let movement_left = self.current_creature()?.speed().saturating_sub(movement_used);
+    Ok(CombatMovement { movement_used, movement_left })
+  }
+
+  pub fn summary(&self) -> Result<CombatSummary, GameError> {
+    let combatants = self
+      .combat
+      .creatures
+      .iter()
+      .map(|&(creature_id, initiative)| {
+        let name = self.game.get_creature(creature_id)?.creature.name.clone();
+        Ok(Combatant { creature_id, name, initiative })
+      })
+      .collect::<Result<Vec<Combatant>, GameError>>()?;
+    Ok(CombatSummary {
+      scene_id: self.scene.id,
+      current_creature: self.combat.current_creature_id(),
+      combatants,
+      movement_trail: self.combat.movement_trail.clone(),
+    })
+  }
+
   pub fn reroll_initiative(&self) -> Result<ChangedCombat<'game>, GameError> {
     let cids = self.combat.creature_ids();
     let combatants = Combat::roll_initiative(self.game, cids)?;
@@ -131,6 +151,15 @@ pub trait CombatExt {
 
   fn current_creature_id(&self) -> CreatureID;
 
+  /// Move on to the next creature's turn, starting a new round if the turn order wraps around.
+  fn next_turn(&mut self);
+
+  /// Add a path that the current creature moved along to its `movement_trail`.
+  fn record_movement(&mut self, path: &[Point3]);
+
+  /// The initiative value a combatant is ordered by.
+  fn initiative_of(&self, cid: CreatureID) -> Result<i16, GameError>;
+
   fn contains_creature(&self, cid: CreatureID) -> bool;
 
   /// the Option<Combat> will be None if you're removing the last creature from a combat.
@@ -139,7 +168,13 @@ pub trait CombatExt {
 
 impl CombatExt for Combat {
   fn new(scene: SceneID, combatants: Vec<(CreatureID, i16)>) -> Result<Combat, GameError> {
-    Ok(Combat { scene, movement_used: Zero::zero(), creatures: sort_combatants(combatants)? })
+    Ok(Combat {
+      scene,
+      movement_used: Zero::zero(),
+      movement_trail: vec![],
+      creatures: sort_combatants(combatants)?,
+      round: 1,
+    })
   }
 
   fn creature_ids(&self) -> Vec<CreatureID> { self.creatures.iter().map(|&(c, _)| c).collect() }
@@ -158,6 +193,33 @@ impl CombatExt for Combat {
 
   fn current_creature_id(&self) -> CreatureID { self.creatures.get_current().0 }
 
+  fn next_turn(&mut self) {
+    self.movement_used = Zero::zero();
+    self.movement_trail.clear();
+    self.creatures.next_circular();
+    if self.creatures.get_cursor() == 0 {
+      self.round += 1;
+    }
+  }
+
+  fn record_movement(&mut self, path: &[Point3]) {
+    // Each path starts where the last one ended, and that cell only goes in the trail once.
+    let skip = match (self.movement_trail.last(), path.first()) {
+      (Some(last), Some(first)) if last == first => 1,
+      _ => 0,
+    };
+    self.movement_trail.extend(path.iter().skip(skip).cloned());
+  }
+
+  fn initiative_of(&self, cid: CreatureID) -> Result<i16, GameError> {
+    self
+      .creatures
+      .iter()
+      .find(|&&(c, _)| c == cid)
+      .map(|&(_, initiative)| initiative)
+      .ok_or_else(|| GameError::CreatureNotFound(cid.to_string()))
+  }
+
   fn contains_creature(&self, cid: CreatureID) -> bool {
     self.creatures.iter().any(|&(c, _)| c == cid)
   }
@@ -227,6 +289,7 @@ impl<'game> ChangedCombat<'game> {
 
 #[cfg(test)]
 pub mod test {
+  use maplit::hashset;
 
   use crate::{combat::*, game::test::*, types::test::*};
 
@@ -248,6 +311,64 @@ pub mod test {
     perf(game, GMCommand::CombatAct { ability_id, target })
   }
 
+  fn t_kill(game: &mut Game, creature_id: CreatureID) {
+    game.creatures.mutate(&creature_id, |c| {
+      c.conditions.insert(ConditionID(uuid_5()), app_cond(Condition::Dead, Duration::Interminate));
+    });
+  }
+
+  fn t_current(game: &Game) -> CreatureID {
+    game.get_combat().unwrap().combat.current_creature_id()
+  }
+
+  #[test]
+  fn end_turn_skips_dead_creatures() {
+    let mut game = t_combat();
+    t_kill(&mut game, cid_ranger());
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(t_current(&game), cid_cleric());
+  }
+
+  #[test]
+  fn end_turn_wraps_past_multiple_dead_creatures() {
+    let mut game = t_combat();
+    t_kill(&mut game, cid_ranger());
+    t_kill(&mut game, cid_cleric());
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(t_current(&game), cid_rogue());
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(t_current(&game), cid_rogue());
+  }
+
+  #[test]
+  fn rounds_count_wraps_of_the_turn_order() {
+    let round = |game: &Game| game.get_combat().unwrap().combat.round;
+    let mut game = t_combat();
+    assert_eq!(round(&game), 1);
+    for _ in 0..3 {
+      game = t_perform(&game, GMCommand::EndTurn);
+    }
+    assert_eq!(round(&game), 2);
+    let game = t_perform(&game, GMCommand::ForcePrevTurn);
+    assert_eq!(round(&game), 1);
+    let game = t_perform(&game, GMCommand::ForceNextTurn);
+    assert_eq!(round(&game), 2);
+    // There's no round before the first.
+    let game = t_perform(&t_combat(), GMCommand::ForcePrevTurn);
+    assert_eq!(round(&game), 1);
+  }
+
+  /// Even when nobody can act, ending a turn still advances the combat by at most a round.
+  #[test]
+  fn end_turn_with_everyone_dead() {
+    let mut game = t_combat();
+    for cid in [cid_rogue(), cid_ranger(), cid_cleric()] {
+      t_kill(&mut game, cid);
+    }
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(t_current(&game), cid_rogue());
+  }
+
   /// Try to melee-atack the ranger when the ranger is out of melee range.
   #[test]
   fn target_melee_out_of_range() {
@@ -295,6 +416,7 @@ pub mod test {
       name: "MultiEffect".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::Melee,
 
@@ -303,6 +425,8 @@ pub mod test {
           CreatureEffect::ApplyCondition(Duration::Interminate, Condition::Dead),
         ]),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     };
     game.abilities.insert(ab);
     game.classes.mutate(&classid_rogue(), |r| r.abilities.push(abid_multi));
@@ -382,17 +506,51 @@ pub mod test {
     }
   }
 
+  /// A dash lets the current creature move further this turn, but not on its next turn.
+  #[test]
+  fn dash_grants_movement_for_the_turn() {
+    let mut game = t_combat();
+    let abid_dash: AbilityID = "00000000-0000-0000-0000-000000000009".parse().unwrap();
+    game.abilities.insert(Ability {
+      id: abid_dash,
+      name: "Dash".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Action,
+      action: Action::Creature {
+        target: CreatureTarget::Actor,
+        effect: CreatureEffect::GrantMovement(u32cm(500)),
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    });
+    game.classes.mutate(&classid_rogue(), |r| r.abilities.push(abid_dash));
+    let far = Point3::new(1500, 0, 0);
+    assert!(!game.get_combat().unwrap().current_movement_options().unwrap().contains(&far));
+
+    let game = t_act(&game, abid_dash, DecidedTarget::Actor).unwrap().game;
+    assert!(game.get_combat().unwrap().current_movement_options().unwrap().contains(&far));
+    let game = t_perform(&game, GMCommand::PathCurrentCombatCreature { destination: far });
+    assert_eq!(game.get_combat().unwrap().combat.movement_used, u32cm(1500));
+
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(rogue.speed(), rogue.creature.speed);
+  }
+
   /// The length of the path is deducted from combat.movement_used after PathCurrentCreature.
   #[test]
   fn move_honors_path() {
     let mut game = t_combat();
-    let terrain = vec![
+    let terrain = hashset! {
       Point3::new(0, 0, 0),
       Point3::new(0, 100, 0),
       Point3::new(100, 100, 0),
       Point3::new(200, 100, 0),
       Point3::new(200, 0, 0),
-    ];
+    };
     game.scenes.mutate(&t_scene_id(), move |s| s.terrain = terrain);
 
     let next_game = game
@@ -405,4 +563,81 @@ pub mod test {
       .game;
     assert_eq!(next_game.get_combat().unwrap().combat.movement_used, u32cm(400));
   }
+
+  #[test]
+  fn movement_trail_follows_paths_until_end_of_turn() {
+    let game = t_combat();
+    let path_to = |x, y| GMCommand::PathCurrentCombatCreature { destination: Point3::new(x, y, 0) };
+    let game = t_perform(&game, path_to(200, 0));
+    let game = t_perform(&game, path_to(200, 100));
+    let trail = game.get_combat().unwrap().summary().unwrap().movement_trail;
+    assert_eq!(
+      trail,
+      vec![
+        Point3::new(0, 0, 0),
+        Point3::new(100, 0, 0),
+        Point3::new(200, 0, 0),
+        Point3::new(200, 100, 0),
+      ]
+    );
+
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(game.get_combat().unwrap().summary().unwrap().movement_trail, vec![]);
+  }
+
+  /// The summary lists each combatant with the initiative it was given in the StartCombat log.
+  #[test]
+  fn summary_initiatives() {
+    let changed = perf(
+      &t_game(),
+      GMCommand::StartCombat {
+        scene_id: t_scene_id(),
+        combatants: vec![cid_cleric(), cid_rogue(), cid_ranger()],
+      },
+    )
+    .unwrap();
+    let mut combatants = match &changed.logs[..] {
+      [GameLog::StartCombat { combatants, .. }, GameLog::TurnBegan { .. }] => combatants.clone(),
+      logs => panic!("Unexpected logs: {:?}", logs),
+    };
+    combatants.sort_by_key(|&(_, init)| -init);
+
+    let combat = changed.game.get_combat().unwrap();
+    let summary = combat.summary().unwrap();
+    assert_eq!(summary.scene_id, t_scene_id());
+    assert_eq!(summary.current_creature, combatants[0].0);
+    let summarized: Vec<(CreatureID, i16)> =
+      summary.combatants.iter().map(|c| (c.creature_id, c.initiative)).collect();
+    assert_eq!(summarized, combatants);
+    for (cid, init) in combatants {
+      assert_eq!(combat.combat.initiative_of(cid), Ok(init));
+    }
+    let rogue = summary.combatants.iter().find(|c| c.creature_id == cid_rogue()).unwrap();
+    assert_eq!(rogue.name, "rogue");
+  }
+
+  #[test]
+  fn combat_order_survives_a_reload() {
+    let game = t_perform(&t_combat(), GMCommand::EndTurn);
+    let order = |game: &Game| game.current_combat.as_ref().unwrap().creatures.clone();
+    assert_eq!(order(&game).get_cursor(), 1);
+
+    // The order is stored as a list along with the current position in it, so nothing about it
+    // depends on how a map or set happens to be ordered.
+    let json = serde_json::to_value(&game).unwrap();
+    let creatures = &json["current_combat"]["creatures"];
+    assert_eq!(creatures["cursor"], 1);
+    let stored_ids: Vec<CreatureID> =
+      serde_json::from_value::<Vec<(CreatureID, i16)>>(creatures["data"].clone())
+        .unwrap()
+        .into_iter()
+        .map(|(cid, _)| cid)
+        .collect();
+    assert_eq!(stored_ids, vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+
+    let reloaded: Game = serde_json::from_value(json).unwrap();
+    assert_eq!(order(&reloaded), order(&game));
+    assert_eq!(t_current(&reloaded), t_current(&game));
+    assert_eq!(reloaded.current_combat, game.current_combat);
+  }
 }
@@ -1,6 +1,9 @@
 use std::{cmp, collections::HashMap};
 
 use num::Saturating;
+use serde_derive::Deserialize;
+use uom::si::length::centimeter;
+use uuid::Uuid;
 
 use crate::{game::GameExt, scene::SceneExt, types::*};
 use indexed::*;
@@ -18,6 +21,152 @@ use indexed::*;
 /// circular movement distance.
 const STANDARD_CREATURE_SPEED: u32 = 1086;
 
+/// How deeply effects may be nested in `MultiEffect`s and `RecurringEffect`s. Effects are authored
+/// by GMs, and without a limit a badly-built one could overflow the stack.
+const MAX_EFFECT_DEPTH: usize = 16;
+
+/// A simple format for importing creatures from external bestiary data. Only `name` is required:
+///
+/// ```json
+/// {
+///   "name": "Goblin",
+///   "hp": 7,
+///   "energy": 2,
+///   "speed": 9,
+///   "abilities": ["Punch"],
+///   "attributes": {"strength": "Skilled"},
+///   "initiative": {"Flat": {"value": 2}},
+///   "note": "Sneaky"
+/// }
+/// ```
+///
+/// `speed` is in meters, and abilities are referred to by their name in the game.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatBlock {
+  name: String,
+  hp: Option<u8>,
+  energy: Option<u8>,
+  speed: Option<u32>,
+  #[serde(default)]
+  abilities: Vec<String>,
+  #[serde(default)]
+  attributes: HashMap<String, SkillLevel>,
+  initiative: Option<Dice>,
+  #[serde(default)]
+  note: String,
+}
+
+/// Builds a `Creature` one property at a time; see `CreatureExt::builder`. Anything that isn't
+/// set gets the same default as in `Creature::create`. The default size is a single cell, and the
+/// default class is the nil ID, so most callers will want to set it.
+#[derive(Clone, Debug)]
+pub struct CreatureBuilder {
+  creature: Creature,
+  reach: Option<u32units::Length>,
+}
+
+impl CreatureBuilder {
+  pub fn id(mut self, id: CreatureID) -> Self {
+    self.creature.id = id;
+    self
+  }
+
+  pub fn class(mut self, class: ClassID) -> Self {
+    self.creature.class = class;
+    self
+  }
+
+  /// Give the creature these abilities, all off cooldown.
+  pub fn abilities(mut self, abilities: &[AbilityID]) -> Self {
+    for &ability_id in abilities {
+      self.creature.abilities.insert(AbilityStatus { ability_id, cooldown: 0 });
+    }
+    self
+  }
+
+  /// Set both the maximum and current health.
+  pub fn hp(mut self, hp: HP) -> Self {
+    self.creature.max_health = hp;
+    self.creature.cur_health = hp;
+    self
+  }
+
+  /// Set both the maximum and current energy.
+  pub fn energy(mut self, energy: Energy) -> Self {
+    self.creature.max_energy = energy;
+    self.creature.cur_energy = energy;
+    self
+  }
+
+  pub fn speed(mut self, speed: u32units::Length) -> Self {
+    self.creature.speed = speed;
+    self
+  }
+
+  /// Set the creature's size. Unless `reach` is also set, the reach follows from the size.
+  pub fn size(mut self, size: AABB) -> Self {
+    self.creature.size = size;
+    self
+  }
+
+  pub fn reach(mut self, reach: u32units::Length) -> Self {
+    self.reach = Some(reach);
+    self
+  }
+
+  pub fn movement_mode(mut self, movement_mode: MovementMode) -> Self {
+    self.creature.movement_mode = movement_mode;
+    self
+  }
+
+  /// Make the creature immune to these conditions.
+  pub fn immunities(mut self, immunities: &[Condition]) -> Self {
+    self.creature.immunities = immunities.to_vec();
+    self
+  }
+
+  /// Keep abilities in these categories from targeting the creature.
+  pub fn untargetable_by(mut self, categories: &[TargetCategory]) -> Self {
+    self.creature.untargetable_by = categories.to_vec();
+    self
+  }
+
+  pub fn attribute(mut self, attr: &str, level: SkillLevel) -> Self {
+    self.creature.attributes.insert(AttrID(attr.to_string()), level);
+    self
+  }
+
+  pub fn initiative(mut self, initiative: Dice) -> Self {
+    self.creature.initiative = initiative;
+    self
+  }
+
+  pub fn note(mut self, note: &str) -> Self {
+    self.creature.note = note.to_string();
+    self
+  }
+
+  pub fn portrait_url(mut self, url: &str) -> Self {
+    self.creature.portrait_url = url.to_string();
+    self
+  }
+
+  pub fn icon_url(mut self, url: &str) -> Self {
+    self.creature.icon_url = url.to_string();
+    self
+  }
+
+  pub fn build(self) -> Creature {
+    let reach = self.reach.unwrap_or_else(|| default_reach(&self.creature.size));
+    Creature { reach, ..self.creature }
+  }
+}
+
+/// Convert a dice roll to HP, clamping it to the range of `HP` instead of letting it wrap. A roll
+/// of 300 is 255 HP, not 44, and a negative roll is 0 HP.
+fn clamp_hp(amt: i32) -> HP { HP(amt.clamp(0, i32::from(u8::MAX)) as u8) }
+
 impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
   pub fn new(
     creature: &'creature Creature, game: &'game Game,
@@ -31,18 +180,51 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
 
   pub fn can_move(&self) -> bool { conditions_able(&self.all_conditions()) }
 
+  /// The creature's speed after all of its conditions. Bonuses are added up first, and then
+  /// `Hastened` and `Slowed` scale the total.
   pub fn speed(&self) -> u32units::Length {
     let mut speed = self.creature.speed;
+    let (mut hastened, mut slowed) = (1u32, 1u32);
     for acondition in self.all_conditions() {
-      if acondition.condition == Condition::DoubleMaxMovement {
-        speed += self.creature.speed;
+      match acondition.condition {
+        Condition::DoubleMaxMovement => speed += self.creature.speed,
+        Condition::ExtraMovement(distance) => speed += distance,
+        Condition::Hastened(factor) => hastened = hastened.saturating_mul(factor.max(1).into()),
+        Condition::Slowed(factor) => slowed = slowed.saturating_mul(factor.max(1).into()),
+        _ => {}
       }
     }
-    speed
+    u32cm(speed.get::<centimeter>().saturating_mul(hastened) / slowed)
+  }
+
+  pub fn reach(&self) -> u32units::Length { self.creature.reach }
+
+  /// The total of all `ReduceDamage` conditions on the creature.
+  pub fn damage_reduction(&self) -> HP {
+    self.all_conditions().iter().fold(HP(0), |total, ac| match ac.condition {
+      Condition::ReduceDamage(hp) => total.saturating_add(hp),
+      _ => total,
+    })
+  }
+
+  /// The highest defense provided by any of the creature's conditions, or 0.
+  pub fn defense(&self) -> u8 {
+    self
+      .all_conditions()
+      .iter()
+      .filter_map(|ac| match ac.condition {
+        Condition::ProvideDefense(defense) => Some(defense),
+        _ => None,
+      })
+      .max()
+      .unwrap_or(0)
   }
 
   /// Get all conditions applied to a creature, including permanent conditions associated with
   /// the creature's class and any volume-conditions from the current active scene.
+  ///
+  /// The conditions are sorted by their ID, so the result is always in the same order. Class
+  /// conditions have no ID, and come first in the order the class lists them.
   pub fn all_conditions(&self) -> Vec<AppliedCondition> {
     // TODO: DynamicCreature should have a `conditions` field, since calculating the conditions is
     // becoming more expensive, and we look up the conditions several times even just to serialize a
@@ -51,13 +233,14 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     // an update?
     // We could at least reduce the number of times we calculate it by passing conditions to
     // can_act, can_move, and speed.
-    let mut conditions: Vec<AppliedCondition> =
-      self.creature.conditions.values().cloned().collect();
+    let mut conditions: Vec<(Option<ConditionID>, AppliedCondition)> =
+      self.creature.conditions.iter().map(|(id, c)| (Some(*id), c.clone())).collect();
     let applied_class_conditions =
-      self.class.conditions.iter().map(|c| c.apply(Duration::Interminate));
+      self.class.conditions.iter().map(|c| (None, c.apply(Duration::Interminate)));
     conditions.extend(applied_class_conditions);
-    conditions.extend(self.volume_conditions().into_values());
-    conditions
+    conditions.extend(self.volume_conditions().into_iter().map(|(id, c)| (Some(id), c)));
+    conditions.sort_by_key(|(id, _)| *id);
+    conditions.into_iter().map(|(_, c)| c).collect()
   }
 
   pub fn own_conditions(&self) -> &HashMap<ConditionID, AppliedCondition> {
@@ -125,10 +308,11 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
 
   fn damage(&self, expr: &Dice) -> Vec<CreatureLog> {
     let (rolls, amt) = expr.roll();
-    let amt = HP(amt as u8);
-    if amt >= self.creature.cur_health {
+    let hit = clamp_hp(amt);
+    let amt = hit.saturating_sub(self.damage_reduction());
+    if amt >= self.creature.cur_health || self.is_massive_damage(hit) {
       let mut logs = vec![];
-      logs.push(CreatureLog::Damage { hp: self.creature.cur_health, rolls });
+      logs.push(CreatureLog::Damage { hp: cmp::min(amt, self.creature.cur_health), rolls });
       if !self.creature.conditions.values().any(|ac| matches!(ac.condition, Condition::Dead)) {
         logs.push(Self::apply_condition_log(Duration::Interminate, Condition::Dead));
       }
@@ -138,9 +322,18 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     }
   }
 
+  /// Whether a hit is big enough to kill the creature outright, under the game's
+  /// `massive_damage_threshold`. This goes by the damage before any reduction.
+  fn is_massive_damage(&self, amt: HP) -> bool {
+    match self.game.settings.massive_damage_threshold {
+      Some(threshold) => amt > self.creature.cur_health.saturating_add(threshold),
+      None => false,
+    }
+  }
+
   fn heal(&self, expr: &Dice) -> Vec<CreatureLog> {
     let (dice, amt) = expr.roll();
-    let amt = HP(amt as u8);
+    let amt = clamp_hp(amt);
     let missing = self.creature.max_health - self.creature.cur_health;
     vec![CreatureLog::Heal { hp: cmp::min(missing, amt), rolls: dice }]
   }
@@ -150,16 +343,27 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
       CreatureEffect::Damage(ref expr) => self.damage(expr),
       CreatureEffect::Heal(ref expr) => self.heal(expr),
       CreatureEffect::GenerateEnergy(amt) => self.generate_energy(amt),
+      CreatureEffect::GrantMovement(distance) => {
+        vec![Self::apply_condition_log(Duration::Rounds(0), Condition::ExtraMovement(distance))]
+      }
       CreatureEffect::MultiEffect(ref effects) => {
         effects.iter().flat_map(|x| self.eff2log(x)).collect()
       }
+      // This involves a second creature, so it's done by `transfer_condition` instead.
+      CreatureEffect::TransferCondition { .. } => vec![],
+      CreatureEffect::ApplyCondition(_, ref condition) if self.is_immune_to(condition) => vec![],
       CreatureEffect::ApplyCondition(ref duration, ref condition) => {
         vec![Self::apply_condition_log(*duration, condition.clone())]
       }
     }
   }
 
+  /// Apply an effect to the creature. This is also how `tick` applies recurring effects, so both
+  /// reject effects nested deeper than `MAX_EFFECT_DEPTH` with `GameError::EffectTooDeep`.
   pub fn apply_effect(&self, effect: &CreatureEffect) -> Result<ChangedCreature, GameError> {
+    if effect.nested_deeper_than(MAX_EFFECT_DEPTH) {
+      return Err(GameError::EffectTooDeep);
+    }
     let ops = Self::eff2log(self, effect);
     let mut changes = self.creature.change();
     for op in &ops {
@@ -188,14 +392,57 @@ impl<'creature, 'game: 'creature> DynamicCreature<'creature, 'game> {
     abs
   }
 
+  pub fn is_immune_to(&self, condition: &Condition) -> bool {
+    self.creature.immunities.contains(condition)
+  }
+
+  /// Whether this creature can be targeted by an ability, going by its `untargetable_by`.
+  pub fn can_be_targeted_by(&self, ability: &Ability) -> bool {
+    !ability.target_categories().iter().any(|c| self.creature.untargetable_by.contains(c))
+  }
+
   pub fn has_ability(&self, ability: AbilityID) -> bool {
     self.ability_statuses().iter().any(|ac| ac.ability_id == ability)
   }
+
+  /// The ability as this creature uses it, i.e. with its damage dice overridden if the creature
+  /// has its own dice for it.
+  pub fn customize_ability(&self, ability: &Ability) -> Ability {
+    let mut ability = ability.clone();
+    if let (Some(dice), Action::Creature { effect, .. }) =
+      (self.creature.ability_dice.get(&ability.id), &mut ability.action)
+    {
+      *effect = effect.with_damage_dice(dice);
+    }
+    ability
+  }
+
+  /// Whether the creature has used up all the uses it gets of an ability until it rests.
+  pub fn is_depleted(&self, ability: &Ability) -> bool {
+    match ability.uses_per_rest {
+      Some((uses, _)) => self.creature.ability_uses.get(&ability.id).cloned().unwrap_or(0) >= uses,
+      None => false,
+    }
+  }
 }
 
 pub trait CreatureExt {
   fn create(spec: &CreatureCreation) -> Creature;
 
+  /// Start building a creature with the given name. See `CreatureBuilder`.
+  fn builder(name: &str) -> CreatureBuilder;
+
+  /// Create a creature of the given class from a stat block (see `StatBlock` for the format).
+  /// This returns a full `Creature` rather than a `CreatureCreation`, since the latter has no room
+  /// for HP, speed, abilities or attributes.
+  fn from_statblock(
+    game: &Game, class: ClassID, value: serde_json::Value,
+  ) -> Result<Creature, GameError>;
+
+  /// Check that the creature's portrait and icon URLs are each either empty or a well-formed
+  /// http(s) URL, so that they're safe to use as image sources.
+  fn validate_image_urls(&self) -> Result<(), GameError>;
+
   fn apply_log(&self, item: &CreatureLog) -> Result<Creature, GameError>;
 
   fn id(&self) -> CreatureID;
@@ -210,7 +457,20 @@ pub trait CreatureExt {
 
   fn get_attribute_score(&self, attr: &AttrID) -> Result<SkillLevel, GameError>;
 
-  fn attribute_check(&self, check: &AttributeCheck) -> Result<(u8, bool), GameError>;
+  /// The modifier derived from an attribute score by the game's settings.
+  fn get_attribute_modifier(
+    &self, attr: &AttrID, settings: &GameSettings,
+  ) -> Result<i8, GameError>;
+
+  /// The minimum 1d100 roll the creature needs to pass a check, without rolling. This is 0 for a
+  /// reliable check that passes automatically.
+  fn check_difficulty(
+    &self, check: &AttributeCheck, settings: &GameSettings,
+  ) -> Result<u8, GameError>;
+
+  fn attribute_check(
+    &self, check: &AttributeCheck, settings: &GameSettings,
+  ) -> Result<(u8, bool), GameError>;
 }
 
 impl CreatureExt for Creature {
@@ -220,12 +480,19 @@ impl CreatureExt for Creature {
       name: spec.name.to_string(),
       class: spec.class,
       speed: u32cm(STANDARD_CREATURE_SPEED),
+      reach: default_reach(&spec.size),
       max_energy: Energy(3),
       cur_energy: Energy(3),
       abilities: IndexedHashMap::new(),
+      ability_dice: HashMap::new(),
+      ability_uses: HashMap::new(),
       max_health: HP(10),
       cur_health: HP(10),
       conditions: HashMap::new(),
+      immunities: vec![],
+      untargetable_by: vec![],
+      movement_mode: MovementMode::Ground,
+      readied_reaction: None,
       note: spec.note.clone(),
       bio: spec.bio.clone(),
       portrait_url: spec.portrait_url.clone(),
@@ -237,6 +504,64 @@ impl CreatureExt for Creature {
     }
   }
 
+  fn builder(name: &str) -> CreatureBuilder {
+    let creature = Creature::create(&CreatureCreation {
+      name: name.to_string(),
+      class: ClassID(Uuid::nil()),
+      portrait_url: "".to_string(),
+      icon_url: "".to_string(),
+      note: "".to_string(),
+      bio: "".to_string(),
+      initiative: Dice::flat(0),
+      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft },
+    });
+    CreatureBuilder { creature, reach: None }
+  }
+
+  fn from_statblock(
+    game: &Game, class: ClassID, value: serde_json::Value,
+  ) -> Result<Creature, GameError> {
+    let block: StatBlock =
+      serde_json::from_value(value).map_err(|e| GameError::InvalidStatBlock(e.to_string()))?;
+    game.get_class(class)?;
+    let mut abilities = IndexedHashMap::new();
+    for name in &block.abilities {
+      let mut matches = game.abilities.values().filter(|ab| &ab.name == name);
+      let ability = matches
+        .next()
+        .ok_or_else(|| GameError::InvalidStatBlock(format!("No ability named {name:?}")))?;
+      if matches.next().is_some() {
+        return Err(GameError::InvalidStatBlock(format!("More than one ability named {name:?}")));
+      }
+      abilities.insert(AbilityStatus { ability_id: ability.id, cooldown: 0 });
+    }
+    let mut creature = Creature::create(&CreatureCreation {
+      name: block.name,
+      class,
+      portrait_url: "".to_string(),
+      icon_url: "".to_string(),
+      note: block.note,
+      bio: "".to_string(),
+      initiative: block.initiative.unwrap_or_else(|| Dice::flat(0)),
+      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft },
+    });
+    if let Some(hp) = block.hp {
+      creature.max_health = HP(hp);
+      creature.cur_health = HP(hp);
+    }
+    if let Some(energy) = block.energy {
+      creature.max_energy = Energy(energy);
+      creature.cur_energy = Energy(energy);
+    }
+    if let Some(speed) = block.speed {
+      creature.speed = u32meter(speed);
+    }
+    creature.abilities = abilities;
+    creature.attributes =
+      block.attributes.into_iter().map(|(attr, level)| (AttrID(attr), level)).collect();
+    Ok(creature)
+  }
+
   fn apply_log(&self, item: &CreatureLog) -> Result<Creature, GameError> {
     let mut new = self.clone();
     match *item {
@@ -273,6 +598,15 @@ impl CreatureExt for Creature {
       CreatureLog::RemoveCondition { ref id } => {
         new.conditions.remove(id).ok_or_else(|| GameError::ConditionNotFound(*id))?;
       }
+      CreatureLog::ReadyReaction { ability_id } => new.readied_reaction = ability_id,
+      CreatureLog::UseAbility { ability_id } => {
+        *new.ability_uses.entry(ability_id).or_insert(0) += 1;
+      }
+      CreatureLog::RechargeAbilities { ref ability_ids } => {
+        for ability_id in ability_ids {
+          new.ability_uses.remove(ability_id);
+        }
+      }
     }
     Ok(new)
   }
@@ -292,6 +626,16 @@ impl CreatureExt for Creature {
     Ok(ChangedCreature { creature, logs: vec![log] })
   }
 
+  fn validate_image_urls(&self) -> Result<(), GameError> {
+    if !is_image_url(&self.portrait_url) {
+      return Err(GameError::InvalidPortraitURL(self.portrait_url.clone()));
+    }
+    if !is_image_url(&self.icon_url) {
+      return Err(GameError::InvalidIconURL(self.icon_url.clone()));
+    }
+    Ok(())
+  }
+
   fn get_attribute_score(&self, attr: &AttrID) -> Result<SkillLevel, GameError> {
     self
       .attributes
@@ -300,14 +644,33 @@ impl CreatureExt for Creature {
       .ok_or_else(|| GameError::AttributeNotFound(self.id, attr.clone()))
   }
 
-  fn attribute_check(&self, check: &AttributeCheck) -> Result<(u8, bool), GameError> {
-    let my_skill = self.get_attribute_score(&check.attr)?;
-    if check.reliable && check.target <= my_skill {
+  fn get_attribute_modifier(
+    &self, attr: &AttrID, settings: &GameSettings,
+  ) -> Result<i8, GameError> {
+    Ok(settings.attribute_modifier(self.get_attribute_score(attr)?))
+  }
+
+  fn check_difficulty(
+    &self, check: &AttributeCheck, settings: &GameSettings,
+  ) -> Result<u8, GameError> {
+    let modifier = self.get_attribute_modifier(&check.attr, settings)?;
+    if check.reliable && check.target.to_ord() <= modifier {
+      Ok(0)
+    } else {
+      Ok(SkillLevel::modifier_difficulty(modifier, check.target))
+    }
+  }
+
+  fn attribute_check(
+    &self, check: &AttributeCheck, settings: &GameSettings,
+  ) -> Result<(u8, bool), GameError> {
+    let modifier = self.get_attribute_modifier(&check.attr, settings)?;
+    if check.reliable && check.target.to_ord() <= modifier {
       Ok((100, true))
     } else {
       let dice = Dice::expr(1, 100);
       let roll = dice.roll().1 as u8; // panic: 1d100 better fit into a u8!
-      let success = roll >= my_skill.difficulty(check.target);
+      let success = roll >= self.check_difficulty(check, settings)?;
       Ok((roll, success))
     }
   }
@@ -343,6 +706,26 @@ impl ChangedCreature {
   pub fn done(self) -> (Creature, Vec<CreatureLog>) { (self.creature, self.logs) }
 }
 
+/// Whether a URL is empty or an absolute http(s) URL with a host. Anything that could break out of
+/// an HTML attribute or be interpreted as another scheme (like `javascript:`) is rejected.
+fn is_image_url(url: &str) -> bool {
+  if url.is_empty() {
+    return true;
+  }
+  if url.chars().any(|c| c.is_whitespace() || c.is_control() || "\"'<>`\\{}|^".contains(c)) {
+    return false;
+  }
+  let lower = url.to_ascii_lowercase();
+  let rest = match lower.strip_prefix("https://").or_else(|| lower.strip_prefix("http://")) {
+    Some(rest) => rest,
+    None => return false,
+  };
+  let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+  !host.is_empty()
+    && host.chars().all(|c| c.is_ascii_alphanumeric() || ".-:[]".contains(c))
+    && !host.starts_with(['.', '-', ':'])
+}
+
 fn conditions_able(conditions: &[AppliedCondition]) -> bool {
   !conditions.iter().any(|AppliedCondition { condition, .. }| {
     condition == &Condition::Incapacitated || condition == &Condition::Dead
@@ -355,6 +738,298 @@ pub mod test {
 
   use std::iter::FromIterator;
 
+  #[test]
+  fn builder_minimal() {
+    let creature = Creature::builder("bob").build();
+    assert_eq!(creature.name, "bob");
+    assert_eq!(creature.max_health, HP(10));
+    assert_eq!(creature.cur_health, HP(10));
+    assert_eq!(creature.cur_energy, Energy(3));
+    assert_eq!(creature.speed, u32cm(STANDARD_CREATURE_SPEED));
+    assert_eq!(creature.reach, standard_reach());
+    assert!(creature.abilities.is_empty());
+    assert!(creature.attributes.is_empty());
+    assert_eq!(creature.movement_mode, MovementMode::Ground);
+    assert!(creature.immunities.is_empty() && creature.untargetable_by.is_empty());
+  }
+
+  #[test]
+  fn builder_fully_specified() {
+    let size = AABB { x: u32cm(200), y: u32cm(200), z: u32cm(200), anchor: AABBAnchor::TopLeft };
+    let creature = Creature::builder("bob")
+      .id(cid_rogue())
+      .class(classid_rogue())
+      .abilities(&[abid_punch(), abid_shoot()])
+      .hp(HP(25))
+      .energy(Energy(7))
+      .speed(u32cm(600))
+      .size(size)
+      .movement_mode(MovementMode::Flying)
+      .immunities(&[Condition::Incapacitated])
+      .untargetable_by(&[TargetCategory::Healing])
+      .attribute("strength", SkillLevel::Expert)
+      .initiative(Dice::flat(3))
+      .note("A big guy")
+      .portrait_url("https://example.com/bob.png")
+      .icon_url("https://example.com/bob-icon.png")
+      .build();
+    assert_eq!(creature.id, cid_rogue());
+    assert_eq!(creature.class, classid_rogue());
+    assert_eq!(creature.abilities.len(), 2);
+    assert_eq!(
+      creature.abilities.get(&abid_shoot()),
+      Some(&AbilityStatus { ability_id: abid_shoot(), cooldown: 0 })
+    );
+    assert_eq!((creature.max_health, creature.cur_health), (HP(25), HP(25)));
+    assert_eq!((creature.max_energy, creature.cur_energy), (Energy(7), Energy(7)));
+    assert_eq!(creature.speed, u32cm(600));
+    assert_eq!(creature.size, size);
+    // The reach follows from the size, unless it's given explicitly.
+    assert_eq!(creature.reach, u32cm(250));
+    assert_eq!(creature.movement_mode, MovementMode::Flying);
+    assert_eq!(creature.immunities, vec![Condition::Incapacitated]);
+    assert_eq!(creature.untargetable_by, vec![TargetCategory::Healing]);
+    let strength = AttrID("strength".to_string());
+    assert_eq!(creature.get_attribute_score(&strength), Ok(SkillLevel::Expert));
+    assert_eq!(creature.initiative, Dice::flat(3));
+    assert_eq!(creature.note, "A big guy");
+    assert_eq!(creature.validate_image_urls(), Ok(()));
+    let creature = Creature::builder("bob").size(size).reach(u32cm(100)).build();
+    assert_eq!(creature.reach, u32cm(100));
+  }
+
+  #[test]
+  fn statblock_minimal() {
+    let value = serde_json::json!({"name": "Goblin"});
+    let creature = Creature::from_statblock(&t_game(), classid_rogue(), value).unwrap();
+    assert_eq!(creature.name, "Goblin");
+    assert_eq!(creature.class, classid_rogue());
+    assert_eq!(creature.max_health, HP(10));
+    assert_eq!(creature.speed, u32cm(STANDARD_CREATURE_SPEED));
+  }
+
+  #[test]
+  fn all_conditions_sorted() {
+    let conditions: Vec<(ConditionID, AppliedCondition)> = (0..8)
+      .map(|i| (ConditionID::gen(), app_cond(Condition::ReduceDamage(HP(i)), Duration::Rounds(i))))
+      .collect();
+    let mut forward = t_game();
+    forward.creatures.mutate(&cid_rogue(), |c| c.conditions.extend(conditions.iter().cloned()));
+    let mut backward = t_game();
+    backward
+      .creatures
+      .mutate(&cid_rogue(), |c| c.conditions.extend(conditions.iter().rev().cloned()));
+
+    let mut sorted = conditions.clone();
+    sorted.sort_by_key(|(id, _)| *id);
+    let expected: Vec<AppliedCondition> = sorted.into_iter().map(|(_, c)| c).collect();
+    let rogue = forward.get_creature(cid_rogue()).unwrap();
+    assert_eq!(rogue.all_conditions(), expected);
+    assert_eq!(rogue.all_conditions(), rogue.all_conditions());
+    assert_eq!(backward.get_creature(cid_rogue()).unwrap().all_conditions(), expected);
+  }
+
+  #[test]
+  fn large_creatures_get_larger_reach() {
+    let mut spec = CreatureCreation {
+      name: "Ogre".to_string(),
+      class: classid_rogue(),
+      portrait_url: "".to_string(),
+      icon_url: "".to_string(),
+      note: "".to_string(),
+      bio: "".to_string(),
+      initiative: Dice::flat(0),
+      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft },
+    };
+    assert_eq!(Creature::create(&spec).reach, standard_reach());
+    spec.size.x = u32cm(200);
+    spec.size.y = u32cm(200);
+    assert_eq!(Creature::create(&spec).reach, u32cm(250));
+  }
+
+  #[test]
+  fn statblock_full() {
+    let value = serde_json::json!({
+      "name": "Goblin Archer",
+      "hp": 7,
+      "energy": 2,
+      "speed": 9,
+      "abilities": ["Shoot", "Punch"],
+      "attributes": {"strength": "Unskilled"},
+      "initiative": {"Flat": {"value": 2}},
+      "note": "Sneaky",
+    });
+    let creature = Creature::from_statblock(&t_game(), classid_ranger(), value).unwrap();
+    assert_eq!((creature.max_health, creature.cur_health), (HP(7), HP(7)));
+    assert_eq!((creature.max_energy, creature.cur_energy), (Energy(2), Energy(2)));
+    assert_eq!(creature.speed, u32meter(9u32));
+    let mut abilities: Vec<AbilityID> = creature.abilities.keys().cloned().collect();
+    abilities.sort();
+    assert_eq!(abilities, vec![abid_punch(), abid_shoot()]);
+    assert_eq!(
+      creature.get_attribute_score(&AttrID("strength".to_string())),
+      Ok(SkillLevel::Unskilled)
+    );
+    assert_eq!(creature.initiative, Dice::flat(2));
+    assert_eq!(creature.note, "Sneaky");
+  }
+
+  #[test]
+  fn statblock_malformed() {
+    let game = t_game();
+    let no_name = serde_json::json!({"hp": 7});
+    assert!(matches!(
+      Creature::from_statblock(&game, classid_rogue(), no_name),
+      Err(GameError::InvalidStatBlock(_))
+    ));
+    let bad_hp = serde_json::json!({"name": "Goblin", "hp": "lots"});
+    assert!(matches!(
+      Creature::from_statblock(&game, classid_rogue(), bad_hp),
+      Err(GameError::InvalidStatBlock(_))
+    ));
+    let bad_ability = serde_json::json!({"name": "Goblin", "abilities": ["Teleport"]});
+    assert!(matches!(
+      Creature::from_statblock(&game, classid_rogue(), bad_ability),
+      Err(GameError::InvalidStatBlock(_))
+    ));
+  }
+
+  #[test]
+  fn statblock_ambiguous_ability() {
+    let mut game = t_game();
+    let mut punch = game.get_ability(abid_punch()).unwrap().clone();
+    punch.id = AbilityID::gen();
+    game.abilities.insert(punch);
+    let value = serde_json::json!({"name": "Goblin", "abilities": ["Punch"]});
+    assert!(matches!(
+      Creature::from_statblock(&game, classid_rogue(), value),
+      Err(GameError::InvalidStatBlock(msg)) if msg.starts_with("More than one ability")
+    ));
+  }
+
+  fn big_roll() -> Dice { Dice::flat(100).plus(Dice::flat(100)).plus(Dice::flat(100)) }
+
+  #[test]
+  fn damage_larger_than_hp_range_is_clamped() {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.max_health = HP(255);
+      c.cur_health = HP(255);
+    });
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Damage(big_roll())).unwrap();
+    assert_eq!(changed.logs[0], CreatureLog::Damage { hp: HP(255), rolls: vec![100, 100, 100] });
+    assert_eq!(changed.creature.cur_health, HP(0));
+  }
+
+  /// The rogue has 10 HP and reduces all damage by 10, and a hit of more than 15 is massive.
+  fn t_massive_damage_game() -> Game {
+    let mut game = t_game();
+    game.settings.massive_damage_threshold = Some(HP(5));
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.conditions.insert(
+        ConditionID(uuid_0()),
+        app_cond(Condition::ReduceDamage(HP(10)), Duration::Interminate),
+      );
+    });
+    game
+  }
+
+  fn is_dead(changed: &ChangedCreature) -> bool {
+    changed.creature.conditions.values().any(|ac| ac.condition == Condition::Dead)
+  }
+
+  #[test]
+  fn massive_damage_kills_outright() {
+    let game = t_massive_damage_game();
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Damage(Dice::flat(16))).unwrap();
+    assert_eq!(changed.logs[0], CreatureLog::Damage { hp: HP(6), rolls: vec![16] });
+    assert_eq!(changed.creature.cur_health, HP(4));
+    assert!(is_dead(&changed));
+  }
+
+  #[test]
+  fn smaller_hits_follow_the_usual_death_rules() {
+    let game = t_massive_damage_game();
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Damage(Dice::flat(15))).unwrap();
+    assert_eq!(changed.creature.cur_health, HP(5));
+    assert!(!is_dead(&changed));
+
+    // Running out of health kills whether or not the hit was massive.
+    let mut game = game;
+    game.creatures.mutate(&cid_rogue(), |c| c.conditions.clear());
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Damage(Dice::flat(10))).unwrap();
+    assert_eq!(changed.creature.cur_health, HP(0));
+    assert!(is_dead(&changed));
+  }
+
+  #[test]
+  fn heal_larger_than_hp_range_is_clamped() {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.max_health = HP(255);
+      c.cur_health = HP(1);
+    });
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Heal(big_roll())).unwrap();
+    assert_eq!(changed.creature.cur_health, HP(255));
+  }
+
+  #[test]
+  fn negative_damage_does_nothing() {
+    let game = t_game();
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&CreatureEffect::Damage(Dice::flat(-5))).unwrap();
+    assert_eq!(changed.creature.cur_health, HP(10));
+  }
+
+  fn nested_damage(depth: usize) -> CreatureEffect {
+    (1..depth).fold(CreatureEffect::Damage(Dice::flat(1)), |effect, _| {
+      CreatureEffect::MultiEffect(vec![effect])
+    })
+  }
+
+  #[test]
+  fn reasonably_nested_effects_apply() {
+    let game = t_game();
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    let changed = creature.apply_effect(&nested_damage(MAX_EFFECT_DEPTH)).unwrap();
+    assert_eq!(changed.creature.cur_health, HP(9));
+  }
+
+  #[test]
+  fn deeply_nested_effects_are_rejected() {
+    let game = t_game();
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(
+      creature.apply_effect(&nested_damage(1000)).map(|_| ()),
+      Err(GameError::EffectTooDeep)
+    );
+  }
+
+  /// A recurring effect that keeps applying itself is stopped when it ticks.
+  #[test]
+  fn deeply_nested_recurring_effects_are_rejected() {
+    let recurring = (0..MAX_EFFECT_DEPTH).fold(CreatureEffect::Damage(Dice::flat(1)), |effect, _| {
+      CreatureEffect::ApplyCondition(
+        Duration::Interminate,
+        Condition::RecurringEffect(Box::new(effect)),
+      )
+    });
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.conditions.insert(
+        ConditionID::gen(),
+        app_cond(Condition::RecurringEffect(Box::new(recurring)), Duration::Interminate),
+      );
+    });
+    let creature = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(creature.tick().map(|_| ()), Err(GameError::EffectTooDeep));
+  }
+
   #[test]
   fn test_tick_and_expire_condition_remaining() {
     let mut game = t_game();
@@ -418,4 +1093,35 @@ pub mod test {
     let c = game.dyn_creature(&c).unwrap().tick().unwrap().creature;
     assert_eq!(c.conditions, HashMap::new());
   }
+
+  #[test]
+  fn image_urls() {
+    let with_urls = |portrait: &str, icon: &str| Creature {
+      portrait_url: portrait.to_string(),
+      icon_url: icon.to_string(),
+      ..t_rogue("rogue")
+    };
+    assert_eq!(with_urls("", "").validate_image_urls(), Ok(()));
+    let valid = "https://example.com/images/goblin.png?size=large";
+    assert_eq!(with_urls(valid, "http://localhost:8080/token.png").validate_image_urls(), Ok(()));
+    for invalid in [
+      "example.com/goblin.png",
+      "https://",
+      "https:///goblin.png",
+      "ftp://example.com/goblin.png",
+      "javascript:alert(1)",
+      "https://example.com/goblin.png\" onerror=\"alert(1)",
+      "https://exa mple.com/goblin.png",
+      "https://user@example.com/goblin.png",
+    ] {
+      assert_eq!(
+        with_urls(invalid, "").validate_image_urls(),
+        Err(GameError::InvalidPortraitURL(invalid.to_string()))
+      );
+      assert_eq!(
+        with_urls(valid, invalid).validate_image_urls(),
+        Err(GameError::InvalidIconURL(invalid.to_string()))
+      );
+    }
+  }
 }
@@ -1,17 +1,26 @@
 use std::{
   cmp,
   collections::{HashMap, HashSet},
+  io,
   iter::FromIterator,
 };
 
 use crate::{
   combat::*,
   creature::{ChangedCreature, CreatureExt},
-  grid::{line_through_point, TileSystemExt},
+  grid::{cone_through_point, cover_between, line_through_point, TileSystemExt},
   scene::SceneExt,
   types::*,
 };
+use arptypes::multitenant::GameIndex;
 use foldertree::{FolderPath, FolderTreeError};
+use indexed::{DeriveKey, IndexedHashMap};
+use num::Saturating;
+use tracing::warn;
+use uom::si::length::centimeter;
+
+/// How far from the caster we look for open terrain to place a summoned creature.
+const SUMMON_RANGE: u32 = 500;
 
 pub trait GameExt {
   fn export_module(&self, export_path: &FolderPath) -> Result<Game, GameError>;
@@ -20,6 +29,10 @@ pub trait GameExt {
 
   fn validate_campaign(&self) -> Result<(), GameError>;
 
+  /// A hash of the game's (deterministic) serialization. Clients can compare this to find out
+  /// whether they have the latest state.
+  fn content_hash(&self) -> Result<u64, GameError>;
+
   fn creatures(&self) -> Result<HashMap<CreatureID, DynamicCreature>, GameError>;
 
   fn get_item(&self, iid: ItemID) -> Result<&Item, GameError>;
@@ -28,22 +41,56 @@ pub trait GameExt {
 
   fn player_path(&self, suffix: FolderPath, player_id: &PlayerID) -> (FolderPath, Option<GameLog>);
 
-  /// Perform a PlayerCommand on the current Game.
+  /// Perform a PlayerCommand on the current Game. If the game's `VettingPolicy` applies to the
+  /// command, it is only checked for validity and then held as a `PendingCommand` for the GM.
+  /// `now` is the current time in seconds since the Unix epoch, for timing out pending commands.
   fn perform_player_command(
-    &self, player_id: PlayerID, cmd: PlayerCommand,
+    &self, player_id: PlayerID, cmd: PlayerCommand, now: u64,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Perform a PlayerCommand regardless of the `VettingPolicy`.
+  fn perform_unvetted_player_command(
+    &self, player_id: PlayerID, cmd: PlayerCommand, now: u64,
   ) -> Result<ChangedGame, GameError>;
 
+  /// Check whether a PlayerCommand must be approved by the GM under the current `VettingPolicy`.
+  fn needs_vetting(&self, cmd: &PlayerCommand) -> Result<bool, GameError>;
+
+  /// List the pending player commands, oldest first. If a player is given, only that player's
+  /// commands are included.
+  fn list_pending(&self, player_id: Option<&PlayerID>) -> Vec<PendingCommand>;
+
+  /// Deny all pending commands which have been waiting longer than the game's
+  /// `pending_command_timeout`.
+  fn expire_pending_commands(&self, now: u64) -> Result<ChangedGame, GameError>;
+
+  /// Carry out the action in the `reaction_window` if it has been open longer than the game's
+  /// `reaction_window` setting allows, whether or not everyone has reacted.
+  fn expire_reaction_window(&self, now: u64) -> Result<ChangedGame, GameError>;
+
   /// Check that the player controls the current combat creature.
   fn auth_combat(&self, player: &Player) -> Result<(), GameError>;
 
+  /// The creatures controlled by a player, sorted by ID.
+  fn controlled_creatures(&self, player_id: &PlayerID) -> Result<Vec<CreatureID>, GameError>;
+
+  /// The players who control a creature, sorted by ID. Control can be shared, so there may be more
+  /// than one.
+  fn creature_controllers(&self, creature_id: CreatureID) -> Vec<PlayerID>;
+
   /// Perform a GMCommand on the current Game.
   ///
   /// The result includes a new Game instance and a Vec of GameLogs. These GameLogs should be a
   /// deterministic representation of the changes made to the Game, so they can be used to replay
   /// history and get the same exact result. An Undo operation can be implemented by rolling back to
   /// a previous game Snapshot and replaying until the desired GameLog.
+  ///
   fn perform_gm_command(&self, cmd: GMCommand) -> Result<ChangedGame, GameError>;
 
+  /// Perform a GMCommand at a particular time. `now` is the current time in seconds since the Unix
+  /// epoch, for timing out reaction windows.
+  fn perform_gm_command_at(&self, cmd: GMCommand, now: u64) -> Result<ChangedGame, GameError>;
+
   fn start_combat(
     &self, scene_id: SceneID, cids: Vec<CreatureID>,
   ) -> Result<ChangedGame, GameError>;
@@ -62,8 +109,62 @@ pub trait GameExt {
     &self, scene_id: SceneID, creature_id: CreatureID, pt: Point3, max_distance: u32units::Length,
   ) -> Result<(ChangedGame, u32units::Length), GameError>;
 
+  /// Implement `PlayerCommand::StepDirection`. In combat, the step uses up the creature's movement
+  /// just like `PathCurrentCombatCreature`, and is only allowed on its own turn.
+  fn step_creature(
+    &self, scene_id: SceneID, creature_id: CreatureID, direction: Direction,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// End the current creature's turn and tick its conditions. This happens whether or not the
+  /// creature is able to act, so a stunned creature's durations still count down. The turns of
+  /// any following creatures that can't act are ended as well.
   fn next_turn(&self) -> Result<ChangedGame, GameError>;
 
+  /// Implement `GMCommand::ForceNextTurn` and `GMCommand::ForcePrevTurn`, which change turns
+  /// without ending them properly, so nothing ticks.
+  fn force_turn(&self, log: CombatLog) -> Result<ChangedGame, GameError>;
+
+  /// Implement `GMCommand::ReadyReaction`.
+  fn ready_reaction(
+    &self, creature_id: CreatureID, ability_id: Option<AbilityID>,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Implement `GMCommand::React`. Once every creature in the reaction window has reacted, the
+  /// held action is carried out.
+  fn react(
+    &self, creature_id: CreatureID, ability_id: Option<AbilityID>,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Implement `GMCommand::Rest`. Each creature gets back the share of its health and energy that
+  /// the game's `RestPolicy` gives for this kind of rest.
+  fn rest(&self, creatures: &[CreatureID], kind: RestKind) -> Result<ChangedGame, GameError>;
+
+  /// Implement `GMCommand::ApplyConditionToMany`.
+  fn apply_condition_to_many(
+    &self, creatures: &[CreatureID], condition: Condition, duration: Duration,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Implement `GMCommand::UndoCreature`. `snapshot` and `history` must be the game snapshot and
+  /// the logs applied to it to produce this game.
+  ///
+  /// Logs undone by a `Rollback` don't count: only the logs still in effect are considered.
+  /// These are replayed without the last one that affected the creature, and the creature's
+  /// resulting state is restored with an `EditCreature` log. This only works when nothing else
+  /// depended on the skipped log: if any later log fails to apply without it, or if the rest of
+  /// the game ends up different, the undo is rejected. Undoing a creature's creation is also
+  /// rejected; delete the creature instead.
+  fn undo_creature(
+    &self, snapshot: &Game, history: &[GameLog], creature_id: CreatureID,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Summarize what a creature did on its most recent completed turn, i.e. the logs between the
+  /// previous turn boundary and the `EndTurn` that ended the creature's last turn. `history` must
+  /// be the logs that were applied to produce this game. A creature that hasn't finished a turn in
+  /// `history` gets an empty summary.
+  fn last_turn_summary(
+    &self, history: &[GameLog], creature_id: CreatureID,
+  ) -> Result<TurnSummary, GameError>;
+
   fn link_folder_item(
     &mut self, path: &FolderPath, item_id: &FolderItemID,
   ) -> Result<(), GameError>;
@@ -96,6 +197,8 @@ pub trait GameExt {
 
   fn check_creature_id(&self, cid: CreatureID) -> Result<(), GameError>;
 
+  /// Make sure a scene exists, failing with `SceneNotFound` if it doesn't. Every `GMCommand` that
+  /// takes a scene is checked with this before it's performed. See `GMCommand::scene_id`.
   fn check_scene(&self, scene: SceneID) -> Result<(), GameError>;
 
   fn is_in_combat(&self, cid: CreatureID) -> bool;
@@ -109,12 +212,26 @@ pub trait GameExt {
 
   fn get_combat(&self) -> Result<DynamicCombat, GameError>;
 
+  /// `None` if the creature isn't fighting in a combat in the given scene, otherwise whether it's
+  /// the creature's turn.
+  fn combat_turn(&self, scene_id: SceneID, cid: CreatureID) -> Option<bool>;
+
   // ** CONSIDER ** moving this chunk of code to... Scene.rs?
 
-  fn combat_act(&self, abid: AbilityID, target: DecidedTarget) -> Result<ChangedGame, GameError>;
+  fn combat_act(
+    &self, abid: AbilityID, target: DecidedTarget, now: u64,
+  ) -> Result<ChangedGame, GameError>;
 
   fn ooc_act(
-    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget,
+    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget, now: u64,
+  ) -> Result<ChangedGame, GameError>;
+
+  /// Act, unless the game has a `reaction_window` and the action attacks creatures that have a
+  /// reaction they could use. Then the action is only checked, and held in a `ReactionWindow`
+  /// until they've all reacted or the window runs out.
+  fn act_or_hold(
+    &self, scene: &Scene, cid: CreatureID, abid: AbilityID, target: DecidedTarget, in_combat: bool,
+    now: u64,
   ) -> Result<ChangedGame, GameError>;
 
   fn _act(
@@ -126,6 +243,30 @@ pub trait GameExt {
     change: ChangedGame, in_combat: bool,
   ) -> Result<ChangedGame, GameError>;
 
+  /// Logs that create a copy of `template` on the open point nearest to `caster`, marked as
+  /// `Summoned` for `duration`. The copy joins combat if `in_combat` is set. Rounds only pass in
+  /// combat, so a summoning that lasts for some rounds can't be cast out of combat.
+  fn summon(
+    &self, caster: CreatureID, scene: &Scene, template: CreatureID, duration: Duration,
+    in_combat: bool,
+  ) -> Result<Vec<GameLog>, GameError>;
+
+  /// The log that teleports `caster` onto `point`, or onto the closest point next to it that the
+  /// caster fits in without overlapping any other creature. Ties go to the point nearest the caster.
+  fn teleport(
+    &self, caster: CreatureID, scene: &Scene, point: Point3,
+  ) -> Result<GameLog, GameError>;
+
+  /// Logs that remove a summoned creature from the game if its `Summoned` condition is about to
+  /// expire, or if `combat_over` and the summoning lasts for some rounds, since no more rounds will
+  /// pass for it. This is empty for any other creature.
+  fn dismiss_summon(
+    &self, creature_id: CreatureID, combat_over: bool,
+  ) -> Result<Vec<GameLog>, GameError>;
+
+  /// The folder containing the given creature.
+  fn creature_folder(&self, creature_id: CreatureID) -> Option<FolderPath>;
+
   fn resolve_creature_targets(
     &self, creature: &DynamicCreature, scene: &Scene, target: CreatureTarget,
     decision: DecidedTarget,
@@ -145,6 +286,30 @@ pub trait GameExt {
     &self, scene: &Scene, actor_id: CreatureID, ability_id: AbilityID, pt: Point3,
   ) -> Result<(Vec<CreatureID>, Vec<Point3>), GameError>;
 
+  /// Predict what an ability would do to each creature it targets, sorted by creature ID, without
+  /// performing it. Like performing it, this skips creatures that can't be targeted by it and
+  /// conditions they're immune to. Abilities with scene effects don't affect creatures directly, so
+  /// they have no previews.
+  fn preview_ability(
+    &self, scene: &Scene, actor_id: CreatureID, ability_id: AbilityID, target: DecidedTarget,
+  ) -> Result<Vec<(CreatureID, EffectPreview)>, GameError>;
+
+  /// Find the creatures and open terrain cells that a volume placed at a point would cover, both
+  /// sorted. This doesn't involve any ability, so it can be used to preview arbitrary volumes.
+  fn creatures_and_terrain_in_volume(
+    &self, scene: &Scene, pt: Point3, volume: Volume,
+  ) -> Result<(Vec<CreatureID>, Vec<Point3>), GameError>;
+
+  /// Describe a single cell of a scene: whether it's open, and which creatures and volume
+  /// conditions cover it, both sorted.
+  fn cell_info(&self, scene_id: SceneID, pt: Point3) -> Result<CellInfo, GameError>;
+
+  /// Everything needed to draw a scene. When `player_id` is given, the creatures and highlights
+  /// that only the GM can see are left out, except for the player's own creatures.
+  fn scene_render_state(
+    &self, scene_id: SceneID, player_id: Option<&PlayerID>,
+  ) -> Result<SceneRenderState, GameError>;
+
   fn get_movement_options(
     &self, scene: SceneID, creature_id: CreatureID,
   ) -> Result<Vec<Point3>, GameError>;
@@ -158,14 +323,49 @@ pub trait GameExt {
     &self, scene: SceneID, creature_id: CreatureID, range: u32units::Length,
   ) -> Result<PotentialTargets, GameError>;
 
+  /// Make sure a point chosen for a ranged volume ability is open terrain within the ability's
+  /// range of the actor, and that the actor can see it.
+  fn check_point_target(
+    &self, scene: &Scene, actor_id: CreatureID, action: &Action, pt: Point3,
+  ) -> Result<(), GameError>;
+
   fn creatures_in_range(
     &self, scene: SceneID, creature_id: CreatureID, distance: u32units::Length,
   ) -> Result<PotentialTargets, GameError>;
 
+  /// The open terrain within a creature's reach, other than the point it's standing on.
+  fn threatened_points(
+    &self, scene: SceneID, creature_id: CreatureID,
+  ) -> Result<Vec<Point3>, GameError>;
+
   // ** END CONSIDERATION **
 
   fn get_class(&self, class: ClassID) -> Result<&Class, GameError>;
 
+  /// `CreatureLeftScene` logs for every scene that any of the given creatures is in, e.g. because
+  /// they're about to be deleted.
+  fn scene_departures(&self, cids: &[CreatureID]) -> Vec<GameLog>;
+
+  /// Logs that take a dead creature out of the current combat and off of every scene it's in,
+  /// for `GameSettings::auto_remove_dead`. This is empty for creatures controlled by a player.
+  fn dead_creature_removal(&self, creature_id: CreatureID) -> Vec<GameLog>;
+
+  /// Find dangling references between the parts of the game, and creatures or scenes that aren't
+  /// in any folder. The result is in a deterministic order.
+  fn integrity_check(&self) -> Vec<IntegrityIssue>;
+
+  /// Implement `GMCommand::RepairIntegrity`, with one log per repair.
+  fn repair_integrity(&self) -> Result<ChangedGame, GameError>;
+
+  /// Record the current positions and vitals of the creatures in a scene.
+  fn scene_checkpoint(&self, scene_id: SceneID) -> Result<SceneCheckpoint, GameError>;
+
+  /// Record one of the combats fought over the course of `logs`, which must be the logs applied
+  /// to this game, in order. Combats are numbered from 0 in the order they started.
+  fn export_combat_replay(
+    &self, logs: &[GameLog], combat: usize,
+  ) -> Result<CombatReplay, GameError>;
+
   fn change(&self) -> ChangedGame;
 
   fn change_with(&self, log: GameLog) -> Result<ChangedGame, GameError>;
@@ -309,6 +509,13 @@ impl GameExt for Game {
     Ok(())
   }
 
+  fn content_hash(&self) -> Result<u64, GameError> {
+    let mut hasher = FnvWriter::new();
+    serde_json::to_writer(&mut hasher, self)
+      .map_err(|e| GameError::BuggyProgram(format!("Couldn't serialize game: {e}")))?;
+    Ok(hasher.0)
+  }
+
   fn creatures(&self) -> Result<HashMap<CreatureID, DynamicCreature>, GameError> {
     let mut map = HashMap::new();
     for creature in self.creatures.values() {
@@ -339,13 +546,36 @@ impl GameExt for Game {
 
   /// Perform a PlayerCommand on the current Game.
   fn perform_player_command(
-    &self, player_id: PlayerID, cmd: PlayerCommand,
+    &self, player_id: PlayerID, cmd: PlayerCommand, now: u64,
+  ) -> Result<ChangedGame, GameError> {
+    if self.needs_vetting(&cmd)? {
+      // Make sure the command would work right now, so the GM doesn't have to deal with commands
+      // that are obviously invalid.
+      self.perform_unvetted_player_command(player_id.clone(), cmd.clone(), now)?;
+      let pending =
+        PendingCommand { id: PendingCommandID::gen(), player_id, command: cmd, submitted_at: now };
+      return Ok(self.change_with(GameLog::AddPendingCommand { pending })?.with_movement());
+    }
+    self.perform_unvetted_player_command(player_id, cmd, now)
+  }
+
+  fn perform_unvetted_player_command(
+    &self, player_id: PlayerID, cmd: PlayerCommand, now: u64,
   ) -> Result<ChangedGame, GameError> {
     use self::PlayerCommand::*;
     let player =
       self.players.get(&player_id).ok_or_else(|| GameError::PlayerNotFound(player_id.clone()))?;
+    // These would change what the action held in a reaction window does.
+    if self.reaction_window.is_some()
+      && matches!(
+        cmd,
+        PathCreature { .. } | StepDirection { .. } | PathCurrentCombatCreature { .. } | EndTurn
+      )
+    {
+      return Err(GameError::WaitingForReactions);
+    }
 
-    match cmd {
+    let change = match cmd {
       ChatFromPlayer { message } => {
         self.change_with(GameLog::ChatFromPlayer { player_id, message: message.to_owned() })
       }
@@ -363,15 +593,32 @@ impl GameExt for Game {
         logs.extend(log);
         self.change_with_logs(logs)
       }
+      SetDisplay { display_name, color } => {
+        self.change_with(GameLog::SetPlayerDisplay { player_id, display_name, color })
+      }
       PathCreature { creature_id, destination } => {
+        if !player.creatures.contains(&creature_id) {
+          return Err(GameError::PlayerDoesntControlCreature(player_id, creature_id));
+        }
         let scene_id =
           player.scene.ok_or(GameError::BuggyProgram("Player isn't in a scene".to_string()))?;
         let scene = self.get_scene(scene_id)?;
+        if self.combat_turn(scene.id, creature_id) == Some(false) {
+          return Err(GameError::NotYourTurn(creature_id));
+        }
         Ok(self.path_creature(scene.id, creature_id, destination)?.0)
       }
+      StepDirection { creature_id, direction } => {
+        if !player.creatures.contains(&creature_id) {
+          return Err(GameError::PlayerDoesntControlCreature(player_id, creature_id));
+        }
+        let scene_id =
+          player.scene.ok_or(GameError::BuggyProgram("Player isn't in a scene".to_string()))?;
+        self.step_creature(scene_id, creature_id, direction)
+      }
       CombatAct { ability_id, target } => {
         self.auth_combat(player)?;
-        self.combat_act(ability_id, target)
+        self.combat_act(ability_id, target, now)
       }
       PathCurrentCombatCreature { destination } => {
         self.auth_combat(player)?;
@@ -381,27 +628,136 @@ impl GameExt for Game {
         self.auth_combat(player)?;
         self.next_turn()
       }
+      React { creature_id, ability_id } => {
+        if !player.creatures.contains(&creature_id) {
+          return Err(GameError::PlayerDoesntControlCreature(player_id, creature_id));
+        }
+        self.react(creature_id, ability_id)
+      }
+    }?;
+    Ok(change.with_movement())
+  }
+
+  fn needs_vetting(&self, cmd: &PlayerCommand) -> Result<bool, GameError> {
+    use self::PlayerCommand::*;
+    // Reactions aren't held either, since the action they respond to is already waiting on them.
+    let is_action = !matches!(
+      cmd,
+      ChatFromPlayer { .. }
+        | CreateNote { .. }
+        | EditNote { .. }
+        | SetDisplay { .. }
+        | React { .. }
+    );
+    match self.settings.vetting_policy {
+      VettingPolicy::Never => Ok(false),
+      VettingPolicy::AllActions => Ok(is_action),
+      VettingPolicy::NPCTargetingActions => match cmd {
+        CombatAct { ability_id, target } => {
+          let combat = self.get_combat()?;
+          let actor = combat.current_creature()?;
+          let targets = match self.get_ability(*ability_id)?.action {
+            Action::Creature { target: tspec, .. } => {
+              self.resolve_creature_targets(&actor, combat.scene, tspec, target.clone())?
+            }
+            // Volume conditions affect whoever ends up in them, so we can't know ahead of time.
+            Action::SceneVolume { .. } => return Ok(true),
+          };
+          let is_pc = |cid: &CreatureID| !self.creature_controllers(*cid).is_empty();
+          Ok(!targets.iter().all(is_pc))
+        }
+        _ => Ok(false),
+      },
+    }
+  }
+
+  fn list_pending(&self, player_id: Option<&PlayerID>) -> Vec<PendingCommand> {
+    self
+      .pending_commands
+      .iter()
+      .filter(|p| player_id.is_none_or(|pid| &p.player_id == pid))
+      .cloned()
+      .collect()
+  }
+
+  fn expire_pending_commands(&self, now: u64) -> Result<ChangedGame, GameError> {
+    let timeout = match self.settings.pending_command_timeout {
+      Some(timeout) => u64::from(timeout),
+      None => return Ok(self.change()),
+    };
+    let logs = self
+      .pending_commands
+      .iter()
+      .filter(|p| now >= p.submitted_at.saturating_add(timeout))
+      .map(|p| GameLog::PendingCommandExpired { id: p.id })
+      .collect();
+    self.change_with_logs(logs)
+  }
+
+  fn expire_reaction_window(&self, now: u64) -> Result<ChangedGame, GameError> {
+    match (&self.reaction_window, self.settings.reaction_window) {
+      (Some(window), Some(timeout))
+        if now >= window.opened_at.saturating_add(u64::from(timeout)) =>
+      {
+        resolve_reaction_window(self.change())
+      }
+      // The GM turned reaction windows off while one was open, so it shouldn't wait any longer.
+      (Some(_), None) => resolve_reaction_window(self.change()),
+      _ => Ok(self.change()),
     }
   }
 
   /// Check that the player controls the current combat creature.
   fn auth_combat(&self, player: &Player) -> Result<(), GameError> {
-    if !player.creatures.contains(&self.get_combat()?.combat.current_creature_id()) {
-      return Err(GameError::BuggyProgram(
-        "You don't control the current combat creature".to_string(),
-      ));
+    let current = self.get_combat()?.combat.current_creature_id();
+    if !player.creatures.contains(&current) {
+      return Err(GameError::PlayerDoesntControlCreature(player.player_id.clone(), current));
     }
     Ok(())
   }
 
+  fn controlled_creatures(&self, player_id: &PlayerID) -> Result<Vec<CreatureID>, GameError> {
+    let player =
+      self.players.get(player_id).ok_or_else(|| GameError::PlayerNotFound(player_id.clone()))?;
+    let mut creatures: Vec<CreatureID> = player.creatures.iter().cloned().collect();
+    creatures.sort();
+    Ok(creatures)
+  }
+
+  fn creature_controllers(&self, creature_id: CreatureID) -> Vec<PlayerID> {
+    let mut players: Vec<PlayerID> = self
+      .players
+      .values()
+      .filter(|p| p.creatures.contains(&creature_id))
+      .map(|p| p.player_id.clone())
+      .collect();
+    players.sort();
+    players
+  }
+
   /// Perform a GMCommand on the current Game.
   ///
   /// The result includes a new Game instance and a Vec of GameLogs. These GameLogs should be a
   /// deterministic representation of the changes made to the Game, so they can be used to replay
   /// history and get the same exact result. An Undo operation can be implemented by rolling back to
   /// a previous game Snapshot and replaying until the desired GameLog.
+  ///
+  /// This doesn't know what time it is, so any reaction window it opens counts as having been
+  /// opened at the Unix epoch. Servers should use `perform_gm_command_at`.
   fn perform_gm_command(&self, cmd: GMCommand) -> Result<ChangedGame, GameError> {
+    self.perform_gm_command_at(cmd, 0)
+  }
+
+  fn perform_gm_command_at(&self, cmd: GMCommand, now: u64) -> Result<ChangedGame, GameError> {
     use self::GMCommand::*;
+    if self.reaction_window.is_some() && disturbs_reaction_window(&cmd) {
+      return Err(GameError::WaitingForReactions);
+    }
+    // Checking the scene first means a missing scene is reported as such, rather than as whatever
+    // goes wrong later on for lack of it.
+    if let Some(scene_id) = cmd.scene_id() {
+      self.check_scene(scene_id)?;
+    }
     let change = match cmd {
       LoadModule { ref name, ref path, source, game } => self.change_with(GameLog::LoadModule {
         name: name.clone(),
@@ -411,6 +767,8 @@ impl GameExt for Game {
       }),
       SetActiveScene { id } => self.change_with(GameLog::SetActiveScene { id }),
       // ** Player Management **
+      // Registering an existing player is a no-op, so that retried registrations don't fail.
+      RegisterPlayer { ref id } if self.players.contains_key(id) => Ok(self.change()),
       RegisterPlayer { ref id } => self.change_with(GameLog::RegisterPlayer { id: id.clone() }),
       GiveCreaturesToPlayer { ref player_id, ref creature_ids } => {
         self.change_with(GameLog::GiveCreaturesToPlayer {
@@ -428,6 +786,29 @@ impl GameExt for Game {
       SetPlayerScene { ref player_id, scene_id } => {
         self.change_with(GameLog::SetPlayerScene { player_id: player_id.clone(), scene_id })
       }
+      SetPlayerDisplay { player_id, display_name, color } => {
+        self.change_with(GameLog::SetPlayerDisplay { player_id, display_name, color })
+      }
+      EditGameSettings { settings } => self.change_with(GameLog::EditGameSettings { settings }),
+      ApprovePending { id } => {
+        let pending = self
+          .pending_commands
+          .iter()
+          .find(|p| p.id == id)
+          .ok_or(GameError::PendingCommandNotFound(id))?;
+        let mut change = self.change_with(GameLog::ResolvePendingCommand { id, approved: true })?;
+        let approved = change.game.perform_unvetted_player_command(
+          pending.player_id.clone(),
+          pending.command.clone(),
+          now,
+        )?;
+        change.game = approved.game;
+        change.logs.extend(approved.logs);
+        Ok(change)
+      }
+      DenyPending { id } => {
+        self.change_with(GameLog::ResolvePendingCommand { id, approved: false })
+      }
 
       // ** Chat **
       ChatFromGM { ref message } => {
@@ -456,7 +837,38 @@ impl GameExt for Game {
         self.change_with(GameLog::CopyFolderItem { source, item_id, dest, new_item_id })
       }
       DeleteFolderItem { path, item_id } => {
-        self.change_with(GameLog::DeleteFolderItem { path, item_id })
+        // Deleting creatures or scenes (possibly a whole folder of them) takes creatures out of
+        // scenes, so record that before they disappear.
+        let (creatures, scenes) = match item_id {
+          FolderItemID::CreatureID(cid) => (vec![cid], vec![]),
+          FolderItemID::SceneID(sid) => (vec![], vec![sid]),
+          FolderItemID::SubfolderID(ref name) => {
+            let (mut cids, mut sids) = (vec![], vec![]);
+            for folder in self.campaign.walk_paths(&path.child(name.clone())) {
+              let node = self.campaign.get(folder)?;
+              cids.extend(node.creatures.iter().cloned());
+              sids.extend(node.scenes.iter().cloned());
+            }
+            cids.sort();
+            sids.sort();
+            (cids, sids)
+          }
+          _ => (vec![], vec![]),
+        };
+        let mut logs = self.scene_departures(&creatures);
+        for scene_id in scenes {
+          let mut cids: Vec<CreatureID> =
+            self.get_scene(scene_id)?.creatures.keys().cloned().collect();
+          cids.sort();
+          for creature_id in cids {
+            let log = GameLog::CreatureLeftScene { scene_id, creature_id };
+            if !logs.contains(&log) {
+              logs.push(log);
+            }
+          }
+        }
+        logs.push(GameLog::DeleteFolderItem { path, item_id });
+        self.change_with_logs(logs)
       }
       RenameFolderItem { path, item_id, new_name } => {
         self.change_with(GameLog::RenameFolderItem { path, item_id, new_name })
@@ -496,14 +908,24 @@ impl GameExt for Game {
         self.change_with(GameLog::SetSceneCreatureVisibility { scene_id, creature_id, visibility })
       }
       AddCreatureToScene { scene_id, creature_id, ref visibility } => {
-        self.change_with(GameLog::AddCreatureToScene {
+        let already_present = self.get_scene(scene_id)?.creatures.contains_key(&creature_id);
+        let mut logs = vec![GameLog::AddCreatureToScene {
           scene_id,
           creature_id,
           visibility: visibility.clone(),
-        })
+        }];
+        if !already_present {
+          logs.push(GameLog::CreatureEnteredScene { scene_id, creature_id });
+        }
+        self.change_with_logs(logs)
       }
       RemoveCreatureFromScene { scene_id, creature_id } => {
-        self.change_with(GameLog::RemoveCreatureFromScene { scene_id, creature_id })
+        let was_present = self.get_scene(scene_id)?.creatures.contains_key(&creature_id);
+        let mut logs = vec![GameLog::RemoveCreatureFromScene { scene_id, creature_id }];
+        if was_present {
+          logs.push(GameLog::CreatureLeftScene { scene_id, creature_id });
+        }
+        self.change_with_logs(logs)
       }
       AddSceneChallenge { scene_id, ref description, ref challenge } => {
         self.change_with(GameLog::AddSceneChallenge {
@@ -541,6 +963,9 @@ impl GameExt for Game {
           cost: ability.cost,
           action: ability.action.clone(),
           usable_ooc: ability.usable_ooc,
+          action_kind: ability.action_kind,
+          attack_roll: ability.attack_roll.clone(),
+          uses_per_rest: ability.uses_per_rest,
         };
         self.change_with(GameLog::CreateAbility { path, ability })
       }
@@ -548,25 +973,66 @@ impl GameExt for Game {
 
       CreateCreature { path, creature } => {
         let creature = Creature::create(&creature);
+        creature.validate_image_urls()?;
         self.change_with(GameLog::CreateCreature { path, creature })
       }
-      EditCreatureDetails { creature } => self.change_with(GameLog::EditCreature { creature }),
+      EditCreatureDetails { creature } => {
+        creature.validate_image_urls()?;
+        self.change_with(GameLog::EditCreature { creature })
+      }
       PathCreature { scene_id, creature_id, destination } => {
         Ok(self.path_creature(scene_id, creature_id, destination)?.0)
       }
       SetCreaturePos { scene_id, creature_id, pos } => {
         self.change_with(GameLog::SetCreaturePos { scene_id, creature_id, pos })
       }
+      ApplyConditionToMany { creatures, condition, duration } => {
+        self.apply_condition_to_many(&creatures, condition, duration)
+      }
+      RepairIntegrity => self.repair_integrity(),
+      SetCreatureAbilityDice { creature_id, ability_id, dice } => {
+        let creature = self.get_creature(creature_id)?;
+        if !creature.has_ability(ability_id) {
+          return Err(GameError::CreatureLacksAbility(creature_id, ability_id));
+        }
+        let mut creature = creature.creature.clone();
+        match dice {
+          Some(dice) => creature.ability_dice.insert(ability_id, dice),
+          None => creature.ability_dice.remove(&ability_id),
+        };
+        self.change_with(GameLog::EditCreature { creature })
+      }
+      SetCreatureReach { creature_id, reach } => {
+        let mut creature = self.get_creature(creature_id)?.creature.clone();
+        creature.reach = reach;
+        self.change_with(GameLog::EditCreature { creature })
+      }
       PathCurrentCombatCreature { destination } => {
         self.get_combat()?.get_movement()?.move_current(destination)
       }
-      CombatAct { ability_id, target } => self.combat_act(ability_id, target),
+      CombatAct { ability_id, target } => self.combat_act(ability_id, target, now),
       ActCreature { scene_id, creature_id, ability_id, target } => {
-        self.ooc_act(scene_id, creature_id, ability_id, target)
+        self.ooc_act(scene_id, creature_id, ability_id, target, now)
       }
       EditSceneTerrain { scene_id, ref terrain } => {
+        // Only newly painted cells are checked, so terrain from before the bounds were set can
+        // still be kept (or erased).
+        let scene = self.get_scene(scene_id)?;
+        let outside =
+          terrain.iter().find(|pt| !scene.in_grid_bounds(**pt) && !scene.terrain.contains(*pt));
+        if let Some(pt) = outside {
+          return Err(GameError::OutsideGridBounds(scene_id, *pt));
+        }
         self.change_with(GameLog::EditSceneTerrain { scene_id, terrain: terrain.clone() })
       }
+      EditSceneTerrainCosts { scene_id, ref costs } => {
+        self.get_scene(scene_id)?;
+        self.change_with(GameLog::EditSceneTerrainCosts { scene_id, costs: costs.clone() })
+      }
+      SetSceneGridBounds { scene_id, bounds } => {
+        self.get_scene(scene_id)?;
+        self.change_with(GameLog::SetSceneGridBounds { scene_id, bounds })
+      }
       EditSceneHighlights { scene_id, ref highlights } => {
         self.change_with(GameLog::EditSceneHighlights { scene_id, highlights: highlights.clone() })
       }
@@ -584,11 +1050,34 @@ impl GameExt for Game {
           scene_hotspots: scene_hotspots.clone(),
         })
       }
+      SnapshotScene { scene_id, name } => {
+        let checkpoint = self.scene_checkpoint(scene_id)?;
+        self.change_with(GameLog::SnapshotScene { scene_id, name, checkpoint })
+      }
+      RestoreScene { scene_id, name } => {
+        self.change_with(GameLog::RestoreScene { scene_id, name })
+      }
       StartCombat { scene_id, combatants } => self.start_combat(scene_id, combatants),
-      StopCombat => self.change_with(GameLog::StopCombat),
+      StopCombat { preserve_initiative } => {
+        let combat = self.current_combat.as_ref().ok_or(GameError::NotInCombat)?;
+        let mut change = self
+          .change_with(GameLog::StopCombat { preserve_initiative })?
+          .remove_if_dead(combat.current_creature_id())?;
+        // Summonings that last for rounds are over along with the combat.
+        for creature_id in combat.creature_ids() {
+          for log in change.game.dismiss_summon(creature_id, true)? {
+            change = change.apply(&log)?;
+          }
+        }
+        Ok(change)
+      }
       AddCreatureToCombat { creature_id } => self.add_creature_to_combat(creature_id),
       RemoveCreatureFromCombat { creature_id } => {
-        self.change_with(GameLog::RemoveCreatureFromCombat { creature_id })
+        let mut change = self.change_with(GameLog::RemoveCreatureFromCombat { creature_id })?;
+        for log in change.game.dismiss_summon(creature_id, true)? {
+          change = change.apply(&log)?;
+        }
+        Ok(change)
       }
       RerollCombatInitiative => self.change().apply_combat(|c| c.reroll_initiative()),
       ChangeCreatureInitiative { creature_id, initiative } => {
@@ -596,21 +1085,138 @@ impl GameExt for Game {
           log: CombatLog::ChangeCreatureInitiative { creature_id, initiative },
         })
       }
-      ForceNextTurn => self.change_with(GameLog::CombatLog { log: CombatLog::ForceNextTurn }),
-      ForcePrevTurn => self.change_with(GameLog::CombatLog { log: CombatLog::ForcePrevTurn }),
+      ForceNextTurn => self.force_turn(CombatLog::ForceNextTurn),
+      ForcePrevTurn => self.force_turn(CombatLog::ForcePrevTurn),
       EndTurn => self.next_turn(),
+      ReadyReaction { creature_id, ability_id } => self.ready_reaction(creature_id, ability_id),
+      React { creature_id, ability_id } => self.react(creature_id, ability_id),
+      Rest { creatures, kind } => self.rest(&creatures, kind),
+      ApplyCreatureLog { creature_id, log } => {
+        self.change_with(GameLog::CreatureLog { creature_id, log })
+      }
 
       // These are handled by the app before being passed to the Game:
       Rollback { .. } => bug("Game Rollback"),
+      RollbackToHash { .. } => bug("Game RollbackToHash"),
+      UndoCreature { .. } => bug("Game UndoCreature"),
     }?;
-    Ok(change)
+    Ok(change.with_movement())
   }
 
   fn start_combat(
     &self, scene_id: SceneID, cids: Vec<CreatureID>,
   ) -> Result<ChangedGame, GameError> {
-    let combatants = Combat::roll_initiative(self, cids)?;
-    self.change_with(GameLog::StartCombat { scene_id, combatants })
+    let scene = self.get_scene(scene_id)?;
+    let (preserved, to_roll): (Vec<CreatureID>, Vec<CreatureID>) = cids
+      .into_iter()
+      .partition(|cid| scene.preserved_initiative.iter().any(|(pcid, _)| pcid == cid));
+    let mut combatants: Vec<(CreatureID, i16)> = scene
+      .preserved_initiative
+      .iter()
+      .filter(|(cid, _)| preserved.contains(cid))
+      .cloned()
+      .collect();
+    combatants.extend(Combat::roll_initiative(self, to_roll)?);
+    let missing: Vec<CreatureID> = combatants
+      .iter()
+      .map(|(cid, _)| *cid)
+      .filter(|cid| !scene.creatures.contains_key(cid))
+      .collect();
+    if !missing.is_empty() {
+      return Err(GameError::CreaturesNotInScene(scene_id, missing));
+    }
+    // Record the rolls in turn order, so the log shows players how initiative was decided.
+    combatants.sort_by_key(|&(_, initiative)| cmp::Reverse(initiative));
+    self.change_with(GameLog::StartCombat { scene_id, combatants })?.with_turn_began()
+  }
+
+  fn undo_creature(
+    &self, snapshot: &Game, history: &[GameLog], creature_id: CreatureID,
+  ) -> Result<ChangedGame, GameError> {
+    let cant_undo = |reason: &str| GameError::CannotUndoCreature(creature_id, reason.to_string());
+    let current = self.get_creature(creature_id)?.creature.clone();
+    let history = logs_in_effect(history)?;
+    let skip_idx = history
+      .iter()
+      .rposition(|log| match log {
+        GameLog::CreatureLog { creature_id: cid, .. }
+        | GameLog::EditCreatureDetails { creature_id: cid, .. } => *cid == creature_id,
+        GameLog::EditCreature { creature } | GameLog::CreateCreature { creature, .. } => {
+          creature.id == creature_id
+        }
+        _ => false,
+      })
+      .ok_or_else(|| cant_undo("it hasn't changed since the last snapshot"))?;
+    if let GameLog::CreateCreature { .. } = *history[skip_idx] {
+      return Err(cant_undo("its last change was its creation"));
+    }
+
+    let mut replayed = snapshot.clone();
+    for (idx, log) in history.iter().enumerate() {
+      if idx != skip_idx {
+        replayed = replayed.apply_log(log).map_err(|_| cant_undo("later changes depend on it"))?;
+      }
+    }
+    let restored = replayed.get_creature(creature_id)?.creature.clone();
+    replayed.creatures.insert(current);
+    if replayed != *self {
+      return Err(cant_undo("other things changed along with it"));
+    }
+    self.change_with(GameLog::EditCreature { creature: restored })
+  }
+
+  fn last_turn_summary(
+    &self, history: &[GameLog], creature_id: CreatureID,
+  ) -> Result<TurnSummary, GameError> {
+    self.get_creature(creature_id)?;
+    let history = logs_in_effect(history)?;
+    let is_boundary = |log: &GameLog| match log {
+      GameLog::CombatLog { log } => matches!(
+        log,
+        CombatLog::EndTurn { .. } | CombatLog::ForceNextTurn | CombatLog::ForcePrevTurn
+      ),
+      GameLog::TurnBegan { .. } | GameLog::StartCombat { .. } | GameLog::StopCombat { .. } => true,
+      _ => false,
+    };
+    let end = history.iter().rposition(|log| match log {
+      GameLog::CombatLog { log: CombatLog::EndTurn { creature_id: cid } } => *cid == creature_id,
+      _ => false,
+    });
+    let end = match end {
+      Some(end) => end,
+      None => return Ok(TurnSummary::default()),
+    };
+    let start = history[..end].iter().rposition(|log| is_boundary(log)).map_or(0, |idx| idx + 1);
+
+    let mut summary = TurnSummary::default();
+    // Damage only counts as dealt once the creature has used one of its own abilities; other
+    // creatures' reactions in the middle of the turn don't end that.
+    let mut acting = false;
+    for log in &history[start..end] {
+      match *log {
+        GameLog::PathCreature { creature_id: cid, path, .. } if *cid == creature_id => {
+          if let (Some(&first), Some(&last)) = (path.first(), path.last()) {
+            let from = summary.moved.map_or(first, |(from, _)| from);
+            summary.moved = Some((from, last));
+          }
+        }
+        GameLog::CreatureUsedAbility { creature_id: cid, ability_id, .. }
+          if *cid == creature_id =>
+        {
+          acting = true;
+          summary.abilities.push(*ability_id);
+        }
+        GameLog::CreatureLog { creature_id: cid, log: CreatureLog::Damage { hp, .. } } => {
+          if *cid == creature_id {
+            summary.damage_taken = summary.damage_taken.saturating_add(*hp);
+          } else if acting {
+            summary.damage_dealt = summary.damage_dealt.saturating_add(*hp);
+          }
+        }
+        _ => {}
+      }
+    }
+    Ok(summary)
   }
 
   fn add_creature_to_combat(&self, creature_id: CreatureID) -> Result<ChangedGame, GameError> {
@@ -623,7 +1229,7 @@ impl GameExt for Game {
     &self, creature_id: CreatureID, check: &AttributeCheck,
   ) -> Result<ChangedGame, GameError> {
     let creature = self.get_creature(creature_id)?;
-    let (actual, success) = creature.creature.attribute_check(check)?;
+    let (actual, success) = creature.creature.attribute_check(check, &self.settings)?;
     self.change_with(GameLog::AttributeCheckResult {
       creature_id,
       attribute_check: check.clone(),
@@ -650,7 +1256,9 @@ impl GameExt for Game {
         scene.get_pos(creature_id)?,
         max_distance,
         &scene.terrain,
+        &scene.terrain_costs,
         Volume::AABB(creature.creature.size),
+        creature.creature.movement_mode,
         pt,
       )
       .ok_or(GameError::NoPathFound)?;
@@ -660,9 +1268,157 @@ impl GameExt for Game {
     Ok((change, distance))
   }
 
+  fn step_creature(
+    &self, scene_id: SceneID, creature_id: CreatureID, direction: Direction,
+  ) -> Result<ChangedGame, GameError> {
+    let scene = self.get_scene(scene_id)?;
+    let pos = scene.get_pos(creature_id)?;
+    let destination = direction.step_from(pos);
+    let occupied =
+      scene.creatures.iter().any(|(cid, (pt, _))| *cid != creature_id && *pt == destination);
+    if !scene.terrain.contains(&destination) || occupied {
+      return Err(GameError::StepBlocked(pos, destination));
+    }
+    match self.combat_turn(scene_id, creature_id) {
+      Some(false) => Err(GameError::NotYourTurn(creature_id)),
+      Some(true) => {
+        let combat = self.get_combat()?;
+        let movement = combat.get_movement()?;
+        if combat.movement()?.movement_left < self.tile_system.point3_distance(pos, destination) {
+          return Err(GameError::NotEnoughMovement(creature_id));
+        }
+        movement.move_current(destination)
+      }
+      None => Ok(self.path_creature(scene_id, creature_id, destination)?.0),
+    }
+  }
+
   fn next_turn(&self) -> Result<ChangedGame, GameError> {
-    let change = self.change().apply_combat(|c| c.next_turn())?;
-    change.apply_creature(self.current_combat.as_ref().unwrap().current_creature_id(), |c| c.tick())
+    let mut change = self.change();
+    // Creatures that can't act (dead, incapacitated, ...) have their turns ended automatically, so
+    // their conditions still tick. Nobody's turn is skipped twice, so a combat where no one can
+    // act still stops after a single round.
+    for _ in 0..self.get_combat()?.combat.creatures.len() {
+      let current = change.game.get_combat()?.combat.current_creature_id();
+      // Summoned creatures disappear at the end of their turn once their time is up.
+      let departures = change.game.dismiss_summon(current, false)?;
+      change = change.apply_combat(|c| c.next_turn())?;
+      change = change.apply_creature(current, |c| c.tick())?;
+      for log in departures {
+        change = change.apply(&log)?;
+      }
+      change = change.remove_if_dead(current)?;
+      match change.game.get_combat() {
+        Ok(combat) if !combat.current_creature()?.can_act() => continue,
+        _ => break,
+      }
+    }
+    change.with_turn_began()
+  }
+
+  fn force_turn(&self, log: CombatLog) -> Result<ChangedGame, GameError> {
+    let current = self.current_combat.as_ref().ok_or(GameError::NotInCombat)?;
+    let current = current.current_creature_id();
+    self.change_with(GameLog::CombatLog { log })?.remove_if_dead(current)?.with_turn_began()
+  }
+
+  fn ready_reaction(
+    &self, creature_id: CreatureID, ability_id: Option<AbilityID>,
+  ) -> Result<ChangedGame, GameError> {
+    if let Some(ability_id) = ability_id {
+      if !self.get_creature(creature_id)?.has_ability(ability_id) {
+        return Err(GameError::CreatureLacksAbility(creature_id, ability_id));
+      }
+      if !self.get_ability(ability_id)?.can_be_readied() {
+        return Err(GameError::CannotReadyAbility(ability_id));
+      }
+    }
+    self.change().apply_creature(creature_id, |c| {
+      c.creature.change_with(CreatureLog::ReadyReaction { ability_id })
+    })
+  }
+
+  fn react(
+    &self, creature_id: CreatureID, ability_id: Option<AbilityID>,
+  ) -> Result<ChangedGame, GameError> {
+    let window = self.reaction_window.as_ref().ok_or(GameError::NoReactionWindow)?;
+    if !window.reactors.contains(&creature_id) {
+      return Err(GameError::CannotReact(creature_id));
+    }
+    let change = match ability_id {
+      Some(ability_id) => {
+        if !self.get_ability(ability_id)?.can_be_readied() {
+          return Err(GameError::CannotReadyAbility(ability_id));
+        }
+        let scene = self.get_scene(window.scene_id)?;
+        let in_combat = self.combat_turn(scene.id, creature_id).is_some();
+        self._act(scene, creature_id, ability_id, DecidedTarget::Actor, in_combat)?
+      }
+      None => self.change(),
+    };
+    let change = change.apply(&GameLog::Reacted { creature_id })?;
+    if matches!(change.game.reaction_window, Some(ref window) if window.reactors.is_empty()) {
+      resolve_reaction_window(change)
+    } else {
+      Ok(change)
+    }
+  }
+
+  fn rest(&self, creatures: &[CreatureID], kind: RestKind) -> Result<ChangedGame, GameError> {
+    let recovery = self.settings.rest_policy.recovery(kind);
+    let mut change = self.change();
+    for &creature_id in creatures {
+      let creature = self.get_creature(creature_id)?.creature;
+      let mut logs = vec![];
+      let hp = cmp::min(
+        recovery.health(creature.max_health),
+        creature.max_health.saturating_sub(creature.cur_health),
+      );
+      if hp > HP(0) {
+        logs.push(CreatureLog::Heal { hp, rolls: vec![] });
+      }
+      let energy = cmp::min(
+        recovery.energy(creature.max_energy),
+        creature.max_energy.saturating_sub(creature.cur_energy),
+      );
+      if energy > Energy(0) {
+        logs.push(CreatureLog::GenerateEnergy { energy });
+      }
+      let mut ability_ids: Vec<AbilityID> = creature
+        .ability_uses
+        .keys()
+        .filter(|abid| match self.abilities.get(*abid).and_then(|ab| ab.uses_per_rest) {
+          Some((_, recharge)) => kind.recharges(recharge),
+          // The ability was deleted or isn't limited any more, so its uses can always be cleared.
+          None => true,
+        })
+        .cloned()
+        .collect();
+      if !ability_ids.is_empty() {
+        ability_ids.sort();
+        logs.push(CreatureLog::RechargeAbilities { ability_ids });
+      }
+      change = change.apply_creature(creature_id, |c| {
+        logs.iter().try_fold(c.creature.change(), |changed, log| changed.apply(log))
+      })?;
+    }
+    Ok(change)
+  }
+
+  fn apply_condition_to_many(
+    &self, creatures: &[CreatureID], condition: Condition, duration: Duration,
+  ) -> Result<ChangedGame, GameError> {
+    let mut change = self.change();
+    let effect = CreatureEffect::ApplyCondition(duration, condition.clone());
+    for &creature_id in creatures {
+      let creature = change.game.get_creature(creature_id)?;
+      // Conditions don't stack: a creature only gets one copy.
+      if creature.creature.conditions.values().any(|ac| ac.condition == condition) {
+        continue;
+      }
+      change = change.apply_creature(creature_id, |c| c.apply_effect(&effect))?;
+    }
+    Ok(change)
   }
 
   fn link_folder_item(
@@ -777,6 +1533,23 @@ impl GameExt for Game {
 
       SetActiveScene { id } => self.active_scene = id,
 
+      EditGameSettings { ref settings } => self.settings = settings.clone(),
+      AddPendingCommand { ref pending } => self.pending_commands.push(pending.clone()),
+      ResolvePendingCommand { id, .. } | PendingCommandExpired { id } => {
+        let idx = self
+          .pending_commands
+          .iter()
+          .position(|p| p.id == id)
+          .ok_or(GameError::PendingCommandNotFound(id))?;
+        self.pending_commands.remove(idx);
+      }
+      OpenReactionWindow { ref window } => self.reaction_window = Some(window.clone()),
+      Reacted { creature_id } => {
+        let window = self.reaction_window.as_mut().ok_or(GameError::NoReactionWindow)?;
+        window.reactors.retain(|cid| *cid != creature_id);
+      }
+      CloseReactionWindow => self.reaction_window = None,
+
       // Player stuff
       RegisterPlayer { ref id } => {
         if self.players.contains_key(id) {
@@ -818,8 +1591,25 @@ impl GameExt for Game {
           .ok_or_else(|| GameError::PlayerNotFound(player_id.clone()))?;
       }
 
+      SetPlayerDisplay { ref player_id, ref display_name, ref color } => {
+        self
+          .players
+          .mutate(player_id, |p| {
+            p.display_name = display_name.clone();
+            p.color = color.clone();
+          })
+          .ok_or_else(|| GameError::PlayerNotFound(player_id.clone()))?;
+      }
+
       // purely informational
-      ChatFromGM { .. } | ChatFromPlayer { .. } | AttributeCheckResult { .. } => {}
+      ChatFromGM { .. }
+      | ChatFromPlayer { .. }
+      | AttributeCheckResult { .. }
+      | AttackRolled { .. }
+      | TurnBegan { .. }
+      | CreatureEnteredScene { .. }
+      | CreatureLeftScene { .. }
+      | CreatureUsedAbility { .. } => {}
 
       // purely informational
       CreateFolder { ref path } => self.campaign.make_folders(path, Folder::new()),
@@ -937,12 +1727,12 @@ impl GameExt for Game {
                 sc.creatures.remove(&cid);
               });
             }
-            self.current_combat = {
-              if let Ok(combat) = self.get_combat() {
+            self.current_combat = match self.get_combat() {
+              Ok(combat) if combat.combat.contains_creature(cid) => {
                 combat.remove_from_combat(cid)?
-              } else {
-                None
               }
+              Ok(combat) => Some(combat.combat.clone()),
+              Err(_) => None,
             };
 
             self
@@ -1051,6 +1841,8 @@ impl GameExt for Game {
           }
         }
       }
+      LinkFolderItem { ref path, ref item_id } => self.link_folder_item(path, item_id)?,
+      UnlinkFolderItem { ref path, ref item_id } => self.unlink_folder_item(path, item_id)?,
 
       RenameFolderItem { ref path, ref item_id, ref new_name } => match item_id {
         FolderItemID::SceneID(id) => {
@@ -1210,6 +2002,18 @@ impl GameExt for Game {
           .mutate(&scene_id, move |s| s.terrain = terrain.clone())
           .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
       }
+      EditSceneTerrainCosts { scene_id, ref costs } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.terrain_costs = costs.clone())
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      SetSceneGridBounds { scene_id, bounds } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| s.grid_bounds = bounds)
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
       EditSceneHighlights { scene_id, ref highlights } => {
         self
           .scenes
@@ -1234,6 +2038,34 @@ impl GameExt for Game {
           .mutate(&scene_id, move |s| s.scene_hotspots = scene_hotspots.clone())
           .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
       }
+      SnapshotScene { scene_id, ref name, ref checkpoint } => {
+        self
+          .scenes
+          .mutate(&scene_id, move |s| {
+            s.checkpoints.insert(name.clone(), checkpoint.clone());
+          })
+          .ok_or_else(|| GameError::SceneNotFound(scene_id))?;
+      }
+      RestoreScene { scene_id, ref name } => {
+        let scene = self.get_scene(scene_id)?;
+        let checkpoint = scene
+          .checkpoints
+          .get(name)
+          .ok_or_else(|| GameError::SceneCheckpointNotFound(scene_id, name.clone()))?
+          .clone();
+        let mut scene = scene.clone();
+        for (creature_id, saved) in &checkpoint.creatures {
+          if let Some(entry) = scene.creatures.get_mut(creature_id) {
+            entry.0 = saved.pos;
+          }
+          self.creatures.mutate(creature_id, |c| {
+            c.cur_health = saved.cur_health;
+            c.cur_energy = saved.cur_energy;
+            c.conditions = saved.conditions.clone();
+          });
+        }
+        self.scenes.insert(scene);
+      }
 
       // ** Classes & Abilities **
       CreateClass { ref path, ref class } => {
@@ -1258,6 +2090,8 @@ impl GameExt for Game {
           a.cost = ability.cost;
           a.action = ability.action.clone();
           a.usable_ooc = ability.usable_ooc;
+          a.action_kind = ability.action_kind;
+          a.uses_per_rest = ability.uses_per_rest;
         });
       }
 
@@ -1316,11 +2150,18 @@ impl GameExt for Game {
         for &(cid, _) in combatants {
           self.check_creature_id(cid)?;
         }
-        self.check_scene(*scene_id)?;
+        let mut scene = self.get_scene(*scene_id)?.clone();
         self.current_combat = Some(Combat::new(*scene_id, combatants.clone())?);
+        scene.preserved_initiative = vec![];
+        self.scenes.insert(scene);
       }
-      StopCombat => {
-        self.current_combat.take().ok_or(GameError::NotInCombat)?;
+      StopCombat { preserve_initiative } => {
+        let combat = self.current_combat.take().ok_or(GameError::NotInCombat)?;
+        if preserve_initiative {
+          let mut scene = self.get_scene(combat.scene)?.clone();
+          scene.preserved_initiative = combat.creatures.iter().cloned().collect();
+          self.scenes.insert(scene);
+        }
       }
       SetCreaturePos { ref scene_id, ref creature_id, ref pos } => {
         let scene = self.get_scene(*scene_id)?.set_pos(*creature_id, *pos)?;
@@ -1334,6 +2175,11 @@ impl GameExt for Game {
           scene.set_pos(*creature_id, dest)?
         };
         self.scenes.insert(scene);
+        if let Some(combat) = self.current_combat.as_mut() {
+          if combat.scene == *scene_id && combat.current_creature_id() == *creature_id {
+            combat.record_movement(path);
+          }
+        }
       }
 
       AddVolumeCondition { ref scene_id, point, volume, condition_id, ref condition, duration } => {
@@ -1397,20 +2243,67 @@ impl GameExt for Game {
     Ok(DynamicCombat { scene, combat, game: self })
   }
 
+  fn combat_turn(&self, scene_id: SceneID, cid: CreatureID) -> Option<bool> {
+    let combat = self.current_combat.as_ref()?;
+    if combat.scene != scene_id || !combat.contains_creature(cid) {
+      return None;
+    }
+    Some(combat.current_creature_id() == cid)
+  }
+
   // ** CONSIDER ** moving this chunk of code to... Scene.rs?
 
-  fn combat_act(&self, abid: AbilityID, target: DecidedTarget) -> Result<ChangedGame, GameError> {
+  fn combat_act(
+    &self, abid: AbilityID, target: DecidedTarget, now: u64,
+  ) -> Result<ChangedGame, GameError> {
     let combat = self.get_combat()?;
     let scene = combat.scene;
     let actor = combat.combat.current_creature_id();
-    self._act(scene, actor, abid, target, true)
+    self.act_or_hold(scene, actor, abid, target, true, now)
   }
 
   fn ooc_act(
-    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget,
+    &self, scene: SceneID, cid: CreatureID, abid: AbilityID, target: DecidedTarget, now: u64,
   ) -> Result<ChangedGame, GameError> {
     let scene = self.get_scene(scene)?;
-    self._act(scene, cid, abid, target, false)
+    // Creatures in combat can only act outside of their own turn with reactions. On their own turn
+    // it's just a regular combat action.
+    let in_combat = match self.combat_turn(scene.id, cid) {
+      Some(true) => return self.combat_act(abid, target, now),
+      Some(false) if self.get_ability(abid)?.action_kind != ActionKind::Reaction => {
+        return Err(GameError::NotYourTurn(cid));
+      }
+      Some(false) => true,
+      None => false,
+    };
+    self.act_or_hold(scene, cid, abid, target, in_combat, now)
+  }
+
+  fn act_or_hold(
+    &self, scene: &Scene, cid: CreatureID, abid: AbilityID, target: DecidedTarget, in_combat: bool,
+    now: u64,
+  ) -> Result<ChangedGame, GameError> {
+    if self.reaction_window.is_some() {
+      return Err(GameError::WaitingForReactions);
+    }
+    let change = self._act(scene, cid, abid, target.clone(), in_combat)?;
+    if self.settings.reaction_window.is_none() {
+      return Ok(change);
+    }
+    let reactors = potential_reactors(self, scene, cid, abid, &target)?;
+    if reactors.is_empty() {
+      return Ok(change);
+    }
+    let window = ReactionWindow {
+      scene_id: scene.id,
+      creature_id: cid,
+      ability_id: abid,
+      target,
+      in_combat,
+      reactors,
+      opened_at: now,
+    };
+    self.change_with(GameLog::OpenReactionWindow { window })
   }
 
   fn _act(
@@ -1422,14 +2315,19 @@ impl GameExt for Game {
     let creature = self.get_creature(cid)?;
     if creature.can_act() {
       if creature.has_ability(abid) {
-        self.creature_act(
-          &creature,
-          scene,
-          self.get_ability(abid)?,
-          target,
-          self.change(),
-          in_combat,
-        )
+        let ability = creature.customize_ability(self.get_ability(abid)?);
+        // Resolving the target reports the more specific problems (like being out of range), and
+        // anything else the client wasn't offered is rejected before the ability is used.
+        if let DecidedTarget::Point(pt) = target {
+          self.check_point_target(scene, cid, &ability.action, pt)?;
+        }
+        if let Action::Creature { target: tspec, .. } = ability.action {
+          self.resolve_creature_targets(&creature, scene, tspec, target.clone())?;
+        }
+        if !self.get_target_options(scene.id, cid, abid)?.allows(cid, &target) {
+          return Err(GameError::IllegalTarget(target));
+        }
+        self.creature_act(&creature, scene, &ability, target, self.change(), in_combat)
       } else {
         Err(GameError::CreatureLacksAbility(creature.id(), abid))
       }
@@ -1442,20 +2340,66 @@ impl GameExt for Game {
     &self, creature: &DynamicCreature, scene: &Scene, ability: &Ability, target: DecidedTarget,
     mut change: ChangedGame, in_combat: bool,
   ) -> Result<ChangedGame, GameError> {
-    let mut change = match ability.action {
+    change = change.apply(&GameLog::CreatureUsedAbility {
+      scene_id: scene.id,
+      creature_id: creature.id(),
+      ability_id: ability.id,
+      target: target.clone(),
+    })?;
+    if let DecidedTarget::Point(pt) = target {
+      self.check_point_target(scene, creature.id(), &ability.action, pt)?;
+    }
+    if ability.uses_per_rest.is_some() {
+      if creature.is_depleted(ability) {
+        return Err(GameError::AbilityDepleted(creature.id(), ability.id));
+      }
+      change = change.apply_creature(creature.id(), |c| {
+        c.creature.change_with(CreatureLog::UseAbility { ability_id: ability.id })
+      })?;
+    }
+    // The energy is spent up front, so the history shows it right after the ability is used and
+    // before any of its effects.
+    if in_combat && ability.cost > Energy(0) {
+      change = change.apply_creature(creature.id(), |c| c.creature.reduce_energy(ability.cost))?;
+    }
+    let change = match ability.action {
       Action::Creature { ref effect, target: tspec } => {
         let targets = self.resolve_creature_targets(creature, scene, tspec, target)?;
-        for creature_id in &targets {
-          change = change.apply_creature(*creature_id, |c| c.apply_effect(effect))?;
+        let targets = targetable_by(self, ability, targets)?;
+        let mut affected = vec![];
+        for creature_id in targets {
+          match ability.attack_roll {
+            Some(ref attack) if creature_id != creature.id() => {
+              let log = roll_attack(&change.game, scene, creature.id(), creature_id, attack)?;
+              change = change.apply(&log)?;
+              let hit = matches!(log, GameLog::AttackRolled { hit: true, .. });
+              let effect = if hit { Some(effect) } else { attack.miss_effect.as_ref() };
+              affected.extend(effect.map(|effect| (creature_id, effect)));
+            }
+            _ => affected.push((creature_id, effect)),
+          }
         }
-        change
-      }
+        // Creatures being attacked get to use their readied reactions before the attack lands.
+        for (creature_id, effect) in &affected {
+          if *creature_id != creature.id() && effect.deals_damage() {
+            change = use_readied_reaction(change, scene, *creature_id)?;
+          }
+        }
+        for (creature_id, effect) in affected {
+          change = change.apply_creature(creature_id, |c| c.apply_effect(effect))?;
+          for (which, to_target) in effect.condition_transfers() {
+            let (from, to) =
+              if to_target { (creature.id(), creature_id) } else { (creature_id, creature.id()) };
+            change = transfer_condition(change, from, to, which)?;
+          }
+        }
+        change
+      }
       Action::SceneVolume { ref effect, target: tspec } => {
         match (effect, tspec, target) {
           (
             &SceneEffect::CreateVolumeCondition { duration, ref condition },
             SceneTarget::RangedVolume { volume, .. },
-            // TODO: unimplemented!: honor and check `range`
             DecidedTarget::Point(point),
           ) => {
             let log = GameLog::AddVolumeCondition {
@@ -1468,16 +2412,143 @@ impl GameExt for Game {
             };
             change = change.apply(&log)?;
           }
+          (&SceneEffect::Summon { template, duration }, _, _) => {
+            for log in change.game.summon(creature.id(), scene, template, duration, in_combat)? {
+              change = change.apply(&log)?;
+            }
+          }
+          (&SceneEffect::Teleport, _, DecidedTarget::Point(point)) => {
+            change = change.apply(&change.game.teleport(creature.id(), scene, point)?)?;
+          }
           _ => return Err(GameError::BuggyProgram("Ugh".to_string())),
         }
         change
       }
     };
+    Ok(change)
+  }
 
+  fn summon(
+    &self, caster: CreatureID, scene: &Scene, template: CreatureID, duration: Duration,
+    in_combat: bool,
+  ) -> Result<Vec<GameLog>, GameError> {
+    if !in_combat && matches!(duration, Duration::Rounds(_)) {
+      return Err(GameError::SummonNeedsCombat(caster));
+    }
+    let template_creature = self.get_creature(template)?.creature;
+    let creature = Creature {
+      id: CreatureID::gen(),
+      conditions: HashMap::new(),
+      ..template_creature.clone()
+    };
+    let creature_id = creature.id;
+    let caster_pos = scene.get_pos(caster)?;
+    let occupied: HashSet<Point3> = scene.creatures.values().map(|&(pt, _)| pt).collect();
+    let pos = self
+      .tile_system
+      .get_all_accessible(
+        caster_pos,
+        &scene.terrain,
+        &scene.terrain_costs,
+        Volume::AABB(creature.size),
+        creature.movement_mode,
+        u32cm(SUMMON_RANGE),
+      )
+      .into_iter()
+      .filter(|pt| !occupied.contains(pt))
+      .min_by_key(|pt| {
+        (self.tile_system.point3_distance(caster_pos, *pt).get::<centimeter>(), *pt)
+      })
+      .ok_or(GameError::NoRoomToSummon(caster))?;
+
+    let path = self.creature_folder(template).unwrap_or_else(FolderPath::root);
+    let initiative = creature.initiative.roll().1 as i16;
+    let mut logs = vec![
+      GameLog::CreateCreature { path, creature },
+      GameLog::AddCreatureToScene {
+        scene_id: scene.id,
+        creature_id,
+        visibility: Visibility::AllPlayers,
+      },
+      GameLog::CreatureEnteredScene { scene_id: scene.id, creature_id },
+      GameLog::SetCreaturePos { scene_id: scene.id, creature_id, pos },
+      GameLog::CreatureLog {
+        creature_id,
+        log: CreatureLog::ApplyCondition {
+          id: ConditionID::gen(),
+          duration,
+          condition: Condition::Summoned,
+        },
+      },
+    ];
     if in_combat {
-      change = change.apply_creature(creature.id(), |c| c.creature.reduce_energy(ability.cost))?;
+      logs.push(GameLog::AddCreatureToCombat { creature_id, initiative });
     }
-    Ok(change)
+    Ok(logs)
+  }
+
+  fn teleport(
+    &self, caster: CreatureID, scene: &Scene, point: Point3,
+  ) -> Result<GameLog, GameError> {
+    let size = self.get_creature(caster)?.creature.size;
+    let caster_pos = scene.get_pos(caster)?;
+    if cover_between(caster_pos, point, &scene.terrain) == Cover::Total {
+      return Err(GameError::PointNotVisible(point));
+    }
+    let mut occupied = HashSet::new();
+    for (&creature_id, &(pos, _)) in scene.creatures.iter().filter(|(cid, _)| **cid != caster) {
+      let other_size = self.get_creature(creature_id)?.creature.size;
+      occupied.extend(self.tile_system.points_in_volume(Volume::AABB(other_size), pos));
+    }
+    let pos = std::iter::once(point)
+      .chain(Direction::ALL.iter().map(|direction| direction.step_from(point)))
+      .filter(|pt| {
+        self.tile_system.volume_fits_at_point(Volume::AABB(size), &scene.terrain, *pt)
+          && self
+            .tile_system
+            .points_in_volume(Volume::AABB(size), *pt)
+            .iter()
+            .all(|footprint| !occupied.contains(footprint))
+      })
+      .min_by_key(|pt| {
+        (
+          self.tile_system.point3_distance(point, *pt).get::<centimeter>(),
+          self.tile_system.point3_distance(caster_pos, *pt).get::<centimeter>(),
+        )
+      })
+      .ok_or(GameError::NoRoomToTeleport(point))?;
+    Ok(GameLog::SetCreaturePos { scene_id: scene.id, creature_id: caster, pos })
+  }
+
+  fn dismiss_summon(
+    &self, creature_id: CreatureID, combat_over: bool,
+  ) -> Result<Vec<GameLog>, GameError> {
+    let creature = self.get_creature(creature_id)?.creature;
+    let expired = creature.conditions.values().any(|applied| match applied.remaining {
+      Duration::Rounds(rounds) if applied.condition == Condition::Summoned => {
+        rounds == 0 || combat_over
+      }
+      _ => false,
+    });
+    if !expired {
+      return Ok(vec![]);
+    }
+    let mut logs = self.scene_departures(&[creature_id]);
+    logs.push(GameLog::DeleteFolderItem {
+      path: self.creature_folder(creature_id).unwrap_or_else(FolderPath::root),
+      item_id: FolderItemID::CreatureID(creature_id),
+    });
+    Ok(logs)
+  }
+
+  fn creature_folder(&self, creature_id: CreatureID) -> Option<FolderPath> {
+    self
+      .campaign
+      .walk_paths(&FolderPath::root())
+      .find(|path| {
+        matches!(self.campaign.get(path), Ok(folder) if folder.creatures.contains(&creature_id))
+      })
+      .cloned()
   }
 
   fn resolve_creature_targets(
@@ -1489,8 +2560,9 @@ impl GameExt for Game {
         if self.tile_system.points_within_distance(
           scene.get_pos(creature.id())?,
           scene.get_pos(cid)?,
-          MELEE_RANGE,
+          creature.reach(),
         ) {
+          check_total_cover(scene, creature.id(), cid)?;
           Ok(vec![cid])
         } else {
           Err(GameError::CreatureOutOfRange(cid))
@@ -1502,6 +2574,7 @@ impl GameExt for Game {
           scene.get_pos(cid)?,
           max,
         ) {
+          check_total_cover(scene, creature.id(), cid)?;
           Ok(vec![cid])
         } else {
           Err(GameError::CreatureOutOfRange(cid))
@@ -1522,10 +2595,15 @@ impl GameExt for Game {
   fn volume_creature_targets(
     &self, scene: &Scene, actor_id: CreatureID, target: CreatureTarget, pt: Point3,
   ) -> Result<Vec<CreatureID>, GameError> {
+    // Creatures with total cover from the volume's origin are shielded from it entirely.
+    let exposed = |origin: Point3, cid: &CreatureID| match scene.get_pos(*cid) {
+      Ok(pos) => cover_between(origin, pos, &scene.terrain) != Cover::Total,
+      Err(_) => false,
+    };
     match target {
       CreatureTarget::AllCreaturesInVolumeInRange { volume, .. } => {
-        // TODO: unimplemented! honor and check `range`
-        Ok(scene.creatures_in_volume(self.tile_system, pt, volume))
+        let cids = scene.creatures_in_volume(self.tile_system, pt, volume);
+        Ok(cids.into_iter().filter(|cid| exposed(pt, cid)).collect())
       }
       CreatureTarget::LineFromActor { distance } => {
         let actor_pos = scene.get_pos(actor_id)?;
@@ -1533,7 +2611,16 @@ impl GameExt for Game {
         let cids = scene.creatures_in_volume(self.tile_system, actor_pos, volume);
         // TODO: *ideally* we should start the line adjacent to the caster, but filtering out
         // also works.
-        let cids = cids.into_iter().filter(|cid| *cid != actor_id).collect();
+        let cids =
+          cids.into_iter().filter(|cid| *cid != actor_id && exposed(actor_pos, cid)).collect();
+        Ok(cids)
+      }
+      CreatureTarget::ConeFromActor { length, angle_degrees } => {
+        let actor_pos = scene.get_pos(actor_id)?;
+        let volume = cone_through_point(actor_pos, pt, length, angle_degrees);
+        let cids = scene.creatures_in_volume(self.tile_system, actor_pos, volume);
+        let cids =
+          cids.into_iter().filter(|cid| *cid != actor_id && exposed(actor_pos, cid)).collect();
         Ok(cids)
       }
       _ => Err(GameError::InvalidTargetForTargetSpec(target, DecidedTarget::Point(pt))),
@@ -1549,7 +2636,8 @@ impl GameExt for Game {
 
     let cids = match ability.action {
       Action::Creature { target, .. } => {
-        self.volume_creature_targets(scene, actor_id, target, pt)?
+        let cids = self.volume_creature_targets(scene, actor_id, target, pt)?;
+        targetable_by(self, ability, cids)?
       }
       Action::SceneVolume { target: SceneTarget::RangedVolume { volume, .. }, .. } => {
         scene.creatures_in_volume(self.tile_system, pt, volume)
@@ -1561,7 +2649,6 @@ impl GameExt for Game {
         ..
       }
       | Action::SceneVolume { target: SceneTarget::RangedVolume { volume, .. }, .. } => {
-        // TODO: unimplemented! honor and check `range`
         scene.open_terrain_in_volume(self, pt, volume)?
       }
       Action::Creature { target: CreatureTarget::LineFromActor { distance }, .. } => {
@@ -1569,11 +2656,111 @@ impl GameExt for Game {
         let volume = line_through_point(actor_pos, pt, distance);
         scene.open_terrain_in_volume(self, actor_pos, volume)?
       }
+      Action::Creature {
+        target: CreatureTarget::ConeFromActor { length, angle_degrees }, ..
+      } => {
+        let actor_pos = scene.get_pos(actor_id)?;
+        let volume = cone_through_point(actor_pos, pt, length, angle_degrees);
+        scene.open_terrain_in_volume(self, actor_pos, volume)?
+      }
       _ => vec![],
     };
     Ok((cids, tiles))
   }
 
+  fn preview_ability(
+    &self, scene: &Scene, actor_id: CreatureID, ability_id: AbilityID, target: DecidedTarget,
+  ) -> Result<Vec<(CreatureID, EffectPreview)>, GameError> {
+    let actor = self.get_creature(actor_id)?;
+    let ability = actor.customize_ability(self.get_ability(ability_id)?);
+    match ability.action {
+      Action::Creature { ref effect, target: tspec } => {
+        let origin = match (tspec, &target) {
+          (CreatureTarget::AllCreaturesInVolumeInRange { .. }, DecidedTarget::Point(pt)) => *pt,
+          _ => scene.get_pos(actor_id)?,
+        };
+        let targets = self.resolve_creature_targets(&actor, scene, tspec, target)?;
+        let mut targets = targetable_by(self, &ability, targets)?;
+        targets.sort();
+        targets
+          .into_iter()
+          .map(|cid| {
+            let cover = cover_between(origin, scene.get_pos(cid)?, &scene.terrain);
+            let preview = preview_effect(effect, &self.get_creature(cid)?);
+            Ok((cid, EffectPreview { cover, ..preview }))
+          })
+          .collect()
+      }
+      Action::SceneVolume { .. } => Ok(vec![]),
+    }
+  }
+
+  fn cell_info(&self, scene_id: SceneID, pt: Point3) -> Result<CellInfo, GameError> {
+    let scene = self.get_scene(scene_id)?;
+    let mut creatures = vec![];
+    for (creature_id, &(pos, _)) in &scene.creatures {
+      let size = self.get_creature(*creature_id)?.creature.size;
+      if self.tile_system.points_in_volume(Volume::AABB(size), pos).contains(&pt) {
+        creatures.push(*creature_id);
+      }
+    }
+    creatures.sort();
+    let cell = HashMap::from([((), pt)]);
+    let covers = |vc: &VolumeCondition| {
+      !self.tile_system.items_within_volume(vc.volume, vc.point, &cell).is_empty()
+    };
+    let mut volume_conditions: Vec<ConditionID> =
+      scene.volume_conditions.iter().filter(|(_, vc)| covers(vc)).map(|(id, _)| *id).collect();
+    volume_conditions.sort();
+    Ok(CellInfo { open: scene.terrain.contains(&pt), creatures, volume_conditions })
+  }
+
+  fn scene_render_state(
+    &self, scene_id: SceneID, player_id: Option<&PlayerID>,
+  ) -> Result<SceneRenderState, GameError> {
+    let scene = self.get_scene(scene_id)?;
+    let controlled = match player_id {
+      Some(player_id) => Some(self.controlled_creatures(player_id)?),
+      None => None,
+    };
+    let visible = |creature_id: CreatureID, visibility: &Visibility| match &controlled {
+      Some(controlled) => {
+        *visibility == Visibility::AllPlayers || controlled.contains(&creature_id)
+      }
+      None => true,
+    };
+    let mut creatures: Vec<(CreatureID, Point3)> = scene
+      .creatures
+      .iter()
+      .filter(|(creature_id, (_, visibility))| visible(**creature_id, visibility))
+      .map(|(creature_id, &(pos, _))| (*creature_id, pos))
+      .collect();
+    creatures.sort();
+    let highlights = scene
+      .highlights
+      .iter()
+      .filter(|(_, (_, visibility))| player_id.is_none() || *visibility == Visibility::AllPlayers)
+      .map(|(pt, highlight)| (*pt, highlight.clone()))
+      .collect();
+    Ok(SceneRenderState {
+      creatures,
+      terrain: scene.terrain.clone(),
+      terrain_costs: scene.terrain_costs.clone(),
+      volume_conditions: scene.volume_conditions.clone(),
+      highlights,
+    })
+  }
+
+  fn creatures_and_terrain_in_volume(
+    &self, scene: &Scene, pt: Point3, volume: Volume,
+  ) -> Result<(Vec<CreatureID>, Vec<Point3>), GameError> {
+    let mut cids = scene.creatures_in_volume(self.tile_system, pt, volume);
+    cids.sort();
+    let mut tiles = scene.open_terrain_in_volume(self, pt, volume)?;
+    tiles.sort();
+    Ok((cids, tiles))
+  }
+
   fn get_movement_options(
     &self, scene: SceneID, creature_id: CreatureID,
   ) -> Result<Vec<Point3>, GameError> {
@@ -1583,7 +2770,9 @@ impl GameExt for Game {
       Ok(self.tile_system.get_all_accessible(
         scene.get_pos(creature_id)?,
         &scene.terrain,
+        &scene.terrain_costs,
         Volume::AABB(creature.creature.size),
+        creature.creature.movement_mode,
         creature.speed(),
       ))
     } else {
@@ -1596,11 +2785,16 @@ impl GameExt for Game {
     &self, scene: SceneID, creature_id: CreatureID, ability_id: AbilityID,
   ) -> Result<PotentialTargets, GameError> {
     let ability = self.get_ability(ability_id)?;
+    let creature = self.get_creature(creature_id)?;
+    // The creature may have lost the ability, e.g. to an expired condition, since it was chosen.
+    if !creature.has_ability(ability_id) {
+      return Err(GameError::CreatureLacksAbility(creature_id, ability_id));
+    }
 
     use crate::types::{Action as A, CreatureTarget as CT};
-    Ok(match ability.action {
+    let options = match ability.action {
       A::Creature { target: CT::Melee, .. } => {
-        self.creatures_in_range(scene, creature_id, MELEE_RANGE)?
+        self.creatures_in_range(scene, creature_id, creature.reach())?
       }
       A::Creature { target: CT::Range(distance), .. } => {
         self.creatures_in_range(scene, creature_id, distance)?
@@ -1608,14 +2802,33 @@ impl GameExt for Game {
       A::Creature { target: CT::Actor, .. } => PotentialTargets::CreatureIDs(vec![creature_id]),
       A::Creature { target: CT::AllCreaturesInVolumeInRange { range, .. }, .. }
       | A::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => {
-        self.open_terrain_in_range(scene, creature_id, range)?
+        // Only offer the points that check_point_target will accept, i.e. the ones in sight.
+        let scene = self.get_scene(scene)?;
+        match self.open_terrain_in_range(scene.id, creature_id, range)? {
+          PotentialTargets::Points(pts) => PotentialTargets::Points(
+            pts
+              .into_iter()
+              .filter(|pt| {
+                self.check_point_target(scene, creature_id, &ability.action, *pt).is_ok()
+              })
+              .collect(),
+          ),
+          cids => cids,
+        }
       }
-      A::Creature { target: CT::LineFromActor { distance }, .. } => {
+      A::Creature { target: CT::LineFromActor { distance }, .. }
+      | A::Creature { target: CT::ConeFromActor { length: distance, .. }, .. } => {
         self.open_terrain_in_range(scene, creature_id, distance)?
       }
       A::Creature { target: CT::SomeCreaturesInVolumeInRange { .. }, .. } => {
         unimplemented!("SomeCreaturesInVolumeInRange not implemented")
       }
+    };
+    Ok(match options {
+      PotentialTargets::CreatureIDs(cids) => {
+        PotentialTargets::CreatureIDs(targetable_by(self, ability, cids)?)
+      }
+      points => points,
     })
   }
 
@@ -1625,9 +2838,33 @@ impl GameExt for Game {
     let scene = self.get_scene(scene)?;
     let creature_pos = scene.get_pos(creature_id)?;
     let pts = self.tile_system.open_points_in_range(creature_pos, &scene.terrain, range);
+    let pts = pts
+      .into_iter()
+      .filter(|pt| self.tile_system.points_within_distance(creature_pos, *pt, range))
+      .collect();
     Ok(PotentialTargets::Points(pts))
   }
 
+  fn check_point_target(
+    &self, scene: &Scene, actor_id: CreatureID, action: &Action, pt: Point3,
+  ) -> Result<(), GameError> {
+    let range = match *action {
+      Action::Creature { target: CreatureTarget::AllCreaturesInVolumeInRange { range, .. }, .. }
+      | Action::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => range,
+      _ => return Ok(()),
+    };
+    let actor_pos = scene.get_pos(actor_id)?;
+    if !scene.terrain.contains(&pt)
+      || !self.tile_system.points_within_distance(actor_pos, pt, range)
+    {
+      return Err(GameError::PointOutOfRange(pt));
+    }
+    if !self.tile_system.has_line_of_sight(&scene.terrain, actor_pos, pt) {
+      return Err(GameError::PointNotVisible(pt));
+    }
+    Ok(())
+  }
+
   fn creatures_in_range(
     &self, scene: SceneID, creature_id: CreatureID, distance: u32units::Length,
   ) -> Result<PotentialTargets, GameError> {
@@ -1635,24 +2872,231 @@ impl GameExt for Game {
     let my_pos = scene.get_pos(creature_id)?;
     let mut results = vec![];
     for (creature_id, &(creature_pos, _)) in &scene.creatures {
-      if self.tile_system.points_within_distance(my_pos, creature_pos, distance) {
+      if self.tile_system.points_within_distance(my_pos, creature_pos, distance)
+        && cover_between(my_pos, creature_pos, &scene.terrain) != Cover::Total
+      {
         results.push(*creature_id);
       }
     }
     Ok(PotentialTargets::CreatureIDs(results))
   }
 
+  fn threatened_points(
+    &self, scene: SceneID, creature_id: CreatureID,
+  ) -> Result<Vec<Point3>, GameError> {
+    let reach = self.get_creature(creature_id)?.reach();
+    let scene = self.get_scene(scene)?;
+    let pos = scene.get_pos(creature_id)?;
+    let mut pts: Vec<Point3> = self
+      .tile_system
+      .open_points_in_range(pos, &scene.terrain, reach)
+      .into_iter()
+      .filter(|pt| *pt != pos && self.tile_system.points_within_distance(pos, *pt, reach))
+      .collect();
+    pts.sort();
+    Ok(pts)
+  }
+
   // ** END CONSIDERATION **
 
   fn get_class(&self, class: ClassID) -> Result<&Class, GameError> {
     self.classes.get(&class).ok_or_else(|| GameError::ClassNotFound(class))
   }
 
-  fn change(&self) -> ChangedGame { ChangedGame { game: self.clone(), logs: vec![] } }
+  fn scene_departures(&self, cids: &[CreatureID]) -> Vec<GameLog> {
+    let mut scenes: Vec<&Scene> = self.scenes.values().collect();
+    scenes.sort_by_key(|s| s.id);
+    let mut logs = vec![];
+    for creature_id in cids {
+      for scene in &scenes {
+        if scene.creatures.contains_key(creature_id) {
+          logs.push(GameLog::CreatureLeftScene { scene_id: scene.id, creature_id: *creature_id });
+        }
+      }
+    }
+    logs
+  }
+
+  fn dead_creature_removal(&self, creature_id: CreatureID) -> Vec<GameLog> {
+    if !self.creature_controllers(creature_id).is_empty() {
+      return vec![];
+    }
+    let mut logs = vec![];
+    if matches!(&self.current_combat, Some(combat) if combat.contains_creature(creature_id)) {
+      logs.push(GameLog::RemoveCreatureFromCombat { creature_id });
+    }
+    for departure in self.scene_departures(&[creature_id]) {
+      if let GameLog::CreatureLeftScene { scene_id, .. } = departure {
+        logs.push(GameLog::RemoveCreatureFromScene { scene_id, creature_id });
+      }
+      logs.push(departure);
+    }
+    logs
+  }
+
+  fn integrity_check(&self) -> Vec<IntegrityIssue> {
+    use IntegrityIssue::*;
+    let mut issues = vec![];
+
+    let mut scenes: Vec<&Scene> = self.scenes.values().collect();
+    scenes.sort_by_key(|s| s.id);
+    for scene in scenes {
+      let mut cids: Vec<CreatureID> =
+        scene.creatures.keys().filter(|cid| !self.creatures.contains_key(cid)).cloned().collect();
+      cids.sort();
+      let scene_id = scene.id;
+      issues.extend(
+        cids.into_iter().map(|creature_id| MissingSceneCreature { scene_id, creature_id }),
+      );
+    }
+
+    if let Some(combat) = &self.current_combat {
+      if !self.scenes.contains_key(&combat.scene) {
+        issues.push(MissingCombatScene { scene_id: combat.scene });
+      }
+      for creature_id in combat.creature_ids() {
+        if !self.creatures.contains_key(&creature_id) {
+          issues.push(MissingCombatant { creature_id });
+        }
+      }
+    }
+
+    let mut filed_creatures = HashSet::new();
+    let mut filed_scenes = HashSet::new();
+    for path in self.campaign.walk_paths(&FolderPath::root()) {
+      let folder = self.campaign.get(path).expect("folder we're walking must exist");
+      filed_creatures.extend(folder.creatures.iter().cloned());
+      filed_scenes.extend(folder.scenes.iter().cloned());
+      let mut missing = vec![];
+      missing.extend(missing_ids(&folder.creatures, &self.creatures, FolderItemID::CreatureID));
+      missing.extend(missing_ids(&folder.scenes, &self.scenes, FolderItemID::SceneID));
+      missing.extend(missing_ids(&folder.items, &self.items, FolderItemID::ItemID));
+      missing.extend(missing_ids(&folder.abilities, &self.abilities, FolderItemID::AbilityID));
+      missing.extend(missing_ids(&folder.classes, &self.classes, FolderItemID::ClassID));
+      issues.extend(
+        missing.into_iter().map(|item_id| MissingFolderItem { path: path.clone(), item_id }),
+      );
+    }
+
+    let mut players: Vec<&Player> = self.players.values().collect();
+    players.sort_by(|a, b| a.player_id.cmp(&b.player_id));
+    for player in players {
+      let player_id = &player.player_id;
+      let mut cids: Vec<CreatureID> =
+        player.creatures.iter().filter(|cid| !self.creatures.contains_key(cid)).cloned().collect();
+      cids.sort();
+      issues.extend(cids.into_iter().map(|creature_id| MissingPlayerCreature {
+        player_id: player_id.clone(),
+        creature_id,
+      }));
+      if let Some(scene_id) = player.scene {
+        if !self.scenes.contains_key(&scene_id) {
+          issues.push(MissingPlayerScene { player_id: player_id.clone(), scene_id });
+        }
+      }
+    }
+
+    let mut unfiled_creatures: Vec<CreatureID> =
+      self.creatures.keys().filter(|cid| !filed_creatures.contains(*cid)).cloned().collect();
+    unfiled_creatures.sort();
+    issues.extend(unfiled_creatures.into_iter().map(|creature_id| UnfiledCreature { creature_id }));
+    let mut unfiled_scenes: Vec<SceneID> =
+      self.scenes.keys().filter(|sid| !filed_scenes.contains(*sid)).cloned().collect();
+    unfiled_scenes.sort();
+    issues.extend(unfiled_scenes.into_iter().map(|scene_id| UnfiledScene { scene_id }));
+
+    issues
+  }
+
+  fn scene_checkpoint(&self, scene_id: SceneID) -> Result<SceneCheckpoint, GameError> {
+    let scene = self.get_scene(scene_id)?;
+    let mut creatures = HashMap::new();
+    for (creature_id, (pos, _)) in &scene.creatures {
+      let creature = self
+        .creatures
+        .get(creature_id)
+        .ok_or_else(|| GameError::CreatureNotFound(creature_id.to_string()))?;
+      let checkpoint = CreatureCheckpoint {
+        pos: *pos,
+        cur_health: creature.cur_health,
+        cur_energy: creature.cur_energy,
+        conditions: creature.conditions.clone(),
+      };
+      creatures.insert(*creature_id, checkpoint);
+    }
+    Ok(SceneCheckpoint { creatures })
+  }
+
+  fn export_combat_replay(
+    &self, logs: &[GameLog], combat: usize,
+  ) -> Result<CombatReplay, GameError> {
+    let mut game = self.clone();
+    let mut combats_started = 0;
+    let mut logs = logs_in_effect(logs)?.into_iter();
+    while let Some(log) = logs.next() {
+      if let GameLog::StartCombat { .. } = log {
+        if combats_started == combat {
+          let start = game.clone();
+          game.apply_log_mut(log)?;
+          let mut replay_logs = vec![log.clone()];
+          // The combat doesn't necessarily end with a StopCombat; removing the last combatant
+          // ends it too.
+          for log in logs {
+            if game.current_combat.is_none() {
+              break;
+            }
+            game.apply_log_mut(log)?;
+            replay_logs.push(log.clone());
+          }
+          return Ok(CombatReplay { version: COMBAT_REPLAY_VERSION, start, logs: replay_logs });
+        }
+        combats_started += 1;
+      }
+      game.apply_log_mut(log)?;
+    }
+    Err(GameError::CombatNotFound(combat))
+  }
+
+  fn repair_integrity(&self) -> Result<ChangedGame, GameError> {
+    use IntegrityIssue::*;
+    let mut change = self.change();
+    for issue in self.integrity_check() {
+      let log = match issue {
+        MissingSceneCreature { scene_id, creature_id } => {
+          GameLog::RemoveCreatureFromScene { scene_id, creature_id }
+        }
+        MissingCombatScene { .. } => GameLog::StopCombat { preserve_initiative: false },
+        // Stopping a combat whose scene is missing also takes care of its combatants.
+        MissingCombatant { .. } if change.game.current_combat.is_none() => continue,
+        MissingCombatant { creature_id } => GameLog::RemoveCreatureFromCombat { creature_id },
+        MissingFolderItem { path, item_id } => GameLog::UnlinkFolderItem { path, item_id },
+        MissingPlayerCreature { player_id, creature_id } => {
+          GameLog::RemoveCreaturesFromPlayer { player_id, creature_ids: vec![creature_id] }
+        }
+        MissingPlayerScene { player_id, .. } => {
+          GameLog::SetPlayerScene { player_id, scene_id: None }
+        }
+        UnfiledCreature { creature_id } => GameLog::LinkFolderItem {
+          path: FolderPath::root(),
+          item_id: FolderItemID::CreatureID(creature_id),
+        },
+        UnfiledScene { scene_id } => GameLog::LinkFolderItem {
+          path: FolderPath::root(),
+          item_id: FolderItemID::SceneID(scene_id),
+        },
+      };
+      change = change.apply(&log)?;
+    }
+    Ok(change)
+  }
+
+  fn change(&self) -> ChangedGame {
+    ChangedGame { game: self.clone(), logs: vec![], movement: None }
+  }
 
   fn change_with(&self, log: GameLog) -> Result<ChangedGame, GameError> {
     let game = self.apply_log(&log)?;
-    Ok(ChangedGame { game, logs: vec![log] })
+    Ok(ChangedGame { game, logs: vec![log], movement: None })
   }
 
   fn change_with_logs(&self, logs: Vec<GameLog>) -> Result<ChangedGame, GameError> {
@@ -1660,11 +3104,13 @@ impl GameExt for Game {
     for log in logs.iter() {
       game = game.apply_log(log)?;
     }
-    Ok(ChangedGame { game, logs })
+    Ok(ChangedGame { game, logs, movement: None })
   }
 }
 
 pub trait ChangedGameExt {
+  /// Apply a log to the game and record it after the logs already in the change, the same way
+  /// `change_with_logs` would. Nothing is recorded if the log can't be applied.
   fn apply(&self, log: &GameLog) -> Result<ChangedGame, GameError>;
 
   fn apply_combat<'game, F>(&'game self, f: F) -> Result<ChangedGame, GameError>
@@ -1675,6 +3121,17 @@ pub trait ChangedGameExt {
   where
     F: FnOnce(DynamicCreature) -> Result<ChangedCreature, GameError>;
 
+  /// Fill in `movement` from the current combat, if there is one.
+  fn with_movement(self) -> ChangedGame;
+
+  /// Log that the current combat creature's turn has begun, if there's still a combat.
+  fn with_turn_began(self) -> Result<ChangedGame, GameError>;
+
+  /// Take a creature out of the combat and off of its scenes if it's dead and
+  /// `GameSettings::auto_remove_dead` is on. A creature that dies during its own turn is left
+  /// alone until that turn is over, so this is also done whenever a turn ends.
+  fn remove_if_dead(self, creature_id: CreatureID) -> Result<ChangedGame, GameError>;
+
   fn done(self) -> (Game, Vec<GameLog>);
 }
 
@@ -1682,6 +3139,7 @@ impl ChangedGameExt for ChangedGame {
   fn apply(&self, log: &GameLog) -> Result<ChangedGame, GameError> {
     let mut new = self.clone();
     new.game = self.game.apply_log(log)?;
+    new.logs.push(log.clone());
     Ok(new)
   }
 
@@ -1706,16 +3164,452 @@ impl ChangedGameExt for ChangedGame {
     let change = f(creature)?;
     let mut new = self.clone();
     let (creature, logs) = change.done();
+    let died = logs.iter().any(|log| {
+      matches!(log, CreatureLog::ApplyCondition { condition: Condition::Dead, .. })
+    });
     new.game.creatures.mutate(&cid, move |c| *c = creature);
     new.logs.extend(creature_logs_into_game_logs(cid, logs));
+    let current_turn = matches!(
+      &new.game.current_combat,
+      Some(combat) if combat.current_creature_id() == cid
+    );
+    if died && !current_turn {
+      new = new.remove_if_dead(cid)?;
+    }
+    Ok(new)
+  }
+
+  fn with_movement(self) -> ChangedGame {
+    let movement = self.game.get_combat().and_then(|combat| combat.movement()).ok();
+    ChangedGame { movement, ..self }
+  }
+
+  fn with_turn_began(self) -> Result<ChangedGame, GameError> {
+    match self.game.get_combat().map(|combat| combat.combat.current_creature_id()) {
+      Ok(creature_id) => self.apply(&GameLog::TurnBegan { creature_id }),
+      Err(_) => Ok(self),
+    }
+  }
+
+  fn remove_if_dead(self, creature_id: CreatureID) -> Result<ChangedGame, GameError> {
+    let dead = self.game.creatures.get(&creature_id).is_some_and(|creature| {
+      creature.conditions.values().any(|applied| applied.condition == Condition::Dead)
+    });
+    if !dead || !self.game.settings.auto_remove_dead {
+      return Ok(self);
+    }
+    let mut new = self;
+    for log in new.game.dead_creature_removal(creature_id) {
+      new = new.apply(&log)?;
+    }
     Ok(new)
   }
 
   fn done(self) -> (Game, Vec<GameLog>) { (self.game, self.logs) }
 }
 
+/// An `io::Write` that computes the 64-bit FNV-1a hash of everything written to it. We don't use
+/// `DefaultHasher` because its output isn't guaranteed to be stable across Rust releases.
+struct FnvWriter(u64);
+
+impl FnvWriter {
+  fn new() -> FnvWriter { FnvWriter(0xcbf2_9ce4_8422_2325) }
+}
+
+impl io::Write for FnvWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for byte in buf {
+      self.0 ^= u64::from(*byte);
+      self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
 fn bug<T>(msg: &str) -> Result<T, GameError> { Err(GameError::BuggyProgram(msg.to_string())) }
 
+/// The IDs in a folder's set that don't exist in `existing`, sorted and wrapped as FolderItemIDs.
+fn missing_ids<T: DeriveKey>(
+  ids: &HashSet<T::KeyType>, existing: &IndexedHashMap<T>, wrap: fn(T::KeyType) -> FolderItemID,
+) -> Vec<FolderItemID>
+where
+  T::KeyType: Copy + Ord,
+{
+  let mut missing: Vec<T::KeyType> =
+    ids.iter().filter(|id| !existing.contains_key(id)).cloned().collect();
+  missing.sort();
+  missing.into_iter().map(wrap).collect()
+}
+
+/// Have a creature use its readied reaction on itself, if it's able to, and clear it.
+fn use_readied_reaction(
+  change: ChangedGame, scene: &Scene, reactor: CreatureID,
+) -> Result<ChangedGame, GameError> {
+  let reacted = {
+    let game = &change.game;
+    let creature = game.get_creature(reactor)?;
+    let ability = match creature.creature.readied_reaction {
+      Some(abid) if creature.can_act() && creature.has_ability(abid) => game.get_ability(abid)?,
+      _ => return Ok(change),
+    };
+    let in_combat = game.combat_turn(scene.id, reactor).is_some();
+    if !ability.can_be_readied()
+      || (in_combat && creature.creature.cur_energy < ability.cost)
+      || creature.is_depleted(ability)
+    {
+      return Ok(change);
+    }
+    let ability = creature.customize_ability(ability);
+    game.creature_act(&creature, scene, &ability, DecidedTarget::Actor, change.clone(), in_combat)?
+  };
+  reacted.apply_creature(reactor, |c| {
+    c.creature.change_with(CreatureLog::ReadyReaction { ability_id: None })
+  })
+}
+
+/// Leave out the creatures that can't be targeted by an ability at all, keeping the rest in order.
+fn targetable_by(
+  game: &Game, ability: &Ability, cids: Vec<CreatureID>,
+) -> Result<Vec<CreatureID>, GameError> {
+  let mut targetable = vec![];
+  for cid in cids {
+    if game.get_creature(cid)?.can_be_targeted_by(ability) {
+      targetable.push(cid);
+    }
+  }
+  Ok(targetable)
+}
+
+/// The creatures other than `actor` that an ability would deal damage to and that could react to
+/// it, sorted by ID. Volume abilities hit whoever is in the volume when they land, so nobody gets
+/// to react to those ahead of time. Creatures with a readied reaction use that one instead.
+fn potential_reactors(
+  game: &Game, scene: &Scene, actor: CreatureID, abid: AbilityID, target: &DecidedTarget,
+) -> Result<Vec<CreatureID>, GameError> {
+  let creature = game.get_creature(actor)?;
+  let ability = creature.customize_ability(game.get_ability(abid)?);
+  let (effect, tspec) = match ability.action {
+    Action::Creature { ref effect, target } => (effect, target),
+    Action::SceneVolume { .. } => return Ok(vec![]),
+  };
+  let miss_effect = ability.attack_roll.as_ref().and_then(|attack| attack.miss_effect.as_ref());
+  if !effect.deals_damage() && !matches!(miss_effect, Some(miss) if miss.deals_damage()) {
+    return Ok(vec![]);
+  }
+  let can_react = |cid: CreatureID| -> Result<bool, GameError> {
+    let reactor = game.get_creature(cid)?;
+    if !reactor.can_act() || reactor.creature.readied_reaction.is_some() {
+      return Ok(false);
+    }
+    Ok(reactor.ability_statuses().iter().any(|status| {
+      game.get_ability(status.ability_id).is_ok_and(|ability| ability.can_be_readied())
+    }))
+  };
+  let targets = game.resolve_creature_targets(&creature, scene, tspec, target.clone())?;
+  let mut reactors = vec![];
+  for cid in targetable_by(game, &ability, targets)? {
+    if cid != actor && !reactors.contains(&cid) && can_react(cid)? {
+      reactors.push(cid);
+    }
+  }
+  reactors.sort();
+  Ok(reactors)
+}
+
+/// Whether a GMCommand would change what the action held in a reaction window does, by moving
+/// creatures, ending the turn or combat, or taking creatures out of the scene, so it has to wait
+/// until the window is closed.
+fn disturbs_reaction_window(cmd: &GMCommand) -> bool {
+  use GMCommand::*;
+  matches!(
+    cmd,
+    EndTurn
+      | ForceNextTurn
+      | ForcePrevTurn
+      | StopCombat { .. }
+      | PathCurrentCombatCreature { .. }
+      | PathCreature { .. }
+      | SetCreaturePos { .. }
+      | RemoveCreatureFromCombat { .. }
+      | RemoveCreatureFromScene { .. }
+      | DeleteFolderItem { item_id: FolderItemID::CreatureID(_), .. }
+  )
+}
+
+/// Close the reaction window and carry out the action it was holding. If the action can't be done
+/// anymore (say, the actor was knocked out by a reaction), the window is just closed, and why is
+/// logged.
+fn resolve_reaction_window(change: ChangedGame) -> Result<ChangedGame, GameError> {
+  let window = change.game.reaction_window.clone().ok_or(GameError::NoReactionWindow)?;
+  let mut change = change.apply(&GameLog::CloseReactionWindow)?;
+  let acted = change.game.get_scene(window.scene_id).and_then(|scene| {
+    change.game._act(scene, window.creature_id, window.ability_id, window.target, window.in_combat)
+  });
+  match acted {
+    Ok(acted) => {
+      change.game = acted.game;
+      change.logs.extend(acted.logs);
+    }
+    Err(error) => warn!(
+      event = "held-action-failed",
+      creature_id = %window.creature_id,
+      ability_id = %window.ability_id,
+      ?error
+    ),
+  }
+  Ok(change)
+}
+
+/// Move a condition matching `which` from one creature to another, keeping its remaining duration.
+/// If several match, the one with the lowest ID is moved. This does nothing if none match.
+fn transfer_condition(
+  change: ChangedGame, from: CreatureID, to: CreatureID, which: &ConditionSelector,
+) -> Result<ChangedGame, GameError> {
+  let (id, applied) = {
+    let source = change.game.get_creature(from)?;
+    let found = source
+      .creature
+      .conditions
+      .iter()
+      .filter(|(_, applied)| which.matches(&applied.condition))
+      .min_by_key(|(id, _)| **id);
+    match found {
+      Some((id, applied)) => (*id, applied.clone()),
+      None => return Ok(change),
+    }
+  };
+  // A receiver that's immune can't take the condition, so the source keeps it.
+  if change.game.get_creature(to)?.is_immune_to(&applied.condition) {
+    return Ok(change);
+  }
+  let change =
+    change.apply_creature(from, |c| c.creature.change_with(CreatureLog::RemoveCondition { id }))?;
+  let effect = CreatureEffect::ApplyCondition(applied.remaining, applied.condition);
+  change.apply_creature(to, |c| c.apply_effect(&effect))
+}
+
+/// Roll an attack against a target, whose defense is raised by any cover it has from the attacker.
+fn roll_attack(
+  game: &Game, scene: &Scene, attacker: CreatureID, target: CreatureID, attack: &AttackRoll,
+) -> Result<GameLog, GameError> {
+  let cover = cover_between(scene.get_pos(attacker)?, scene.get_pos(target)?, &scene.terrain);
+  let defense = game.get_creature(target)?.defense().saturating_add(cover.defense_bonus());
+  let roll = attack.dice.roll().1;
+  Ok(GameLog::AttackRolled {
+    scene_id: scene.id,
+    creature_id: attacker,
+    target_id: target,
+    roll,
+    defense,
+    hit: roll >= i32::from(defense),
+  })
+}
+
+/// Fail if terrain completely shields the target from the actor.
+fn check_total_cover(
+  scene: &Scene, actor_id: CreatureID, target_id: CreatureID,
+) -> Result<(), GameError> {
+  let cover = cover_between(scene.get_pos(actor_id)?, scene.get_pos(target_id)?, &scene.terrain);
+  if cover == Cover::Total {
+    return Err(GameError::TargetHasTotalCover(target_id));
+  }
+  Ok(())
+}
+
+/// Apply a combat replay's logs to its starting game, reproducing the game as it was when the
+/// combat ended.
+pub fn replay_combat(replay: &CombatReplay) -> Result<Game, GameError> {
+  if replay.version != COMBAT_REPLAY_VERSION {
+    return Err(GameError::UnsupportedReplayVersion(replay.version));
+  }
+  let mut game = replay.start.clone();
+  for log in &replay.logs {
+    game.apply_log_mut(log)?;
+  }
+  Ok(game)
+}
+
+/// Rebuild the game as it was after the first `index.log_idx` logs were applied to the snapshot, so
+/// index 0 is the snapshot itself. Games only have a single snapshot so far, so `snapshot` and
+/// `logs` must be snapshot 0 and every log applied to it.
+pub fn game_at_index(
+  snapshot: &Game, logs: &[GameLog], index: GameIndex,
+) -> Result<Game, GameError> {
+  let mut game = snapshot.clone();
+  for log in logs_in_effect(history_at(logs, index)?)? {
+    game.apply_log_mut(log)?;
+  }
+  Ok(game)
+}
+
+/// The logs of a snapshot's history that are still in effect at the end of it: every log except
+/// `Rollback`s and the logs they rolled back, in order. Applying these to the snapshot gives the
+/// same game as the whole history does, without having to handle any rollbacks.
+pub fn logs_in_effect(logs: &[GameLog]) -> Result<Vec<&GameLog>, GameError> {
+  // For each log, the last log in effect right after it (None when that's just the snapshot), and
+  // for each log that isn't a rollback, the last log in effect right before it.
+  let mut latest: Vec<Option<usize>> = Vec::with_capacity(logs.len());
+  let mut previous: Vec<Option<usize>> = Vec::with_capacity(logs.len());
+  for (idx, log) in logs.iter().enumerate() {
+    match *log {
+      GameLog::Rollback { snapshot_index, log_index } => {
+        let index = GameIndex { game_idx: snapshot_index, log_idx: log_index };
+        let target = history_at(&logs[..idx], index)?.len();
+        latest.push(target.checked_sub(1).and_then(|last| latest[last]));
+        previous.push(None);
+      }
+      _ => {
+        previous.push(idx.checked_sub(1).and_then(|prior| latest[prior]));
+        latest.push(Some(idx));
+      }
+    }
+  }
+  let mut in_effect = vec![];
+  let mut next = latest.last().copied().flatten();
+  while let Some(idx) = next {
+    in_effect.push(&logs[idx]);
+    next = previous[idx];
+  }
+  in_effect.reverse();
+  Ok(in_effect)
+}
+
+/// The logs of a snapshot's history that were applied to produce the game at `index`.
+fn history_at(logs: &[GameLog], index: GameIndex) -> Result<&[GameLog], GameError> {
+  if index.game_idx != 0 || index.log_idx > logs.len() {
+    return Err(GameError::HistoryNotFound(index.game_idx, index.log_idx));
+  }
+  Ok(&logs[..index.log_idx])
+}
+
+/// Apply the next log of a snapshot's history to `game`, which must be the result of applying
+/// `prior` to `snapshot`. Unlike `apply_log`, this handles `Rollback` logs, which reset the game
+/// to an earlier point in that history. Any way of replaying a history has to go through this or
+/// `logs_in_effect`.
+pub fn apply_history_log(
+  snapshot: &Game, prior: &[GameLog], game: &mut Game, log: &GameLog,
+) -> Result<(), GameError> {
+  match *log {
+    GameLog::Rollback { snapshot_index, log_index } => {
+      let index = GameIndex { game_idx: snapshot_index, log_idx: log_index };
+      *game = game_at_index(snapshot, prior, index)?;
+      Ok(())
+    }
+    _ => game.apply_log_mut(log),
+  }
+}
+
+/// The hex encoding of a game's content hash, as reported to clients.
+pub fn content_hash_hex(game: &Game) -> Result<String, GameError> {
+  Ok(format!("{:016x}", game.content_hash()?))
+}
+
+/// Find the index of the most recent point in a snapshot's history where the game had the given
+/// hex-encoded content hash. This is index 0 if only the snapshot itself has it.
+pub fn find_content_hash(
+  snapshot: &Game, logs: &[GameLog], content_hash: &str,
+) -> Result<GameIndex, GameError> {
+  let mut game = snapshot.clone();
+  let mut found = None;
+  if content_hash_hex(&game)? == content_hash {
+    found = Some(GameIndex { game_idx: 0, log_idx: 0 });
+  }
+  for (idx, log) in logs.iter().enumerate() {
+    apply_history_log(snapshot, &logs[..idx], &mut game, log)?;
+    if content_hash_hex(&game)? == content_hash {
+      found = Some(GameIndex { game_idx: 0, log_idx: idx + 1 });
+    }
+  }
+  found.ok_or_else(|| GameError::ContentHashNotFound(content_hash.to_string()))
+}
+
+/// Splits the most recent combat fought over a snapshot's history into rounds and turns, one log at
+/// a time, so it can be kept up to date as logs are applied. A turn ends with the `EndTurn` or
+/// forced turn change that follows it, and rounds are numbered by the combat's own `round`. Turns
+/// the GM forces back to are recorded as new turns in the latest round.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CombatTimelineRecorder {
+  rounds: Vec<RoundSummary>,
+  in_combat: bool,
+}
+
+impl CombatTimelineRecorder {
+  /// Record every log of a snapshot's history.
+  pub fn from_history(snapshot: &Game, logs: &[GameLog]) -> Result<Self, GameError> {
+    let mut game = snapshot.clone();
+    let mut recorder = Self::default();
+    for (idx, log) in logs.iter().enumerate() {
+      apply_history_log(snapshot, &logs[..idx], &mut game, log)?;
+      recorder.record_history(snapshot, &logs[..idx], &game, log)?;
+    }
+    Ok(recorder)
+  }
+
+  /// Record the next log of a snapshot's history, which `apply_history_log` just applied to
+  /// `game`. A `Rollback` puts the timeline back the way it was at the point rolled back to, which
+  /// is rebuilt from `prior`.
+  pub fn record_history(
+    &mut self, snapshot: &Game, prior: &[GameLog], game: &Game, log: &GameLog,
+  ) -> Result<(), GameError> {
+    match *log {
+      GameLog::Rollback { snapshot_index, log_index } => {
+        let index = GameIndex { game_idx: snapshot_index, log_idx: log_index };
+        *self = Self::default();
+        let mut game = snapshot.clone();
+        for log in logs_in_effect(history_at(prior, index)?)? {
+          game.apply_log_mut(log)?;
+          self.record(&game, log);
+        }
+      }
+      _ => self.record(game, log),
+    }
+    Ok(())
+  }
+
+  /// Record a log that was just applied to `game`. This can't undo anything for a `Rollback`; use
+  /// `record_history` for those.
+  pub fn record(&mut self, game: &Game, log: &GameLog) {
+    if self.in_combat {
+      if let Some(turn) = self.rounds.last_mut().and_then(|round| round.turns.last_mut()) {
+        turn.logs.push(log.clone());
+      }
+    }
+    self.in_combat = game.current_combat.is_some();
+    let combat = match &game.current_combat {
+      Some(combat) => combat,
+      None => return,
+    };
+    match log {
+      GameLog::StartCombat { .. } => self.rounds.clear(),
+      GameLog::CombatLog {
+        log: CombatLog::EndTurn { .. } | CombatLog::ForceNextTurn | CombatLog::ForcePrevTurn,
+      } => {}
+      _ => return,
+    }
+    if self.rounds.last().is_none_or(|round| combat.round > round.round) {
+      self.rounds.push(RoundSummary { round: combat.round, turns: vec![] });
+    }
+    if let Some(round) = self.rounds.last_mut() {
+      let creature_id = combat.current_creature_id();
+      round.turns.push(RoundTurn { creature_id, logs: vec![] });
+    }
+  }
+
+  pub fn rounds(&self) -> &[RoundSummary] { &self.rounds }
+}
+
+/// Implement `GMCommand::Rollback`: reset the game to how it was at `index` (see `game_at_index`).
+/// The result is recorded as a `Rollback` log, so replaying the history reproduces it.
+pub fn rollback_to(
+  snapshot: &Game, logs: &[GameLog], index: GameIndex,
+) -> Result<ChangedGame, GameError> {
+  let game = game_at_index(snapshot, logs, index)?;
+  let log = GameLog::Rollback { snapshot_index: index.game_idx, log_index: index.log_idx };
+  Ok(ChangedGame { game, logs: vec![log], movement: None })
+}
+
 #[cfg(test)]
 pub mod test {
   use std::{collections::HashSet, iter::FromIterator};
@@ -1737,15 +3631,9 @@ pub mod test {
     let mut game: Game = Default::default();
     game.abilities = t_abilities();
     game.classes = t_classes();
-    let mut rogue = t_rogue("rogue");
-    rogue.id = cid_rogue();
-    let mut ranger = t_ranger("ranger");
-    ranger.id = cid_ranger();
-    let mut cleric = t_cleric("cleric");
-    cleric.id = cid_cleric();
-    game.creatures.insert(rogue);
-    game.creatures.insert(ranger);
-    game.creatures.insert(cleric);
+    game.creatures.insert(t_rogue("rogue"));
+    game.creatures.insert(t_ranger("ranger"));
+    game.creatures.insert(t_cleric("cleric"));
     game.scenes.insert(t_scene());
     let mut folder = Folder::new();
     for creature_id in game.creatures.keys() {
@@ -1765,19 +3653,50 @@ pub mod test {
   }
 
   #[test]
-  fn validate_test_game() { t_game().validate_campaign().expect("Test game must validate"); }
+  fn serialization_is_deterministic() {
+    // Separately-built games have HashMaps with different iteration orders.
+    let (game, other) = (t_game(), t_game());
+    assert_eq!(game, other);
+    assert_eq!(serde_json::to_string(&game).unwrap(), serde_json::to_string(&other).unwrap());
+    let serialized = RPIGame(&game).serialize_game().unwrap();
+    let other_serialized = RPIGame(&other).serialize_game().unwrap();
+    assert_eq!(
+      serde_json::to_string(&serialized).unwrap(),
+      serde_json::to_string(&other_serialized).unwrap()
+    );
+  }
 
-  pub fn t_classes() -> IndexedHashMap<Class> {
-    let rogue_abs = vec![abid_punch()];
-    let ranger_abs = vec![abid_shoot(), abid_piercing_shot()];
-    let cleric_abs = vec![abid_heal(), abid_fireball()];
-    IndexedHashMap::from_iter(vec![
-      Class {
-        id: classid_rogue(),
-        name: "Rogue".to_string(),
-        abilities: rogue_abs,
-        conditions: vec![],
-        color: "purple".to_string(),
+  #[test]
+  fn content_hash() {
+    let game = t_game();
+    assert_eq!(game.content_hash().unwrap(), t_game().content_hash().unwrap());
+    let changed = t_perform(&game, GMCommand::ChatFromGM { message: "hi".to_string() });
+    assert_eq!(changed.content_hash().unwrap(), game.content_hash().unwrap());
+    let changed = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(100, 0, 0),
+      },
+    );
+    assert_ne!(changed.content_hash().unwrap(), game.content_hash().unwrap());
+  }
+
+  #[test]
+  fn validate_test_game() { t_game().validate_campaign().expect("Test game must validate"); }
+
+  pub fn t_classes() -> IndexedHashMap<Class> {
+    let rogue_abs = vec![abid_punch()];
+    let ranger_abs = vec![abid_shoot(), abid_piercing_shot()];
+    let cleric_abs = vec![abid_heal(), abid_fireball()];
+    IndexedHashMap::from_iter(vec![
+      Class {
+        id: classid_rogue(),
+        name: "Rogue".to_string(),
+        abilities: rogue_abs,
+        conditions: vec![],
+        color: "purple".to_string(),
       },
       Class {
         id: classid_ranger(),
@@ -1802,6 +3721,49 @@ pub mod test {
 
   pub fn t_perform(game: &Game, cmd: GMCommand) -> Game { perf(game, cmd).unwrap().game }
 
+  #[test]
+  fn scene_commands_reject_missing_scene() {
+    let mut game = t_game();
+    game.players.insert(Player::new(bob()));
+    let missing = SceneID::gen();
+    let cmds = vec![
+      GMCommand::SetActiveScene { id: Some(missing) },
+      GMCommand::SetPlayerScene { player_id: bob(), scene_id: Some(missing) },
+      GMCommand::SetCreaturePos {
+        scene_id: missing,
+        creature_id: cid_rogue(),
+        pos: Point3::new(0, 0, 0),
+      },
+      GMCommand::StartCombat { scene_id: missing, combatants: vec![cid_rogue(), cid_ranger()] },
+      GMCommand::SetSceneCreatureVisibility {
+        scene_id: missing,
+        creature_id: cid_rogue(),
+        visibility: Visibility::GMOnly,
+      },
+      GMCommand::SetFocusedSceneCreatures { scene_id: missing, creatures: vec![cid_rogue()] },
+      GMCommand::RemoveSceneChallenge { scene_id: missing, description: "Jump".to_string() },
+      GMCommand::SnapshotScene { scene_id: missing, name: "start".to_string() },
+      GMCommand::ActCreature {
+        scene_id: missing,
+        creature_id: cid_rogue(),
+        ability_id: abid_punch(),
+        target: DecidedTarget::Creature(cid_ranger()),
+      },
+      GMCommand::PathCreature {
+        scene_id: missing,
+        creature_id: cid_rogue(),
+        destination: Point3::new(100, 0, 0),
+      },
+    ];
+    for cmd in cmds {
+      assert_eq!(
+        perf(&game, cmd.clone()).map(|_| ()),
+        Err(GameError::SceneNotFound(missing)),
+        "{cmd:?}"
+      );
+    }
+  }
+
   #[test]
   fn start_combat_not_found() {
     let game = t_game();
@@ -1825,6 +3787,105 @@ pub mod test {
     }
   }
 
+  #[test]
+  fn start_combat_in_scene() {
+    let game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger()]);
+    assert_eq!(game.get_combat().unwrap().combat.creature_ids(), vec![cid_rogue(), cid_ranger()]);
+  }
+
+  #[test]
+  fn start_combat_logs_initiative_in_order() {
+    let changed = perf(
+      &t_game(),
+      GMCommand::StartCombat {
+        scene_id: t_scene_id(),
+        combatants: vec![cid_cleric(), cid_rogue(), cid_ranger()],
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      changed.logs,
+      vec![
+        GameLog::StartCombat {
+          scene_id: t_scene_id(),
+          combatants: vec![(cid_rogue(), 20), (cid_ranger(), 10), (cid_cleric(), 0)],
+        },
+        GameLog::TurnBegan { creature_id: cid_rogue() },
+      ]
+    );
+  }
+
+  #[test]
+  fn start_combat_creature_not_in_scene() {
+    let game = t_perform(
+      &t_game(),
+      GMCommand::RemoveCreatureFromScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+    );
+    let result = game.perform_gm_command(GMCommand::StartCombat {
+      scene_id: t_scene_id(),
+      combatants: vec![cid_rogue(), cid_ranger()],
+    });
+    assert_eq!(result, Err(GameError::CreaturesNotInScene(t_scene_id(), vec![cid_ranger()])));
+  }
+
+  #[test]
+  fn scene_membership_logs() {
+    let game = t_game();
+    let removed = perf(
+      &game,
+      GMCommand::RemoveCreatureFromScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+    )
+    .unwrap();
+    assert_eq!(
+      removed.logs,
+      vec![
+        GameLog::RemoveCreatureFromScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+        GameLog::CreatureLeftScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+      ]
+    );
+    let added = perf(
+      &removed.game,
+      GMCommand::AddCreatureToScene {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        visibility: Visibility::AllPlayers,
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      added.logs,
+      vec![
+        GameLog::AddCreatureToScene {
+          scene_id: t_scene_id(),
+          creature_id: cid_ranger(),
+          visibility: Visibility::AllPlayers,
+        },
+        GameLog::CreatureEnteredScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+      ]
+    );
+  }
+
+  #[test]
+  fn deleting_creature_leaves_scenes() {
+    let game = t_game();
+    let path: FolderPath = "/testdata".parse().unwrap();
+    let changed = perf(
+      &game,
+      GMCommand::DeleteFolderItem {
+        path: path.clone(),
+        item_id: FolderItemID::CreatureID(cid_rogue()),
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      changed.logs,
+      vec![
+        GameLog::CreatureLeftScene { scene_id: t_scene_id(), creature_id: cid_rogue() },
+        GameLog::DeleteFolderItem { path, item_id: FolderItemID::CreatureID(cid_rogue()) },
+      ]
+    );
+  }
+
   #[test]
   fn stop_combat() {
     let game = t_game();
@@ -1837,10 +3898,392 @@ pub mod test {
       },
     );
     assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health(), HP(7));
-    let game = t_perform(&game, GMCommand::StopCombat);
+    let game = t_perform(&game, GMCommand::StopCombat { preserve_initiative: false });
     assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health(), HP(7));
   }
 
+  #[test]
+  fn act_reports_movement() {
+    let game = t_start_combat(&t_game(), vec![cid_ranger(), cid_cleric()]);
+    let changed = game
+      .perform_gm_command(GMCommand::PathCurrentCombatCreature {
+        destination: Point3::new(300, 0, 0),
+      })
+      .unwrap();
+    let speed = game.get_creature(cid_ranger()).unwrap().speed();
+    let expected = CombatMovement { movement_used: u32cm(300), movement_left: speed - u32cm(300) };
+    assert_eq!(changed.movement, Some(expected));
+    let changed = changed
+      .game
+      .perform_gm_command(GMCommand::CombatAct {
+        ability_id: abid_shoot(),
+        target: DecidedTarget::Creature(cid_cleric()),
+      })
+      .unwrap();
+    assert_eq!(changed.movement, Some(expected));
+  }
+
+  #[test]
+  fn no_movement_outside_of_combat() {
+    let changed =
+      t_game().perform_gm_command(GMCommand::ChatFromGM { message: "hi".to_string() }).unwrap();
+    assert_eq!(changed.movement, None);
+  }
+
+  fn bob() -> PlayerID { PlayerID("bob".to_string()) }
+
+  #[test]
+  fn register_player_twice() {
+    let game = t_perform(&t_game(), GMCommand::RegisterPlayer { id: bob() });
+    let game = t_perform(
+      &game,
+      GMCommand::GiveCreaturesToPlayer { player_id: bob(), creature_ids: vec![cid_ranger()] },
+    );
+    let changed = perf(&game, GMCommand::RegisterPlayer { id: bob() }).unwrap();
+    assert_eq!(changed.logs, vec![]);
+    assert_eq!(changed.game, game);
+  }
+
+  #[test]
+  fn player_display_name_and_color() {
+    let game = t_perform(&t_game(), GMCommand::RegisterPlayer { id: bob() });
+    let player = game.players.get(&bob()).unwrap();
+    assert_eq!((player.name(), player.color.as_str()), ("bob", ""));
+
+    let cmd = PlayerCommand::SetDisplay { display_name: "Bobby".to_string(), color: "#f00".into() };
+    let game = game.perform_player_command(bob(), cmd, 0).unwrap().game;
+    let player = game.players.get(&bob()).unwrap();
+    assert_eq!((player.name(), player.color.as_str()), ("Bobby", "#f00"));
+
+    // The GM can change it too, and clearing the name falls back to the player ID.
+    let display_name = "".to_string();
+    let color = "blue".to_string();
+    let game =
+      t_perform(&game, GMCommand::SetPlayerDisplay { player_id: bob(), display_name, color });
+    let player = game.players.get(&bob()).unwrap();
+    assert_eq!((player.name(), player.color.as_str()), ("bob", "blue"));
+
+    let carol = PlayerID("carol".to_string());
+    let cmd = GMCommand::SetPlayerDisplay {
+      player_id: carol.clone(),
+      display_name: "Carol".to_string(),
+      color: "".to_string(),
+    };
+    assert_eq!(perf(&game, cmd).map(|_| ()), Err(GameError::PlayerNotFound(carol)));
+  }
+
+  /// A game in combat between the ranger (controlled by bob) and the cleric (controlled by alice),
+  /// with the given vetting policy. The rogue is an NPC.
+  fn t_vetting_game(vetting_policy: VettingPolicy) -> Game {
+    let settings = GameSettings { vetting_policy, ..Default::default() };
+    let game = t_perform(&t_game(), GMCommand::EditGameSettings { settings });
+    let alice = PlayerID("alice".to_string());
+    let game = t_perform(&game, GMCommand::RegisterPlayer { id: bob() });
+    let game = t_perform(&game, GMCommand::RegisterPlayer { id: alice.clone() });
+    let game = t_perform(
+      &game,
+      GMCommand::GiveCreaturesToPlayer { player_id: bob(), creature_ids: vec![cid_ranger()] },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::GiveCreaturesToPlayer { player_id: alice, creature_ids: vec![cid_cleric()] },
+    );
+    t_start_combat(&game, vec![cid_ranger(), cid_cleric()])
+  }
+
+  #[test]
+  fn controlled_creatures() {
+    let game = t_vetting_game(VettingPolicy::Never);
+    assert_eq!(game.controlled_creatures(&bob()), Ok(vec![cid_ranger()]));
+    assert_eq!(
+      game.controlled_creatures(&PlayerID("carol".to_string())),
+      Err(GameError::PlayerNotFound(PlayerID("carol".to_string())))
+    );
+  }
+
+  #[test]
+  fn players_share_control_of_a_creature() {
+    let alice = PlayerID("alice".to_string());
+    let carol = PlayerID("carol".to_string());
+    let game = t_vetting_game(VettingPolicy::Never);
+    let give = GMCommand::GiveCreaturesToPlayer {
+      player_id: alice.clone(),
+      creature_ids: vec![cid_ranger()],
+    };
+    let game = t_perform(&t_perform(&game, give), GMCommand::RegisterPlayer { id: carol.clone() });
+    assert_eq!(game.creature_controllers(cid_ranger()), vec![alice.clone(), bob()]);
+    // Giving the ranger to alice didn't take it away from bob.
+    assert_eq!(game.controlled_creatures(&bob()), Ok(vec![cid_ranger()]));
+
+    // It's the ranger's turn, so either of its controllers can end it, but nobody else can.
+    let next = game.perform_player_command(bob(), PlayerCommand::EndTurn, 0).unwrap();
+    assert_eq!(next.game.get_combat().unwrap().combat.current_creature_id(), cid_cleric());
+    let next = game.perform_player_command(alice, PlayerCommand::EndTurn, 0).unwrap();
+    assert_eq!(next.game.get_combat().unwrap().combat.current_creature_id(), cid_cleric());
+    assert_eq!(
+      game.perform_player_command(carol.clone(), PlayerCommand::EndTurn, 0).map(|_| ()),
+      Err(GameError::PlayerDoesntControlCreature(carol, cid_ranger()))
+    );
+  }
+
+  #[test]
+  fn turn_began_is_logged_for_the_next_creature() {
+    let game = t_vetting_game(VettingPolicy::Never);
+    let changed = game.perform_player_command(bob(), PlayerCommand::EndTurn, 0).unwrap();
+    // The cleric is next, so alice's client knows it's her turn.
+    assert_eq!(changed.logs.last(), Some(&GameLog::TurnBegan { creature_id: cid_cleric() }));
+    let alice = PlayerID("alice".to_string());
+    assert_eq!(changed.game.creature_controllers(cid_cleric()), vec![alice]);
+
+    // Creatures whose turns are skipped don't get a turn-began log.
+    let mut game = t_combat();
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.conditions.insert(ConditionID(uuid_5()), app_cond(Condition::Dead, Duration::Interminate));
+    });
+    let changed = perf(&game, GMCommand::EndTurn).unwrap();
+    let began: Vec<&GameLog> =
+      changed.logs.iter().filter(|log| matches!(log, GameLog::TurnBegan { .. })).collect();
+    assert_eq!(began, vec![&GameLog::TurnBegan { creature_id: cid_cleric() }]);
+  }
+
+  #[test]
+  fn player_cant_move_uncontrolled_creature() {
+    let game = t_vetting_game(VettingPolicy::Never);
+    let game = t_perform(
+      &game,
+      GMCommand::SetPlayerScene { player_id: bob(), scene_id: Some(t_scene_id()) },
+    );
+    let cmd =
+      PlayerCommand::PathCreature { creature_id: cid_rogue(), destination: Point3::new(100, 0, 0) };
+    assert_eq!(
+      game.perform_player_command(bob(), cmd, 0),
+      Err(GameError::PlayerDoesntControlCreature(bob(), cid_rogue()))
+    );
+  }
+
+  /// A vetting-free game where bob is in the scene, and it's his ranger's turn.
+  fn t_step_game() -> Game {
+    let game = t_vetting_game(VettingPolicy::Never);
+    t_perform(&game, GMCommand::SetPlayerScene { player_id: bob(), scene_id: Some(t_scene_id()) })
+  }
+
+  fn t_step(game: &Game, direction: Direction) -> Result<ChangedGame, GameError> {
+    let cmd = PlayerCommand::StepDirection { creature_id: cid_ranger(), direction };
+    game.perform_player_command(bob(), cmd, 0)
+  }
+
+  #[test]
+  fn step_into_open_terrain() {
+    let game = t_step(&t_step_game(), Direction::SouthEast).unwrap().game;
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_ranger()), Ok(Point3::new(100, 100, 0)));
+    assert_eq!(game.get_combat().unwrap().combat.movement_used, u32cm(141));
+  }
+
+  #[test]
+  fn step_into_wall_or_creature() {
+    let scene_id = t_scene_id();
+    let terrain = hashset! {Point3::new(0, 0, 0), Point3::new(100, 0, 0)};
+    let game = t_perform(&t_step_game(), GMCommand::EditSceneTerrain { scene_id, terrain });
+    assert_eq!(
+      t_step(&game, Direction::North).map(|_| ()),
+      Err(GameError::StepBlocked(Point3::new(0, 0, 0), Point3::new(0, -100, 0)))
+    );
+    let creature_id = cid_cleric();
+    let pos = Point3::new(100, 0, 0);
+    let game = t_perform(&game, GMCommand::SetCreaturePos { scene_id, creature_id, pos });
+    assert_eq!(
+      t_step(&game, Direction::East).map(|_| ()),
+      Err(GameError::StepBlocked(Point3::new(0, 0, 0), pos))
+    );
+  }
+
+  #[test]
+  fn step_without_movement_left() {
+    let mut game = t_step_game();
+    game.creatures.mutate(&cid_ranger(), |c| c.speed = u32cm(50));
+    assert_eq!(
+      t_step(&game, Direction::West).map(|_| ()),
+      Err(GameError::NotEnoughMovement(cid_ranger()))
+    );
+  }
+
+  #[test]
+  fn player_cant_act_on_someone_elses_turn() {
+    // It's the ranger's turn, but alice controls the cleric.
+    let game = t_vetting_game(VettingPolicy::Never);
+    let alice = PlayerID("alice".to_string());
+    assert_eq!(
+      game.perform_player_command(alice.clone(), shoot(cid_rogue()), 0),
+      Err(GameError::PlayerDoesntControlCreature(alice, cid_ranger()))
+    );
+  }
+
+  #[test]
+  fn player_cant_move_out_of_turn() {
+    // It's the ranger's turn; bob controls the ranger and alice controls the cleric.
+    let mut game = t_vetting_game(VettingPolicy::Never);
+    let alice = PlayerID("alice".to_string());
+    for player_id in [bob(), alice.clone()] {
+      let scene_id = Some(t_scene_id());
+      game = t_perform(&game, GMCommand::SetPlayerScene { player_id, scene_id });
+    }
+    let path = |creature_id| PlayerCommand::PathCreature {
+      creature_id,
+      destination: Point3::new(100, 0, 0),
+    };
+    assert_eq!(
+      game.perform_player_command(alice, path(cid_cleric()), 0).map(|_| ()),
+      Err(GameError::NotYourTurn(cid_cleric()))
+    );
+    game.perform_player_command(bob(), path(cid_ranger()), 0).unwrap();
+  }
+
+  fn shoot(cid: CreatureID) -> PlayerCommand {
+    PlayerCommand::CombatAct { ability_id: abid_shoot(), target: DecidedTarget::Creature(cid) }
+  }
+
+  fn hp(game: &Game, cid: CreatureID) -> HP { game.get_creature(cid).unwrap().creature.cur_health }
+
+  /// Submit a command as bob at time `now`, asserting that it's held for vetting.
+  fn t_submit(game: &Game, cmd: PlayerCommand, now: u64) -> (Game, PendingCommand) {
+    let changed = game.perform_player_command(bob(), cmd, now).unwrap();
+    match &changed.logs[..] {
+      [GameLog::AddPendingCommand { pending }] => (changed.game.clone(), pending.clone()),
+      logs => panic!("Expected the command to be held, got {logs:?}"),
+    }
+  }
+
+  #[test]
+  fn vetted_action_applies_on_approval() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let (game, pending) = t_submit(&game, shoot(cid_cleric()), 0);
+    assert_eq!(game.pending_commands, vec![pending.clone()]);
+    assert_eq!(hp(&game, cid_cleric()), HP(10));
+
+    let game = t_perform(&game, GMCommand::ApprovePending { id: pending.id });
+    assert_eq!(game.pending_commands, vec![]);
+    assert_eq!(hp(&game, cid_cleric()), HP(7));
+  }
+
+  #[test]
+  fn vetted_action_denied() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let (game, pending) = t_submit(&game, shoot(cid_cleric()), 0);
+    let game = t_perform(&game, GMCommand::DenyPending { id: pending.id });
+    assert_eq!(game.pending_commands, vec![]);
+    assert_eq!(hp(&game, cid_cleric()), HP(10));
+    assert_eq!(
+      perf(&game, GMCommand::ApprovePending { id: pending.id }),
+      Err(GameError::PendingCommandNotFound(pending.id))
+    );
+  }
+
+  #[test]
+  fn invalid_vetted_action_is_rejected_immediately() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let cmd = PlayerCommand::CombatAct {
+      ability_id: abid_heal(),
+      target: DecidedTarget::Creature(cid_cleric()),
+    };
+    assert_eq!(
+      game.perform_player_command(bob(), cmd, 0),
+      Err(GameError::CreatureLacksAbility(cid_ranger(), abid_heal()))
+    );
+  }
+
+  #[test]
+  fn unvetted_action_applies_immediately() {
+    let game = t_vetting_game(VettingPolicy::Never);
+    let changed = game.perform_player_command(bob(), shoot(cid_cleric()), 0).unwrap();
+    assert_eq!(changed.game.pending_commands, vec![]);
+    assert_eq!(hp(&changed.game, cid_cleric()), HP(7));
+  }
+
+  #[test]
+  fn chat_is_never_vetted() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let cmd = PlayerCommand::ChatFromPlayer { message: "hi".to_string() };
+    let changed = game.perform_player_command(bob(), cmd, 0).unwrap();
+    assert_eq!(changed.game.pending_commands, vec![]);
+  }
+
+  #[test]
+  fn npc_targeting_actions_are_vetted() {
+    let game = t_vetting_game(VettingPolicy::NPCTargetingActions);
+    let changed = game.perform_player_command(bob(), shoot(cid_cleric()), 0).unwrap();
+    assert_eq!(hp(&changed.game, cid_cleric()), HP(7));
+
+    let (game, _) = t_submit(&game, shoot(cid_rogue()), 0);
+    assert_eq!(hp(&game, cid_rogue()), HP(10));
+  }
+
+  #[test]
+  fn list_pending_commands() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let (game, first) = t_submit(&game, shoot(cid_cleric()), 0);
+    let (game, second) = t_submit(&game, shoot(cid_rogue()), 1);
+    assert_eq!(game.list_pending(None), vec![first.clone(), second.clone()]);
+    assert_eq!(game.list_pending(Some(&bob())), vec![first.clone(), second.clone()]);
+    assert_eq!(game.list_pending(Some(&PlayerID("alice".to_string()))), vec![]);
+
+    let game = t_perform(&game, GMCommand::ApprovePending { id: first.id });
+    assert_eq!(game.list_pending(None), vec![second]);
+    assert_eq!(hp(&game, cid_cleric()), HP(7));
+  }
+
+  #[test]
+  fn pending_commands_expire() {
+    let mut game = t_vetting_game(VettingPolicy::AllActions);
+    game.settings.pending_command_timeout = Some(60);
+    let (game, pending) = t_submit(&game, shoot(cid_cleric()), 1000);
+    assert_eq!(pending.submitted_at, 1000);
+
+    let changed = game.expire_pending_commands(1059).unwrap();
+    assert_eq!(changed.logs, vec![]);
+
+    let changed = game.expire_pending_commands(1060).unwrap();
+    assert_eq!(changed.logs, vec![GameLog::PendingCommandExpired { id: pending.id }]);
+    assert_eq!(changed.game.list_pending(None), vec![]);
+    assert_eq!(hp(&changed.game, cid_cleric()), HP(10));
+  }
+
+  #[test]
+  fn pending_commands_without_timeout_never_expire() {
+    let game = t_vetting_game(VettingPolicy::AllActions);
+    let (game, _) = t_submit(&game, shoot(cid_cleric()), 0);
+    assert_eq!(game.expire_pending_commands(u64::MAX).unwrap().logs, vec![]);
+  }
+
+  #[test]
+  fn restart_combat_with_preserved_initiative() {
+    let game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    let order: Vec<(CreatureID, i16)> =
+      game.current_combat.as_ref().unwrap().creatures.iter().cloned().collect();
+    let game = t_perform(&game, GMCommand::StopCombat { preserve_initiative: true });
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().preserved_initiative, order);
+    // The initiative dice are all flat, so change them to prove they aren't re-rolled.
+    let mut game = game;
+    for cid in [cid_rogue(), cid_ranger(), cid_cleric()] {
+      game.creatures.mutate(&cid, |c| c.initiative = Dice::flat(-100));
+    }
+    let game = t_start_combat(&game, vec![cid_cleric(), cid_ranger(), cid_rogue()]);
+    let combat = game.current_combat.as_ref().unwrap();
+    assert_eq!(combat.creatures.iter().cloned().collect::<Vec<_>>(), order);
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().preserved_initiative, vec![]);
+  }
+
+  #[test]
+  fn restart_combat_without_preserved_initiative() {
+    let game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    let mut game = t_perform(&game, GMCommand::StopCombat { preserve_initiative: false });
+    assert_eq!(game.get_scene(t_scene_id()).unwrap().preserved_initiative, vec![]);
+    game.creatures.mutate(&cid_cleric(), |c| c.initiative = Dice::flat(100));
+    let game = t_start_combat(&game, vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    let combat = game.current_combat.as_ref().unwrap();
+    assert_eq!(combat.current_creature_id(), cid_cleric());
+  }
+
   #[test]
   fn movement() {
     let game = t_game();
@@ -1887,136 +4330,1863 @@ pub mod test {
     iter(&game).unwrap();
   }
 
+  /// Perform a series of commands, returning the resulting game along with all the logs.
+  fn t_history(game: &Game, cmds: Vec<GMCommand>) -> (Game, Vec<GameLog>) {
+    let mut game = game.clone();
+    let mut logs = vec![];
+    for cmd in cmds {
+      let changed = perf(&game, cmd).unwrap();
+      game = changed.game;
+      logs.extend(changed.logs);
+    }
+    (game, logs)
+  }
+
+  fn t_punch_cmd(target: CreatureID) -> GMCommand {
+    GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_rogue(),
+      ability_id: abid_punch(),
+      target: DecidedTarget::Creature(target),
+    }
+  }
+
   #[test]
-  fn ability_creatures_within_area() {
-    // the cleric moves away, then casts a fireball at the ranger and rogue.
+  fn changed_game_apply_records_logs_in_order() {
     let game = t_game();
+    let logs = vec![
+      GameLog::CreatureLog {
+        creature_id: cid_rogue(),
+        log: CreatureLog::Damage { hp: HP(3), rolls: vec![3] },
+      },
+      GameLog::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(100, 0, 0),
+      },
+    ];
+    let mut change = game.change();
+    for log in &logs {
+      change = change.apply(log).unwrap();
+    }
+    assert_eq!(change, game.change_with_logs(logs).unwrap());
+
+    let bad = GameLog::SetCreaturePos {
+      scene_id: t_scene_id(),
+      creature_id: CreatureID::gen(),
+      pos: Point3::new(0, 0, 0),
+    };
+    assert!(change.apply(&bad).is_err());
+  }
+
+  #[test]
+  fn last_turn_summary_of_active_creature() {
+    let snapshot = t_start_combat(&t_game(), vec![cid_ranger(), cid_cleric()]);
+    let (game, history) = t_history(
+      &snapshot,
+      vec![
+        GMCommand::PathCurrentCombatCreature { destination: Point3::new(300, 0, 0) },
+        GMCommand::CombatAct {
+          ability_id: abid_shoot(),
+          target: DecidedTarget::Creature(cid_cleric()),
+        },
+        GMCommand::EndTurn,
+        GMCommand::EndTurn,
+      ],
+    );
+    let summary = game.last_turn_summary(&history, cid_ranger()).unwrap();
+    assert_eq!(
+      summary,
+      TurnSummary {
+        moved: Some((Point3::new(0, 0, 0), Point3::new(300, 0, 0))),
+        abilities: vec![abid_shoot()],
+        damage_dealt: HP(3),
+        damage_taken: HP(0),
+      }
+    );
+    // The cleric was shot during the ranger's turn, not its own.
+    let summary = game.last_turn_summary(&history, cid_cleric()).unwrap();
+    assert_eq!(summary, TurnSummary::default());
+  }
+
+  #[test]
+  fn last_turn_summary_counts_damage_after_a_reaction() {
+    let mut game = t_game();
+    let shield = t_shield(&mut game, HP(1));
     let game = t_perform(
       &game,
+      GMCommand::ReadyReaction { creature_id: cid_ranger(), ability_id: Some(shield) },
+    );
+    let snapshot = t_start_combat(&game, vec![cid_rogue(), cid_ranger()]);
+    let (game, history) = t_history(
+      &snapshot,
+      vec![
+        GMCommand::CombatAct {
+          ability_id: abid_punch(),
+          target: DecidedTarget::Creature(cid_ranger()),
+        },
+        GMCommand::EndTurn,
+      ],
+    );
+    let summary = game.last_turn_summary(&history, cid_rogue()).unwrap();
+    assert_eq!(summary.abilities, vec![abid_punch()]);
+    assert_eq!(summary.damage_dealt, HP(2));
+  }
+
+  #[test]
+  fn last_turn_summary_of_idle_creature() {
+    let snapshot = t_start_combat(&t_game(), vec![cid_ranger(), cid_cleric()]);
+    let (game, history) = t_history(&snapshot, vec![GMCommand::EndTurn]);
+    let summary = game.last_turn_summary(&history, cid_ranger()).unwrap();
+    assert_eq!(summary, TurnSummary::default());
+    // The rogue never had a turn at all.
+    let summary = game.last_turn_summary(&history, cid_rogue()).unwrap();
+    assert_eq!(summary, TurnSummary::default());
+  }
+
+  #[test]
+  fn apply_raw_damage_log() {
+    let game = t_game();
+    let log = CreatureLog::Damage { hp: HP(3), rolls: vec![3] };
+    let cmd = GMCommand::ApplyCreatureLog { creature_id: cid_ranger(), log: log.clone() };
+    let changed = perf(&game, cmd).unwrap();
+    assert_eq!(changed.game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(changed.logs, vec![GameLog::CreatureLog { creature_id: cid_ranger(), log }]);
+  }
+
+  #[test]
+  fn apply_raw_condition_log() {
+    let game = t_game();
+    let id = ConditionID::gen();
+    let log =
+      CreatureLog::ApplyCondition { id, duration: Duration::Rounds(2), condition: Condition::Dead };
+    let cmd = GMCommand::ApplyCreatureLog { creature_id: cid_ranger(), log: log.clone() };
+    let changed = perf(&game, cmd).unwrap();
+    let creature = changed.game.get_creature(cid_ranger()).unwrap().creature;
+    let applied = AppliedCondition { remaining: Duration::Rounds(2), condition: Condition::Dead };
+    assert_eq!(creature.conditions.get(&id), Some(&applied));
+    assert_eq!(changed.logs, vec![GameLog::CreatureLog { creature_id: cid_ranger(), log }]);
+  }
+
+  #[test]
+  fn undo_creature_damage() {
+    let snapshot = t_game();
+    let (game, history) =
+      t_history(&snapshot, vec![t_punch_cmd(cid_ranger()), t_punch_cmd(cid_cleric())]);
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(game.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(7));
+
+    let game = game.undo_creature(&snapshot, &history, cid_ranger()).unwrap().game;
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(10));
+    assert_eq!(game.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(7));
+  }
+
+  #[test]
+  fn undo_creature_only_undoes_last_change() {
+    let snapshot = t_game();
+    let (game, history) =
+      t_history(&snapshot, vec![t_punch_cmd(cid_ranger()), t_punch_cmd(cid_ranger())]);
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(4));
+    let game = game.undo_creature(&snapshot, &history, cid_ranger()).unwrap().game;
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+  }
+
+  #[test]
+  fn undo_creature_without_changes() {
+    let snapshot = t_game();
+    let (game, history) = t_history(&snapshot, vec![t_punch_cmd(cid_ranger())]);
+    assert!(matches!(
+      game.undo_creature(&snapshot, &history, cid_cleric()),
+      Err(GameError::CannotUndoCreature(..))
+    ));
+  }
+
+  #[test]
+  fn undo_creature_creation() {
+    let snapshot = t_game();
+    let creature = CreatureCreation {
+      name: "goblin".to_string(),
+      class: classid_rogue(),
+      portrait_url: "".to_string(),
+      icon_url: "".to_string(),
+      note: "".to_string(),
+      bio: "".to_string(),
+      initiative: Dice::flat(0),
+      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft },
+    };
+    let (game, history) = t_history(
+      &snapshot,
+      vec![GMCommand::CreateCreature { path: FolderPath::from_vec(vec![]), creature }],
+    );
+    let cid = match &history[..] {
+      [GameLog::CreateCreature { creature, .. }] => creature.id,
+      logs => panic!("Unexpected logs {logs:?}"),
+    };
+    assert!(matches!(
+      game.undo_creature(&snapshot, &history, cid),
+      Err(GameError::CannotUndoCreature(..))
+    ));
+  }
+
+  #[test]
+  fn undo_creature_ignores_rolled_back_logs() {
+    let snapshot = t_game();
+    let (after_first, first_logs) = t_history(&snapshot, vec![t_punch_cmd(cid_ranger())]);
+    let (_, cleric_logs) = t_history(&after_first, vec![t_punch_cmd(cid_cleric())]);
+    let logs = [first_logs.clone(), cleric_logs].concat();
+    let index = GameIndex { game_idx: 0, log_idx: first_logs.len() };
+    let rolled_back = rollback_to(&snapshot, &logs, index).unwrap();
+    let (game, later_logs) = t_history(&rolled_back.game, vec![t_punch_cmd(cid_ranger())]);
+    let history = [logs, rolled_back.logs, later_logs].concat();
+
+    let undone = game.undo_creature(&snapshot, &history, cid_ranger()).unwrap().game;
+    assert_eq!(undone.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(undone.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(10));
+    // The cleric's only change was rolled back, so there's nothing left to undo.
+    assert!(matches!(
+      game.undo_creature(&snapshot, &history, cid_cleric()),
+      Err(GameError::CannotUndoCreature(..))
+    ));
+  }
+
+  fn t_ooc_punch(game: &Game, actor: CreatureID) -> Result<ChangedGame, GameError> {
+    perf(
+      game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: actor,
+        ability_id: abid_punch(),
+        target: DecidedTarget::Creature(cid_ranger()),
+      },
+    )
+  }
+
+  #[test]
+  fn reaction_on_other_creatures_turn() {
+    let mut game = t_combat();
+    game.abilities.mutate(&abid_punch(), |ab| ab.action_kind = ActionKind::Reaction);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_ooc_punch(&game, cid_rogue()).unwrap().game;
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+  }
+
+  #[test]
+  fn action_on_other_creatures_turn() {
+    let game = t_perform(&t_combat(), GMCommand::EndTurn);
+    assert_eq!(t_ooc_punch(&game, cid_rogue()), Err(GameError::NotYourTurn(cid_rogue())));
+    // but it's fine on their own turn
+    t_ooc_punch(&t_combat(), cid_rogue()).unwrap();
+  }
+
+  #[test]
+  fn act_creature_on_own_turn_is_a_combat_act() {
+    let mut game = t_combat();
+    game.abilities.mutate(&abid_punch(), |ab| ab.cost = Energy(2));
+    let energy = game.get_creature(cid_rogue()).unwrap().creature.cur_energy;
+    let ooc = t_ooc_punch(&game, cid_rogue()).unwrap();
+    let target = DecidedTarget::Creature(cid_ranger());
+    let combat_act = perf(&game, GMCommand::CombatAct { ability_id: abid_punch(), target }).unwrap();
+    assert_eq!(ooc, combat_act);
+    let creature = ooc.game.get_creature(cid_rogue()).unwrap().creature;
+    assert_eq!(creature.cur_energy, energy - Energy(2));
+  }
+
+  #[test]
+  fn creature_ability_dice_override() {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_cleric(), |c| {
+      c.abilities.insert(AbilityStatus { ability_id: abid_punch(), cooldown: 0 });
+    });
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreatureAbilityDice {
+        creature_id: cid_rogue(),
+        ability_id: abid_punch(),
+        dice: Some(Dice::flat(5)),
+      },
+    );
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(5));
+    // The cleric shares the punch ability, but still rolls its normal dice.
+    let game = t_perform(
+      &game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: abid_punch(),
+        target: DecidedTarget::Creature(cid_ranger()),
+      },
+    );
+    assert_eq!(hp(&game, cid_ranger()), HP(2));
+    assert_eq!(game.get_ability(abid_punch()).unwrap(), &t_punch());
+  }
+
+  #[test]
+  fn ability_dice_need_the_ability() {
+    let cmd = GMCommand::SetCreatureAbilityDice {
+      creature_id: cid_cleric(),
+      ability_id: abid_punch(),
+      dice: Some(Dice::flat(5)),
+    };
+    assert_eq!(
+      perf(&t_game(), cmd).map(|_| ()),
+      Err(GameError::CreatureLacksAbility(cid_cleric(), abid_punch()))
+    );
+  }
+
+  /// Give the ranger a reaction that reduces the damage it takes by `reduction`, and return its ID.
+  fn t_shield(game: &mut Game, reduction: HP) -> AbilityID {
+    let shield = Ability {
+      id: AbilityID::gen(),
+      name: "Shield".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Reaction,
+      action: Action::Creature {
+        target: CreatureTarget::Actor,
+        effect: CreatureEffect::ApplyCondition(
+          Duration::Rounds(0),
+          Condition::ReduceDamage(reduction),
+        ),
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    };
+    let id = shield.id;
+    game.abilities.insert(shield);
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.abilities.insert(AbilityStatus { ability_id: id, cooldown: 0 });
+    });
+    id
+  }
+
+  #[test]
+  fn readied_reaction_reduces_damage() {
+    let mut game = t_game();
+    let shield = t_shield(&mut game, HP(2));
+    let game = t_perform(
+      &game,
+      GMCommand::ReadyReaction { creature_id: cid_ranger(), ability_id: Some(shield) },
+    );
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(9));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.readied_reaction, None);
+  }
+
+  #[test]
+  fn readied_reaction_negates_damage() {
+    let mut game = t_game();
+    let shield = t_shield(&mut game, HP(5));
+    let game = t_perform(
+      &game,
+      GMCommand::ReadyReaction { creature_id: cid_ranger(), ability_id: Some(shield) },
+    );
+    let changed = perf(&game, t_punch_cmd(cid_ranger())).unwrap();
+    assert_eq!(hp(&changed.game, cid_ranger()), HP(10));
+    // The reaction happens before the punch's damage.
+    let used: Vec<AbilityID> = changed
+      .logs
+      .iter()
+      .filter_map(|log| match log {
+        GameLog::CreatureUsedAbility { ability_id, .. } => Some(*ability_id),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(used, vec![abid_punch(), shield]);
+  }
+
+  #[test]
+  fn only_self_targeted_reactions_can_be_readied() {
+    let cmd = GMCommand::ReadyReaction { creature_id: cid_rogue(), ability_id: Some(abid_punch()) };
+    assert_eq!(perf(&t_game(), cmd).map(|_| ()), Err(GameError::CannotReadyAbility(abid_punch())));
+  }
+
+  /// A game where attacks wait 5 seconds for reactions, and the ranger has a shield reaction that
+  /// reduces damage by 2. The ranger is punched at time 100.
+  fn t_punched_in_reaction_window() -> (Game, AbilityID) {
+    let mut game = t_game();
+    let shield = t_shield(&mut game, HP(2));
+    let settings = GameSettings { reaction_window: Some(5), ..Default::default() };
+    let game = t_perform(&game, GMCommand::EditGameSettings { settings });
+    let game = game.perform_gm_command_at(t_punch_cmd(cid_ranger()), 100).unwrap().game;
+    (game, shield)
+  }
+
+  #[test]
+  fn attacks_wait_for_reactions() {
+    let (game, _) = t_punched_in_reaction_window();
+    assert_eq!(hp(&game, cid_ranger()), HP(10));
+    let window = game.reaction_window.clone().unwrap();
+    assert_eq!(window.reactors, vec![cid_ranger()]);
+    assert_eq!(window.opened_at, 100);
+    // Nobody else gets to act while the window is open.
+    assert_eq!(
+      perf(&game, t_punch_cmd(cid_cleric())).map(|_| ()),
+      Err(GameError::WaitingForReactions)
+    );
+  }
+
+  #[test]
+  fn nothing_can_move_or_leave_while_reactions_are_pending() {
+    let (game, _) = t_punched_in_reaction_window();
+    let blocked = vec![
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(1000, 0, 0),
+      },
+      GMCommand::RemoveCreatureFromScene { scene_id: t_scene_id(), creature_id: cid_ranger() },
+      GMCommand::DeleteFolderItem {
+        path: FolderPath::root(),
+        item_id: FolderItemID::CreatureID(cid_rogue()),
+      },
+    ];
+    for cmd in blocked {
+      assert_eq!(perf(&game, cmd).map(|_| ()), Err(GameError::WaitingForReactions));
+    }
+    perf(&game, GMCommand::ChatFromGM { message: "hold on".to_string() }).unwrap();
+  }
+
+  #[test]
+  fn attacks_on_creatures_without_reactions_land_right_away() {
+    let (game, _) = t_punched_in_reaction_window();
+    let game = t_perform(&game, GMCommand::React { creature_id: cid_ranger(), ability_id: None });
+    let game = t_perform(&game, t_punch_cmd(cid_cleric()));
+    assert_eq!(game.reaction_window, None);
+    assert_eq!(hp(&game, cid_cleric()), HP(7));
+  }
+
+  #[test]
+  fn reaction_in_the_window_changes_the_outcome() {
+    let (game, shield) = t_punched_in_reaction_window();
+    let react = GMCommand::React { creature_id: cid_ranger(), ability_id: Some(shield) };
+    let changed = game.perform_gm_command_at(react, 102).unwrap();
+    assert_eq!(changed.game.reaction_window, None);
+    assert_eq!(hp(&changed.game, cid_ranger()), HP(9));
+  }
+
+  #[test]
+  fn declining_to_react_lets_the_attack_land() {
+    let (game, _) = t_punched_in_reaction_window();
+    let game = t_perform(&game, GMCommand::React { creature_id: cid_ranger(), ability_id: None });
+    assert_eq!(game.reaction_window, None);
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
+  }
+
+  #[test]
+  fn attack_lands_when_the_reaction_window_runs_out() {
+    let (game, _) = t_punched_in_reaction_window();
+    assert_eq!(game.expire_reaction_window(104).unwrap().logs, vec![]);
+    let changed = game.expire_reaction_window(105).unwrap();
+    assert_eq!(changed.game.reaction_window, None);
+    assert_eq!(hp(&changed.game, cid_ranger()), HP(7));
+  }
+
+  #[test]
+  fn only_attacked_creatures_can_react() {
+    let (game, _) = t_punched_in_reaction_window();
+    let react = GMCommand::React { creature_id: cid_cleric(), ability_id: None };
+    assert_eq!(perf(&game, react).map(|_| ()), Err(GameError::CannotReact(cid_cleric())));
+  }
+
+  fn t_rest(kind: RestKind) -> GMCommand {
+    GMCommand::Rest { creatures: vec![cid_rogue(), cid_ranger()], kind }
+  }
+
+  /// A game where the rogue can only punch twice before it has to rest.
+  fn t_limited_punch_game(recharge: RestKind) -> Game {
+    let mut game = t_game();
+    game.abilities.mutate(&abid_punch(), |ab| ab.uses_per_rest = Some((2, recharge)));
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    t_perform(&game, t_punch_cmd(cid_ranger()))
+  }
+
+  #[test]
+  fn depleted_ability_is_rejected() {
+    let game = t_limited_punch_game(RestKind::Short);
+    assert_eq!(hp(&game, cid_ranger()), HP(4));
+    assert_eq!(
+      perf(&game, t_punch_cmd(cid_ranger())).map(|_| ()),
+      Err(GameError::AbilityDepleted(cid_rogue(), abid_punch()))
+    );
+  }
+
+  #[test]
+  fn short_rest_recharges_short_rest_abilities() {
+    let game = t_limited_punch_game(RestKind::Short);
+    let game = t_perform(&game, t_rest(RestKind::Short));
+    // The ranger heals from 4 to 9 HP during the rest.
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(6));
+  }
+
+  #[test]
+  fn long_rest_abilities_need_a_long_rest() {
+    let game = t_limited_punch_game(RestKind::Long);
+    let rested = t_perform(&game, t_rest(RestKind::Short));
+    assert_eq!(
+      perf(&rested, t_punch_cmd(cid_ranger())).map(|_| ()),
+      Err(GameError::AbilityDepleted(cid_rogue(), abid_punch()))
+    );
+    let rested = t_perform(&game, t_rest(RestKind::Long));
+    let rested = t_perform(&rested, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&rested, cid_ranger()), HP(7));
+  }
+
+  /// A game where the ranger has 1 HP and no energy left, and the rogue has used up its punches.
+  fn t_tired_game() -> Game {
+    let mut game = t_limited_punch_game(RestKind::Short);
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.cur_health = HP(1);
+      c.cur_energy = Energy(0);
+    });
+    game
+  }
+
+  #[test]
+  fn long_rest_restores_everything() {
+    let game = t_perform(&t_tired_game(), t_rest(RestKind::Long));
+    let ranger = game.get_creature(cid_ranger()).unwrap().creature;
+    assert_eq!((ranger.cur_health, ranger.cur_energy), (HP(10), Energy(3)));
+    let rogue = game.get_creature(cid_rogue()).unwrap().creature;
+    assert_eq!(rogue.ability_uses, HashMap::new());
+  }
+
+  #[test]
+  fn short_rest_restores_part_of_health_and_energy() {
+    let game = t_perform(&t_tired_game(), t_rest(RestKind::Short));
+    let ranger = game.get_creature(cid_ranger()).unwrap().creature;
+    assert_eq!((ranger.cur_health, ranger.cur_energy), (HP(6), Energy(1)));
+  }
+
+  #[test]
+  fn short_rest_follows_the_rest_policy() {
+    let mut game = t_tired_game();
+    game.settings.rest_policy.short = RestRecovery { health_percent: 30, energy_percent: 0 };
+    let changed = perf(&game, t_rest(RestKind::Short)).unwrap();
+    let ranger_logs: Vec<CreatureLog> = changed
+      .logs
+      .iter()
+      .filter_map(|log| match log {
+        GameLog::CreatureLog { creature_id, log } if *creature_id == cid_ranger() => {
+          Some(log.clone())
+        }
+        _ => None,
+      })
+      .collect();
+    assert_eq!(ranger_logs, vec![CreatureLog::Heal { hp: HP(3), rolls: vec![] }]);
+  }
+
+  #[test]
+  fn creatures_not_resting_recover_nothing() {
+    let game = t_tired_game();
+    let cmd = GMCommand::Rest { creatures: vec![cid_rogue()], kind: RestKind::Long };
+    let game = t_perform(&game, cmd);
+    assert_eq!(hp(&game, cid_ranger()), HP(1));
+  }
+
+  #[test]
+  fn ability_creatures_within_area() {
+    // the cleric moves away, then casts a fireball at the ranger and rogue.
+    let game = t_game();
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        pos: Point3::new(1100, 0, 0),
+      },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: abid_fireball(),
+        target: DecidedTarget::Point(Point3::new(0, 0, 0)),
+      },
+    );
+    assert_eq!(game.get_creature(cid_rogue()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
+    assert_eq!(game.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(10));
+  }
+
+  /// Targets that `get_target_options` doesn't offer are rejected.
+  #[test]
+  fn illegal_targets_rejected() {
+    let game = t_game();
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(600, 0, 0),
+      },
+    );
+    let ranger_act = |ability_id, target| GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_ranger(),
+      ability_id,
+      target,
+    };
+    let options = |ability_id| game.get_target_options(t_scene_id(), cid_ranger(), ability_id);
+
+    let shoot_options = options(abid_shoot()).unwrap();
+    assert!(!shoot_options.allows(cid_ranger(), &DecidedTarget::Creature(cid_rogue())));
+    let rogue = DecidedTarget::Creature(cid_rogue());
+    assert!(perf(&game, ranger_act(abid_shoot(), rogue)).is_err());
+    let cleric = DecidedTarget::Creature(cid_cleric());
+    perf(&game, ranger_act(abid_shoot(), cleric)).unwrap();
+
+    // Piercing Shot's line would happily extend to any point, but only points within its length
+    // are offered.
+    let far = DecidedTarget::Point(Point3::new(1500, 0, 0));
+    assert_eq!(
+      perf(&game, ranger_act(abid_piercing_shot(), far.clone())).map(|_| ()),
+      Err(GameError::IllegalTarget(far))
+    );
+    let near = DecidedTarget::Point(Point3::new(500, 0, 0));
+    assert!(options(abid_piercing_shot()).unwrap().allows(cid_ranger(), &near));
+    perf(&game, ranger_act(abid_piercing_shot(), near)).unwrap();
+  }
+
+  /// A sphere placed at a point away from the caster hits everyone inside it, and only them.
+  #[test]
+  fn ability_at_point() {
+    let game = t_game();
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(1500, 0, 0),
+      },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(1800, 0, 0),
+      },
+    );
+    let fireball_at = |pt| GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_cleric(),
+      ability_id: abid_fireball(),
+      target: DecidedTarget::Point(pt),
+    };
+    let game = t_perform(&game, fireball_at(Point3::new(1600, 0, 0)));
+    assert_eq!(hp(&game, cid_rogue()), HP(7));
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
+    assert_eq!(hp(&game, cid_cleric()), HP(10));
+
+    // Fireball's range is 20 meters.
+    let far = Point3::new(1500, 1500, 0);
+    assert_eq!(perf(&game, fireball_at(far)).map(|_| ()), Err(GameError::PointOutOfRange(far)));
+  }
+
+  #[test]
+  fn ability_at_point_needs_line_of_sight() {
+    let mut game = t_game();
+    // A wall across the box, 5 meters in front of the cleric.
+    game.scenes.mutate(&t_scene_id(), |scene| {
+      scene.terrain.retain(|pt| pt.x.get::<centimeter>() != 500);
+    });
+    let behind = Point3::new(1000, 0, 0);
+    let cmd = GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_cleric(),
+      ability_id: abid_fireball(),
+      target: DecidedTarget::Point(behind),
+    };
+    assert_eq!(perf(&game, cmd).map(|_| ()), Err(GameError::PointNotVisible(behind)));
+    match game.get_target_options(t_scene_id(), cid_cleric(), abid_fireball()).unwrap() {
+      PotentialTargets::Points(pts) => {
+        assert!(pts.contains(&Point3::new(300, 0, 0)));
+        assert!(!pts.contains(&behind));
+      }
+      options => panic!("Unexpected options: {options:?}"),
+    }
+  }
+
+  #[test]
+  fn test_creatures_in_sphere() {
+    let game = t_game();
+    let volume = Volume::Sphere(u32cm(200));
+    let pt = Point3::new(500, 0, 0);
+
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(500, 0, 0),
+      },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        pos: Point3::new(600, 0, 0),
+      },
+    );
+    let scene = game.get_scene(t_scene_id()).unwrap();
+
+    let cids = scene.creatures_in_volume(game.tile_system, pt, volume);
+    let cids = HashSet::<CreatureID>::from_iter(cids);
+    assert_eq!(cids, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  }
+
+  #[test]
+  fn test_sphere_targets() {
+    let game = t_game();
+    let target_spec = CreatureTarget::AllCreaturesInVolumeInRange {
+      range: u32cm(1000),
+      volume: Volume::Sphere(u32cm(200)),
+    };
+    let pt = Point3::new(500, 0, 0);
+
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(500, 0, 0),
+      },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        pos: Point3::new(600, 0, 0),
+      },
+    );
+
+    let scene = game.get_scene(t_scene_id()).unwrap();
+
+    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
+    let targets = HashSet::<CreatureID>::from_iter(targets);
+    assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  }
+
+  #[test]
+  fn test_line_targets() {
+    let game = t_game();
+    let target_spec = CreatureTarget::LineFromActor { distance: u32cm(1000) };
+    let pt = Point3::new(100, 0, 0);
+
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(100, 0, 0),
+      },
+    );
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        pos: Point3::new(200, 0, 0),
+      },
+    );
+    let scene = game.get_scene(t_scene_id()).unwrap();
+
+    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
+    let targets = HashSet::<CreatureID>::from_iter(targets);
+    assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  }
+
+  #[test]
+  fn preview_cone_from_actor() {
+    let mut game = t_game();
+    let breath = Ability {
+      id: AbilityID::gen(),
+      name: "Fire Breath".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Action,
+      action: Action::Creature {
+        target: CreatureTarget::ConeFromActor { length: u32cm(500), angle_degrees: 60 },
+        effect: CreatureEffect::Damage(Dice::flat(3)),
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    };
+    game.abilities.insert(breath.clone());
+    let move_to = |game: &Game, creature_id, pos| {
+      t_perform(game, GMCommand::SetCreaturePos { scene_id: t_scene_id(), creature_id, pos })
+    };
+    // The rogue is inside the cone, and the cleric is just outside its edge.
+    let game = move_to(&game, cid_rogue(), Point3::new(300, 100, 0));
+    let game = move_to(&game, cid_cleric(), Point3::new(100, 100, 0));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+
+    let (cids, tiles) =
+      game.preview_volume_targets(scene, cid_ranger(), breath.id, Point3::new(500, 0, 0)).unwrap();
+    assert_eq!(cids, vec![cid_rogue()]);
+    assert!(tiles.contains(&Point3::new(0, 0, 0)));
+    assert!(tiles.contains(&Point3::new(500, 0, 0)));
+    assert!(!tiles.contains(&Point3::new(100, 100, 0)));
+  }
+
+  #[test]
+  fn preview_volume_targets_shows_creatures_for_scene_volume_actions() {
+    // When previewing volume targets for an action that affects a Scene Volume, the creatures
+    // within the volume will be returned.
+
+    let game = t_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let cleric = cid_cleric();
+    let ability_id = abid_thorn_patch();
+    let preview =
+      game.preview_volume_targets(scene, cleric, ability_id, Point3::new(0, 0, 0)).unwrap();
+    let expected = hashset! {cid_cleric(), cid_ranger(), cid_rogue()};
+    assert_eq!(HashSet::from_iter(preview.0), expected);
+  }
+
+  /// A game with a "wolf" template creature (of the rogue class, so it can punch) and a spell
+  /// that lets the cleric summon a wolf for the given duration. Returns the game and the spell.
+  fn t_summon_game(duration: Duration) -> (Game, AbilityID) {
+    let mut game = t_game();
+    let wolf = t_creature("wolf", classid_rogue(), 5);
+    let summon = Ability {
+      id: AbilityID::gen(),
+      name: "Summon Wolf".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Action,
+      action: Action::SceneVolume {
+        target: SceneTarget::RangedVolume { volume: Volume::Sphere(u32cm(100)), range: u32cm(500) },
+        effect: SceneEffect::Summon { template: wolf.id, duration },
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    };
+    let testdata = FolderPath::from_vec(vec!["testdata".to_string()]);
+    game.campaign.get_mut(&testdata).unwrap().creatures.insert(wolf.id);
+    game.creatures.insert(wolf);
+    game.abilities.insert(summon.clone());
+    game.creatures.mutate(&cid_cleric(), |c| {
+      c.abilities.insert(AbilityStatus { ability_id: summon.id, cooldown: 0 });
+    });
+    (game, summon.id)
+  }
+
+  /// The creature that was summoned into `game`, which must be the only one not in `before`.
+  fn t_summoned(before: &Game, game: &Game) -> CreatureID {
+    let new: Vec<CreatureID> =
+      game.creatures.keys().filter(|cid| !before.creatures.contains_key(cid)).cloned().collect();
+    assert_eq!(new.len(), 1);
+    new[0]
+  }
+
+  #[test]
+  fn summon_appears_next_to_caster() {
+    let (game, summon) = t_summon_game(Duration::Interminate);
+    let changed = perf(
+      &game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: summon,
+        target: DecidedTarget::Point(Point3::new(0, 0, 0)),
+      },
+    )
+    .unwrap();
+    let summoned = t_summoned(&game, &changed.game);
+    let creature = changed.game.get_creature(summoned).unwrap().creature;
+    assert_eq!(creature.name, "wolf");
+    assert!(creature
+      .conditions
+      .values()
+      .any(|c| c.condition == Condition::Summoned && c.remaining == Duration::Interminate));
+    let pos = changed.game.get_scene(t_scene_id()).unwrap().get_pos(summoned).unwrap();
+    assert_eq!(changed.game.tile_system.point3_distance(Point3::new(0, 0, 0), pos), u32cm(100));
+    let testdata = FolderPath::from_vec(vec!["testdata".to_string()]);
+    assert!(changed.game.campaign.get(&testdata).unwrap().creatures.contains(&summoned));
+    assert!(changed.logs.contains(&GameLog::CreatureEnteredScene {
+      scene_id: t_scene_id(),
+      creature_id: summoned
+    }));
+  }
+
+  #[test]
+  fn summon_acts_and_is_removed_on_expiry() {
+    let (game, summon) = t_summon_game(Duration::Rounds(0));
+    let game = t_start_combat(&game, vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let summoned_game = t_game_act(&game, summon, DecidedTarget::Point(Point3::new(0, 0, 0)));
+    let summoned = t_summoned(&game, &summoned_game);
+    assert!(summoned_game.get_combat().unwrap().combat.contains_creature(summoned));
+
+    // The wolf gets its own turn after the cleric's, and can punch.
+    let game = t_perform(&summoned_game, GMCommand::EndTurn);
+    assert_eq!(game.get_combat().unwrap().combat.current_creature_id(), summoned);
+    let game = t_game_act(&game, abid_punch(), DecidedTarget::Creature(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
+
+    // Its summoning only lasts this round, so it disappears at the end of its turn.
+    let changed = perf(&game, GMCommand::EndTurn).unwrap();
+    assert!(changed.logs.contains(&GameLog::CreatureLeftScene {
+      scene_id: t_scene_id(),
+      creature_id: summoned
+    }));
+    let game = changed.game;
+    assert!(!game.creatures.contains_key(&summoned));
+    assert!(!game.get_scene(t_scene_id()).unwrap().creatures.contains_key(&summoned));
+    let combat = game.get_combat().unwrap().combat;
+    assert_eq!(combat.creature_ids(), vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    assert_eq!(combat.current_creature_id(), cid_rogue());
+  }
+
+  /// Have the cleric summon a wolf for some rounds on its turn in combat. Returns the game and the
+  /// wolf.
+  fn t_summon_in_combat(rounds: u8) -> (Game, CreatureID) {
+    let (game, summon) = t_summon_game(Duration::Rounds(rounds));
+    let game = t_start_combat(&game, vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let summoned_game = t_game_act(&game, summon, DecidedTarget::Point(Point3::new(0, 0, 0)));
+    let summoned = t_summoned(&game, &summoned_game);
+    (summoned_game, summoned)
+  }
+
+  #[test]
+  fn summons_for_rounds_are_dismissed_when_combat_stops() {
+    let (game, summoned) = t_summon_in_combat(3);
+    let game = t_perform(&game, GMCommand::StopCombat { preserve_initiative: false });
+    assert!(!game.creatures.contains_key(&summoned));
+    assert!(!game.get_scene(t_scene_id()).unwrap().creatures.contains_key(&summoned));
+  }
+
+  #[test]
+  fn summons_for_rounds_are_dismissed_when_removed_from_combat() {
+    let (game, summoned) = t_summon_in_combat(3);
+    let game = t_perform(&game, GMCommand::RemoveCreatureFromCombat { creature_id: summoned });
+    assert!(!game.creatures.contains_key(&summoned));
+    assert!(!game.get_scene(t_scene_id()).unwrap().creatures.contains_key(&summoned));
+    let combat = game.get_combat().unwrap().combat;
+    assert_eq!(combat.creature_ids(), vec![cid_rogue(), cid_ranger(), cid_cleric()]);
+  }
+
+  #[test]
+  fn summons_for_rounds_need_combat() {
+    let (game, summon) = t_summon_game(Duration::Rounds(3));
+    let result = perf(
+      &game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: summon,
+        target: DecidedTarget::Point(Point3::new(0, 0, 0)),
+      },
+    );
+    assert_eq!(result, Err(GameError::SummonNeedsCombat(cid_cleric())));
+  }
+
+  /// A game where the cleric can teleport up to 10 meters, with the rogue 5 meters away from it.
+  /// Returns the game and the teleport ability.
+  fn t_teleport_game() -> (Game, AbilityID) {
+    let mut game = t_game();
+    let teleport = Ability {
+      id: AbilityID::gen(),
+      name: "Dimension Door".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Action,
+      action: Action::SceneVolume {
+        target: SceneTarget::RangedVolume {
+          volume: Volume::Sphere(u32cm(100)),
+          range: u32cm(1000),
+        },
+        effect: SceneEffect::Teleport,
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    };
+    game.abilities.insert(teleport.clone());
+    game.creatures.mutate(&cid_cleric(), |c| {
+      c.abilities.insert(AbilityStatus { ability_id: teleport.id, cooldown: 0 });
+    });
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_rogue(),
+        pos: Point3::new(500, 0, 0),
+      },
+    );
+    (game, teleport.id)
+  }
+
+  fn t_teleport(game: &Game, teleport: AbilityID, pt: Point3) -> Result<ChangedGame, GameError> {
+    perf(
+      game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: teleport,
+        target: DecidedTarget::Point(pt),
+      },
+    )
+  }
+
+  #[test]
+  fn teleport_next_to_distant_target() {
+    let (game, teleport) = t_teleport_game();
+    let changed = t_teleport(&game, teleport, Point3::new(500, 0, 0)).unwrap();
+    // The rogue is standing on the targeted point, so the cleric lands right next to it, on the
+    // side closest to where the cleric started.
+    let pos = Point3::new(400, 0, 0);
+    assert!(changed.logs.contains(&GameLog::SetCreaturePos {
+      scene_id: t_scene_id(),
+      creature_id: cid_cleric(),
+      pos
+    }));
+    assert_eq!(changed.game.get_scene(t_scene_id()).unwrap().get_pos(cid_cleric()), Ok(pos));
+
+    // An open point is landed on directly, but not one that's out of range.
+    let changed = t_teleport(&game, teleport, Point3::new(700, 0, 0)).unwrap();
+    let scene = changed.game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_cleric()), Ok(Point3::new(700, 0, 0)));
+    let far = Point3::new(1500, 0, 0);
+    assert_eq!(t_teleport(&game, teleport, far).map(|_| ()), Err(GameError::PointOutOfRange(far)));
+  }
+
+  #[test]
+  fn teleport_around_a_large_creature() {
+    let (mut game, teleport) = t_teleport_game();
+    // The ranger is 2x2, so it also covers the cells between the cleric and the rogue.
+    let size = AABB { x: u32cm(200), y: u32cm(200), z: u32cm(200), anchor: AABBAnchor::TopLeft };
+    game.creatures.mutate(&cid_ranger(), |c| c.size = size);
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(400, -100, 0),
+      },
+    );
+    let changed = t_teleport(&game, teleport, Point3::new(500, 0, 0)).unwrap();
+    let scene = changed.game.get_scene(t_scene_id()).unwrap();
+    assert_eq!(scene.get_pos(cid_cleric()), Ok(Point3::new(500, 100, 0)));
+  }
+
+  #[test]
+  fn teleport_without_room_next_to_target() {
+    let (game, teleport) = t_teleport_game();
+    // A corridor with the rogue at the end of it and the ranger right in front of the rogue.
+    let terrain = (0..6).map(|x| Point3::new(x * 100, 0, 0)).collect();
+    let game = t_perform(&game, GMCommand::EditSceneTerrain { scene_id: t_scene_id(), terrain });
+    let game = t_perform(
+      &game,
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(400, 0, 0),
+      },
+    );
+    let target = Point3::new(500, 0, 0);
+    assert_eq!(
+      t_teleport(&game, teleport, target).map(|_| ()),
+      Err(GameError::NoRoomToTeleport(target))
+    );
+  }
+
+  #[test]
+  fn preview_ability_on_creature() {
+    let game = t_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let preview = game
+      .preview_ability(scene, cid_ranger(), abid_shoot(), DecidedTarget::Creature(cid_rogue()))
+      .unwrap();
+    let expected = EffectPreview {
+      damage: Some(RollRange { min: 3, max: 3, average: 3.0 }),
+      healing: None,
+      energy: Energy(0),
+      conditions: vec![],
+      cover: Cover::None,
+    };
+    assert_eq!(preview, vec![(cid_rogue(), expected)]);
+  }
+
+  #[test]
+  fn preview_ability_with_creature_dice() {
+    let game = t_perform(
+      &t_game(),
+      GMCommand::SetCreatureAbilityDice {
+        creature_id: cid_ranger(),
+        ability_id: abid_shoot(),
+        dice: Some(Dice::flat(5)),
+      },
+    );
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let preview = game
+      .preview_ability(scene, cid_ranger(), abid_shoot(), DecidedTarget::Creature(cid_rogue()))
+      .unwrap();
+    assert_eq!(preview[0].1.damage, Some(RollRange { min: 5, max: 5, average: 5.0 }));
+  }
+
+  #[test]
+  fn preview_ability_on_volume() {
+    let game = t_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let target = DecidedTarget::Point(Point3::new(0, 0, 0));
+    let preview = game.preview_ability(scene, cid_cleric(), abid_fireball(), target).unwrap();
+    let cids: Vec<CreatureID> = preview.iter().map(|(cid, _)| *cid).collect();
+    assert_eq!(cids, vec![cid_cleric(), cid_ranger(), cid_rogue()]);
+    let damage = Some(RollRange { min: 3, max: 3, average: 3.0 });
+    assert!(preview.iter().all(|(_, p)| p.damage == damage));
+    // Nothing was actually done to anyone.
+    assert_eq!(hp(&game, cid_rogue()), HP(10));
+  }
+
+  #[test]
+  fn preview_ability_leaves_out_conditions_the_target_is_immune_to() {
+    let mut game = t_game();
+    let stun = Ability {
+      id: AbilityID::gen(),
+      name: "Stun".to_string(),
+      cost: Energy(0),
+      usable_ooc: true,
+      action_kind: ActionKind::Action,
+      action: Action::Creature {
+        effect: CreatureEffect::ApplyCondition(Duration::Rounds(1), Condition::Incapacitated),
+        target: CreatureTarget::Range(u32cm(500)),
+      },
+      attack_roll: None,
+      uses_per_rest: None,
+    };
+    game.abilities.insert(stun.clone());
+    game.creatures.mutate(&cid_cleric(), |c| {
+      c.abilities.insert(AbilityStatus { ability_id: stun.id, cooldown: 0 });
+    });
+    game.creatures.mutate(&cid_rogue(), |c| c.immunities.push(Condition::Incapacitated));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let conditions = |cid| {
+      let target = DecidedTarget::Creature(cid);
+      let preview = game.preview_ability(scene, cid_cleric(), stun.id, target).unwrap();
+      preview[0].1.conditions.clone()
+    };
+    assert_eq!(conditions(cid_ranger()), vec![Condition::Incapacitated.apply(Duration::Rounds(1))]);
+    assert_eq!(conditions(cid_rogue()), vec![]);
+  }
+
+  /// A game where the ranger can't be targeted by abilities of the given category.
+  fn t_untargetable_ranger(category: TargetCategory) -> Game {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_ranger(), |c| c.untargetable_by.push(category));
+    game
+  }
+
+  #[test]
+  fn unhealable_creature_isnt_a_heal_target() {
+    let game = t_untargetable_ranger(TargetCategory::Healing);
+    let options = game.get_target_options(t_scene_id(), cid_cleric(), abid_heal()).unwrap();
+    let ranger = DecidedTarget::Creature(cid_ranger());
+    assert!(!options.allows(cid_cleric(), &ranger));
+    assert!(options.allows(cid_cleric(), &DecidedTarget::Creature(cid_rogue())));
+    let heal = GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_cleric(),
+      ability_id: abid_heal(),
+      target: ranger.clone(),
+    };
+    assert_eq!(perf(&game, heal).map(|_| ()), Err(GameError::IllegalTarget(ranger)));
+  }
+
+  #[test]
+  fn unhealable_creature_is_still_a_damage_target() {
+    let game = t_untargetable_ranger(TargetCategory::Healing);
+    let options = game.get_target_options(t_scene_id(), cid_ranger(), abid_shoot()).unwrap();
+    assert!(options.allows(cid_ranger(), &DecidedTarget::Creature(cid_rogue())));
+    let options = game.get_target_options(t_scene_id(), cid_rogue(), abid_punch()).unwrap();
+    assert!(options.allows(cid_rogue(), &DecidedTarget::Creature(cid_ranger())));
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
+  }
+
+  #[test]
+  fn volumes_pass_over_untargetable_creatures() {
+    let game = t_untargetable_ranger(TargetCategory::Damage);
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let target = DecidedTarget::Point(Point3::new(0, 0, 0));
+    let preview =
+      game.preview_ability(scene, cid_cleric(), abid_fireball(), target.clone()).unwrap();
+    let cids: Vec<CreatureID> = preview.iter().map(|(cid, _)| *cid).collect();
+    assert_eq!(cids, vec![cid_cleric(), cid_rogue()]);
+    let game = t_perform(
+      &game,
+      GMCommand::ActCreature {
+        scene_id: t_scene_id(),
+        creature_id: cid_cleric(),
+        ability_id: abid_fireball(),
+        target,
+      },
+    );
+    assert_eq!(hp(&game, cid_ranger()), HP(10));
+    assert_eq!(hp(&game, cid_rogue()), HP(7));
+  }
+
+  fn t_volume_game() -> Game {
+    let game = t_game();
+    let place = |game: &Game, creature_id, pos| {
+      t_perform(game, GMCommand::SetCreaturePos { scene_id: t_scene_id(), creature_id, pos })
+    };
+    let game = place(&game, cid_rogue(), Point3::new(500, 0, 0));
+    let game = place(&game, cid_cleric(), Point3::new(600, 100, 0));
+    place(&game, cid_ranger(), Point3::new(800, 0, 0))
+  }
+
+  #[test]
+  fn creatures_and_terrain_in_sphere() {
+    let game = t_volume_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let volume = Volume::Sphere(u32cm(200));
+    let (cids, tiles) =
+      game.creatures_and_terrain_in_volume(scene, Point3::new(500, 0, 0), volume).unwrap();
+    assert_eq!(cids, vec![cid_cleric(), cid_rogue()]);
+    assert_eq!(tiles.len(), 13);
+    assert!(tiles.contains(&Point3::new(700, 0, 0)));
+    assert!(tiles.contains(&Point3::new(600, 100, 0)));
+    assert!(!tiles.contains(&Point3::new(700, 100, 0)));
+  }
+
+  #[test]
+  fn creatures_and_terrain_in_aabb() {
+    let game = t_volume_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let volume = Volume::AABB(AABB {
+      x: u32cm(300),
+      y: u32cm(200),
+      z: u32cm(100),
+      anchor: AABBAnchor::TopLeft,
+    });
+    let (cids, tiles) =
+      game.creatures_and_terrain_in_volume(scene, Point3::new(500, 0, 0), volume).unwrap();
+    assert_eq!(cids, vec![cid_cleric(), cid_rogue()]);
+    let mut expected = vec![];
+    for x in 5..8 {
+      for y in 0..2 {
+        expected.push(Point3::new(x * 100, y * 100, 0));
+      }
+    }
+    assert_eq!(tiles, expected);
+  }
+
+  #[test]
+  fn granted_ability_expires() {
+    let mut game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger()]);
+    let granted = app_cond(Condition::ActivateAbility(abid_heal()), Duration::Rounds(1));
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.conditions.insert(ConditionID(uuid_5()), granted);
+    });
+    let has_heal = |game: &Game| game.get_creature(cid_rogue()).unwrap().has_ability(abid_heal());
+    assert!(has_heal(&game));
+
+    // The condition lasts through the end of the rogue's next turn.
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert!(has_heal(&game));
+    let game = t_perform(&game, GMCommand::EndTurn);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert!(!has_heal(&game));
+
+    let lacks_heal = GameError::CreatureLacksAbility(cid_rogue(), abid_heal());
+    assert_eq!(game.get_target_options(t_scene_id(), cid_rogue(), abid_heal()), Err(lacks_heal));
+    let result = game.perform_gm_command(GMCommand::CombatAct {
+      ability_id: abid_heal(),
+      target: DecidedTarget::Creature(cid_rogue()),
+    });
+    assert_eq!(result.map(|_| ()), Err(GameError::CreatureLacksAbility(cid_rogue(), abid_heal())));
+  }
+
+  /// An incapacitated creature can't act, but ending its turn still ticks all of its conditions,
+  /// including the one keeping it from acting.
+  #[test]
+  fn stunned_creature_conditions_expire() {
+    let mut game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger()]);
+    let bleed = Condition::RecurringEffect(Box::new(CreatureEffect::Damage(Dice::flat(1))));
+    let stun = app_cond(Condition::Incapacitated, Duration::Rounds(2));
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.conditions.insert(ConditionID(uuid_4()), stun);
+      c.conditions.insert(ConditionID(uuid_5()), app_cond(bleed, Duration::Rounds(1)));
+    });
+    let punch = GMCommand::CombatAct {
+      ability_id: abid_punch(),
+      target: DecidedTarget::Creature(cid_ranger()),
+    };
+    let remaining = |game: &Game| {
+      let mut conds: Vec<_> =
+        game.get_creature(cid_rogue()).unwrap().creature.conditions.clone().into_iter().collect();
+      conds.sort_by_key(|(id, _)| *id);
+      conds.into_iter().map(|(_, c)| c.remaining).collect::<Vec<_>>()
+    };
+
+    let current = |game: &Game| game.get_combat().unwrap().combat.current_creature_id();
+
+    assert_eq!(perf(&game, punch.clone()).map(|_| ()), Err(GameError::CannotAct(cid_rogue())));
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(hp(&game, cid_rogue()), HP(9));
+    assert_eq!(remaining(&game), vec![Duration::Rounds(1), Duration::Rounds(0)]);
+
+    // The rogue's turns are skipped while it's stunned, but its conditions keep ticking.
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(current(&game), cid_ranger());
+    assert_eq!(hp(&game, cid_rogue()), HP(9));
+    assert_eq!(remaining(&game), vec![Duration::Rounds(0)]);
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(current(&game), cid_ranger());
+    assert_eq!(remaining(&game), vec![]);
+
+    let game = t_perform(&game, GMCommand::EndTurn);
+    assert_eq!(current(&game), cid_rogue());
+    perf(&game, punch).unwrap();
+  }
+
+  #[test]
+  fn ability_energy_is_logged_before_effects() {
+    let mut game = t_combat();
+    let energy = Energy(2);
+    game.abilities.mutate(&abid_punch(), |ab| ab.cost = energy);
+    let changed = t_act(&game, abid_punch(), DecidedTarget::Creature(cid_ranger())).unwrap();
+    let spent =
+      GameLog::CreatureLog { creature_id: cid_rogue(), log: CreatureLog::ReduceEnergy { energy } };
+    assert_eq!(changed.logs.len(), 3);
+    assert!(matches!(changed.logs[0], GameLog::CreatureUsedAbility { .. }));
+    assert_eq!(changed.logs[1], spent);
+    assert!(matches!(
+      changed.logs[2],
+      GameLog::CreatureLog { creature_id, log: CreatureLog::Damage { hp: HP(3), .. } }
+        if creature_id == cid_ranger()
+    ));
+    assert_eq!(changed.game.get_creature(cid_rogue()).unwrap().creature.cur_energy, Energy(1));
+  }
+
+  #[test]
+  fn free_abilities_dont_log_energy() {
+    let changed = t_act(&t_combat(), abid_punch(), DecidedTarget::Creature(cid_ranger())).unwrap();
+    assert!(!changed.logs.iter().any(|log| matches!(
+      log,
+      GameLog::CreatureLog { log: CreatureLog::ReduceEnergy { .. }, .. }
+    )));
+  }
+
+  /// A game where the rogue's punch takes a recurring effect from its target instead of doing
+  /// damage.
+  fn t_curse_transfer_game() -> Game {
+    let mut game = t_game();
+    game.abilities.mutate(&abid_punch(), |ab| {
+      if let Action::Creature { ref mut effect, .. } = ab.action {
+        *effect = CreatureEffect::TransferCondition {
+          which: ConditionSelector::AnyRecurringEffect,
+          to_target: false,
+        };
+      }
+    });
+    game
+  }
+
+  fn t_poison() -> Condition {
+    Condition::RecurringEffect(Box::new(CreatureEffect::Damage(Dice::flat(1))))
+  }
+
+  #[test]
+  fn transfer_poison_from_target_to_caster() {
+    let mut game = t_curse_transfer_game();
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.conditions.insert(ConditionID(uuid_4()), app_cond(t_poison(), Duration::Rounds(2)));
+    });
+    let changed = perf(&game, t_punch_cmd(cid_ranger())).unwrap();
+    assert_eq!(
+      changed.logs[1],
+      GameLog::CreatureLog {
+        creature_id: cid_ranger(),
+        log: CreatureLog::RemoveCondition { id: ConditionID(uuid_4()) },
+      }
+    );
+    assert!(matches!(
+      changed.logs[2],
+      GameLog::CreatureLog { creature_id, log: CreatureLog::ApplyCondition { .. } }
+        if creature_id == cid_rogue()
+    ));
+    let game = changed.game;
+    assert!(game.get_creature(cid_ranger()).unwrap().creature.conditions.is_empty());
+    let rogue_conditions: Vec<_> =
+      game.get_creature(cid_rogue()).unwrap().creature.conditions.values().cloned().collect();
+    assert_eq!(rogue_conditions, vec![app_cond(t_poison(), Duration::Rounds(2))]);
+  }
+
+  #[test]
+  fn transfer_condition_to_an_immune_creature_does_nothing() {
+    let mut game = t_curse_transfer_game();
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.conditions.insert(ConditionID(uuid_4()), app_cond(t_poison(), Duration::Rounds(2)));
+    });
+    game.creatures.mutate(&cid_rogue(), |c| c.immunities.push(t_poison()));
+    let changed = perf(&game, t_punch_cmd(cid_ranger())).unwrap();
+    assert_eq!(changed.logs.len(), 1);
+    assert!(changed.game.get_creature(cid_rogue()).unwrap().creature.conditions.is_empty());
+    assert_eq!(changed.game.get_creature(cid_ranger()).unwrap().creature.conditions.len(), 1);
+  }
+
+  #[test]
+  fn transfer_condition_without_a_match_does_nothing() {
+    let mut game = t_curse_transfer_game();
+    game.creatures.mutate(&cid_ranger(), |c| {
+      c.conditions
+        .insert(ConditionID(uuid_4()), app_cond(Condition::Incapacitated, Duration::Rounds(2)));
+    });
+    let changed = perf(&game, t_punch_cmd(cid_ranger())).unwrap();
+    assert_eq!(changed.logs.len(), 1);
+    assert!(matches!(changed.logs[0], GameLog::CreatureUsedAbility { .. }));
+    assert!(changed.game.get_creature(cid_rogue()).unwrap().creature.conditions.is_empty());
+    assert_eq!(changed.game.get_creature(cid_ranger()).unwrap().creature.conditions.len(), 1);
+  }
+
+  #[test]
+  fn apply_condition_to_many() {
+    let mut game = t_game();
+    game.creatures.mutate(&cid_cleric(), |c| c.immunities.push(Condition::Incapacitated));
+    let cmd = GMCommand::ApplyConditionToMany {
+      creatures: vec![cid_rogue(), cid_ranger(), cid_cleric()],
+      condition: Condition::Incapacitated,
+      duration: Duration::Rounds(2),
+    };
+    let changed = perf(&game, cmd.clone()).unwrap();
+    let affected: Vec<CreatureID> = changed
+      .logs
+      .iter()
+      .filter_map(|log| match log {
+        GameLog::CreatureLog { creature_id, log: CreatureLog::ApplyCondition { .. } } => {
+          Some(*creature_id)
+        }
+        _ => None,
+      })
+      .collect();
+    assert_eq!(affected, vec![cid_rogue(), cid_ranger()]);
+    let game = changed.game;
+    assert!(!game.get_creature(cid_rogue()).unwrap().can_act());
+    assert!(!game.get_creature(cid_ranger()).unwrap().can_act());
+    assert!(game.get_creature(cid_cleric()).unwrap().can_act());
+
+    // Applying it again doesn't stack another copy on anyone.
+    assert_eq!(perf(&game, cmd).unwrap().logs, vec![]);
+  }
+
+  #[test]
+  fn scene_creatures() {
+    let mut game = t_perform(
+      &t_game(),
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(300, 200, 0),
+      },
+    );
+    assert_eq!(
+      game.scene_creatures(t_scene_id()),
+      Ok(vec![
+        (cid_cleric(), Point3::new(0, 0, 0)),
+        (cid_ranger(), Point3::new(300, 200, 0)),
+        (cid_rogue(), Point3::new(0, 0, 0)),
+      ])
+    );
+
+    game.scenes.mutate(&t_scene_id(), |s| s.creatures.clear());
+    assert_eq!(game.scene_creatures(t_scene_id()), Ok(vec![]));
+  }
+
+  /// The test game, plus three goblins (two of them with the same name) in a "monsters" folder.
+  fn t_roster_game() -> (Game, Vec<CreatureID>) {
+    let mut game = t_game();
+    game.campaign.make_folder(&FolderPath::root(), "monsters".to_string(), Folder::new()).unwrap();
+    let monsters = FolderPath::from_vec(vec!["monsters".to_string()]);
+    let goblins: Vec<CreatureID> = ["goblin", "goblin", "goblin boss"]
+      .iter()
+      .map(|name| {
+        let goblin = t_creature(name, classid_rogue(), 0);
+        let id = goblin.id;
+        game.creatures.insert(goblin);
+        game.link_folder_item(&monsters, &FolderItemID::CreatureID(id)).unwrap();
+        id
+      })
+      .collect();
+    (game, goblins)
+  }
+
+  #[test]
+  fn creature_pages_cover_every_creature_once() {
+    let (game, _) = t_roster_game();
+    for sort in [CreatureSort::Name, CreatureSort::Folder] {
+      let mut paged = vec![];
+      for offset in (0..8).step_by(2) {
+        paged.extend(game.list_creatures_paged(offset, 2, sort));
+      }
+      assert_eq!(paged, game.list_creatures_paged(0, usize::MAX, sort));
+      let unique: HashSet<CreatureID> = paged.iter().cloned().collect();
+      assert_eq!(unique, game.creatures.keys().cloned().collect());
+      assert_eq!(paged.len(), 6);
+    }
+  }
+
+  #[test]
+  fn creature_listing_is_sorted_and_stable() {
+    let (game, goblins) = t_roster_game();
+    let mut same_goblins = [goblins[0], goblins[1]];
+    same_goblins.sort();
+    let by_name = game.list_creatures_paged(0, 10, CreatureSort::Name);
+    assert_eq!(
+      by_name,
+      vec![cid_cleric(), same_goblins[0], same_goblins[1], goblins[2], cid_ranger(), cid_rogue()]
+    );
+    let by_folder = game.list_creatures_paged(0, 10, CreatureSort::Folder);
+    assert_eq!(
+      by_folder,
+      vec![same_goblins[0], same_goblins[1], goblins[2], cid_cleric(), cid_ranger(), cid_rogue()]
+    );
+    // A copy of the game may store its creatures in a different order, but lists them the same way.
+    let copy: Game = serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+    assert_eq!(copy.list_creatures_paged(0, 10, CreatureSort::Name), by_name);
+    assert_eq!(copy.list_creatures_paged(0, 10, CreatureSort::Folder), by_folder);
+  }
+
+  #[test]
+  fn cell_info_with_creature_and_volume_condition() {
+    let mut game = t_perform(
+      &t_game(),
+      GMCommand::SetCreaturePos {
+        scene_id: t_scene_id(),
+        creature_id: cid_ranger(),
+        pos: Point3::new(300, 200, 0),
+      },
+    );
+    // The ranger is 2x2, so it also takes up the cell diagonally next to its position.
+    let size = AABB { x: u32cm(200), y: u32cm(200), z: u32cm(200), anchor: AABBAnchor::TopLeft };
+    game.creatures.mutate(&cid_ranger(), |c| c.size = size);
+    let cell = Point3::new(400, 300, 0);
+    let volume_condition = VolumeCondition {
+      point: cell,
+      volume: Volume::Sphere(u32cm(100)),
+      remaining: Duration::Interminate,
+      condition: Condition::Dead,
+    };
+    game.scenes.mutate(&t_scene_id(), |s| {
+      s.volume_conditions.insert(ConditionID(uuid_5()), volume_condition);
+    });
+
+    let expected = CellInfo {
+      open: true,
+      creatures: vec![cid_ranger()],
+      volume_conditions: vec![ConditionID(uuid_5())],
+    };
+    assert_eq!(game.cell_info(t_scene_id(), cell), Ok(expected));
+    let mut others = vec![cid_cleric(), cid_rogue()];
+    others.sort();
+    let expected = CellInfo { open: true, creatures: others, volume_conditions: vec![] };
+    assert_eq!(game.cell_info(t_scene_id(), Point3::new(0, 0, 0)), Ok(expected));
+    let off_map = game.cell_info(t_scene_id(), Point3::new(10_000, 0, 0)).unwrap();
+    assert_eq!(off_map, CellInfo::default());
+  }
+
+  /// The test game, with a volume condition, a spot of difficult terrain, and two highlights in
+  /// its scene. Only the red highlight is visible to players.
+  fn t_decorated_game() -> Game {
+    let mut game = t_game();
+    let volume_condition = VolumeCondition {
+      point: Point3::new(0, 0, 0),
+      volume: Volume::Sphere(u32cm(100)),
+      remaining: Duration::Interminate,
+      condition: Condition::Dead,
+    };
+    game.scenes.mutate(&t_scene_id(), |s| {
+      s.volume_conditions.insert(ConditionID(uuid_5()), volume_condition);
+      s.terrain_costs.insert(Point3::new(100, 0, 0), 2);
+      s.highlights.insert(Point3::new(100, 0, 0), ("red".to_string(), Visibility::AllPlayers));
+      s.highlights.insert(Point3::new(200, 0, 0), ("blue".to_string(), Visibility::GMOnly));
+    });
+    game
+  }
+
+  #[test]
+  fn scene_render_state_bundles_the_scene() {
+    let game = t_decorated_game();
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let state = game.scene_render_state(t_scene_id(), None).unwrap();
+    assert_eq!(state.creatures, game.scene_creatures(t_scene_id()).unwrap());
+    assert_eq!(state.creatures.len(), 3);
+    assert_eq!(state.terrain, scene.terrain);
+    assert_eq!(state.terrain_costs, scene.terrain_costs);
+    assert_eq!(state.volume_conditions, scene.volume_conditions);
+    assert_eq!(state.highlights, scene.highlights);
+    assert_eq!(state.highlights.len(), 2);
+  }
+
+  #[test]
+  fn scene_render_state_hides_gm_only_things_from_players() {
+    let game = t_perform(&t_decorated_game(), GMCommand::RegisterPlayer { id: bob() });
+    let mut game = t_perform(
+      &game,
+      GMCommand::GiveCreaturesToPlayer { player_id: bob(), creature_ids: vec![cid_ranger()] },
+    );
+    game.scenes.mutate(&t_scene_id(), |s| {
+      for cid in [cid_rogue(), cid_ranger()] {
+        s.creatures.insert(cid, (Point3::new(0, 0, 0), Visibility::GMOnly));
+      }
+    });
+    let state = game.scene_render_state(t_scene_id(), Some(&bob())).unwrap();
+    // Bob can see his own ranger even though it's hidden, but not the rogue.
+    let mut expected =
+      vec![(cid_cleric(), Point3::new(0, 0, 0)), (cid_ranger(), Point3::new(0, 0, 0))];
+    expected.sort();
+    assert_eq!(state.creatures, expected);
+    let highlights: Vec<Point3> = state.highlights.keys().cloned().collect();
+    assert_eq!(highlights, vec![Point3::new(100, 0, 0)]);
+    assert_eq!(state.terrain_costs.len(), 1);
+    assert_eq!(state.volume_conditions.len(), 1);
+
+    let gm_state = game.scene_render_state(t_scene_id(), None).unwrap();
+    assert_eq!(gm_state.creatures.len(), 3);
+    assert_eq!(gm_state.highlights.len(), 2);
+  }
+
+  #[test]
+  fn longer_reach_threatens_more() {
+    let game = t_perform(
+      &t_game(),
       GMCommand::SetCreaturePos {
         scene_id: t_scene_id(),
-        creature_id: cid_cleric(),
-        pos: Point3::new(1100, 0, 0),
+        creature_id: cid_ranger(),
+        pos: Point3::new(200, 0, 0),
       },
     );
+    let far = Point3::new(200, 0, 0);
+    let threatened = game.threatened_points(t_scene_id(), cid_rogue()).unwrap();
+    assert!(threatened.contains(&Point3::new(100, 100, 0)));
+    assert!(!threatened.contains(&far));
+    assert!(!threatened.contains(&Point3::new(0, 0, 0)));
+    assert_eq!(
+      perf(&game, t_punch_cmd(cid_ranger())).map(|_| ()),
+      Err(GameError::CreatureOutOfRange(cid_ranger()))
+    );
+
     let game = t_perform(
       &game,
-      GMCommand::ActCreature {
-        scene_id: t_scene_id(),
-        creature_id: cid_cleric(),
-        ability_id: abid_fireball(),
-        target: DecidedTarget::Point(Point3::new(0, 0, 0)),
-      },
+      GMCommand::SetCreatureReach { creature_id: cid_rogue(), reach: u32cm(250) },
     );
-    assert_eq!(game.get_creature(cid_rogue()).unwrap().creature.cur_health, HP(7));
-    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(7));
-    assert_eq!(game.get_creature(cid_cleric()).unwrap().creature.cur_health, HP(10));
+    let threatened = game.threatened_points(t_scene_id(), cid_rogue()).unwrap();
+    assert!(threatened.contains(&far));
+    let game = t_perform(&game, t_punch_cmd(cid_ranger()));
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
   }
 
-  #[test]
-  fn test_creatures_in_sphere() {
-    let game = t_game();
-    let volume = Volume::Sphere(u32cm(200));
-    let pt = Point3::new(500, 0, 0);
+  fn t_snapshot_cmd(name: &str) -> GMCommand {
+    GMCommand::SnapshotScene { scene_id: t_scene_id(), name: name.to_string() }
+  }
+
+  fn t_restore_cmd(name: &str) -> GMCommand {
+    GMCommand::RestoreScene { scene_id: t_scene_id(), name: name.to_string() }
+  }
 
+  #[test]
+  fn restore_scene_checkpoint() {
+    let baseline = t_perform(&t_game(), t_snapshot_cmd("start"));
+    let game = t_perform(&baseline, t_punch_cmd(cid_ranger()));
     let game = t_perform(
       &game,
       GMCommand::SetCreaturePos {
         scene_id: t_scene_id(),
-        creature_id: cid_rogue(),
-        pos: Point3::new(500, 0, 0),
+        creature_id: cid_ranger(),
+        pos: Point3::new(300, 0, 0),
       },
     );
-    let game = t_perform(
+    let mut game = t_perform(
       &game,
-      GMCommand::SetCreaturePos {
-        scene_id: t_scene_id(),
-        creature_id: cid_cleric(),
-        pos: Point3::new(600, 0, 0),
+      GMCommand::ApplyConditionToMany {
+        creatures: vec![cid_ranger()],
+        condition: Condition::Incapacitated,
+        duration: Duration::Interminate,
       },
     );
-    let scene = game.get_scene(t_scene_id()).unwrap();
-
-    let cids = scene.creatures_in_volume(game.tile_system, pt, volume);
-    let cids = HashSet::<CreatureID>::from_iter(cids);
-    assert_eq!(cids, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+    game.creatures.mutate(&cid_cleric(), |c| c.cur_energy = Energy(0));
+    assert_eq!(hp(&game, cid_ranger()), HP(7));
+
+    let game = t_perform(&game, t_restore_cmd("start"));
+    for cid in [cid_rogue(), cid_ranger(), cid_cleric()] {
+      assert_eq!(game.get_scene(t_scene_id()).unwrap().get_pos(cid), Ok(Point3::new(0, 0, 0)));
+      let restored = game.get_creature(cid).unwrap().creature;
+      let original = baseline.get_creature(cid).unwrap().creature;
+      assert_eq!(restored.cur_health, original.cur_health);
+      assert_eq!(restored.cur_energy, original.cur_energy);
+      assert_eq!(restored.conditions, original.conditions);
+    }
+    // The checkpoint is still there to be restored again.
+    assert!(game.get_scene(t_scene_id()).unwrap().checkpoints.contains_key("start"));
   }
 
   #[test]
-  fn test_sphere_targets() {
-    let game = t_game();
-    let target_spec = CreatureTarget::AllCreaturesInVolumeInRange {
-      range: u32cm(1000),
-      volume: Volume::Sphere(u32cm(200)),
-    };
-    let pt = Point3::new(500, 0, 0);
+  fn restore_unknown_scene_checkpoint() {
+    let game = t_perform(&t_game(), t_snapshot_cmd("start"));
+    assert_eq!(
+      perf(&game, t_restore_cmd("nope")).map(|_| ()),
+      Err(GameError::SceneCheckpointNotFound(t_scene_id(), "nope".to_string()))
+    );
+  }
+
+  fn testdata() -> FolderPath { FolderPath::from_vec(vec!["testdata".to_string()]) }
 
+  /// A game in combat with bob controlling the rogue, whose data is then deleted out from under
+  /// everything that refers to it.
+  fn t_dangling_creature_game() -> Game {
+    let game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger()]);
+    let game = t_perform(&game, GMCommand::RegisterPlayer { id: bob() });
     let game = t_perform(
       &game,
-      GMCommand::SetCreaturePos {
-        scene_id: t_scene_id(),
-        creature_id: cid_rogue(),
-        pos: Point3::new(500, 0, 0),
-      },
+      GMCommand::GiveCreaturesToPlayer { player_id: bob(), creature_ids: vec![cid_rogue()] },
     );
-    let game = t_perform(
+    let mut game = t_perform(
       &game,
-      GMCommand::SetCreaturePos {
-        scene_id: t_scene_id(),
-        creature_id: cid_cleric(),
-        pos: Point3::new(600, 0, 0),
-      },
+      GMCommand::SetPlayerScene { player_id: bob(), scene_id: Some(t_scene_id()) },
     );
+    game.creatures.remove(&cid_rogue());
+    game
+  }
 
-    let scene = game.get_scene(t_scene_id()).unwrap();
-
-    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
-    let targets = HashSet::<CreatureID>::from_iter(targets);
-    assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  #[test]
+  fn integrity_check_clean() {
+    assert_eq!(t_game().integrity_check(), vec![]);
+    assert_eq!(t_start_combat(&t_game(), vec![cid_rogue()]).integrity_check(), vec![]);
   }
 
   #[test]
-  fn test_line_targets() {
-    let game = t_game();
-    let target_spec = CreatureTarget::LineFromActor { distance: u32cm(1000) };
-    let pt = Point3::new(100, 0, 0);
+  fn integrity_check_missing_creature() {
+    use IntegrityIssue::*;
+    assert_eq!(
+      t_dangling_creature_game().integrity_check(),
+      vec![
+        MissingSceneCreature { scene_id: t_scene_id(), creature_id: cid_rogue() },
+        MissingCombatant { creature_id: cid_rogue() },
+        MissingFolderItem { path: testdata(), item_id: FolderItemID::CreatureID(cid_rogue()) },
+        MissingPlayerCreature { player_id: bob(), creature_id: cid_rogue() },
+      ]
+    );
+  }
 
-    let game = t_perform(
+  #[test]
+  fn integrity_check_missing_scene_and_unfiled_creature() {
+    use IntegrityIssue::*;
+    let game = t_start_combat(&t_game(), vec![cid_rogue()]);
+    let game = t_perform(&game, GMCommand::RegisterPlayer { id: bob() });
+    let mut game = t_perform(
       &game,
-      GMCommand::SetCreaturePos {
-        scene_id: t_scene_id(),
-        creature_id: cid_rogue(),
-        pos: Point3::new(100, 0, 0),
-      },
+      GMCommand::SetPlayerScene { player_id: bob(), scene_id: Some(t_scene_id()) },
     );
-    let game = t_perform(
-      &game,
-      GMCommand::SetCreaturePos {
-        scene_id: t_scene_id(),
-        creature_id: cid_cleric(),
-        pos: Point3::new(200, 0, 0),
-      },
+    game.scenes.remove(&t_scene_id());
+    let stray = t_creature("stray", classid_rogue(), 0);
+    let stray_id = stray.id;
+    game.creatures.insert(stray);
+    assert_eq!(
+      game.integrity_check(),
+      vec![
+        MissingCombatScene { scene_id: t_scene_id() },
+        MissingFolderItem { path: testdata(), item_id: FolderItemID::SceneID(t_scene_id()) },
+        MissingPlayerScene { player_id: bob(), scene_id: t_scene_id() },
+        UnfiledCreature { creature_id: stray_id },
+      ]
     );
-    let scene = game.get_scene(t_scene_id()).unwrap();
+  }
 
-    let targets = game.volume_creature_targets(scene, cid_ranger(), target_spec, pt).unwrap();
-    let targets = HashSet::<CreatureID>::from_iter(targets);
-    assert_eq!(targets, HashSet::from_iter(vec![cid_rogue(), cid_cleric()]));
+  #[test]
+  fn repair_integrity() {
+    let mut game = t_dangling_creature_game();
+    game.creatures.insert(t_creature("stray", classid_rogue(), 0));
+    let changed = perf(&game, GMCommand::RepairIntegrity).unwrap();
+    assert_eq!(changed.logs.len(), 5);
+    assert_eq!(changed.game.integrity_check(), vec![]);
+
+    // Repairing a clean game does nothing.
+    assert_eq!(perf(&changed.game, GMCommand::RepairIntegrity).unwrap().logs, vec![]);
   }
 
   #[test]
-  fn preview_volume_targets_shows_creatures_for_scene_volume_actions() {
-    // When previewing volume targets for an action that affects a Scene Volume, the creatures
-    // within the volume will be returned.
+  fn repair_integrity_missing_combat_scene() {
+    let mut game = t_start_combat(&t_game(), vec![cid_rogue(), cid_ranger()]);
+    game.scenes.remove(&t_scene_id());
+    game.creatures.remove(&cid_rogue());
+    let changed = perf(&game, GMCommand::RepairIntegrity).unwrap();
+    assert_eq!(changed.game.current_combat, None);
+    assert_eq!(changed.game.integrity_check(), vec![]);
+  }
 
+  #[test]
+  fn compact_movement_options() {
     let game = t_game();
-    let scene = game.get_scene(t_scene_id()).unwrap();
-    let cleric = cid_cleric();
-    let ability_id = abid_thorn_patch();
-    let preview =
-      game.preview_volume_targets(scene, cleric, ability_id, Point3::new(0, 0, 0)).unwrap();
-    let expected = hashset! {cid_cleric(), cid_ranger(), cid_rogue()};
-    assert_eq!(HashSet::from_iter(preview.0), expected);
+    let mut options = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap();
+    let compact = CompactPoints::from_points(&options);
+    options.sort();
+    assert_eq!(compact.to_points(), options);
+    let compact_size = serde_json::to_string(&compact).unwrap().len();
+    assert!(compact_size * 5 < serde_json::to_string(&options).unwrap().len());
+  }
+
+  #[test]
+  fn slowed_and_hastened_movement_options() {
+    let with_condition = |condition| {
+      let mut game = t_game();
+      game.creatures.mutate(&cid_rogue(), |c| {
+        c.conditions.insert(ConditionID(uuid_5()), app_cond(condition, Duration::Interminate));
+      });
+      let speed = game.get_creature(cid_rogue()).unwrap().speed();
+      (speed, game.get_movement_options(t_scene_id(), cid_rogue()).unwrap().len())
+    };
+    let game = t_game();
+    let base_speed = game.get_creature(cid_rogue()).unwrap().speed();
+    let base_cells = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap().len();
+
+    let (slowed_speed, slowed_cells) = with_condition(Condition::Slowed(2));
+    assert_eq!(slowed_speed, u32cm(base_speed.get::<centimeter>() / 2));
+    assert!(slowed_cells < base_cells);
+
+    let (hastened_speed, hastened_cells) = with_condition(Condition::Hastened(2));
+    assert_eq!(hastened_speed, u32cm(base_speed.get::<centimeter>() * 2));
+    assert!(hastened_cells > base_cells);
+  }
+
+  #[test]
+  fn difficult_terrain_shrinks_movement_options() {
+    // A slow rogue surrounded by difficult terrain can't get around it.
+    let mut game = t_game();
+    game.creatures.mutate(&cid_rogue(), |c| c.speed = u32cm(200));
+    let base_cells = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap().len();
+    let mut costs = HashMap::new();
+    for x in [-100, 0, 100] {
+      for y in [-100, 0, 100] {
+        if (x, y) != (0, 0) {
+          costs.insert(Point3::new(x, y, 0), 3);
+        }
+      }
+    }
+    let game = t_perform(&game, GMCommand::EditSceneTerrainCosts { scene_id: t_scene_id(), costs });
+    let cells = game.get_movement_options(t_scene_id(), cid_rogue()).unwrap().len();
+    assert!(cells < base_cells);
+  }
+
+  #[test]
+  fn compact_points_round_trip() {
+    let points = vec![Point3::new(100, -100, 100), Point3::new(-300, 200, 0), Point3::new(0, 0, 0)];
+    let compact = CompactPoints::from_points(&points);
+    assert_eq!(compact.origin, Point3::new(-300, -100, 0));
+    assert_eq!(compact.size, (5, 4, 2));
+    let mut expected = points.clone();
+    expected.sort();
+    assert_eq!(compact.to_points(), expected);
+    assert_eq!(CompactPoints::from_points(&[]).to_points(), vec![]);
   }
 
   #[test]
@@ -2097,4 +6267,570 @@ pub mod test {
     );
     assert!(game.campaign.get(&sys_path).unwrap().classes.contains(&classid));
   }
+
+  fn t_strength_check(game: &Game, target: SkillLevel, reliable: bool) -> (u8, bool) {
+    let check = AttributeCheck { reliable, attr: AttrID("strength".to_string()), target };
+    let changed = perf(game, GMCommand::AttributeCheck {
+      creature_id: cid_rogue(),
+      attribute_check: check,
+    })
+    .unwrap();
+    match changed.logs.as_slice() {
+      [GameLog::AttributeCheckResult { actual, success, .. }] => (*actual, *success),
+      logs => panic!("Unexpected logs: {:?}", logs),
+    }
+  }
+
+  fn t_strong_rogue(attribute_modifiers: Vec<(SkillLevel, i8)>) -> Game {
+    let settings = GameSettings { attribute_modifiers, ..Default::default() };
+    let mut game = t_perform(&t_game(), GMCommand::EditGameSettings { settings });
+    game.creatures.mutate(&cid_rogue(), |c| {
+      c.attributes.insert(AttrID("strength".to_string()), SkillLevel::Skilled);
+    });
+    game
+  }
+
+  #[test]
+  fn attribute_scores_are_their_own_modifier_by_default() {
+    let game = t_strong_rogue(vec![]);
+    let rogue = game.get_creature(cid_rogue()).unwrap().creature;
+    let strength = AttrID("strength".to_string());
+    assert_eq!(rogue.get_attribute_modifier(&strength, &game.settings), Ok(1));
+    assert_eq!(t_strength_check(&game, SkillLevel::Skilled, true), (100, true));
+  }
+
+  #[test]
+  fn attribute_checks_use_derived_modifiers() {
+    let game = t_strong_rogue(vec![(SkillLevel::Skilled, 3)]);
+    let rogue = game.get_creature(cid_rogue()).unwrap().creature;
+    let strength = AttrID("strength".to_string());
+    assert_eq!(rogue.get_attribute_modifier(&strength, &game.settings), Ok(3));
+    // A reliable check passes automatically once the derived modifier reaches the target.
+    assert_eq!(t_strength_check(&game, SkillLevel::Supernatural, true), (100, true));
+    // A Skilled creature needs a 90 to pass a Supernatural challenge, but with a +3 modifier it
+    // only needs a 25, the same as a Supernatural creature.
+    assert_eq!(SkillLevel::Skilled.difficulty(SkillLevel::Supernatural), 90);
+    assert_eq!(SkillLevel::modifier_difficulty(3, SkillLevel::Supernatural), 25);
+  }
+
+  #[test]
+  fn extreme_modifiers_always_pass_or_fail() {
+    assert_eq!(SkillLevel::modifier_difficulty(20, SkillLevel::Supernatural), 0);
+    let game = t_strong_rogue(vec![(SkillLevel::Skilled, 20)]);
+    assert!(t_strength_check(&game, SkillLevel::Supernatural, false).1);
+    let game = t_strong_rogue(vec![(SkillLevel::Skilled, -20)]);
+    assert_eq!(SkillLevel::modifier_difficulty(-20, SkillLevel::Inept), 100);
+    let (roll, success) = t_strength_check(&game, SkillLevel::Inept, false);
+    assert_eq!(success, roll >= 100);
+  }
+
+  #[test]
+  fn modifiers_at_the_ends_of_their_range_dont_overflow() {
+    for target in [SkillLevel::Inept, SkillLevel::Supernatural] {
+      assert_eq!(SkillLevel::modifier_difficulty(i8::MAX, target), 0);
+      assert_eq!(SkillLevel::modifier_difficulty(i8::MIN, target), 100);
+    }
+    let game = t_strong_rogue(vec![(SkillLevel::Skilled, i8::MIN)]);
+    let rogue = game.get_creature(cid_rogue()).unwrap().creature;
+    let check = AttributeCheck {
+      reliable: true,
+      attr: AttrID("strength".to_string()),
+      target: SkillLevel::Inept,
+    };
+    assert_eq!(rogue.check_difficulty(&check, &game.settings), Ok(100));
+  }
+
+  #[test]
+  fn check_difficulty_is_what_checks_need_to_roll() {
+    let game = t_strong_rogue(vec![]);
+    let rogue = game.get_creature(cid_rogue()).unwrap().creature;
+    let check = |target, reliable| AttributeCheck {
+      reliable,
+      attr: AttrID("strength".to_string()),
+      target,
+    };
+    // Skilled against Expert is one rank short.
+    assert_eq!(rogue.check_difficulty(&check(SkillLevel::Expert, false), &game.settings), Ok(50));
+    assert_eq!(rogue.check_difficulty(&check(SkillLevel::Skilled, true), &game.settings), Ok(0));
+    for target in [SkillLevel::Inept, SkillLevel::Skilled, SkillLevel::Supernatural] {
+      let difficulty = rogue.check_difficulty(&check(target, false), &game.settings).unwrap();
+      for _ in 0..20 {
+        let (roll, success) = t_strength_check(&game, target, false);
+        assert_eq!(success, roll >= difficulty);
+      }
+    }
+  }
+
+  #[test]
+  fn combat_replay_reproduces_the_fight() {
+    let start = t_game();
+    let (before_combat, logs_before) = t_history(&start, vec![t_punch_cmd(cid_cleric())]);
+    let (after_combat, combat_logs) = t_history(
+      &before_combat,
+      vec![
+        GMCommand::StartCombat {
+          scene_id: t_scene_id(),
+          combatants: vec![cid_rogue(), cid_ranger()],
+        },
+        GMCommand::CombatAct {
+          ability_id: abid_punch(),
+          target: DecidedTarget::Creature(cid_ranger()),
+        },
+        GMCommand::EndTurn,
+        GMCommand::StopCombat { preserve_initiative: false },
+      ],
+    );
+    let (_, logs_after) = t_history(&after_combat, vec![t_punch_cmd(cid_cleric())]);
+    let logs = [logs_before, combat_logs, logs_after].concat();
+
+    let replay = start.export_combat_replay(&logs, 0).unwrap();
+    assert_eq!(replay.start, before_combat);
+    assert!(matches!(replay.logs.first(), Some(GameLog::StartCombat { .. })));
+    assert!(matches!(replay.logs.last(), Some(GameLog::StopCombat { .. })));
+    let json = serde_json::to_string(&replay).unwrap();
+    let replay: CombatReplay = serde_json::from_str(&json).unwrap();
+    assert_eq!(replay_combat(&replay).unwrap(), after_combat);
+  }
+
+  #[test]
+  fn game_at_earlier_index() {
+    let snapshot = t_game();
+    let (after_first, first_logs) = t_history(&snapshot, vec![t_punch_cmd(cid_ranger())]);
+    let (latest, later_logs) =
+      t_history(&after_first, vec![t_punch_cmd(cid_cleric()), t_punch_cmd(cid_ranger())]);
+    let logs = [first_logs.clone(), later_logs].concat();
+
+    let index = |log_idx| GameIndex { game_idx: 0, log_idx };
+    assert_eq!(game_at_index(&snapshot, &logs, index(0)), Ok(snapshot.clone()));
+    assert_eq!(game_at_index(&snapshot, &logs, index(first_logs.len())), Ok(after_first));
+    assert_eq!(game_at_index(&snapshot, &logs, index(logs.len())), Ok(latest));
+    assert_eq!(
+      game_at_index(&snapshot, &logs, index(logs.len() + 1)),
+      Err(GameError::HistoryNotFound(0, logs.len() + 1))
+    );
+    let other_snapshot = GameIndex { game_idx: 1, log_idx: 0 };
+    let result = game_at_index(&snapshot, &logs, other_snapshot);
+    assert_eq!(result, Err(GameError::HistoryNotFound(1, 0)));
+  }
+
+  #[test]
+  fn rollback_to_is_replayed_from_history() {
+    let snapshot = t_game();
+    let (after_both, logs) =
+      t_history(&snapshot, vec![t_punch_cmd(cid_ranger()), t_punch_cmd(cid_cleric())]);
+    let index = GameIndex { game_idx: 0, log_idx: 1 };
+    let changed = rollback_to(&snapshot, &logs, index).unwrap();
+    assert_eq!(changed.game, snapshot.apply_log(&logs[0]).unwrap());
+    assert_ne!(changed.game, after_both);
+
+    // Later logs apply on top of the rolled-back game, and replaying the whole history
+    // reproduces them.
+    let (latest, later_logs) = t_history(&changed.game, vec![t_punch_cmd(cid_ranger())]);
+    let logs = [logs, changed.logs, later_logs].concat();
+    let last = GameIndex { game_idx: 0, log_idx: logs.len() };
+    assert_eq!(game_at_index(&snapshot, &logs, last), Ok(latest));
+  }
+
+  #[test]
+  fn rollback_to_an_earlier_rollback() {
+    let snapshot = t_game();
+    let (_, logs) =
+      t_history(&snapshot, vec![t_punch_cmd(cid_ranger()), t_punch_cmd(cid_cleric())]);
+    let index = |log_idx| GameIndex { game_idx: 0, log_idx };
+    let first = rollback_to(&snapshot, &logs, index(1)).unwrap();
+    let after_first = logs.len() + 1;
+    let (_, later_logs) = t_history(&first.game, vec![t_punch_cmd(cid_ranger())]);
+    let logs = [logs, first.logs, later_logs].concat();
+
+    // Rolling back to the first rollback lands on the game it produced, not the one it undid.
+    let second = rollback_to(&snapshot, &logs, index(after_first)).unwrap();
+    assert_eq!(second.game, first.game);
+    let logs = [logs, second.logs].concat();
+    assert_eq!(game_at_index(&snapshot, &logs, index(logs.len())), Ok(first.game));
+  }
+
+  #[test]
+  fn rollback_to_history_boundaries() {
+    let snapshot = t_game();
+    let (latest, logs) =
+      t_history(&snapshot, vec![t_punch_cmd(cid_ranger()), t_punch_cmd(cid_cleric())]);
+    let index = |log_idx| GameIndex { game_idx: 0, log_idx };
+
+    assert_eq!(rollback_to(&snapshot, &logs, index(0)).unwrap().game, snapshot);
+    let first = rollback_to(&snapshot, &logs, index(1)).unwrap();
+    assert_eq!(first.game, snapshot.apply_log(&logs[0]).unwrap());
+    // Rolling back to the final log leaves the game as it is, but is still recorded.
+    let last = rollback_to(&snapshot, &logs, index(logs.len())).unwrap();
+    assert_eq!(last.game, latest);
+    assert_eq!(last.logs, vec![GameLog::Rollback { snapshot_index: 0, log_index: logs.len() }]);
+    assert_eq!(
+      rollback_to(&snapshot, &logs, index(logs.len() + 1)),
+      Err(GameError::HistoryNotFound(0, logs.len() + 1))
+    );
+  }
+
+  #[test]
+  fn rollback_to_empty_snapshot() {
+    let snapshot = t_game();
+    let index = |log_idx| GameIndex { game_idx: 0, log_idx };
+    assert_eq!(rollback_to(&snapshot, &[], index(0)).unwrap().game, snapshot);
+    assert_eq!(rollback_to(&snapshot, &[], index(1)), Err(GameError::HistoryNotFound(0, 1)));
+    let hash = content_hash_hex(&snapshot).unwrap();
+    assert_eq!(find_content_hash(&snapshot, &[], &hash), Ok(index(0)));
+  }
+
+  #[test]
+  fn rollback_to_the_snapshot_after_changes() {
+    let snapshot = t_game();
+    let log = CreatureLog::Damage { hp: HP(3), rolls: vec![3] };
+    let damage = GMCommand::ApplyCreatureLog { creature_id: cid_ranger(), log };
+    let (_, logs) = t_history(&snapshot, vec![damage]);
+    let hash = content_hash_hex(&snapshot).unwrap();
+    let index = find_content_hash(&snapshot, &logs, &hash).unwrap();
+    assert_eq!(index, GameIndex { game_idx: 0, log_idx: 0 });
+    let rolled_back = rollback_to(&snapshot, &logs, index).unwrap();
+    assert_eq!(rolled_back.game, snapshot);
+
+    // Replaying a history that rolled back to the snapshot reproduces later changes.
+    let (latest, later_logs) = t_history(&rolled_back.game, vec![t_punch_cmd(cid_cleric())]);
+    let logs = [logs, rolled_back.logs, later_logs].concat();
+    let last = GameIndex { game_idx: 0, log_idx: logs.len() };
+    assert_eq!(game_at_index(&snapshot, &logs, last), Ok(latest));
+  }
+
+  #[test]
+  fn rollback_by_content_hash_survives_new_snapshots() {
+    let snapshot = t_game();
+    let (after_first, first_logs) = t_history(&snapshot, vec![t_punch_cmd(cid_ranger())]);
+    let (target, target_logs) = t_history(&after_first, vec![t_punch_cmd(cid_cleric())]);
+    let (_, later_logs) = t_history(&target, vec![t_punch_cmd(cid_ranger())]);
+    let hash = content_hash_hex(&target).unwrap();
+
+    let logs = [first_logs, target_logs.clone(), later_logs.clone()].concat();
+    let index = find_content_hash(&snapshot, &logs, &hash).unwrap();
+    assert_eq!(rollback_to(&snapshot, &logs, index).unwrap().game, target);
+
+    // If the history is later compacted into a new snapshot, the target's index changes but its
+    // hash still finds it.
+    let logs = [target_logs, later_logs].concat();
+    let new_index = find_content_hash(&after_first, &logs, &hash).unwrap();
+    assert_ne!(new_index, index);
+    assert_eq!(rollback_to(&after_first, &logs, new_index).unwrap().game, target);
+
+    assert_eq!(
+      find_content_hash(&snapshot, &logs, "nope"),
+      Err(GameError::ContentHashNotFound("nope".to_string()))
+    );
+  }
+
+  #[test]
+  fn combat_timeline_groups_logs_by_round_and_turn() {
+    let start = t_game();
+    let combatants = vec![cid_rogue(), cid_ranger()];
+    let (in_combat, start_logs) =
+      t_history(&start, vec![GMCommand::StartCombat { scene_id: t_scene_id(), combatants }]);
+    let order = in_combat.get_combat().unwrap().combat.creature_ids();
+    let punch = GMCommand::CombatAct {
+      ability_id: abid_punch(),
+      target: DecidedTarget::Creature(order[1]),
+    };
+    let (_, combat_logs) = t_history(
+      &in_combat,
+      vec![punch, GMCommand::EndTurn, GMCommand::EndTurn, GMCommand::EndTurn],
+    );
+    let logs = [start_logs, combat_logs].concat();
+
+    let recorder = CombatTimelineRecorder::from_history(&start, &logs).unwrap();
+    let timeline = recorder.rounds();
+    assert_eq!(timeline.iter().map(|round| round.round).collect::<Vec<_>>(), vec![1, 2]);
+    let turns = |round: &RoundSummary| -> Vec<CreatureID> {
+      round.turns.iter().map(|turn| turn.creature_id).collect()
+    };
+    assert_eq!(turns(&timeline[0]), order);
+    // The second creature's turn in the second round hasn't ended yet.
+    assert_eq!(turns(&timeline[1]), order);
+    let first_turn = &timeline[0].turns[0].logs;
+    assert_eq!(first_turn[0], GameLog::TurnBegan { creature_id: order[0] });
+    assert!(matches!(first_turn[1], GameLog::CreatureUsedAbility { .. }));
+    let end_turn = GameLog::CombatLog { log: CombatLog::EndTurn { creature_id: order[0] } };
+    assert_eq!(first_turn.last(), Some(&end_turn));
+  }
+
+  #[test]
+  fn combat_timeline_after_rollback() {
+    let start = t_game();
+    let combatants = vec![cid_rogue(), cid_ranger()];
+    let (in_combat, mut logs) =
+      t_history(&start, vec![GMCommand::StartCombat { scene_id: t_scene_id(), combatants }]);
+    let rollback_point = logs.len();
+    let (_, combat_logs) =
+      t_history(&in_combat, vec![GMCommand::EndTurn, GMCommand::EndTurn, GMCommand::EndTurn]);
+    logs.extend(combat_logs);
+    let before = CombatTimelineRecorder::from_history(&start, &logs[..rollback_point]).unwrap();
+    let after = CombatTimelineRecorder::from_history(&start, &logs).unwrap();
+    assert_eq!(after.rounds().len(), 2);
+
+    let index = GameIndex { game_idx: 0, log_idx: rollback_point };
+    let rolled_back = rollback_to(&start, &logs, index).unwrap();
+    logs.extend(rolled_back.logs);
+    let timeline = CombatTimelineRecorder::from_history(&start, &logs).unwrap();
+    assert_eq!(timeline.rounds(), before.rounds());
+
+    // Recording carries on from the rolled back game.
+    let (_, more_logs) = t_history(&rolled_back.game, vec![GMCommand::EndTurn]);
+    logs.extend(more_logs);
+    let timeline = CombatTimelineRecorder::from_history(&start, &logs).unwrap();
+    assert_eq!(timeline.rounds().len(), 1);
+    let turns: Vec<CreatureID> =
+      timeline.rounds()[0].turns.iter().map(|turn| turn.creature_id).collect();
+    assert_eq!(turns, in_combat.get_combat().unwrap().combat.creature_ids());
+  }
+
+  #[test]
+  fn combat_replay_errors() {
+    let game = t_game();
+    let changed = perf(&game, t_punch_cmd(cid_ranger())).unwrap();
+    assert_eq!(game.export_combat_replay(&changed.logs, 0), Err(GameError::CombatNotFound(0)));
+    let replay = CombatReplay { version: COMBAT_REPLAY_VERSION + 1, start: game, logs: vec![] };
+    assert_eq!(
+      replay_combat(&replay),
+      Err(GameError::UnsupportedReplayVersion(COMBAT_REPLAY_VERSION + 1))
+    );
+  }
+
+  #[test]
+  fn auto_remove_dead_creatures() {
+    let t_settings_game = |auto_remove_dead| {
+      let settings = GameSettings { auto_remove_dead, ..Default::default() };
+      let mut game = t_perform(&t_game(), GMCommand::EditGameSettings { settings });
+      game.creatures.mutate(&cid_ranger(), |c| c.cur_health = HP(3));
+      game
+    };
+    let in_scene =
+      |game: &Game| game.get_scene(t_scene_id()).unwrap().creatures.contains_key(&cid_ranger());
+
+    let game = t_perform(&t_settings_game(false), t_punch_cmd(cid_ranger()));
+    assert_eq!(game.get_creature(cid_ranger()).unwrap().creature.cur_health, HP(0));
+    assert!(in_scene(&game));
+
+    let changed = perf(&t_settings_game(true), t_punch_cmd(cid_ranger())).unwrap();
+    assert!(changed.logs.contains(&GameLog::CreatureLeftScene {
+      scene_id: t_scene_id(),
+      creature_id: cid_ranger()
+    }));
+    assert!(!in_scene(&changed.game));
+    assert!(changed.game.creatures.contains_key(&cid_ranger()));
+
+    // Dying in combat takes the creature out of the combat, too.
+    let combatants = vec![cid_rogue(), cid_ranger(), cid_cleric()];
+    let game = t_start_combat(&t_settings_game(true), combatants);
+    let game = t_game_act(&game, abid_punch(), DecidedTarget::Creature(cid_ranger()));
+    assert!(!in_scene(&game));
+    let combat = game.get_combat().unwrap().combat;
+    assert_eq!(combat.creature_ids(), vec![cid_rogue(), cid_cleric()]);
+  }
+
+  #[test]
+  fn creatures_dying_on_their_own_turn_are_removed_when_it_ends() {
+    let settings = GameSettings { auto_remove_dead: true, ..Default::default() };
+    let game = t_perform(&t_game(), GMCommand::EditGameSettings { settings });
+    let combatants = vec![cid_rogue(), cid_ranger(), cid_cleric()];
+    let mut game = t_start_combat(&game, combatants);
+    // The rogue goes first, and dies during its turn.
+    game.creatures.mutate(&cid_rogue(), |c| c.cur_health = HP(1));
+    let damage = CreatureEffect::Damage(Dice::flat(5));
+    let changed =
+      game.change().apply_creature(cid_rogue(), |c| c.apply_effect(&damage)).unwrap();
+    let in_scene =
+      |game: &Game| game.get_scene(t_scene_id()).unwrap().creatures.contains_key(&cid_rogue());
+    let combat = changed.game.get_combat().unwrap().combat;
+    assert_eq!(combat.current_creature_id(), cid_rogue());
+    assert!(in_scene(&changed.game));
+
+    let game = t_perform(&changed.game, GMCommand::EndTurn);
+    assert!(!in_scene(&game));
+    let combat = game.get_combat().unwrap().combat;
+    assert_eq!(combat.creature_ids(), vec![cid_ranger(), cid_cleric()]);
+    assert_eq!(combat.current_creature_id(), cid_ranger());
+  }
+
+  #[test]
+  fn grid_bounds_limit_terrain_painting() {
+    let scene_id = t_scene_id();
+    let terrain = Terrain::new();
+    let game = t_perform(&t_game(), GMCommand::EditSceneTerrain { scene_id, terrain });
+    let bounds = Some((Point3::new(-500, -500, 0), Point3::new(500, 500, 0)));
+    let game = t_perform(&game, GMCommand::SetSceneGridBounds { scene_id, bounds });
+    assert_eq!(game.get_scene(scene_id).unwrap().grid_bounds, bounds);
+
+    let inside = hashset! {Point3::new(0, 0, 0), Point3::new(500, -500, 0)};
+    let game = t_perform(&game, GMCommand::EditSceneTerrain { scene_id, terrain: inside.clone() });
+    assert_eq!(game.get_scene(scene_id).unwrap().terrain, inside);
+
+    let mut outside = inside.clone();
+    outside.insert(Point3::new(600, 0, 0));
+    assert_eq!(
+      perf(&game, GMCommand::EditSceneTerrain { scene_id, terrain: outside.clone() }).map(|_| ()),
+      Err(GameError::OutsideGridBounds(scene_id, Point3::new(600, 0, 0)))
+    );
+
+    let game = t_perform(&game, GMCommand::SetSceneGridBounds { scene_id, bounds: None });
+    perf(&game, GMCommand::EditSceneTerrain { scene_id, terrain: outside }).unwrap();
+  }
+
+  /// A game where the ranger stands at 0,0 and the rogue at `rogue_pos`, on the given terrain.
+  fn t_cover_game(terrain: Terrain, rogue_pos: Point3) -> Game {
+    let scene_id = t_scene_id();
+    let game = t_perform(&t_game(), GMCommand::EditSceneTerrain { scene_id, terrain });
+    let creature_id = cid_rogue();
+    t_perform(&game, GMCommand::SetCreaturePos { scene_id, creature_id, pos: rogue_pos })
+  }
+
+  fn t_ranger_shoots_rogue() -> GMCommand {
+    GMCommand::ActCreature {
+      scene_id: t_scene_id(),
+      creature_id: cid_ranger(),
+      ability_id: abid_shoot(),
+      target: DecidedTarget::Creature(cid_rogue()),
+    }
+  }
+
+  #[test]
+  fn total_cover_prevents_targeting() {
+    // Two rooms separated by a solid wall.
+    let terrain = (0..5)
+      .flat_map(|x| vec![Point3::new(x * 100, 0, 0), Point3::new(x * 100, 200, 0)])
+      .collect();
+    let game = t_cover_game(terrain, Point3::new(0, 200, 0));
+    assert_eq!(
+      perf(&game, t_ranger_shoots_rogue()).map(|_| ()),
+      Err(GameError::TargetHasTotalCover(cid_rogue()))
+    );
+    let options = game.get_target_options(t_scene_id(), cid_ranger(), abid_shoot()).unwrap();
+    assert!(!options.allows(cid_ranger(), &DecidedTarget::Creature(cid_rogue())));
+    // The ranger is still caught by the fireball on its side of the wall, but the rogue isn't.
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let (cids, _) = game
+      .preview_volume_targets(scene, cid_cleric(), abid_fireball(), Point3::new(0, 0, 0))
+      .unwrap();
+    assert!(cids.contains(&cid_ranger()));
+    assert!(!cids.contains(&cid_rogue()));
+  }
+
+  #[test]
+  fn preview_shows_partial_cover() {
+    // The rogue is just around the corner of an L-shaped corridor.
+    let terrain = hashset! {
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(200, 0, 0),
+      Point3::new(200, 100, 0),
+      Point3::new(200, 200, 0),
+    };
+    let game = t_cover_game(terrain, Point3::new(200, 100, 0));
+    let scene = game.get_scene(t_scene_id()).unwrap();
+    let preview = game
+      .preview_ability(scene, cid_ranger(), abid_shoot(), DecidedTarget::Creature(cid_rogue()))
+      .unwrap();
+    assert_eq!(preview[0].1.cover, Cover::Half);
+    // Partial cover doesn't stop the shot.
+    assert_eq!(hp(&t_perform(&game, t_ranger_shoots_rogue()), cid_rogue()), HP(7));
+  }
+
+  /// Make the ranger's Shoot ability roll `roll` to hit, and give the rogue a defense of `defense`.
+  fn t_attack_game(
+    game: &Game, roll: i8, defense: u8, miss_effect: Option<CreatureEffect>,
+  ) -> Game {
+    let mut game = game.clone();
+    let attack_roll = Some(AttackRoll { dice: Dice::flat(roll), miss_effect });
+    game.abilities.mutate(&abid_shoot(), move |ab| ab.attack_roll = attack_roll);
+    game.creatures.mutate(&cid_rogue(), |c| {
+      let defense = app_cond(Condition::ProvideDefense(defense), Duration::Interminate);
+      c.conditions.insert(ConditionID::gen(), defense);
+    });
+    game
+  }
+
+  /// Have the ranger shoot the rogue, returning the game and the logged attack roll.
+  fn t_attack(game: &Game) -> (Game, (i32, u8, bool)) {
+    let changed = perf(game, t_ranger_shoots_rogue()).unwrap();
+    let attack = changed
+      .logs
+      .iter()
+      .find_map(|log| match *log {
+        GameLog::AttackRolled { roll, defense, hit, .. } => Some((roll, defense, hit)),
+        _ => None,
+      })
+      .expect("The attack roll must be logged");
+    (changed.game, attack)
+  }
+
+  #[test]
+  fn attack_roll_hit_applies_effect() {
+    let game = t_attack_game(&t_game(), 10, 10, None);
+    let (game, attack) = t_attack(&game);
+    assert_eq!(attack, (10, 10, true));
+    assert_eq!(hp(&game, cid_rogue()), HP(7));
+  }
+
+  #[test]
+  fn attack_roll_miss_applies_no_effect() {
+    let game = t_attack_game(&t_game(), 10, 11, None);
+    let (game, attack) = t_attack(&game);
+    assert_eq!(attack, (10, 11, false));
+    assert_eq!(hp(&game, cid_rogue()), HP(10));
+  }
+
+  #[test]
+  fn attack_roll_miss_applies_miss_effect() {
+    let miss_effect = Some(CreatureEffect::Damage(Dice::flat(1)));
+    let game = t_attack_game(&t_game(), 10, 11, miss_effect);
+    let (game, _) = t_attack(&game);
+    assert_eq!(hp(&game, cid_rogue()), HP(9));
+  }
+
+  #[test]
+  fn cover_raises_defense_against_attack_rolls() {
+    // The rogue has half cover around the corner of an L-shaped corridor.
+    let terrain = hashset! {
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(200, 0, 0),
+      Point3::new(200, 100, 0),
+      Point3::new(200, 200, 0),
+    };
+    let game = t_cover_game(terrain, Point3::new(200, 100, 0));
+    let game = t_attack_game(&game, 11, 10, None);
+    let (game, attack) = t_attack(&game);
+    assert_eq!(attack, (11, 12, false));
+    assert_eq!(hp(&game, cid_rogue()), HP(10));
+  }
+
+  #[test]
+  fn creatures_need_valid_image_urls() {
+    let spec = |portrait_url: &str| CreatureCreation {
+      name: "goblin".to_string(),
+      class: classid_rogue(),
+      portrait_url: portrait_url.to_string(),
+      icon_url: "".to_string(),
+      note: "".to_string(),
+      bio: "".to_string(),
+      initiative: Dice::flat(0),
+      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft },
+    };
+    let create = |creature| GMCommand::CreateCreature { path: FolderPath::root(), creature };
+    perf(&t_game(), create(spec("https://example.com/goblin.png"))).unwrap();
+    assert_eq!(
+      perf(&t_game(), create(spec("javascript:alert(1)"))).map(|_| ()),
+      Err(GameError::InvalidPortraitURL("javascript:alert(1)".to_string()))
+    );
+
+    let game = t_game();
+    let mut rogue = game.get_creature(cid_rogue()).unwrap().creature.clone();
+    rogue.icon_url = "not a url".to_string();
+    assert_eq!(
+      perf(&game, GMCommand::EditCreatureDetails { creature: rogue }).map(|_| ()),
+      Err(GameError::InvalidIconURL("not a url".to_string()))
+    );
+  }
 }
@@ -1,5 +1,5 @@
 use bresenham;
-use std::{cmp, collections::HashSet, iter::FromIterator};
+use std::{cmp, collections::HashSet, f64::consts::PI, iter::FromIterator};
 
 use ncollide3d::{
   na,
@@ -15,12 +15,12 @@ use num_traits::Signed;
 use uom::si::length::{centimeter, meter};
 
 use crate::types::{
-  i64cm, i64meter, u32cm, u32units, up_length, CollisionData, CollisionWorld, ConditionID,
-  Creature, Point3, Terrain, TileSystem, Volume, VolumeCondition,
+  i64cm, i64meter, u32cm, u32units, up_length, CollisionData, CollisionWorld, ConditionID, Cover,
+  Creature, MovementMode, Point3, Terrain, TerrainCosts, TileSystem, Volume, VolumeCondition,
 };
 
-// unimplemented!: "burst"-style AoE effects, and "wrap-around-corner" AoE effects.
-// This needs to be implemented for both Spheres and Circles (or VerticalCylinder?)
+// unimplemented!: "burst"-style AoE effects. "wrap-around-corner" AoE effects are done by
+// `flood_volume`.
 //
 // Grenades should be "burst"-style spheres. These basically "cast" the effect outward from the
 // center, and are blocked by any solid terrain. This could be implemented by raycasting from
@@ -30,8 +30,8 @@ use crate::types::{
 // effects: Fireball is a Sphere and Thorn Patch is a Circle or VerticalCylinder{height=1}.
 //
 // These "crawl" out from the origin point and can go anywhere within the radius that has a valid
-// path. This could be implemented exactly the same way we implement potential walk-targets.
-// However, for 3d shapes this might get expensive... Probably more efficient to first, find
+// path, the same way we find potential walk-targets. This only floods across a single z level,
+// like walking does; for 3d shapes it might get expensive... Probably more efficient to first find
 // potential targets within the radius, and then try to path to each target.
 
 // I got curious about how to implement this in integer math.
@@ -72,6 +72,19 @@ pub fn line_through_point(origin: Point3, clicked: Point3, length: u32units::Len
   Volume::Line { vector: na_vector_to_point3(new_vec) }
 }
 
+/// Point a cone with its apex at `origin` toward a clicked point.
+pub fn cone_through_point(
+  origin: Point3, clicked: Point3, length: u32units::Length, angle_degrees: u16,
+) -> Volume {
+  Volume::Cone { direction: point3_difference(clicked, origin), length, angle_degrees }
+}
+
+/// A vector in centimeters, for directions that shouldn't be rounded to whole meters.
+fn na_vector_cm(pt: Point3) -> Vector3<f64> {
+  let (x, y, z) = (pt.x.get::<centimeter>(), pt.y.get::<centimeter>(), pt.z.get::<centimeter>());
+  Vector3::new(x as f64, y as f64, z as f64)
+}
+
 /// Get the vector difference between two points, i.e., the offset of pt2 from pt1.
 /// This returns a plain old Point3 to represent the delta... Is that the best thing to do?
 pub fn point3_difference(pt1: Point3, pt2: Point3) -> Point3 {
@@ -84,6 +97,82 @@ pub fn point3_add_vec(pt: Point3, diff: Point3) -> Point3 {
 
 fn is_open(terrain: &Terrain, pt: Point3) -> bool { terrain.contains(&pt) }
 
+/// How well blocking terrain shields something at `to` from something at `from`. This follows the
+/// usual tabletop rule: pick the corner of `from`'s square with the clearest view and trace lines
+/// from it to each corner of `to`'s square. One or two blocked lines give half cover, three give
+/// three-quarters cover, and four give total cover. Lines that only run along the edge of blocked
+/// terrain aren't blocked.
+pub fn cover_between(from: Point3, to: Point3, terrain: &Terrain) -> Cover {
+  if from == to {
+    return Cover::None;
+  }
+  // The squares the two ends are in count as open even if they've been placed off the map.
+  let is_open = |pt: Point3| pt == from || pt == to || terrain.contains(&pt);
+  let corners = |pt: Point3| {
+    let (x, y, z) =
+      (pt.x.get::<centimeter>(), pt.y.get::<centimeter>(), pt.z.get::<centimeter>());
+    [(x, y, z), (x + 100, y, z), (x, y + 100, z), (x + 100, y + 100, z)]
+  };
+  let blocked = corners(from)
+    .iter()
+    .map(|start| corners(to).iter().filter(|end| line_blocked(&is_open, *start, **end)).count())
+    .min()
+    .unwrap_or(0);
+  match blocked {
+    0 => Cover::None,
+    1 | 2 => Cover::Half,
+    3 => Cover::ThreeQuarters,
+    _ => Cover::Total,
+  }
+}
+
+/// Check whether a line between two points (in centimeters) passes through blocked terrain. The
+/// line is sampled about every 10cm, using exact fractions so that samples lying on the boundary
+/// between squares are only blocked when every square they touch is blocked.
+fn line_blocked(
+  is_open: &impl Fn(Point3) -> bool, start: (i64, i64, i64), end: (i64, i64, i64),
+) -> bool {
+  let (dx, dy, dz) = (end.0 - start.0, end.1 - start.1, end.2 - start.2);
+  let steps = cmp::max(dx.abs(), dy.abs()) / 10 + 1;
+  (1..steps).any(|step| {
+    let xs = touched_squares(start.0 * steps + dx * step, steps);
+    let ys = touched_squares(start.1 * steps + dy * step, steps);
+    let z = (start.2 * steps + dz * step).div_euclid(100 * steps) * 100;
+    xs.iter().all(|x| ys.iter().all(|y| !is_open(Point3::new(*x, *y, z))))
+  })
+}
+
+/// The cells on a Bresenham line from the cell containing `from` to the cell containing `to`,
+/// including both ends. The longest axis advances one cell per step and the others follow it,
+/// rounded to the nearest cell, which works the same way in three dimensions as in two.
+fn line_cells(from: Point3, to: Point3) -> Vec<Point3> {
+  let cell = |pt: Point3| [pt.x, pt.y, pt.z].map(|c| c.get::<centimeter>().div_euclid(100));
+  let (start, end) = (cell(from), cell(to));
+  let delta = [end[0] - start[0], end[1] - start[1], end[2] - start[2]];
+  let steps = delta.iter().map(|d| d.abs()).max().unwrap_or(0);
+  if steps == 0 {
+    return vec![Point3::new(start[0] * 100, start[1] * 100, start[2] * 100)];
+  }
+  (0..=steps)
+    .map(|step| {
+      let [x, y, z] =
+        [0, 1, 2].map(|i| (start[i] + (2 * delta[i] * step + steps).div_euclid(2 * steps)) * 100);
+      Point3::new(x, y, z)
+    })
+    .collect()
+}
+
+/// The coordinates of the squares that `numerator / denominator` centimeters falls in along one
+/// axis: two squares if it's exactly on the boundary between them.
+fn touched_squares(numerator: i64, denominator: i64) -> Vec<i64> {
+  let square = numerator.div_euclid(100 * denominator) * 100;
+  if numerator.rem_euclid(100 * denominator) == 0 {
+    vec![square - 100, square]
+  } else {
+    vec![square]
+  }
+}
+
 pub trait TileSystemExt {
   /// Get the distance between two points, considering the system being used.
   /// In DnD, an angular distance is "equivalent" to a horizontal/vertical distance.
@@ -104,22 +193,25 @@ pub trait TileSystemExt {
 
   /// Get the set of points which can be pathed to from some point.
   fn get_all_accessible(
-    &self, start: Point3, terrain: &Terrain, volume: Volume, speed: u32units::Length,
+    &self, start: Point3, terrain: &Terrain, costs: &TerrainCosts, volume: Volume,
+    mode: MovementMode, speed: u32units::Length,
   ) -> Vec<Point3>;
 
   /// Find a path from some start point to some destination point. If one can be found, a Vec of
   /// points on the way to the destination is returned, along with the total length of that path.
+  #[allow(clippy::too_many_arguments)]
   fn find_path(
-    &self, start: Point3, speed: u32units::Length, terrain: &Terrain, volume: Volume,
-    destination: Point3,
+    &self, start: Point3, speed: u32units::Length, terrain: &Terrain, costs: &TerrainCosts,
+    volume: Volume, mode: MovementMode, destination: Point3,
   ) -> Option<(Vec<Point3>, u32units::Length)>;
 
   /// Determine which points a volume occupies.
   /// The way a volume fits at a point is specific to the volume type.
-  /// AABB: top-left
+  /// AABB: top-left or center, depending on its anchor
   /// Sphere: center
   /// Line: origin
   /// VerticalCylinder: center of base
+  /// Cone: apex
   // TODO FIXME XXX: Implement for more volume types.
   // TODO: Should this use ncollide?
   fn points_in_volume(&self, volume: Volume, pt: Point3) -> Vec<Point3>;
@@ -128,10 +220,24 @@ pub trait TileSystemExt {
   /// Note that this doesn't consider other creatures or other map objects.
   fn volume_fits_at_point(&self, volume: Volume, terrain: &Terrain, pt: Point3) -> bool;
 
-  /// Find neighbors of the given point that the given volume can fit in, given the terrain.
+  /// Check whether `to` can be seen from `from`: every cell on a Bresenham line between them must
+  /// be open terrain. The cells at the two ends never block. The line is walked in all three
+  /// dimensions, so looking up or down a level also needs open cells in between.
+  fn has_line_of_sight(&self, terrain: &Terrain, from: Point3, to: Point3) -> bool;
+
+  /// Find neighbors of the given point that the given volume can fit in, given the terrain, along
+  /// with the cost of stepping into each one. Only flying creatures have neighbors above and below.
   fn point3_neighbors(
-    &self, terrain: &Terrain, volume: Volume, pt: Point3,
+    &self, terrain: &Terrain, costs: &TerrainCosts, volume: Volume, mode: MovementMode, pt: Point3,
   ) -> Vec<(Point3, u32units::Length)>;
+
+  /// Get the points that an effect spreading out from `origin` reaches, for "wrap-around-corner"
+  /// AoE effects like fireball. The effect crawls along the terrain the same way a creature of the
+  /// given volume would walk, so it bends around corners but can't pass through walls, and it stops
+  /// once it's gone `radius`. The origin is included.
+  fn flood_volume(
+    &self, origin: Point3, terrain: &Terrain, volume: Volume, radius: u32units::Length,
+  ) -> Vec<Point3>;
 }
 
 impl TileSystemExt for TileSystem {
@@ -150,7 +256,26 @@ impl TileSystemExt for TileSystem {
       TileSystem::DnD => {
         let xdiff = (pos1.x - pos2.x).abs();
         let ydiff = (pos1.y - pos2.y).abs();
-        u32cm(cmp::max(xdiff, ydiff).get::<centimeter>() as u32)
+        let zdiff = (pos1.z - pos2.z).abs();
+        u32cm(cmp::max(cmp::max(xdiff, ydiff), zdiff).get::<centimeter>() as u32)
+      }
+      TileSystem::AlternatingDiagonal => {
+        // Every second diagonal step costs an extra meter. The path runs along the longest axis,
+        // and the second longest decides how many of its steps are diagonal.
+        let mut diffs = [
+          (pos1.x - pos2.x).abs().get::<centimeter>(),
+          (pos1.y - pos2.y).abs().get::<centimeter>(),
+          (pos1.z - pos2.z).abs().get::<centimeter>(),
+        ];
+        diffs.sort_unstable();
+        let extra = diffs[1] / 200 * 100;
+        u32cm((diffs[2] + extra) as u32)
+      }
+      TileSystem::Orthogonal => {
+        let xdiff = (pos1.x - pos2.x).abs();
+        let ydiff = (pos1.y - pos2.y).abs();
+        let zdiff = (pos1.z - pos2.z).abs();
+        u32cm((xdiff + ydiff + zdiff).get::<centimeter>() as u32)
       }
     }
   }
@@ -176,7 +301,14 @@ impl TileSystemExt for TileSystem {
           }
         }
       }
-      Volume::AABB(_) => unimplemented!("unimplemented: items_within_volume for AABB"),
+      Volume::AABB(_) | Volume::VerticalCylinder { .. } | Volume::Cone { .. } => {
+        let points: HashSet<Point3> = HashSet::from_iter(self.points_in_volume(volume, pt));
+        for (item, item_pos) in items {
+          if points.contains(item_pos) {
+            results.push(item.clone());
+          }
+        }
+      }
       Volume::Line { vector } => {
         let dest = point3_add_vec(pt, vector);
         let start = (pt.x.get::<meter>() as isize, pt.y.get::<meter>() as isize);
@@ -191,7 +323,6 @@ impl TileSystemExt for TileSystem {
           }
         }
       }
-      Volume::VerticalCylinder { .. } => unimplemented!("items_within_volume for VerticalCylinder"),
     }
     results
   }
@@ -220,50 +351,49 @@ impl TileSystemExt for TileSystem {
 
   /// Get the set of points which can be pathed to from some point.
   fn get_all_accessible(
-    &self, start: Point3, terrain: &Terrain, volume: Volume, speed: u32units::Length,
+    &self, start: Point3, terrain: &Terrain, costs: &TerrainCosts, volume: Volume,
+    mode: MovementMode, speed: u32units::Length,
   ) -> Vec<Point3> {
-    let points_to_check = self.open_points_in_range(start, terrain, speed);
-    let mut success_fns: Vec<Box<dyn Fn(&Point3) -> bool>> = vec![];
+    let points_to_check = match mode {
+      MovementMode::Ground => self.open_points_in_range(start, terrain, speed),
+      // `open_points_in_range` only looks at the ground level, but flying creatures can end up
+      // above or below where they started.
+      MovementMode::Flying => {
+        terrain.iter().filter(|pt| self.point3_distance(start, **pt) <= speed).cloned().collect()
+      }
+    };
+    let mut success_fns: Vec<Box<dyn Fn(&PathNode) -> bool>> = vec![];
     for pt in points_to_check {
       if pt != start {
-        success_fns.push(Box::new(move |n: &Point3| *n == pt));
+        success_fns.push(Box::new(move |n: &PathNode| n.0 == pt));
       }
     }
-    let mut final_points = vec![];
     let path_result = astar_multi(
-      &start,
-      |n| self.point3_neighbors(terrain, volume, *n),
-      |n| self.point3_distance(start, *n),
+      &(start, false),
+      |n| path_neighbors(*self, terrain, costs, volume, mode, *n),
+      |n| self.point3_distance(start, n.0),
       speed,
       success_fns,
     );
-
-    for (path, cost) in path_result {
-      if cost <= speed {
-        // FIXME: we should NOT be checking cost here, instead astar_multi should support
-        // max distance.  (um, it does support max distance? )
-        final_points.push(*path.last().unwrap())
-      }
-    }
-    final_points
+    path_result.into_iter().map(|(path, _)| path.last().unwrap().0).collect()
   }
 
   /// Find a path from some start point to some destination point. If one can be found, a Vec of
   /// points on the way to the destination is returned, along with the total length of that path.
   fn find_path(
-    &self, start: Point3, speed: u32units::Length, terrain: &Terrain, volume: Volume,
-    destination: Point3,
+    &self, start: Point3, speed: u32units::Length, terrain: &Terrain, costs: &TerrainCosts,
+    volume: Volume, mode: MovementMode, destination: Point3,
   ) -> Option<(Vec<Point3>, u32units::Length)> {
-    let success = Box::new(move |n: &Point3| *n == destination);
+    let success = Box::new(move |n: &PathNode| n.0 == destination);
     let result = astar_multi(
-      &start,
-      |n| self.point3_neighbors(terrain, volume, *n),
-      |n| self.point3_distance(start, *n),
+      &(start, false),
+      |n| path_neighbors(*self, terrain, costs, volume, mode, *n),
+      |n| self.point3_distance(start, n.0),
       speed,
       vec![success],
     );
     if let Some((path, cost)) = result.into_iter().next() {
-      Some((path, cost))
+      Some((path.into_iter().map(|(pt, _)| pt).collect(), cost))
     } else {
       None
     }
@@ -271,7 +401,7 @@ impl TileSystemExt for TileSystem {
 
   /// Determine which points a volume occupies.
   /// The way a volume fits at a point is specific to the volume type.
-  /// AABB: top-left
+  /// AABB: top-left or center, depending on its anchor
   /// Sphere: center
   /// Line: origin
   /// VerticalCylinder: center of base
@@ -279,24 +409,84 @@ impl TileSystemExt for TileSystem {
   // TODO: Should this use ncollide?
   fn points_in_volume(&self, volume: Volume, pt: Point3) -> Vec<Point3> {
     match volume {
-      Volume::Sphere(..) => {
-        unimplemented!("unimplemented: points_in_volume for Sphere");
+      Volume::Sphere(radius) => {
+        // Nothing outside of the cube around the sphere can be in it.
+        let cells = i64::from(radius.get::<meter>());
+        let mut points = vec![];
+        for x in -cells..=cells {
+          for y in -cells..=cells {
+            for z in -cells..=cells {
+              let point =
+                Point3::from_quantities(pt.x + i64meter(x), pt.y + i64meter(y), pt.z + i64meter(z));
+              if self.point3_distance(pt, point) <= radius {
+                points.push(point);
+              }
+            }
+          }
+        }
+        points
       }
       // sadly uom doesn't implement Step for Quantity
       Volume::AABB(aabb) => {
+        let min = aabb.get_min(pt);
         let max = aabb.get_max(pt);
-        (pt.x.get::<meter>()..(max.x.get::<meter>()))
+        (min.x.get::<meter>()..(max.x.get::<meter>()))
           .flat_map(|x| {
-            (pt.y.get::<meter>()..(max.y.get::<meter>())).flat_map(move |y| {
-              (pt.z.get::<meter>()..(max.z.get::<meter>()))
+            (min.y.get::<meter>()..(max.y.get::<meter>())).flat_map(move |y| {
+              (min.z.get::<meter>()..(max.z.get::<meter>()))
                 .map(move |z| Point3::new(x * 100, y * 100, z * 100))
             })
           })
           .collect()
       }
       Volume::Line { .. } => unimplemented!("points_in_volume for Line"),
-      Volume::VerticalCylinder { .. } => {
-        unimplemented!("unimplemented: points_in_volume for VerticalCylinder")
+      Volume::VerticalCylinder { radius, height } => {
+        // The base is a circle around the point, which is stacked up once for every meter of
+        // height.
+        let cells = i64::from(radius.get::<meter>());
+        let mut points = vec![];
+        for x in -cells..=cells {
+          for y in -cells..=cells {
+            let base = Point3::from_quantities(pt.x + i64meter(x), pt.y + i64meter(y), pt.z);
+            if self.point3_distance(pt, base) <= radius {
+              for z in 0..i64::from(height.get::<meter>()) {
+                points.push(Point3::from_quantities(base.x, base.y, base.z + i64meter(z)));
+              }
+            }
+          }
+        }
+        points
+      }
+      Volume::Cone { direction, length, angle_degrees } => {
+        // Like a sphere, but only keeping the cells whose centers are within half the cone's angle
+        // of its direction. The apex is always in the cone, and a cone with no direction is nothing
+        // but its apex.
+        let direction = na_vector_cm(direction);
+        let half_angle = f64::from(angle_degrees).to_radians() / 2.0;
+        let cells = i64::from(length.get::<meter>());
+        let mut points = vec![pt];
+        if direction == Vector3::zeros() {
+          return points;
+        }
+        for x in -cells..=cells {
+          for y in -cells..=cells {
+            for z in -cells..=cells {
+              if (x, y, z) == (0, 0, 0) {
+                continue;
+              }
+              let offset = Vector3::new(x as f64, y as f64, z as f64);
+              let point =
+                Point3::from_quantities(pt.x + i64meter(x), pt.y + i64meter(y), pt.z + i64meter(z));
+              // A little slack keeps cells lying exactly on the edge of the cone inside it.
+              if self.point3_distance(pt, point) <= length
+                && offset.angle(&direction) <= half_angle + 1e-9
+              {
+                points.push(point);
+              }
+            }
+          }
+        }
+        points
       }
     }
   }
@@ -312,16 +502,27 @@ impl TileSystemExt for TileSystem {
     true
   }
 
-  /// Find neighbors of the given point that the given volume can fit in, given the terrain.
+  fn has_line_of_sight(&self, terrain: &Terrain, from: Point3, to: Point3) -> bool {
+    let line = line_cells(from, to);
+    let between = line.len().saturating_sub(2);
+    line.iter().skip(1).take(between).all(|pt| is_open(terrain, *pt))
+  }
+
+  /// Find neighbors of the given point that the given volume can fit in, given the terrain, along
+  /// with the cost of stepping into each one. Only flying creatures have neighbors above and below.
   fn point3_neighbors(
-    &self, terrain: &Terrain, volume: Volume, pt: Point3,
+    &self, terrain: &Terrain, costs: &TerrainCosts, volume: Volume, mode: MovementMode, pt: Point3,
   ) -> Vec<(Point3, u32units::Length)> {
+    // In AlternatingDiagonal, this is the cost of an odd-numbered diagonal step. The search in
+    // `path_neighbors` charges the even-numbered ones extra.
     let diagonal_distance = match *self {
       TileSystem::Realistic => u32cm(141),
-      TileSystem::DnD => u32cm(100),
+      TileSystem::DnD | TileSystem::AlternatingDiagonal | TileSystem::Orthogonal => u32cm(100),
     };
     let straight_distance = match *self {
-      TileSystem::Realistic => u32cm(100),
+      TileSystem::Realistic | TileSystem::AlternatingDiagonal | TileSystem::Orthogonal => {
+        u32cm(100)
+      }
       // ok, this is ridiculous, but:
       // since D&D movement makes diagonals cost the same as cardinals, the pathfinder
       // will arbitrarily choose to move diagonally when a normal person would move in
@@ -329,28 +530,128 @@ impl TileSystemExt for TileSystem {
       // we get it to prefer to move straight.
       TileSystem::DnD => u32cm(99),
     };
+    // The cost of a step along all three axes at once, which only flying creatures can take.
+    let corner_distance = match *self {
+      TileSystem::Realistic => u32cm(173),
+      TileSystem::DnD | TileSystem::AlternatingDiagonal | TileSystem::Orthogonal => {
+        diagonal_distance
+      }
+    };
+    let vertical_steps: &[i64] = match mode {
+      MovementMode::Ground => &[0],
+      MovementMode::Flying => &[-100, 0, 100],
+    };
+    let offset =
+      |x, y, z| Point3::from_quantities(pt.x + i64cm(x), pt.y + i64cm(y), pt.z + i64cm(z));
     let mut results = vec![];
     for &x in [-100, 0, 100].iter() {
       for &y in [-100, 0, 100].iter() {
-        if (x, y) == (0, 0) {
-          continue;
-        }
-        let neighbor = Point3::from_quantities(pt.x + i64cm(x), pt.y + i64cm(y), pt.z);
-        if is_open(terrain, neighbor) && self.volume_fits_at_point(volume, terrain, neighbor) {
-          let is_angle = x.abs() == y.abs(); // there's probably a cheaper way to do this
-          let cost = if is_angle { diagonal_distance } else { straight_distance };
-          // don't allow diagonal movement around corners
-          if is_angle && !is_open(terrain, Point3::from_quantities(neighbor.x, pt.y, pt.z))
-            || !is_open(terrain, Point3::from_quantities(pt.x, neighbor.y, pt.z))
-          {
+        for &z in vertical_steps {
+          if (x, y, z) == (0, 0, 0) {
             continue;
           }
-          results.push((neighbor, cost));
+          let neighbor = offset(x, y, z);
+          if !is_open(terrain, neighbor) || !self.volume_fits_at_point(volume, terrain, neighbor) {
+            continue;
+          }
+          let axes = [x, y, z].iter().filter(|&&d| d != 0).count();
+          if axes > 1 && *self == TileSystem::Orthogonal {
+            continue;
+          }
+          let cost = match axes {
+            1 => straight_distance,
+            2 => diagonal_distance,
+            _ => corner_distance,
+          };
+          // don't allow diagonal movement around corners: the cells reached by taking only some
+          // of the step's axes have to be open too.
+          let partial_steps = [(x, 0, 0), (0, y, 0), (0, 0, z), (x, y, 0), (x, 0, z), (0, y, z)];
+          let cuts_corner = partial_steps.iter().any(|&step| {
+            step != (0, 0, 0)
+              && step != (x, y, z)
+              && !is_open(terrain, offset(step.0, step.1, step.2))
+          });
+          if cuts_corner {
+            continue;
+          }
+          // A volume spanning several cells pays for the most difficult one it steps into. A
+          // multiplier of 0 is treated as 1 so that no cell is free to enter.
+          let multiplier = self
+            .points_in_volume(volume, neighbor)
+            .iter()
+            .filter_map(|cell| costs.get(cell))
+            .max()
+            .map_or(1, |&m| u32::from(m.max(1)));
+          results.push((neighbor, cost * multiplier));
         }
       }
     }
     results
   }
+
+  fn flood_volume(
+    &self, origin: Point3, terrain: &Terrain, volume: Volume, radius: u32units::Length,
+  ) -> Vec<Point3> {
+    // A plain Dijkstra search, since unlike `get_all_accessible` there are no particular
+    // destinations to find paths to.
+    let start = (origin, false);
+    let mut costs: HashMap<PathNode, u32units::Length> = HashMap::new();
+    costs.insert(start, u32cm(0));
+    let mut to_see = BinaryHeap::new();
+    to_see.push(InvCmpHolder { key: u32cm(0), payload: start });
+    // Effects spread just as far over difficult terrain as over anything else.
+    let no_costs = TerrainCosts::new();
+    while let Some(InvCmpHolder { key: cost, payload: node }) = to_see.pop() {
+      if let Some(&best) = costs.get(&node) {
+        if cost > best {
+          continue;
+        }
+      }
+      for (neighbor, step_cost) in
+        path_neighbors(*self, terrain, &no_costs, volume, MovementMode::Ground, node)
+      {
+        let new_cost = cost + step_cost;
+        let improved = match costs.get(&neighbor) {
+          Some(&old_cost) => new_cost < old_cost,
+          None => true,
+        };
+        if improved && new_cost <= radius {
+          costs.insert(neighbor, new_cost);
+          to_see.push(InvCmpHolder { key: new_cost, payload: neighbor });
+        }
+      }
+    }
+    let points: HashSet<Point3> = costs.into_keys().map(|(pt, _)| pt).collect();
+    let mut points: Vec<Point3> = points.into_iter().collect();
+    points.sort();
+    points
+  }
+}
+
+/// A point visited while pathfinding, along with whether an odd number of diagonal steps were
+/// taken to reach it. Only `TileSystem::AlternatingDiagonal` cares about the latter, since the
+/// cost of its next diagonal step depends on it.
+type PathNode = (Point3, bool);
+
+fn path_neighbors(
+  ts: TileSystem, terrain: &Terrain, costs: &TerrainCosts, volume: Volume, mode: MovementMode,
+  (pt, odd_diagonals): PathNode,
+) -> Vec<(PathNode, u32units::Length)> {
+  ts.point3_neighbors(terrain, costs, volume, mode, pt)
+    .into_iter()
+    .map(|(neighbor, cost)| {
+      let axes = [neighbor.x != pt.x, neighbor.y != pt.y, neighbor.z != pt.z];
+      let diagonal = axes.iter().filter(|&&changed| changed).count() > 1;
+      match ts {
+        // Doubling (rather than adding a flat 100) keeps any terrain multiplier applied.
+        TileSystem::AlternatingDiagonal if diagonal && odd_diagonals => {
+          ((neighbor, false), cost * 2)
+        }
+        TileSystem::AlternatingDiagonal if diagonal => ((neighbor, true), cost),
+        _ => ((neighbor, odd_diagonals), cost),
+      }
+    })
+    .collect()
 }
 
 /// Make a `CollisionWorld` given some creatures and volume conditions.
@@ -375,10 +676,10 @@ where
   let query = GeometricQueryType::Contacts(0.0, 0.0);
 
   for (creature, pos) in creatures {
-    let volume = Volume::AABB(creature.size);
+    let (iso, shape) = volume_to_na_shape(Volume::AABB(creature.size), pos);
     world.add(
-      na_iso(pos),
-      volume_to_na_shape(volume),
+      iso,
+      shape,
       creature_group,
       query,
       CollisionData::Creature(creature.id),
@@ -386,9 +687,10 @@ where
   }
 
   for (condition_id, volume_condition) in volume_conditions {
+    let (iso, shape) = volume_to_na_shape(volume_condition.volume, volume_condition.point);
     world.add(
-      na_iso(volume_condition.point),
-      volume_to_na_shape(volume_condition.volume),
+      iso,
+      shape,
       condition_group,
       query,
       CollisionData::ConditionVolume(condition_id),
@@ -419,16 +721,80 @@ where
   results
 }
 
-fn volume_to_na_shape(volume: Volume) -> shape::ShapeHandle<f64> {
+/// Get the position and shape of a volume placed at a point, for adding to a `CollisionWorld`.
+fn volume_to_na_shape(volume: Volume, pt: Point3) -> (Isometry3<f64>, shape::ShapeHandle<f64>) {
   match volume {
-    Volume::Sphere(r) => shape::ShapeHandle::new(shape::Ball::new(r.get::<centimeter>() as f64)),
-    Volume::AABB(aabb) => shape::ShapeHandle::new(shape::Cuboid::new(Vector3::new(
-      (f64::from(aabb.x.get::<centimeter>())) / 2.0,
-      (f64::from(aabb.y.get::<centimeter>())) / 2.0,
-      (f64::from(aabb.z.get::<centimeter>())) / 2.0,
-    ))),
+    Volume::Sphere(r) => {
+      (na_iso(pt), shape::ShapeHandle::new(shape::Ball::new(r.get::<centimeter>() as f64 / 100.0)))
+    }
+    Volume::AABB(aabb) => {
+      // Points name cells and sit in the middle of them, so the cuboid is centered between the
+      // first and last cells it covers, which depends on how it's anchored.
+      let first = na_vector(aabb.get_min(pt));
+      let last = na_vector(aabb.get_max(pt)) - Vector3::new(1.0, 1.0, 1.0);
+      let shape = shape::ShapeHandle::new(shape::Cuboid::new(Vector3::new(
+        (f64::from(aabb.x.get::<centimeter>())) / 200.0,
+        (f64::from(aabb.y.get::<centimeter>())) / 200.0,
+        (f64::from(aabb.z.get::<centimeter>())) / 200.0,
+      )));
+      (Isometry3::new((first + last) / 2.0, na::zero()), shape)
+    }
     Volume::Line { .. } => unimplemented!("volume_to_na_shape for Line"),
-    Volume::VerticalCylinder { .. } => unimplemented!("volume_to_na_shape for VerticalCylinder"),
+    Volume::VerticalCylinder { radius, height } => {
+      // ncollide can't check cylinders for contacts, so this is a hull around circles at the top
+      // and bottom. Like an AABB, it's centered between the first and last cells it covers.
+      let height = f64::from(height.get::<centimeter>()) / 100.0;
+      let radius = f64::from(radius.get::<centimeter>()) / 100.0;
+      let center = na_vector(pt) + Vector3::new(0.0, 0.0, (height - 1.0) / 2.0);
+      let (bottom, top) = (Vector3::z() * (-height / 2.0), Vector3::z() * (height / 2.0));
+      let points = circle_points(bottom, Vector3::x(), Vector3::y(), radius)
+        .chain(circle_points(top, Vector3::x(), Vector3::y(), radius))
+        .collect();
+      (Isometry3::new(center, na::zero()), hull_or_segment(points, bottom, top))
+    }
+    Volume::Cone { direction, length, angle_degrees } => {
+      // ncollide can't check cones for contacts either, so this is a hull around the apex and a
+      // circle at the base. Cones of 180 degrees or more aren't convex, so they're approximated by
+      // a ball around the apex.
+      let length = f64::from(length.get::<centimeter>()) / 100.0;
+      if angle_degrees >= 180 {
+        return (na_iso(pt), shape::ShapeHandle::new(shape::Ball::new(length)));
+      }
+      let direction = na_vector_cm(direction).try_normalize(0.0).unwrap_or_else(Vector3::x);
+      // Any two unit vectors at right angles to the direction will do for laying out the base.
+      let u = direction.cross(&Vector3::z()).try_normalize(0.0).unwrap_or_else(Vector3::x);
+      let v = direction.cross(&u);
+      let base = direction * length;
+      let radius = length * (f64::from(angle_degrees).to_radians() / 2.0).tan();
+      let points =
+        std::iter::once(na::Point3::origin()).chain(circle_points(base, u, v, radius)).collect();
+      (na_iso(pt), hull_or_segment(points, Vector3::zeros(), base))
+    }
+  }
+}
+
+/// Points spaced evenly around a circle with the given center, in the plane of the unit vectors
+/// `u` and `v`.
+fn circle_points(
+  center: Vector3<f64>, u: Vector3<f64>, v: Vector3<f64>, radius: f64,
+) -> impl Iterator<Item = na::Point3<f64>> {
+  const SEGMENTS: u32 = 16;
+  (0..SEGMENTS).map(move |i| {
+    let angle = 2.0 * PI * f64::from(i) / f64::from(SEGMENTS);
+    na::Point3::from(center + (u * angle.cos() + v * angle.sin()) * radius)
+  })
+}
+
+/// The convex hull of some points, or the segment from `start` to `end` if the points are too flat
+/// to have a hull, like those of a cylinder with no height.
+fn hull_or_segment(
+  points: Vec<na::Point3<f64>>, start: Vector3<f64>, end: Vector3<f64>,
+) -> shape::ShapeHandle<f64> {
+  match shape::ConvexHull::try_from_points(&points) {
+    Some(hull) => shape::ShapeHandle::new(hull),
+    None => {
+      shape::ShapeHandle::new(shape::Segment::new(na::Point3::from(start), na::Point3::from(end)))
+    }
   }
 }
 
@@ -473,6 +839,9 @@ fn reverse_path<N: Eq + Hash>(mut parents: HashMap<N, N>, start: N) -> Vec<N> {
   path.into_iter().rev().collect()
 }
 
+/// Find the cheapest path to each node that one of the `successes` functions accepts. Nodes are
+/// only found through paths that cost at most `max_cost`, so a node that's farther away than that
+/// isn't returned, however close the heuristic thinks it is.
 pub fn astar_multi<N, C, FN, IN, FH>(
   start: &N, neighbours: FN, heuristic: FH, max_cost: C,
   mut successes: Vec<Box<dyn Fn(&N) -> bool>>,
@@ -489,6 +858,19 @@ where
   let mut parents: HashMap<N, (N, C)> = HashMap::new();
   let mut found_nodes = vec![];
   while let Some(InvCmpHolder { payload: (cost, node), .. }) = to_see.pop() {
+    // We may have inserted a node several time into the binary heap if we found
+    // a better way to access it. Ensure that we are currently dealing with the
+    // best path and discard the others.
+    if let Some(&(_, c)) = parents.get(&node) {
+      if cost > c {
+        continue;
+      }
+    }
+    // Only nodes within max_cost are ever queued, but check anyway so that nothing farther away
+    // can be found.
+    if cost > max_cost {
+      continue;
+    }
     successes.retain_mut(|ref mut success_fn| {
       let was_successful = success_fn(&node);
       if was_successful {
@@ -499,14 +881,6 @@ where
     if successes.is_empty() {
       break;
     }
-    // We may have inserted a node several time into the binary heap if we found
-    // a better way to access it. Ensure that we are currently dealing with the
-    // best path and discard the others.
-    if let Some(&(_, c)) = parents.get(&node) {
-      if cost > c {
-        continue;
-      }
-    }
     for (neighbour, move_cost) in neighbours(&node) {
       let old_cost = parents.get(&neighbour).map(|&(_, c)| c);
       let new_cost = cost + move_cost;
@@ -531,16 +905,16 @@ where
 
 #[cfg(test)]
 pub mod test {
-  use maplit::hashmap;
+  use maplit::{hashmap, hashset};
 
   use crate::{grid::*, types::*};
 
   /// A map containing a single open block of terrain at 0,0,0
-  fn box_map() -> Terrain { vec![Point3::new(0, 0, 0)] }
+  fn box_map() -> Terrain { hashset! {Point3::new(0, 0, 0)} }
 
   /// A map shaped like a dumbbell, with two 2x3 rooms connected by a 1x1 passage
   fn dumbbell_map() -> Terrain {
-    vec![
+    hashset! {
       Point3::new(0, 0, 0),
       Point3::new(100, 0, 0),
       Point3::new(300, 0, 0),
@@ -554,23 +928,27 @@ pub mod test {
       Point3::new(100, 200, 0),
       Point3::new(300, 200, 0),
       Point3::new(400, 200, 0),
-    ]
+    }
   }
 
   /// A map containing a large 40-meter square of open terrain.
   pub fn huge_box() -> Terrain {
-    let mut map = vec![];
+    let mut map = Terrain::new();
     for x in -20..20 {
       for y in -20..20 {
-        map.push(Point3::new(x * 100, y * 100, 0));
+        map.insert(Point3::new(x * 100, y * 100, 0));
       }
     }
     map
   }
 
-  fn medium_size() -> AABB { AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100) } }
+  fn medium_size() -> AABB {
+    AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100), anchor: AABBAnchor::TopLeft }
+  }
 
-  fn large_size() -> AABB { AABB { x: u32cm(200), y: u32cm(200), z: u32cm(100) } }
+  fn large_size() -> AABB {
+    AABB { x: u32cm(200), y: u32cm(200), z: u32cm(100), anchor: AABBAnchor::TopLeft }
+  }
 
   #[test]
   fn test_simple_distance() {
@@ -590,7 +968,13 @@ pub mod test {
   fn test_neighbors() {
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts = TileSystem::Realistic.point3_neighbors(&terrain, size, Point3::new(0, 0, 0));
+    let mut pts = TileSystem::Realistic.point3_neighbors(
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      Point3::new(0, 0, 0),
+    );
     pts.sort();
     let mut expected = vec![
       (Point3::new(-100, 0, 0), u32cm(100)),
@@ -606,13 +990,152 @@ pub mod test {
     assert_eq!(pts, expected)
   }
 
+  #[test]
+  fn aabb_anchors() {
+    let ts = TileSystem::Realistic;
+    let top_left = large_size();
+    let centered = AABB { anchor: AABBAnchor::Center, ..top_left };
+    let pt = Point3::new(0, 0, 0);
+    assert_eq!(
+      ts.points_in_volume(Volume::AABB(top_left), pt),
+      vec![
+        Point3::new(0, 0, 0),
+        Point3::new(0, 100, 0),
+        Point3::new(100, 0, 0),
+        Point3::new(100, 100, 0),
+      ]
+    );
+    assert_eq!(
+      ts.points_in_volume(Volume::AABB(centered), pt),
+      vec![
+        Point3::new(-100, -100, 0),
+        Point3::new(-100, 0, 0),
+        Point3::new(0, -100, 0),
+        Point3::new(0, 0, 0),
+      ]
+    );
+    // Boxes without an anchor are placed by their top-left corner.
+    let aabb: AABB = serde_json::from_str(r#"{"x": 200, "y": 200, "z": 100}"#).unwrap();
+    assert_eq!(aabb, top_left);
+  }
+
+  #[test]
+  fn odd_centered_aabb_is_symmetric() {
+    let ts = TileSystem::Realistic;
+    let aabb = AABB { x: u32cm(300), y: u32cm(300), z: u32cm(100), anchor: AABBAnchor::Center };
+    let mut expected = vec![];
+    for x in [-100, 0, 100] {
+      for y in [-100, 0, 100] {
+        expected.push(Point3::new(x, y, 0));
+      }
+    }
+    assert_eq!(ts.points_in_volume(Volume::AABB(aabb), Point3::new(0, 0, 0)), expected);
+  }
+
+  #[test]
+  fn dnd_vertical_distance() {
+    let below = Point3::new(100, 100, 0);
+    assert_eq!(TileSystem::DnD.point3_distance(below, Point3::new(100, 100, 300)), u32cm(300));
+    assert_eq!(TileSystem::DnD.point3_distance(below, Point3::new(200, 100, -500)), u32cm(500));
+    assert_eq!(TileSystem::DnD.point3_distance(below, Point3::new(600, 100, 200)), u32cm(500));
+  }
+
+  #[test]
+  fn alternating_diagonal_vertical_distance() {
+    let ts = TileSystem::AlternatingDiagonal;
+    let below = Point3::new(100, 100, 0);
+    assert_eq!(ts.point3_distance(below, Point3::new(100, 100, 500)), u32cm(500));
+    // Climbing diagonally costs the same as moving diagonally on the ground.
+    assert_eq!(ts.point3_distance(below, Point3::new(300, 100, -200)), u32cm(300));
+    assert_eq!(ts.point3_distance(below, Point3::new(300, 300, 0)), u32cm(300));
+    assert_eq!(ts.point3_distance(below, Point3::new(300, 300, 200)), u32cm(300));
+  }
+
+  #[test]
+  fn orthogonal_vertical_distance() {
+    let ts = TileSystem::Orthogonal;
+    let below = Point3::new(100, 100, 0);
+    assert_eq!(ts.point3_distance(below, Point3::new(100, 100, 500)), u32cm(500));
+    assert_eq!(ts.point3_distance(below, Point3::new(200, 100, -300)), u32cm(400));
+    assert_eq!(ts.point3_distance(below, Point3::new(200, 300, 100)), u32cm(400));
+  }
+
+  #[test]
+  fn alternating_diagonal_costs() {
+    let ts = TileSystem::AlternatingDiagonal;
+    let start = Point3::new(0, 0, 0);
+    let size = Volume::AABB(medium_size());
+    // Diagonal steps cost 1, 2, 1, 2 meters...
+    for (steps, expected) in [(1, 100), (2, 300), (3, 400), (4, 600), (5, 700)] {
+      let dest = Point3::new(steps * 100, steps * 100, 0);
+      assert_eq!(ts.point3_distance(start, dest), u32cm(expected));
+      let (_, cost) = ts
+        .find_path(
+          start,
+          u32cm(1000),
+          &huge_box(),
+          &TerrainCosts::new(),
+          size,
+          MovementMode::Ground,
+          dest,
+        )
+        .unwrap();
+      assert_eq!(cost, u32cm(expected));
+    }
+    // ... while straight steps always cost 1.
+    assert_eq!(ts.point3_distance(start, Point3::new(300, 100, 0)), u32cm(300));
+    assert_eq!(ts.point3_distance(start, Point3::new(-400, 200, 0)), u32cm(500));
+  }
+
+  #[test]
+  fn orthogonal_has_no_diagonals() {
+    let ts = TileSystem::Orthogonal;
+    let start = Point3::new(0, 0, 0);
+    let size = Volume::AABB(medium_size());
+    let mut pts: Vec<Point3> = ts
+      .point3_neighbors(&huge_box(), &TerrainCosts::new(), size, MovementMode::Ground, start)
+      .into_iter()
+      .map(|(pt, _)| pt)
+      .collect();
+    pts.sort();
+    let mut expected = vec![
+      Point3::new(-100, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(0, -100, 0),
+      Point3::new(0, 100, 0),
+    ];
+    expected.sort();
+    assert_eq!(pts, expected);
+    let dest = Point3::new(200, 200, 0);
+    assert_eq!(ts.point3_distance(start, dest), u32cm(400));
+    let (path, cost) = ts
+      .find_path(
+        start,
+        u32cm(1000),
+        &huge_box(),
+        &TerrainCosts::new(),
+        size,
+        MovementMode::Ground,
+        dest,
+      )
+      .unwrap();
+    assert_eq!(cost, u32cm(400));
+    assert_eq!(path.len(), 5);
+  }
+
   /// a diagonal neighbor is not considered accessible if it "goes around" a blocked corner
   #[test]
   fn test_neighbors_around_corners() {
-    let terrain = vec![Point3::new(100, 0, 0)];
+    let terrain = hashset! {Point3::new(100, 0, 0)};
     let size = Volume::AABB(medium_size());
     let pts: Vec<Point3> = TileSystem::Realistic
-      .point3_neighbors(&terrain, size, Point3::new(0, 0, 0))
+      .point3_neighbors(
+        &terrain,
+        &TerrainCosts::new(),
+        size,
+        MovementMode::Ground,
+        Point3::new(0, 0, 0),
+      )
       .iter()
       .map(|&(p, _)| p)
       .collect();
@@ -627,7 +1150,15 @@ pub mod test {
     let size = Volume::AABB(medium_size());
     let paths_and_costs = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| {
+        TileSystem::Realistic.point3_neighbors(
+          &huge_box(),
+          &TerrainCosts::new(),
+          size,
+          MovementMode::Ground,
+          *n,
+        )
+      },
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(u32::max_value() / 64 - 1), // FIXME this is a workaround for uom bug #55
       vec![success],
@@ -643,7 +1174,15 @@ pub mod test {
     let size = Volume::AABB(medium_size());
     let result = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| {
+        TileSystem::Realistic.point3_neighbors(
+          &huge_box(),
+          &TerrainCosts::new(),
+          size,
+          MovementMode::Ground,
+          *n,
+        )
+      },
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(499),
       vec![success],
@@ -660,7 +1199,15 @@ pub mod test {
     let size = Volume::AABB(medium_size());
     let result = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| {
+        TileSystem::Realistic.point3_neighbors(
+          &huge_box(),
+          &TerrainCosts::new(),
+          size,
+          MovementMode::Ground,
+          *n,
+        )
+      },
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(500),
       vec![success],
@@ -691,7 +1238,15 @@ pub mod test {
     let size = Volume::AABB(medium_size());
     let paths_and_costs = astar_multi(
       &start,
-      |n| TileSystem::Realistic.point3_neighbors(&huge_box(), size, *n),
+      |n| {
+        TileSystem::Realistic.point3_neighbors(
+          &huge_box(),
+          &TerrainCosts::new(),
+          size,
+          MovementMode::Ground,
+          *n,
+        )
+      },
       |n| TileSystem::Realistic.point3_distance(start, *n),
       u32cm(u32::max_value() / 64 - 1), // FIXME this is a workaround for uom bug #55
       successes,
@@ -701,14 +1256,53 @@ pub mod test {
     assert_eq!(paths_and_costs, [(ex_path_positive, u32cm(141)), (ex_path_negative, u32cm(141))]);
   }
 
+  #[test]
+  fn line_of_sight_through_the_dumbbell() {
+    let ts = TileSystem::Realistic;
+    let terrain = dumbbell_map();
+    // Straight through the passage between the rooms
+    assert!(ts.has_line_of_sight(&terrain, Point3::new(0, 100, 0), Point3::new(400, 100, 0)));
+    // Diagonally through the passage
+    assert!(ts.has_line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(400, 200, 0)));
+    // The wall beside the passage blocks sight between the rooms, in both directions
+    assert!(!ts.has_line_of_sight(&terrain, Point3::new(100, 0, 0), Point3::new(300, 0, 0)));
+    assert!(!ts.has_line_of_sight(&terrain, Point3::new(300, 0, 0), Point3::new(100, 0, 0)));
+    assert!(!ts.has_line_of_sight(&terrain, Point3::new(0, 0, 0), Point3::new(400, 0, 0)));
+  }
+
+  #[test]
+  fn line_of_sight_ignores_the_endpoints() {
+    let ts = TileSystem::Realistic;
+    let terrain = dumbbell_map();
+    // Both ends are in the wall, but the passage between them is open.
+    assert!(ts.has_line_of_sight(&terrain, Point3::new(200, 0, 0), Point3::new(200, 200, 0)));
+    assert!(ts.has_line_of_sight(&terrain, Point3::new(200, 0, 0), Point3::new(200, 0, 0)));
+    assert!(ts.has_line_of_sight(&terrain, Point3::new(100, 0, 0), Point3::new(200, 0, 0)));
+  }
+
+  #[test]
+  fn line_of_sight_between_levels() {
+    let ts = TileSystem::Realistic;
+    let mut terrain = dumbbell_map();
+    let (floor, roof) = (Point3::new(0, 0, 0), Point3::new(0, 0, 200));
+    assert!(!ts.has_line_of_sight(&terrain, floor, roof));
+    terrain.insert(Point3::new(0, 0, 100));
+    assert!(ts.has_line_of_sight(&terrain, floor, roof));
+  }
+
   #[test]
   fn test_accessible_nowhere_to_go() {
     let terrain = box_map();
     let size = Volume::AABB(medium_size());
-    assert_eq!(
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(1000)),
-      vec![]
+    let pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      u32cm(1000),
     );
+    assert_eq!(pts, vec![]);
   }
 
   #[test]
@@ -716,8 +1310,14 @@ pub mod test {
     // a speed of 100 means you can only move on the axes
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(100));
+    let mut pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      u32cm(100),
+    );
     pts.sort();
     let mut expected = vec![
       Point3::new(-100, 0, 0),
@@ -734,8 +1334,14 @@ pub mod test {
     // a speed of 141 means you can also move diagonally, but only once
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let mut pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(141));
+    let mut pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      u32cm(141),
+    );
     pts.sort();
     let mut expected = vec![
       Point3::new(-100, 0, 0),
@@ -762,14 +1368,121 @@ pub mod test {
   fn test_accessible_average_speed() {
     let terrain = huge_box();
     let size = Volume::AABB(medium_size());
-    let pts =
-      TileSystem::Realistic.get_all_accessible(Point3::new(0, 0, 0), &terrain, size, u32cm(1000));
+    let pts = TileSystem::Realistic.get_all_accessible(
+      Point3::new(0, 0, 0),
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      u32cm(1000),
+    );
     // NOTE: The reason this isn't 314 (pie are square of radius=100) is that we only allow
     // 8 degrees of movement, which leaves certain positions within a circle impossible to
     // reach even if you can technically move the radius of the circle in one turn.
     assert_eq!(pts.len(), 284);
   }
 
+  #[test]
+  fn difficult_terrain_limits_movement() {
+    let ts = TileSystem::Realistic;
+    let terrain = huge_box();
+    let size = Volume::AABB(medium_size());
+    let start = Point3::new(0, 0, 0);
+    let open = ts.get_all_accessible(
+      start,
+      &terrain,
+      &TerrainCosts::new(),
+      size,
+      MovementMode::Ground,
+      u32cm(200),
+    );
+    // Two cells in each straight direction, plus the four diagonal neighbors
+    assert_eq!(open.len(), 12);
+
+    let mut ring = TerrainCosts::new();
+    for x in [-100, 0, 100] {
+      for y in [-100, 0, 100] {
+        if (x, y) != (0, 0) {
+          ring.insert(Point3::new(x, y, 0), 2);
+        }
+      }
+    }
+    let mut pts =
+      ts.get_all_accessible(start, &terrain, &ring, size, MovementMode::Ground, u32cm(200));
+    pts.sort();
+    let mut expected = vec![
+      Point3::new(-100, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(0, -100, 0),
+      Point3::new(0, 100, 0),
+    ];
+    expected.sort();
+    assert_eq!(pts, expected);
+  }
+
+  #[test]
+  fn flying_creature_crosses_a_gap_to_a_ledge() {
+    let ts = TileSystem::Realistic;
+    let size = Volume::AABB(medium_size());
+    let costs = TerrainCosts::new();
+    // A pit at 100,0,0 separates the start from a ledge a level up. The cells above the start and
+    // the pit are open air.
+    let terrain = hashset! {
+      Point3::new(0, 0, 0),
+      Point3::new(0, 0, 100),
+      Point3::new(100, 0, 100),
+      Point3::new(200, 0, 100),
+    };
+    let start = Point3::new(0, 0, 0);
+    let ledge = Point3::new(200, 0, 100);
+    let walk =
+      ts.find_path(start, u32cm(1000), &terrain, &costs, size, MovementMode::Ground, ledge);
+    assert_eq!(walk, None);
+    let (path, cost) = ts
+      .find_path(start, u32cm(1000), &terrain, &costs, size, MovementMode::Flying, ledge)
+      .unwrap();
+    // It can't cut diagonally up past the edge of the pit.
+    assert_eq!(path, vec![start, Point3::new(0, 0, 100), Point3::new(100, 0, 100), ledge]);
+    assert_eq!(cost, u32cm(300));
+
+    let walkable = ts.get_all_accessible(start, &terrain, &costs, size, MovementMode::Ground, cost);
+    assert_eq!(walkable, vec![]);
+    let flyable = ts.get_all_accessible(start, &terrain, &costs, size, MovementMode::Flying, cost);
+    assert!(flyable.contains(&ledge));
+  }
+
+  #[test]
+  fn flying_diagonals_cost_more_in_three_dimensions() {
+    let ts = TileSystem::Realistic;
+    let size = Volume::AABB(medium_size());
+    let mut terrain = Terrain::new();
+    for x in [0, 100] {
+      for y in [0, 100] {
+        for z in [0, 100] {
+          terrain.insert(Point3::new(x, y, z));
+        }
+      }
+    }
+    let start = Point3::new(0, 0, 0);
+    let mut neighbors =
+      ts.point3_neighbors(&terrain, &TerrainCosts::new(), size, MovementMode::Flying, start);
+    neighbors.sort();
+    let mut expected = vec![
+      (Point3::new(100, 0, 0), u32cm(100)),
+      (Point3::new(0, 100, 0), u32cm(100)),
+      (Point3::new(0, 0, 100), u32cm(100)),
+      (Point3::new(100, 100, 0), u32cm(141)),
+      (Point3::new(100, 0, 100), u32cm(141)),
+      (Point3::new(0, 100, 100), u32cm(141)),
+      (Point3::new(100, 100, 100), u32cm(173)),
+    ];
+    expected.sort();
+    assert_eq!(neighbors, expected);
+    let walking =
+      ts.point3_neighbors(&terrain, &TerrainCosts::new(), size, MovementMode::Ground, start);
+    assert_eq!(walking.len(), 3);
+  }
+
   #[test]
   fn items_within_volume_sphere() {
     let ts = TileSystem::Realistic;
@@ -838,6 +1551,230 @@ pub mod test {
     );
   }
 
+  #[test]
+  fn points_in_sphere() {
+    let ts = TileSystem::Realistic;
+    let results = ts.points_in_volume(Volume::Sphere(u32cm(100)), Point3::new(100, 100, 0));
+    assert_eq!(
+      results,
+      vec![
+        Point3::new(0, 100, 0),
+        Point3::new(100, 0, 0),
+        Point3::new(100, 100, -100),
+        Point3::new(100, 100, 0),
+        Point3::new(100, 100, 100),
+        Point3::new(100, 200, 0),
+        Point3::new(200, 100, 0),
+      ]
+    );
+  }
+
+  #[test]
+  fn points_in_sphere_dnd() {
+    // Diagonals are as close as anything else in DnD, so the sphere fills its whole cube.
+    let ts = TileSystem::DnD;
+    let results = ts.points_in_volume(Volume::Sphere(u32cm(100)), Point3::new(0, 0, 0));
+    assert_eq!(results.len(), 27);
+    assert!(results.contains(&Point3::new(-100, 100, -100)));
+  }
+
+  #[test]
+  fn points_in_tiny_sphere() {
+    for ts in [TileSystem::Realistic, TileSystem::DnD] {
+      let results = ts.points_in_volume(Volume::Sphere(u32cm(50)), Point3::new(300, 0, 0));
+      assert_eq!(results, vec![Point3::new(300, 0, 0)]);
+    }
+  }
+
+  #[test]
+  fn points_in_vertical_cylinder() {
+    let ts = TileSystem::Realistic;
+    let cylinder = Volume::VerticalCylinder { radius: u32cm(100), height: u32cm(200) };
+    let mut results = ts.points_in_volume(cylinder, Point3::new(100, 100, 0));
+    results.sort_by_key(|pt| (pt.z, pt.x, pt.y));
+    let mut expected = vec![];
+    for z in [0, 100] {
+      expected.extend([
+        Point3::new(0, 100, z),
+        Point3::new(100, 0, z),
+        Point3::new(100, 100, z),
+        Point3::new(100, 200, z),
+        Point3::new(200, 100, z),
+      ]);
+    }
+    assert_eq!(results, expected);
+  }
+
+  #[test]
+  fn points_in_cone() {
+    let ts = TileSystem::Realistic;
+    let cone =
+      Volume::Cone { direction: Point3::new(100, 0, 0), length: u32cm(300), angle_degrees: 60 };
+    let points = ts.points_in_volume(cone, Point3::new(0, 0, 0));
+    // The apex and the cells straight along the cone are in it, as are cells a little off to the
+    // side.
+    for pt in [
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(300, 0, 0),
+      Point3::new(200, 100, 0),
+      Point3::new(200, -100, 0),
+      Point3::new(200, 0, 100),
+    ] {
+      assert!(points.contains(&pt), "{pt:?} should be in the cone");
+    }
+    // Cells outside the half-angle are left out even when they're close enough, along with cells
+    // behind the apex or past the end of the cone.
+    for pt in [
+      Point3::new(100, 100, 0),
+      Point3::new(0, 100, 0),
+      Point3::new(-100, 0, 0),
+      Point3::new(400, 0, 0),
+    ] {
+      assert!(!points.contains(&pt), "{pt:?} shouldn't be in the cone");
+    }
+  }
+
+  #[test]
+  fn cone_through_point_points_at_the_click() {
+    let ts = TileSystem::Realistic;
+    let cone = cone_through_point(Point3::new(0, 0, 0), Point3::new(0, 500, 0), u32cm(200), 90);
+    let items = hashmap! {
+      "ahead" => Point3::new(0, 200, 0),
+      "on the edge" => Point3::new(100, 100, 0),
+      "off to the side" => Point3::new(200, 0, 0),
+    };
+    let mut results = ts.items_within_volume(cone, Point3::new(0, 0, 0), &items);
+    results.sort();
+    assert_eq!(results, vec!["ahead", "on the edge"]);
+  }
+
+  #[test]
+  fn items_within_volume_cylinder() {
+    let ts = TileSystem::Realistic;
+    let thorn_patch = Volume::VerticalCylinder { radius: u32cm(200), height: u32cm(100) };
+    let items = hashmap! {
+      "in the patch" => Point3::new(200, 0, 0),
+      "outside the radius" => Point3::new(200, 200, 0),
+      "above the patch" => Point3::new(0, 0, 100),
+    };
+    let results = ts.items_within_volume(thorn_patch, Point3::new(0, 0, 0), &items);
+    assert_eq!(results, vec!["in the patch"]);
+  }
+
+  #[test]
+  fn flood_stays_in_the_room() {
+    let ts = TileSystem::Realistic;
+    let origin = Point3::new(0, 0, 0);
+    let radius = u32cm(300);
+    let flooded = ts.flood_volume(origin, &dumbbell_map(), Volume::AABB(medium_size()), radius);
+    // The flood has to squeeze through the passage to get to the other room, which takes more than
+    // 3 meters, even though the other room's corner is only 3 meters away as the crow flies.
+    assert_eq!(
+      flooded,
+      vec![
+        Point3::new(0, 0, 0),
+        Point3::new(0, 100, 0),
+        Point3::new(0, 200, 0),
+        Point3::new(100, 0, 0),
+        Point3::new(100, 100, 0),
+        Point3::new(100, 200, 0),
+        Point3::new(200, 100, 0),
+      ]
+    );
+    assert!(ts.points_in_volume(Volume::Sphere(radius), origin).contains(&Point3::new(300, 0, 0)));
+  }
+
+  #[test]
+  fn flood_fills_the_dumbbell() {
+    let ts = TileSystem::Realistic;
+    let flooded = ts.flood_volume(
+      Point3::new(0, 0, 0),
+      &dumbbell_map(),
+      Volume::AABB(medium_size()),
+      u32cm(500),
+    );
+    let mut expected: Vec<Point3> = dumbbell_map().into_iter().collect();
+    expected.sort();
+    assert_eq!(flooded, expected);
+  }
+
+  #[test]
+  fn creature_next_to_a_wall_can_step_straight() {
+    let ts = TileSystem::Realistic;
+    let size = Volume::AABB(medium_size());
+    // A corridor running along x, with a wall cell at (100, 100) beside it.
+    let terrain = hashset! {
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(200, 0, 0),
+      Point3::new(0, 100, 0),
+      Point3::new(200, 100, 0),
+    };
+    let neighbors = |pt| -> Vec<Point3> {
+      ts.point3_neighbors(&terrain, &TerrainCosts::new(), size, MovementMode::Ground, pt)
+        .into_iter()
+        .map(|(pt, _)| pt)
+        .collect()
+    };
+    // It can go straight along the corridor, but not diagonally around the wall.
+    let beside_the_wall = neighbors(Point3::new(100, 0, 0));
+    assert_eq!(beside_the_wall, vec![Point3::new(0, 0, 0), Point3::new(200, 0, 0)]);
+    // Even a creature that's somehow standing in the wall can step straight out of it.
+    let in_the_wall = neighbors(Point3::new(100, 100, 0));
+    assert_eq!(
+      in_the_wall,
+      vec![
+        Point3::new(0, 0, 0),
+        Point3::new(0, 100, 0),
+        Point3::new(100, 0, 0),
+        Point3::new(200, 0, 0),
+        Point3::new(200, 100, 0),
+      ]
+    );
+  }
+
+  #[test]
+  fn astar_multi_respects_max_cost() {
+    let ts = TileSystem::Realistic;
+    let terrain = dumbbell_map();
+    let start = (Point3::new(100, 0, 0), false);
+    // Only 2 meters away, but there's a wall in between, so getting there takes 4 meters.
+    let destination = Point3::new(300, 0, 0);
+    let search = |max_cost| {
+      astar_multi(
+        &start,
+        |n| {
+          path_neighbors(
+            ts,
+            &terrain,
+            &TerrainCosts::new(),
+            Volume::AABB(medium_size()),
+            MovementMode::Ground,
+            *n,
+          )
+        },
+        |n| ts.point3_distance(n.0, destination),
+        max_cost,
+        vec![Box::new(move |n: &PathNode| n.0 == destination)],
+      )
+    };
+    assert_eq!(search(u32cm(300)), vec![]);
+    let found = search(u32cm(400));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].1, u32cm(400));
+
+    let accessible = ts.get_all_accessible(
+      start.0,
+      &terrain,
+      &TerrainCosts::new(),
+      Volume::AABB(medium_size()),
+      MovementMode::Ground,
+      u32cm(300),
+    );
+    assert!(!accessible.contains(&destination));
+  }
+
   #[test]
   fn line_through_point_simple() {
     let line = line_through_point(Point3::new(0, 0, 0), Point3::new(100, 0, 0), u32cm(200));
@@ -861,8 +1798,15 @@ pub mod test {
     let ts = TileSystem::Realistic;
     let dumbbell = dumbbell_map();
     let big_guy = Volume::AABB(large_size());
-    let path =
-      ts.find_path(Point3::new(0, 0, 0), u32cm(1000), &dumbbell, big_guy, Point3::new(300, 0, 0));
+    let path = ts.find_path(
+      Point3::new(0, 0, 0),
+      u32cm(1000),
+      &dumbbell,
+      &TerrainCosts::new(),
+      big_guy,
+      MovementMode::Ground,
+      Point3::new(300, 0, 0),
+    );
     assert_eq!(path, None);
   }
 
@@ -870,10 +1814,17 @@ pub mod test {
   fn large_creature_can_fit_through_large_opening() {
     let ts = TileSystem::Realistic;
     let mut dumbbell = dumbbell_map();
-    dumbbell.push(Point3::new(200, 200, 0));
+    dumbbell.insert(Point3::new(200, 200, 0));
     let big_guy = Volume::AABB(large_size());
-    let path =
-      ts.find_path(Point3::new(0, 0, 0), u32cm(1000), &dumbbell, big_guy, Point3::new(300, 0, 0));
+    let path = ts.find_path(
+      Point3::new(0, 0, 0),
+      u32cm(1000),
+      &dumbbell,
+      &TerrainCosts::new(),
+      big_guy,
+      MovementMode::Ground,
+      Point3::new(300, 0, 0),
+    );
     assert_eq!(
       path,
       Some((
@@ -888,4 +1839,38 @@ pub mod test {
       ))
     );
   }
+
+  #[test]
+  fn no_cover_in_the_open() {
+    let cover = cover_between(Point3::new(0, 0, 0), Point3::new(300, 200, 0), &huge_box());
+    assert_eq!(cover, Cover::None);
+  }
+
+  #[test]
+  fn corner_grants_half_cover() {
+    // An L-shaped corridor; the target is just around the corner.
+    let terrain = hashset! {
+      Point3::new(0, 0, 0),
+      Point3::new(100, 0, 0),
+      Point3::new(200, 0, 0),
+      Point3::new(200, 100, 0),
+      Point3::new(200, 200, 0),
+    };
+    let from = Point3::new(0, 0, 0);
+    assert_eq!(cover_between(from, Point3::new(200, 0, 0), &terrain), Cover::None);
+    assert_eq!(cover_between(from, Point3::new(200, 100, 0), &terrain), Cover::Half);
+    assert_eq!(cover_between(from, Point3::new(200, 200, 0), &terrain), Cover::ThreeQuarters);
+  }
+
+  #[test]
+  fn wall_grants_total_cover() {
+    // Two rooms separated by a solid wall along y=100.
+    let terrain: Terrain = (0..5)
+      .flat_map(|x| vec![Point3::new(x * 100, 0, 0), Point3::new(x * 100, 200, 0)])
+      .collect();
+    let cover = cover_between(Point3::new(0, 0, 0), Point3::new(0, 200, 0), &terrain);
+    assert_eq!(cover, Cover::Total);
+    let cover = cover_between(Point3::new(0, 0, 0), Point3::new(400, 200, 0), &terrain);
+    assert_eq!(cover, Cover::Total);
+  }
 }
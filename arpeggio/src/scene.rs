@@ -24,11 +24,14 @@ pub trait SceneExt {
   /// Largely used for previewing the area that will be affected by a volume-affecting ability.
   fn open_terrain_in_volume(
     &self, game: &Game, pt: Point3, volume: Volume,
-  ) -> Result<Terrain, GameError>;
+  ) -> Result<Vec<Point3>, GameError>;
 
   fn creatures_in_volume(&self, ts: TileSystem, pt: Point3, volume: Volume) -> Vec<CreatureID>;
 
   fn get_world(&self, game: &Game) -> Result<CollisionWorld, GameError>;
+
+  /// Whether a point lies within the scene's `grid_bounds`. Always true if it has none.
+  fn in_grid_bounds(&self, pt: Point3) -> bool;
 }
 
 impl SceneExt for Scene {
@@ -39,7 +42,8 @@ impl SceneExt for Scene {
       background_image_url: creation.background_image_url.clone(),
       background_image_offset: creation.background_image_offset,
       background_image_scale: creation.background_image_scale,
-      terrain: vec![],
+      terrain: Terrain::new(),
+      terrain_costs: HashMap::new(),
       highlights: HashMap::new(),
       annotations: HashMap::new(),
       scene_hotspots: HashMap::new(),
@@ -49,6 +53,9 @@ impl SceneExt for Scene {
       inventory: HashMap::new(),
       volume_conditions: HashMap::new(),
       focused_creatures: vec![],
+      preserved_initiative: vec![],
+      checkpoints: HashMap::new(),
+      grid_bounds: None,
     }
   }
   fn get_pos(&self, creature_id: CreatureID) -> Result<Point3, GameError> {
@@ -110,7 +117,7 @@ impl SceneExt for Scene {
   /// Largely used for previewing the area that will be affected by a volume-affecting ability.
   fn open_terrain_in_volume(
     &self, game: &Game, pt: Point3, volume: Volume,
-  ) -> Result<Terrain, GameError> {
+  ) -> Result<Vec<Point3>, GameError> {
     let all_open = self.terrain.iter().map(|pt| (*pt, *pt)).collect();
     Ok(game.tile_system.items_within_volume(volume, pt, &all_open))
   }
@@ -127,12 +134,22 @@ impl SceneExt for Scene {
     let vcs = self.volume_conditions.iter().map(|(c, vc)| (*c, vc));
     Ok(make_world(creatures, vcs))
   }
+
+  fn in_grid_bounds(&self, pt: Point3) -> bool {
+    match self.grid_bounds {
+      None => true,
+      Some((a, b)) => {
+        let within = |v, a, b| if a <= b { a <= v && v <= b } else { b <= v && v <= a };
+        within(pt.x, a.x, b.x) && within(pt.y, a.y, b.y) && within(pt.z, a.z, b.z)
+      }
+    }
+  }
 }
 
 #[cfg(test)]
 mod test {
   use crate::{
-    game::test::*,
+    game::{test::*, GameExt},
     scene::SceneExt,
     types::{test::*, *},
   };
@@ -153,4 +170,122 @@ mod test {
     let conds = scene.creature_volume_conditions(&game, &rogue).expect("Couldn't get conds");
     assert_eq!(conds, vec![(cond_id, &volume_cond)]);
   }
+
+  #[test]
+  fn creature_volume_conditions_in_cylinder() {
+    let game = t_game();
+    let mut scene = t_scene();
+    let cond_id = ConditionID::gen();
+    let volume_cond = VolumeCondition {
+      point: Point3::new(100, 0, 0),
+      volume: Volume::VerticalCylinder { radius: u32cm(200), height: u32cm(100) },
+      remaining: Duration::Interminate,
+      condition: Condition::Incapacitated,
+    };
+    scene.volume_conditions.insert(cond_id, volume_cond.clone());
+    let rogue = t_rogue("rogue");
+    let conds = scene.creature_volume_conditions(&game, &rogue).expect("Couldn't get conds");
+    assert_eq!(conds, vec![(cond_id, &volume_cond)]);
+  }
+
+  #[test]
+  fn creature_volume_conditions_in_cone() {
+    let game = t_game();
+    let mut scene = t_scene();
+    let cond_id = ConditionID::gen();
+    let volume_cond = VolumeCondition {
+      point: Point3::new(-200, 0, 0),
+      volume: Volume::Cone {
+        direction: Point3::new(100, 0, 0),
+        length: u32cm(500),
+        angle_degrees: 60,
+      },
+      remaining: Duration::Interminate,
+      condition: Condition::Incapacitated,
+    };
+    scene.volume_conditions.insert(cond_id, volume_cond.clone());
+    let rogue = t_rogue("rogue");
+    let conds = scene.creature_volume_conditions(&game, &rogue).expect("Couldn't get conds");
+    assert_eq!(conds, vec![(cond_id, &volume_cond)]);
+  }
+
+  #[test]
+  fn creature_volume_conditions_behind_cone() {
+    let game = t_game();
+    let mut scene = t_scene();
+    // The same cone as above, turned around so that the rogue is behind its apex.
+    scene.volume_conditions.insert(
+      ConditionID::gen(),
+      VolumeCondition {
+        point: Point3::new(-200, 0, 0),
+        volume: Volume::Cone {
+          direction: Point3::new(-100, 0, 0),
+          length: u32cm(500),
+          angle_degrees: 60,
+        },
+        remaining: Duration::Interminate,
+        condition: Condition::Incapacitated,
+      },
+    );
+    let rogue = t_rogue("rogue");
+    let conds = scene.creature_volume_conditions(&game, &rogue).expect("Couldn't get conds");
+    assert_eq!(conds, vec![]);
+  }
+
+  #[test]
+  fn sphere_volumes_reach_creature_edges() {
+    let game = t_game();
+    let mut scene = t_scene();
+    scene.creatures.insert(cid_ranger(), (Point3::new(200, 0, 0), Visibility::AllPlayers));
+    scene.creatures.insert(cid_cleric(), (Point3::new(400, 0, 0), Visibility::AllPlayers));
+    let touched = |radius| {
+      let mut scene = scene.clone();
+      scene.volume_conditions.insert(
+        ConditionID::gen(),
+        VolumeCondition {
+          point: Point3::new(0, 0, 0),
+          volume: Volume::Sphere(u32cm(radius)),
+          remaining: Duration::Interminate,
+          condition: Condition::Incapacitated,
+        },
+      );
+      let mut cids: Vec<CreatureID> = [cid_rogue(), cid_ranger(), cid_cleric()]
+        .into_iter()
+        .filter(|cid| {
+          let creature = game.get_creature(*cid).unwrap().creature;
+          !scene.creature_volume_conditions(&game, creature).unwrap().is_empty()
+        })
+        .collect();
+      cids.sort();
+      cids
+    };
+    let mut rogue_and_ranger = vec![cid_rogue(), cid_ranger()];
+    rogue_and_ranger.sort();
+    // The ranger's cell starts 150cm away, so a sphere has to reach past that to touch it.
+    assert_eq!(touched(140), vec![cid_rogue()]);
+    assert_eq!(touched(160), rogue_and_ranger);
+
+    // Targeting measures to the middle of each creature's cell instead.
+    let in_volume = |radius| {
+      let volume = Volume::Sphere(u32cm(radius));
+      let mut cids = scene.creatures_in_volume(game.tile_system, Point3::new(0, 0, 0), volume);
+      cids.sort();
+      cids
+    };
+    assert_eq!(in_volume(160), vec![cid_rogue()]);
+    assert_eq!(in_volume(200), rogue_and_ranger);
+  }
+
+  #[test]
+  fn grid_bounds() {
+    let mut scene = t_scene();
+    assert!(scene.in_grid_bounds(Point3::new(100_000, 0, 0)));
+    // The corners can be given in any order.
+    scene.grid_bounds = Some((Point3::new(500, 0, 0), Point3::new(-500, -300, 0)));
+    assert!(scene.in_grid_bounds(Point3::new(500, -300, 0)));
+    assert!(scene.in_grid_bounds(Point3::new(-200, -100, 0)));
+    assert!(!scene.in_grid_bounds(Point3::new(600, 0, 0)));
+    assert!(!scene.in_grid_bounds(Point3::new(0, 100, 0)));
+    assert!(!scene.in_grid_bounds(Point3::new(0, 0, 100)));
+  }
 }
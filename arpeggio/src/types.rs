@@ -4,6 +4,8 @@
 // that for a while
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::large_enum_variant))]
 
+use std::collections::BTreeMap;
+
 use rand::Rng;
 use serde::{ser::Error as SerError, Serialize, Serializer};
 
@@ -21,6 +23,9 @@ pub trait DiceExt {
   /// Roll the dice, returning a vector containing all of the individual die rolls, and then the
   /// final result.
   fn roll(&self) -> (Vec<i16>, i32);
+
+  /// The smallest, largest and average results of rolling the dice.
+  fn range(&self) -> RollRange;
 }
 
 impl DiceExt for Dice {
@@ -69,6 +74,104 @@ impl DiceExt for Dice {
       }
     }
   }
+
+  fn range(&self) -> RollRange {
+    match *self {
+      Dice::Expr { num, size } => {
+        let (num, size) = (i32::from(num), i32::from(size));
+        RollRange { min: num, max: num * size, average: f64::from(num * (size + 1)) / 2.0 }
+      }
+      Dice::Flat { value } => {
+        let value = i32::from(value);
+        RollRange { min: value, max: value, average: f64::from(value) }
+      }
+      Dice::Plus(ref l, ref r) => {
+        let (l, r) = (l.range(), r.range());
+        RollRange { min: l.min + r.min, max: l.max + r.max, average: l.average + r.average }
+      }
+      Dice::BestOf(_, ref dice) => {
+        let average = distribution(self).iter().map(|(value, p)| f64::from(*value) * p).sum();
+        RollRange { average, ..dice.range() }
+      }
+    }
+  }
+}
+
+/// The probability of each possible result of rolling some dice.
+fn distribution(dice: &Dice) -> BTreeMap<i32, f64> {
+  fn convolve(left: &BTreeMap<i32, f64>, right: &BTreeMap<i32, f64>) -> BTreeMap<i32, f64> {
+    let mut result = BTreeMap::new();
+    for (lv, lp) in left {
+      for (rv, rp) in right {
+        *result.entry(lv + rv).or_insert(0.0) += lp * rp;
+      }
+    }
+    result
+  }
+  match *dice {
+    Dice::Expr { num, size } => {
+      let die: BTreeMap<i32, f64> =
+        (1..=i32::from(size)).map(|value| (value, 1.0 / f64::from(size))).collect();
+      (0..num).fold(BTreeMap::from([(0, 1.0)]), |acc, _| convolve(&acc, &die))
+    }
+    Dice::Flat { value } => BTreeMap::from([(i32::from(value), 1.0)]),
+    Dice::Plus(ref l, ref r) => convolve(&distribution(l), &distribution(r)),
+    Dice::BestOf(count, ref dice) => {
+      // The best of `count` rolls is at most `value` exactly when every roll is.
+      let mut result = BTreeMap::new();
+      let mut at_most = 0.0f64;
+      for (value, p) in distribution(dice) {
+        let below = at_most;
+        at_most += p;
+        result.insert(value, at_most.powi(i32::from(count)) - below.powi(i32::from(count)));
+      }
+      result
+    }
+  }
+}
+
+/// Predict what an effect would do to `target`. Like applying the effect, this leaves out any
+/// conditions the target is immune to.
+pub fn preview_effect(effect: &CreatureEffect, target: &DynamicCreature) -> EffectPreview {
+  fn collect(
+    effect: &CreatureEffect, target: &DynamicCreature, damage: &mut Option<Dice>,
+    healing: &mut Option<Dice>, energy: &mut Energy, conditions: &mut Vec<AppliedCondition>,
+  ) {
+    let add = |total: &mut Option<Dice>, dice: &Dice| {
+      *total = Some(match total.take() {
+        Some(total) => total.plus(dice.clone()),
+        None => dice.clone(),
+      });
+    };
+    match effect {
+      CreatureEffect::Damage(dice) => add(damage, dice),
+      CreatureEffect::Heal(dice) => add(healing, dice),
+      CreatureEffect::GenerateEnergy(amount) => *energy = Energy(energy.0.saturating_add(amount.0)),
+      CreatureEffect::GrantMovement(distance) => {
+        conditions.push(Condition::ExtraMovement(*distance).apply(Duration::Rounds(0)))
+      }
+      CreatureEffect::ApplyCondition(_, condition) if target.is_immune_to(condition) => {}
+      CreatureEffect::ApplyCondition(duration, condition) => {
+        conditions.push(condition.apply(*duration))
+      }
+      CreatureEffect::MultiEffect(effects) => {
+        for effect in effects {
+          collect(effect, target, damage, healing, energy, conditions);
+        }
+      }
+      // Which condition gets moved depends on the creatures' conditions when the ability is used.
+      CreatureEffect::TransferCondition { .. } => {}
+    }
+  }
+  let (mut damage, mut healing, mut energy, mut conditions) = (None, None, Energy(0), vec![]);
+  collect(effect, target, &mut damage, &mut healing, &mut energy, &mut conditions);
+  EffectPreview {
+    damage: damage.map(|dice| dice.range()),
+    healing: healing.map(|dice| dice.range()),
+    energy,
+    conditions,
+    cover: Cover::None,
+  }
 }
 
 pub type CollisionWorld = ::ncollide3d::world::CollisionWorld<f64, CollisionData>;
@@ -113,6 +216,13 @@ impl<'c, 'g> DynamicCreature<'c, 'g> {
       size: self.creature.size,
       inventory: self.creature.inventory.clone(),
       conditions: self.creature.conditions.clone(),
+      immunities: self.creature.immunities.clone(),
+      untargetable_by: self.creature.untargetable_by.clone(),
+      movement_mode: self.creature.movement_mode,
+      readied_reaction: self.creature.readied_reaction,
+      ability_dice: self.creature.ability_dice.clone(),
+      ability_uses: self.creature.ability_uses.clone(),
+      reach: self.creature.reach,
       // overriden fields:
       speed: self.speed(),
       abilities: self.ability_statuses(),
@@ -149,6 +259,8 @@ impl<'a> RPIGame<'a> {
       items: game.items.clone(),
       players: game.players.clone(),
       active_scene: game.active_scene.clone(),
+      settings: game.settings.clone(),
+      reaction_window: game.reaction_window.clone(),
     };
     Ok(sgame)
   }
@@ -178,28 +290,31 @@ pub mod test {
   pub fn cid_rogue() -> CreatureID { CreatureID(uuid_2()) }
 
   pub fn t_creature(name: &str, class: ClassID, init: i8) -> Creature {
-    Creature::create(&CreatureCreation {
-      name: name.to_string(),
-      note: "".to_string(),
-      bio: "".to_string(),
-      class,
-      portrait_url: "".to_string(),
-      icon_url: "".to_string(),
-      initiative: Dice::flat(init),
-      size: AABB { x: u32cm(100), y: u32cm(100), z: u32cm(100) },
-    })
+    Creature::builder(name).class(class).initiative(Dice::flat(init)).build()
   }
 
   pub fn t_rogue(name: &str) -> Creature {
-    Creature { id: cid_rogue(), ..t_creature(name, classid_rogue(), 20) }
+    Creature::builder(name)
+      .id(cid_rogue())
+      .class(classid_rogue())
+      .initiative(Dice::flat(20))
+      .build()
   }
 
   pub fn t_ranger(name: &str) -> Creature {
-    Creature { id: cid_ranger(), ..t_creature(name, classid_ranger(), 10) }
+    Creature::builder(name)
+      .id(cid_ranger())
+      .class(classid_ranger())
+      .initiative(Dice::flat(10))
+      .build()
   }
 
   pub fn t_cleric(name: &str) -> Creature {
-    Creature { id: cid_rogue(), ..t_creature(name, classid_cleric(), 0) }
+    Creature::builder(name)
+      .id(cid_cleric())
+      .class(classid_cleric())
+      .initiative(Dice::flat(0))
+      .build()
   }
 
   pub fn t_scene_id() -> SceneID { SceneID(uuid_3()) }
@@ -212,6 +327,7 @@ pub mod test {
       background_image_offset: None,
       background_image_scale: (1., 1.),
       terrain: huge_box(),
+      terrain_costs: HashMap::new(),
       highlights: HashMap::new(),
       annotations: HashMap::new(),
 
@@ -227,6 +343,9 @@ pub mod test {
       inventory: HashMap::new(),
       volume_conditions: HashMap::new(),
       focused_creatures: vec![],
+      preserved_initiative: vec![],
+      checkpoints: HashMap::new(),
+      grid_bounds: None,
     }
   }
 
@@ -251,10 +370,13 @@ pub mod test {
       name: "Punch".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::Melee,
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -264,10 +386,13 @@ pub mod test {
       name: "Shoot".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::Range(u32cm(500)),
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -277,10 +402,13 @@ pub mod test {
       name: "Heal".to_string(),
       cost: Energy(0),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::Range(u32cm(500)),
         effect: CreatureEffect::Heal(Dice::flat(3)),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -290,6 +418,7 @@ pub mod test {
       name: "Fireball".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::AllCreaturesInVolumeInRange {
           volume: Volume::Sphere(u32cm(1000)),
@@ -297,6 +426,8 @@ pub mod test {
         },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -306,10 +437,13 @@ pub mod test {
       name: "Piercing Shot".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::Creature {
         target: CreatureTarget::LineFromActor { distance: u32cm(1000) },
         effect: CreatureEffect::Damage(Dice::flat(3)),
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -319,6 +453,7 @@ pub mod test {
       name: "Thorn Patch".to_string(),
       cost: Energy(8),
       usable_ooc: true,
+      action_kind: ActionKind::Action,
       action: Action::SceneVolume {
         target: SceneTarget::RangedVolume {
           volume: Volume::Sphere(u32cm(200)),
@@ -329,6 +464,8 @@ pub mod test {
           condition: Condition::RecurringEffect(Box::new(CreatureEffect::Damage(Dice::flat(3)))),
         },
       },
+      attack_roll: None,
+      uses_per_rest: None,
     }
   }
 
@@ -372,10 +509,159 @@ pub mod test {
     assert_eq!(d.roll(), (vec![1, -5], -4));
   }
 
+  #[test]
+  fn dice_display() {
+    assert_eq!(Dice::expr(2, 6).to_string(), "2d6");
+    assert_eq!(Dice::expr(1, 20).plus(Dice::flat(3)).to_string(), "1d20+3");
+    assert_eq!(Dice::expr(1, 20).plus(Dice::flat(-2)).to_string(), "1d20-2");
+    assert_eq!(Dice::BestOf(2, Box::new(Dice::expr(1, 20))).to_string(), "best of 2x(1d20)");
+  }
+
+  #[test]
+  fn dice_range() {
+    let range = |min, max, average| RollRange { min, max, average };
+    assert_eq!(Dice::flat(3).range(), range(3, 3, 3.0));
+    assert_eq!(Dice::expr(2, 6).range(), range(2, 12, 7.0));
+    assert_eq!(Dice::expr(1, 20).plus(Dice::flat(-2)).range(), range(-1, 18, 8.5));
+    let best = Dice::BestOf(2, Box::new(Dice::expr(1, 6))).range();
+    assert_eq!((best.min, best.max), (1, 6));
+    assert!((best.average - 161.0 / 36.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn preview_multi_effect() {
+    let effect = CreatureEffect::MultiEffect(vec![
+      CreatureEffect::Damage(Dice::expr(2, 6)),
+      CreatureEffect::Damage(Dice::flat(1)),
+      CreatureEffect::ApplyCondition(Duration::Rounds(3), Condition::DoubleMaxMovement),
+      CreatureEffect::GenerateEnergy(Energy(1)),
+    ]);
+    let game = crate::game::test::t_game();
+    let rogue = game.get_creature(cid_rogue()).unwrap();
+    assert_eq!(
+      preview_effect(&effect, &rogue),
+      EffectPreview {
+        damage: Some(RollRange { min: 3, max: 13, average: 8.0 }),
+        healing: None,
+        energy: Energy(1),
+        conditions: vec![Condition::DoubleMaxMovement.apply(Duration::Rounds(3))],
+        cover: Cover::None,
+      }
+    );
+  }
+
+  #[test]
+  fn describe_multi_effect_ability() {
+    let ability = Ability {
+      name: "Flame Strike".to_string(),
+      action: Action::Creature {
+        target: CreatureTarget::Melee,
+        effect: CreatureEffect::MultiEffect(vec![
+          CreatureEffect::Damage(Dice::expr(2, 6)),
+          CreatureEffect::ApplyCondition(
+            Duration::Rounds(3),
+            Condition::RecurringEffect(Box::new(CreatureEffect::Damage(Dice::flat(1)))),
+          ),
+          CreatureEffect::GenerateEnergy(Energy(1)),
+        ]),
+      },
+      ..t_punch()
+    };
+    assert_eq!(
+      ability.describe(),
+      "Flame Strike (0 energy): deals 2d6 damage, applies a recurring effect that deals 1 damage \
+       for 3 rounds and generates 1 energy"
+    );
+  }
+
+  #[test]
+  fn describe_scene_ability() {
+    assert_eq!(
+      t_thorn_patch().describe(),
+      "Thorn Patch (8 energy): creates an area that applies a recurring effect that deals 3 damage"
+    );
+  }
+
+  #[test]
+  fn list_abilities() {
+    let game = Game { abilities: t_abilities(), ..Default::default() };
+    let description = |name: &str, range: Option<u32>, targeting: &str, effect: &str| {
+      AbilityDescription {
+        name: name.to_string(),
+        range: range.map(u32cm),
+        targeting: targeting.to_string(),
+        effect: effect.to_string(),
+      }
+    };
+    assert_eq!(
+      game.list_abilities(),
+      vec![
+        (
+          abid_fireball(),
+          description(
+            "Fireball",
+            Some(2000),
+            "every creature in an area within 20m",
+            "deals 3 damage",
+          ),
+        ),
+        (abid_heal(), description("Heal", Some(500), "a creature within 5m", "heals 3 HP")),
+        (
+          abid_piercing_shot(),
+          description(
+            "Piercing Shot",
+            Some(1000),
+            "every creature in a 10m line",
+            "deals 3 damage",
+          ),
+        ),
+        (abid_punch(), description("Punch", None, "a creature within reach", "deals 3 damage")),
+        (abid_shoot(), description("Shoot", Some(500), "a creature within 5m", "deals 3 damage")),
+        (
+          abid_thorn_patch(),
+          description(
+            "Thorn Patch",
+            Some(1000),
+            "an area within 10m",
+            "creates an area that applies a recurring effect that deals 3 damage",
+          ),
+        ),
+      ]
+    );
+  }
+
   #[test]
   fn serialize_hashmap_point3() {
     let p = Point3::new(0, 0, 0);
     let hm = hashmap! {p => 5};
     assert_eq!(serde_json::to_string(&hm).unwrap(), "{\"0/0/0\":5}");
   }
+
+  #[test]
+  fn terrain_round_trips_compressed() {
+    let mut terrain = Terrain::new();
+    for x in 0..100 {
+      for y in 0..100 {
+        terrain.insert(Point3::new(x * 100, y * 100, 0));
+      }
+    }
+    let mut scene = t_scene();
+    scene.terrain = terrain.clone();
+    let compressed = serde_json::to_string(&scene).unwrap();
+    let naive = serde_json::to_string(&terrain).unwrap();
+    assert!(compressed.len() * 20 < naive.len());
+
+    let loaded: Scene = serde_json::from_str(&compressed).unwrap();
+    assert_eq!(loaded.terrain, terrain);
+  }
+
+  #[test]
+  fn terrain_accepts_plain_list() {
+    let json = r#"{"t": "EditSceneTerrain", "scene_id": "00000000-0000-0000-0000-000000000003",
+      "terrain": ["0/0/0", "0/100/0", "500/0/0"]}"#;
+    let log: GameLog = serde_json::from_str(json).unwrap();
+    let terrain =
+      [Point3::new(0, 0, 0), Point3::new(0, 100, 0), Point3::new(500, 0, 0)].into_iter().collect();
+    assert_eq!(log, GameLog::EditSceneTerrain { scene_id: t_scene_id(), terrain });
+  }
 }
@@ -20,56 +20,88 @@ pub fn main() -> Result<(), anyhow::Error> {
     <MT::UserID as TS>::decl(),
     <MT::ImageType as TS>::decl(),
     <T::AABB as TS>::decl(),
+    <T::AABBAnchor as TS>::decl(),
     <T::Ability as TS>::decl(),
     <T::AbilityCreation as TS>::decl(),
+    <T::AbilityDescription as TS>::decl(),
     <T::AbilityID as TS>::decl(),
     <T::AbilityStatus as TS>::decl(),
     <T::Action as TS>::decl(),
+    <T::ActionKind as TS>::decl(),
     <T::AppliedCondition as TS>::decl(),
+    <T::AttackRoll as TS>::decl(),
     <T::AttributeCheck as TS>::decl(),
     <T::AttrID as TS>::decl(),
+    <T::CellInfo as TS>::decl(),
     <T::ChangedGame as TS>::decl(),
     <T::Class as TS>::decl(),
     <T::ClassCreation as TS>::decl(),
     <T::ClassID as TS>::decl(),
     <T::Combat as TS>::decl(),
     <T::CombatLog as TS>::decl(),
+    <T::CombatMovement as TS>::decl(),
+    <T::CombatSummary as TS>::decl(),
+    <T::Combatant as TS>::decl(),
+    <T::CompactPoints as TS>::decl(),
     <T::Condition as TS>::decl(),
     <T::ConditionID as TS>::decl(),
+    <T::ConditionSelector as TS>::decl(),
+    <T::Cover as TS>::decl(),
     <T::Creature as TS>::decl(),
+    <T::CreatureCheckpoint as TS>::decl(),
     <T::CreatureCreation as TS>::decl(),
     <T::CreatureEffect as TS>::decl(),
     <T::CreatureID as TS>::decl(),
     <T::CreatureLog as TS>::decl(),
+    <T::CreatureSort as TS>::decl(),
     <T::CreatureTarget as TS>::decl(),
     <T::DecidedTarget as TS>::decl(),
     <T::Dice as TS>::decl(),
+    <T::Direction as TS>::decl(),
     <T::Duration as TS>::decl(),
+    <T::EffectPreview as TS>::decl(),
     <T::Energy as TS>::decl(),
     <T::Folder as TS>::decl(),
     <T::FolderItemID as TS>::decl(),
     <T::Game as TS>::decl(),
     <T::GameLog as TS>::decl(),
+    <T::GameSettings as TS>::decl(),
     <T::GMCommand as TS>::decl(),
     <T::HP as TS>::decl(),
+    <T::IntegrityIssue as TS>::decl(),
     <T::InventoryOwner as TS>::decl(),
     <T::Item as TS>::decl(),
     <T::ItemID as TS>::decl(),
     <T::ModuleSource as TS>::decl(),
+    <T::MovementMode as TS>::decl(),
     <T::Note as TS>::decl(),
+    <T::PendingCommand as TS>::decl(),
+    <T::PendingCommandID as TS>::decl(),
     <T::Player as TS>::decl(),
     <T::PlayerCommand as TS>::decl(),
     <T::PlayerID as TS>::decl(),
     <T::PotentialTargets as TS>::decl(),
+    <T::ReactionWindow as TS>::decl(),
+    <T::RestKind as TS>::decl(),
+    <T::RestPolicy as TS>::decl(),
+    <T::RestRecovery as TS>::decl(),
+    <T::RollRange as TS>::decl(),
+    <T::RoundSummary as TS>::decl(),
+    <T::RoundTurn as TS>::decl(),
     <T::Scene as TS>::decl(),
+    <T::SceneCheckpoint as TS>::decl(),
     <T::SceneCreation as TS>::decl(),
     <T::SceneEffect as TS>::decl(),
     <T::SceneID as TS>::decl(),
+    <T::SceneRenderState as TS>::decl(),
     <T::SceneTarget as TS>::decl(),
     <T::SerializedCreature as TS>::decl(),
     <T::SerializedGame as TS>::decl(),
     <T::SkillLevel as TS>::decl(),
+    <T::TargetCategory as TS>::decl(),
     <T::TileSystem as TS>::decl(),
+    <T::TurnSummary as TS>::decl(),
+    <T::VettingPolicy as TS>::decl(),
     <T::Visibility as TS>::decl(),
     <T::Volume as TS>::decl(),
     <T::VolumeCondition as TS>::decl(),
@@ -77,7 +109,7 @@ pub fn main() -> Result<(), anyhow::Error> {
   file.write_all(b"\
   import type { Map } from 'immutable';
   import type {
-     Point3, Highlights, NonEmpty, Annotations, SceneHotspots, RelatedScenes, SceneAttributeChecks, SceneCreatures, SceneInventory, SceneVolumeConditions, Terrain, SceneFocusedCreatures,
+     Point3, Highlights, NonEmpty, Annotations, SceneHotspots, RelatedScenes, SceneAttributeChecks, SceneCreatures, SceneInventory, SceneVolumeConditions, Terrain, TerrainCosts, SceneFocusedCreatures,
      GameAbilities, GameCreatures, GameCreaturesData, GameClasses, GameScenes, GameItems, GamePlayers,
      CreatureAttributes, CreatureConditions, CreatureInventory
 
@@ -4,7 +4,7 @@ use foldertree::FolderPath;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::types::*;
+use crate::{log::CreatureLog, types::*};
 
 /// Top-level commands that can be sent from a Player
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -27,6 +27,13 @@ pub enum PlayerCommand {
     note: Note,
   },
 
+  /// Change the name and color the player is shown with. Empty strings go back to the defaults:
+  /// the player ID and the default color.
+  SetDisplay {
+    display_name: String,
+    color: Color,
+  },
+
   // Out-of-combat actions:
   // /// Use an Ability out of combat.
   // ActCreature { // NYI
@@ -41,6 +48,12 @@ pub enum PlayerCommand {
     creature_id: CreatureID,
     destination: Point3,
   },
+  /// Move a creature one cell in a direction. The cell must be open and unoccupied, and in combat
+  /// the creature must have enough movement left this turn.
+  StepDirection {
+    creature_id: CreatureID,
+    direction: Direction,
+  },
 
   // In-combat actions:
   /// Make the current creature use an ability.
@@ -55,6 +68,12 @@ pub enum PlayerCommand {
   },
   /// End the current creature's turn.
   EndTurn,
+  /// Respond to the action in the game's `ReactionWindow` with a reaction ability the creature
+  /// uses on itself, or with `None` to let the action go ahead without reacting.
+  React {
+    creature_id: CreatureID,
+    ability_id: Option<AbilityID>,
+  },
   // GiveItem or something should be here
 }
 
@@ -202,7 +221,18 @@ pub enum GMCommand {
   EditSceneTerrain {
     scene_id: SceneID,
     #[ts(type = "Terrain")]
-    terrain: Vec<Point3>,
+    terrain: Terrain,
+  },
+  /// Replace the movement cost multipliers of a scene's cells. See `TerrainCosts`.
+  EditSceneTerrainCosts {
+    scene_id: SceneID,
+    #[ts(type = "TerrainCosts")]
+    costs: TerrainCosts,
+  },
+  /// Set or clear the nominal bounds of a scene's grid. See `Scene::grid_bounds`.
+  SetSceneGridBounds {
+    scene_id: SceneID,
+    bounds: Option<(Point3, Point3)>,
   },
   EditSceneHighlights {
     scene_id: SceneID,
@@ -224,6 +254,18 @@ pub enum GMCommand {
     #[ts(type = "SceneHotspots")]
     scene_hotspots: HashMap<Point3, SceneID>,
   },
+  /// Save the positions, health, energy and conditions of the creatures in a scene under a name,
+  /// replacing any existing checkpoint with that name.
+  SnapshotScene {
+    scene_id: SceneID,
+    name: String,
+  },
+  /// Put the creatures of a scene back the way they were when the named checkpoint was taken.
+  /// Creatures that have since left the scene are not brought back into it.
+  RestoreScene {
+    scene_id: SceneID,
+    name: String,
+  },
 
   // ** Combat management **
   /// Start a combat with the specified creatures.
@@ -231,8 +273,12 @@ pub enum GMCommand {
     scene_id: SceneID,
     combatants: Vec<CreatureID>,
   },
-  /// Stop the current combat.
-  StopCombat,
+  /// Stop the current combat. If `preserve_initiative` is set, the initiative order is saved on
+  /// the scene so that the next `StartCombat` in that scene resumes it instead of re-rolling.
+  StopCombat {
+    #[serde(default)]
+    preserve_initiative: bool,
+  },
   /// Add a creature to combat.
   AddCreatureToCombat {
     creature_id: CreatureID,
@@ -275,6 +321,25 @@ pub enum GMCommand {
   },
   /// End the current creature's turn.
   EndTurn,
+  /// Ready a reaction ability which the creature will use on itself the next time it's attacked,
+  /// before the attack's damage is dealt. `None` clears the readied reaction.
+  ReadyReaction {
+    creature_id: CreatureID,
+    ability_id: Option<AbilityID>,
+  },
+  /// Respond to the action in the game's `ReactionWindow` on behalf of a creature. See
+  /// `PlayerCommand::React`.
+  React {
+    creature_id: CreatureID,
+    ability_id: Option<AbilityID>,
+  },
+  /// Have some creatures rest. They get back health and energy according to the game's
+  /// `RestPolicy`, and the uses of their abilities that recharge on this kind of rest. See
+  /// `Ability::uses_per_rest`.
+  Rest {
+    creatures: Vec<CreatureID>,
+    kind: RestKind,
+  },
 
   // ** Classes & Abilities **
   CreateClass {
@@ -302,6 +367,19 @@ pub enum GMCommand {
   EditCreatureDetails {
     creature: Creature,
   },
+  /// Make a creature roll different damage dice for one of its abilities than the ability
+  /// normally does. `None` goes back to the ability's own dice.
+  SetCreatureAbilityDice {
+    creature_id: CreatureID,
+    ability_id: AbilityID,
+    dice: Option<Dice>,
+  },
+  /// Change how far a creature can reach in melee.
+  SetCreatureReach {
+    creature_id: CreatureID,
+    #[ts(type = "number")]
+    reach: u32units::Length,
+  },
   /// Assign a creature's position within a scene.
   SetCreaturePos {
     scene_id: SceneID,
@@ -316,9 +394,21 @@ pub enum GMCommand {
     creature_id: CreatureID,
     destination: Point3,
   },
+  /// Fix everything reported by `GameExt::integrity_check`: missing creatures are dropped from
+  /// scenes, combat and players, deleted objects are unlinked from folders, and unfiled creatures
+  /// and scenes are put in the root folder.
+  RepairIntegrity,
+  /// Apply a condition to several creatures at once. Creatures that are immune to the condition,
+  /// or that already have it, are skipped.
+  ApplyConditionToMany {
+    creatures: Vec<CreatureID>,
+    condition: Condition,
+    duration: Duration,
+  },
 
   // ** Player Manipulation **
-  /// Register a player as available for controlling a creature.
+  /// Register a player as available for controlling a creature. Registering a player that already
+  /// exists does nothing.
   RegisterPlayer {
     id: PlayerID,
   },
@@ -343,14 +433,136 @@ pub enum GMCommand {
     player_id: PlayerID,
     scene_id: Option<SceneID>,
   },
+  /// Change the name and color a player is shown with. See `PlayerCommand::SetDisplay`.
+  SetPlayerDisplay {
+    player_id: PlayerID,
+    display_name: String,
+    color: Color,
+  },
 
   SetActiveScene {
     id: Option<SceneID>,
   },
 
-  /// Roll back to a specific snapshot + log index
+  EditGameSettings {
+    settings: GameSettings,
+  },
+  /// Let a player command that was held by the `VettingPolicy` take effect.
+  ApprovePending {
+    id: PendingCommandID,
+  },
+  /// Discard a player command that was held by the `VettingPolicy`.
+  DenyPending {
+    id: PendingCommandID,
+  },
+
+  /// Revert a creature to its state before the most recent log that changed it, leaving the rest
+  /// of the game alone. This is rejected if later changes depended on that log.
+  UndoCreature {
+    creature_id: CreatureID,
+  },
+
+  /// Roll back to a specific snapshot + log index, where the log index is the number of that
+  /// snapshot's logs to keep. A log index of 0 rolls back to the snapshot itself.
   Rollback {
     snapshot_index: usize,
     log_index: usize,
   },
+  /// Roll back to the most recent point in history where the game had this hex-encoded content
+  /// hash (see `GameMetadata::content_hash`). Unlike `Rollback`'s indices, the hash keeps naming
+  /// the same state no matter how history is stored later.
+  RollbackToHash {
+    content_hash: String,
+  },
+  /// Apply a raw `CreatureLog` to a creature, bypassing all the rules that would normally produce
+  /// it. This is for debugging and admin tools, and servers only accept it when they've been
+  /// configured to allow admin commands.
+  ApplyCreatureLog {
+    creature_id: CreatureID,
+    log: CreatureLog,
+  },
+}
+
+impl GMCommand {
+  /// The scene that this command works on, if any. The scene has to exist for the command to be
+  /// performed.
+  pub fn scene_id(&self) -> Option<SceneID> {
+    use GMCommand::*;
+    match *self {
+      SetActiveScene { id: scene_id } | SetPlayerScene { scene_id, .. } => scene_id,
+      EditSceneDetails { scene_id, .. }
+      | SetSceneCreatureVisibility { scene_id, .. }
+      | AddCreatureToScene { scene_id, .. }
+      | RemoveCreatureFromScene { scene_id, .. }
+      | AddSceneChallenge { scene_id, .. }
+      | RemoveSceneChallenge { scene_id, .. }
+      | SetFocusedSceneCreatures { scene_id, .. }
+      | RemoveSceneVolumeCondition { scene_id, .. }
+      | EditSceneTerrain { scene_id, .. }
+      | EditSceneTerrainCosts { scene_id, .. }
+      | SetSceneGridBounds { scene_id, .. }
+      | EditSceneHighlights { scene_id, .. }
+      | EditSceneAnnotations { scene_id, .. }
+      | EditSceneRelatedScenes { scene_id, .. }
+      | EditSceneSceneHotspots { scene_id, .. }
+      | SnapshotScene { scene_id, .. }
+      | RestoreScene { scene_id, .. }
+      | StartCombat { scene_id, .. }
+      | ActCreature { scene_id, .. }
+      | SetCreaturePos { scene_id, .. }
+      | PathCreature { scene_id, .. } => Some(scene_id),
+      LoadModule { .. }
+      | ChatFromGM { .. }
+      | AttributeCheck { .. }
+      | CreateFolder { .. }
+      | RenameFolder { .. }
+      | MoveFolderItem { .. }
+      | CopyFolderItem { .. }
+      | DeleteFolderItem { .. }
+      | RenameFolderItem { .. }
+      | CreateItem { .. }
+      | EditItem { .. }
+      | CreateNote { .. }
+      | EditNote { .. }
+      | TransferItem { .. }
+      | RemoveItem { .. }
+      | SetItemCount { .. }
+      | CreateScene { .. }
+      | StopCombat { .. }
+      | AddCreatureToCombat { .. }
+      | RemoveCreatureFromCombat { .. }
+      | ChangeCreatureInitiative { .. }
+      | RerollCombatInitiative
+      | ForceNextTurn
+      | ForcePrevTurn
+      | CombatAct { .. }
+      | PathCurrentCombatCreature { .. }
+      | EndTurn
+      | ReadyReaction { .. }
+      | React { .. }
+      | Rest { .. }
+      | CreateClass { .. }
+      | EditClass { .. }
+      | CreateAbility { .. }
+      | EditAbility { .. }
+      | CreateCreature { .. }
+      | EditCreatureDetails { .. }
+      | SetCreatureAbilityDice { .. }
+      | SetCreatureReach { .. }
+      | RepairIntegrity
+      | ApplyConditionToMany { .. }
+      | RegisterPlayer { .. }
+      | GiveCreaturesToPlayer { .. }
+      | UnregisterPlayer { .. }
+      | RemoveCreaturesFromPlayer { .. }
+      | SetPlayerDisplay { .. }
+      | EditGameSettings { .. }
+      | ApprovePending { .. }
+      | DenyPending { .. }
+      | UndoCreature { .. }
+      | Rollback { .. }
+      | RollbackToHash { .. }
+      | ApplyCreatureLog { .. } => None,
+    }
+  }
 }
@@ -17,6 +17,11 @@ pub enum CreatureLog {
   ApplyCondition { id: ConditionID, duration: Duration, condition: Condition },
   DecrementConditionRemaining { id: ConditionID },
   RemoveCondition { id: ConditionID },
+  ReadyReaction { ability_id: Option<AbilityID> },
+  /// Count a use of an ability that can only be used a limited number of times per rest.
+  UseAbility { ability_id: AbilityID },
+  /// Give back all the uses of some abilities, after a rest.
+  RechargeAbilities { ability_ids: Vec<AbilityID> },
 }
 
 // TODO: get rid of CombatLog, it's dumb... unless we ever support multiple Combats?
@@ -85,6 +90,40 @@ pub enum GameLog {
     player_id: PlayerID,
     scene_id: Option<SceneID>,
   },
+  SetPlayerDisplay {
+    player_id: PlayerID,
+    display_name: String,
+    color: Color,
+  },
+
+  EditGameSettings {
+    settings: GameSettings,
+  },
+  AddPendingCommand {
+    pending: PendingCommand,
+  },
+  /// Remove a pending command from the queue. If it was approved, the logs from actually running
+  /// the command follow this one.
+  ResolvePendingCommand {
+    id: PendingCommandID,
+    approved: bool,
+  },
+  /// A pending command was automatically denied because the GM didn't resolve it in time.
+  PendingCommandExpired {
+    id: PendingCommandID,
+  },
+  /// Hold an action until the creatures it attacks have had a chance to react to it.
+  OpenReactionWindow {
+    window: ReactionWindow,
+  },
+  /// A creature in the reaction window has reacted, or chosen not to. Any reaction it used is
+  /// logged before this.
+  Reacted {
+    creature_id: CreatureID,
+  },
+  /// Close the reaction window. The logs from carrying out the held action follow this one, unless
+  /// it couldn't be done anymore.
+  CloseReactionWindow,
 
   ChatFromGM {
     message: String,
@@ -126,6 +165,16 @@ pub enum GameLog {
     path: FolderPath,
     item_id: FolderItemID,
   },
+  /// Put an existing object into a folder.
+  LinkFolderItem {
+    path: FolderPath,
+    item_id: FolderItemID,
+  },
+  /// Take an object out of a folder without deleting it.
+  UnlinkFolderItem {
+    path: FolderPath,
+    item_id: FolderItemID,
+  },
   RenameFolderItem {
     path: FolderPath,
     item_id: FolderItemID,
@@ -190,6 +239,16 @@ pub enum GameLog {
     scene_id: SceneID,
     creature_id: CreatureID,
   },
+  /// Purely informational: a creature became a member of a scene.
+  CreatureEnteredScene {
+    scene_id: SceneID,
+    creature_id: CreatureID,
+  },
+  /// Purely informational: a creature stopped being a member of a scene.
+  CreatureLeftScene {
+    scene_id: SceneID,
+    creature_id: CreatureID,
+  },
   AddSceneChallenge {
     scene_id: SceneID,
     description: String,
@@ -210,8 +269,18 @@ pub enum GameLog {
 
   EditSceneTerrain {
     scene_id: SceneID,
+    #[serde(with = "terrain_serde")]
     #[ts(type = "Terrain")]
-    terrain: Vec<Point3>,
+    terrain: Terrain,
+  },
+  EditSceneTerrainCosts {
+    scene_id: SceneID,
+    #[ts(type = "TerrainCosts")]
+    costs: TerrainCosts,
+  },
+  SetSceneGridBounds {
+    scene_id: SceneID,
+    bounds: Option<(Point3, Point3)>,
   },
   EditSceneHighlights {
     scene_id: SceneID,
@@ -233,6 +302,15 @@ pub enum GameLog {
     #[ts(type = "SceneHotspots")]
     scene_hotspots: HashMap<Point3, SceneID>,
   },
+  SnapshotScene {
+    scene_id: SceneID,
+    name: String,
+    checkpoint: SceneCheckpoint,
+  },
+  RestoreScene {
+    scene_id: SceneID,
+    name: String,
+  },
 
   CombatLog {
     log: CombatLog,
@@ -252,6 +330,28 @@ pub enum GameLog {
     creature_id: CreatureID,
     path: Vec<Point3>,
   },
+  /// Purely informational: a creature used an ability. The ability's effects follow in separate
+  /// logs.
+  CreatureUsedAbility {
+    scene_id: SceneID,
+    creature_id: CreatureID,
+    ability_id: AbilityID,
+    target: DecidedTarget,
+  },
+  /// A creature rolled to hit another with an ability. `defense` includes any bonus from cover.
+  AttackRolled {
+    scene_id: SceneID,
+    creature_id: CreatureID,
+    target_id: CreatureID,
+    roll: i32,
+    defense: u8,
+    hit: bool,
+  },
+  /// Purely informational: it's now this creature's turn in combat, so whoever controls it should
+  /// be told.
+  TurnBegan {
+    creature_id: CreatureID,
+  },
 
   AddVolumeCondition {
     scene_id: SceneID,
@@ -266,7 +366,10 @@ pub enum GameLog {
     scene_id: SceneID,
     combatants: Vec<(CreatureID, i16)>,
   },
-  StopCombat,
+  StopCombat {
+    #[serde(default)]
+    preserve_initiative: bool,
+  },
 
   // ** Classes & Abilities **
   CreateClass {
@@ -311,6 +414,36 @@ pub enum GameLog {
   },
 }
 
+/// The version of the `CombatReplay` format. Bump this when old replays can no longer be applied.
+pub const COMBAT_REPLAY_VERSION: u32 = 1;
+
+/// A recording of a single combat: the game as it was right before the combat started, and every
+/// log from the `StartCombat` until the combat ended. Applying the logs to `start` reproduces the
+/// fight without needing anything else from the game's history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CombatReplay {
+  pub version: u32,
+  pub start: Game,
+  pub logs: Vec<GameLog>,
+}
+
+/// One round of a combat, for showing the combat as a timeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+pub struct RoundSummary {
+  /// The number of the round, starting from 1.
+  pub round: u32,
+  /// The turns taken during the round, in order.
+  pub turns: Vec<RoundTurn>,
+}
+
+/// A single turn within a `RoundSummary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+pub struct RoundTurn {
+  pub creature_id: CreatureID,
+  /// The logs applied during the turn, including the one that ended it.
+  pub logs: Vec<GameLog>,
+}
+
 pub fn combat_logs_into_game_logs(ls: Vec<CombatLog>) -> Vec<GameLog> {
   ls.into_iter().map(|log| GameLog::CombatLog { log }).collect()
 }
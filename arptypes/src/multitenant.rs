@@ -1,11 +1,15 @@
 use std::collections::VecDeque;
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use ts_rs::TS;
 use uuid::Uuid;
 use strum;
 
-use crate::{uuid_id, AbilityID, CreatureID, GMCommand, PlayerCommand, PlayerID, Point3, SceneID};
+use crate::{
+  uuid_id, AbilityID, CreatureID, CreatureSort, DecidedTarget, GMCommand, PlayerCommand, PlayerID,
+  Point3, SceneID, Volume,
+};
 
 uuid_id!(GameID);
 uuid_id!(InvitationID);
@@ -21,13 +25,19 @@ impl UserID {
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug, Default, TS)]
 pub struct GameMetadata {
   pub name: String,
+  /// The hex-encoded `content_hash` of the game's current state, when known.
+  #[serde(default)]
+  pub content_hash: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct GameAndMetadata {
   pub game: crate::SerializedGame,
   pub metadata: GameMetadata,
-  pub logs: VecDeque<(GameIndex, crate::GameLog)>
+  pub logs: VecDeque<(GameIndex, crate::GameLog)>,
+  /// The creatures controlled by the requesting player, sorted by ID. Always empty for the GM.
+  #[serde(default)]
+  pub controlled_creatures: Vec<CreatureID>,
 }
 
 
@@ -38,7 +48,12 @@ pub struct GameAndMetadata {
 /// to when a new command is performed) So a
 ///
 /// e.g., a GameIndex of 0/0 has one snapshot and no logs.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Default, TS)]
+///
+/// GameIndexes are ordered by snapshot and then by log, so a greater index is always a later state
+/// of the game, even if it's in a later snapshot with fewer logs. Don't reorder the fields!
+#[derive(
+  Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Debug, Default, TS,
+)]
 pub struct GameIndex {
   pub game_idx: usize,
   pub log_idx: usize,
@@ -61,6 +76,21 @@ pub enum Role {
   Player,
 }
 
+impl Role {
+  /// Parse a role that was stored as a string, e.g. in a database column. `row` describes where
+  /// the value came from, so that errors about corrupt or legacy values can say where to look.
+  pub fn parse_stored(value: &str, row: &str) -> Result<Role, InvalidRole> {
+    value.parse().map_err(|_| InvalidRole { value: value.to_string(), row: row.to_string() })
+  }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Invalid role {value:?} in {row}")]
+pub struct InvalidRole {
+  pub value: String,
+  pub row: String,
+}
+
 /// The result from listing a game. Includes a name (and maybe other data)
 #[derive(PartialEq, Eq, Clone, Serialize, Deserialize, Debug, TS)]
 pub struct GameList {
@@ -90,6 +120,13 @@ pub enum RPIGameRequest {
   GMCommand {
     command: GMCommand,
   },
+  /// List dangling references and orphaned objects in the game.
+  GMIntegrityCheck,
+  /// Get the game as it was at `index`, i.e. after its first `log_idx` logs were applied. Index 0
+  /// is the snapshot itself.
+  GMGameAtIndex {
+    index: GameIndex,
+  },
 
   // GM Commands for managing invitations happen here in the RPIGameRequest, but the check/accept
   // operations happen on regular HTTP endpoints, because you can't get a websocket to a game unless
@@ -115,15 +152,24 @@ pub enum RPIGameRequest {
   PlayerCommand {
     command: PlayerCommand,
   },
+  /// List the player commands waiting for GM approval. Players only see their own.
+  ListPendingCommands,
 
   // These things *technically* could be split up into GM and Player variants,
   // but it's not really a big deal if players can view movement & target
   // options for other creatures
+  // Movement options can be requested as `CompactPoints` instead of a list of points, which is
+  // much smaller for fast creatures.
   MovementOptions {
     scene_id: SceneID,
     creature_id: CreatureID,
+    #[serde(default)]
+    compact: bool,
+  },
+  CombatMovementOptions {
+    #[serde(default)]
+    compact: bool,
   },
-  CombatMovementOptions,
   TargetOptions {
     scene_id: SceneID,
     creature_id: CreatureID,
@@ -135,4 +181,76 @@ pub enum RPIGameRequest {
     ability_id: AbilityID,
     point: Point3,
   },
+  PreviewAbility {
+    scene_id: SceneID,
+    creature_id: CreatureID,
+    ability_id: AbilityID,
+    target: DecidedTarget,
+  },
+  CreaturesAndTerrainInVolume {
+    scene_id: SceneID,
+    point: Point3,
+    volume: Volume,
+  },
+  LastTurnSummary {
+    creature_id: CreatureID,
+  },
+  CombatSummary,
+  /// The most recent combat, split up into rounds and turns.
+  CombatTimeline,
+  SceneCreatures {
+    scene_id: SceneID,
+  },
+  /// Everything needed to draw a scene, in one payload. Players don't get the creatures and
+  /// highlights that only the GM can see.
+  SceneRenderState {
+    scene_id: SceneID,
+  },
+  /// Everything at a single cell of a scene.
+  CellInfo {
+    scene_id: SceneID,
+    point: Point3,
+  },
+  /// Every ability defined in the game, with descriptions.
+  ListAbilities,
+  /// A page of the game's creatures for the roster, in a stable order.
+  ListCreatures {
+    offset: usize,
+    limit: usize,
+    #[serde(default)]
+    sort: CreatureSort,
+  },
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn parse_stored_role() {
+    assert_eq!(Role::parse_stored("GM", "row 1"), Ok(Role::GM));
+    assert_eq!(Role::parse_stored("Player", "row 1"), Ok(Role::Player));
+    let error = Role::parse_stored("Admin", "row 1").unwrap_err();
+    assert_eq!(error, InvalidRole { value: "Admin".to_string(), row: "row 1".to_string() });
+    assert_eq!(error.to_string(), "Invalid role \"Admin\" in row 1");
+  }
+
+  #[test]
+  fn unknown_stored_roles_are_rejected() {
+    // Near misses of real roles mustn't quietly parse as one of them.
+    for value in ["", "gm", "player", " GM", "Player "] {
+      let result = Role::parse_stored(value, "row 2");
+      assert_eq!(result, Err(InvalidRole { value: value.to_string(), row: "row 2".to_string() }));
+    }
+  }
+
+  #[test]
+  fn game_index_ordering() {
+    let idx = |game_idx, log_idx| GameIndex { game_idx, log_idx };
+    assert!(idx(0, 1) > idx(0, 0));
+    assert!(idx(0, 5) < idx(0, 10));
+    assert!(idx(1, 0) > idx(0, 10));
+    assert!(idx(2, 3) > idx(1, 100));
+    assert_eq!(idx(1, 1).cmp(&idx(1, 1)), std::cmp::Ordering::Equal);
+  }
 }
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use derive_more::{Add, Display, Div, Mul, Sub};
 use foldertree::{FolderPath, FolderTree, FolderTreeError};
@@ -11,11 +11,16 @@ use ts_rs::TS;
 use uom::si::length::{centimeter, meter};
 use uuid::{Error as UuidParseError, Uuid};
 
-use crate::{GMCommand, GameLog};
+use crate::{GMCommand, GameLog, PlayerCommand};
 
 pub type Color = String;
 pub type Inventory = HashMap<ItemID, u64>;
-pub type Terrain = Vec<Point3>;
+/// The open cells of a scene. Kept as a set, since pathfinding checks whether cells are open many
+/// thousands of times per query.
+pub type Terrain = HashSet<Point3>;
+/// How many times the usual movement cost it takes to step into a cell, e.g. 2 for mud or rubble.
+/// Cells that aren't listed cost the usual amount.
+pub type TerrainCosts = HashMap<Point3, u8>;
 
 pub mod u32units {
   ISQ!(uom::si, u32, (centimeter, gram, second, ampere, kelvin, mole, candela));
@@ -35,6 +40,105 @@ pub fn i64meter<T: Into<i64>>(v: T) -> i64units::Length { i64units::Length::new:
 
 pub fn up_length(v: u32units::Length) -> i64units::Length { i64cm(v.get::<centimeter>()) }
 
+/// The reach of a creature that takes up a single cell. This is set to 1.5 cells so that it's
+/// greater than sqrt(2) -- meaning that creatures can attack diagonally!
+pub fn standard_reach() -> u32units::Length { u32cm(150) }
+
+/// The default reach of a creature of the given size: one cell past the edge of its footprint, so
+/// larger creatures reach further.
+pub fn default_reach(size: &AABB) -> u32units::Length {
+  let footprint = size.x.get::<centimeter>().max(size.y.get::<centimeter>());
+  standard_reach() + u32cm(footprint.saturating_sub(100))
+}
+
+/// Serialize a `HashMap` with its entries sorted by key, so that the same game state always
+/// serializes to byte-identical JSON.
+pub fn serialize_sorted_map<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+  K: Ord + Serialize,
+  V: Serialize,
+  S: Serializer,
+{
+  map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Serialize a `HashSet` in sorted order. See `serialize_sorted_map`.
+pub fn serialize_sorted_set<T, S>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+  T: Ord + Serialize,
+  S: Serializer,
+{
+  set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
+
+/// Compact serialization for `Terrain`, for use with `#[serde(with = "terrain_serde")]`.
+///
+/// Terrain is written as `{"runs": [["x/y/z", len], ...]}`, where each run covers `len` open
+/// cells starting at the given point and stepping one meter along the y axis. Points are sorted
+/// and deduplicated first, so a large open map collapses into one run per column instead of one
+/// string per cell. A plain list of points is still accepted when deserializing, so older
+/// snapshots and logs load unchanged.
+pub mod terrain_serde {
+  use std::collections::BTreeSet;
+
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+  use super::{i64cm, Point3, Terrain};
+
+  const STEP_CM: i64 = 100;
+
+  #[derive(Serialize)]
+  struct Runs {
+    runs: Vec<(Point3, u32)>,
+  }
+
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Repr {
+    Points(Vec<Point3>),
+    Runs { runs: Vec<(Point3, u32)> },
+  }
+
+  pub fn serialize<S: Serializer>(terrain: &Terrain, serializer: S) -> Result<S::Ok, S::Error> {
+    Runs { runs: encode(terrain) }.serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Terrain, D::Error> {
+    Ok(match Repr::deserialize(deserializer)? {
+      Repr::Points(points) => points.into_iter().collect(),
+      Repr::Runs { runs } => decode(&runs),
+    })
+  }
+
+  fn encode(terrain: &Terrain) -> Vec<(Point3, u32)> {
+    let mut runs: Vec<(Point3, u32)> = vec![];
+    for pt in terrain.iter().collect::<BTreeSet<_>>() {
+      match runs.last_mut() {
+        Some((start, len))
+          if start.x == pt.x
+            && start.z == pt.z
+            && start.y + i64cm(STEP_CM * i64::from(*len)) == pt.y =>
+        {
+          *len += 1
+        }
+        _ => runs.push((*pt, 1)),
+      }
+    }
+    runs
+  }
+
+  fn decode(runs: &[(Point3, u32)]) -> Terrain {
+    runs
+      .iter()
+      .flat_map(|&(start, len)| {
+        (0..len).map(move |i| {
+          Point3::from_quantities(start.x, start.y + i64cm(STEP_CM * i64::from(i)), start.z)
+        })
+      })
+      .collect()
+  }
+}
+
 #[derive(
   Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, TS, Display,
 )]
@@ -46,7 +150,7 @@ impl std::str::FromStr for PlayerID {
   }
 }
 
-#[derive(Clone, Hash, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+#[derive(Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, TS)]
 pub struct AttrID(pub String);
 
 #[macro_export]
@@ -82,6 +186,7 @@ uuid_id!(ItemID);
 uuid_id!(SceneID);
 uuid_id!(AbilityID);
 uuid_id!(ClassID);
+uuid_id!(PendingCommandID);
 
 #[derive(
   Add,
@@ -95,6 +200,7 @@ uuid_id!(ClassID);
   Ord,
   PartialOrd,
   Debug,
+  Default,
   Hash,
   Serialize,
   Deserialize,
@@ -154,8 +260,14 @@ impl SkillLevel {
   }
 
   pub fn difficulty(&self, difficulty_level: SkillLevel) -> u8 {
+    SkillLevel::modifier_difficulty(self.to_ord(), difficulty_level)
+  }
+
+  /// The minimum 1d100 roll needed to pass a check of the given difficulty with an attribute
+  /// modifier. Modifiers far above or below the difficulty always or never pass.
+  pub fn modifier_difficulty(modifier: i8, difficulty_level: SkillLevel) -> u8 {
     100
-      - match difficulty_level.to_ord() - self.to_ord() {
+      - match (i16::from(difficulty_level.to_ord()) - i16::from(modifier)).clamp(-4, 4) {
         -4 => 100,
         -3 => 99,
         -2 => 95,
@@ -177,6 +289,17 @@ pub enum Duration {
   Rounds(u8),
 }
 
+impl Duration {
+  /// A suffix for descriptions of conditions, e.g. " for 3 rounds".
+  fn describe(&self) -> String {
+    match self {
+      Duration::Interminate => "".to_string(),
+      Duration::Rounds(1) => " for 1 round".to_string(),
+      Duration::Rounds(n) => format!(" for {n} rounds"),
+    }
+  }
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
 pub struct SceneCreation {
   pub name: String,
@@ -192,6 +315,10 @@ pub enum TileSystem {
   Realistic,
   /// Square grid with diagonal movement costing 1
   DnD,
+  /// Square grid where diagonal movement alternates between costing 1 and 2, starting at 1
+  AlternatingDiagonal,
+  /// Square grid with no diagonal movement at all
+  Orthogonal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
@@ -261,6 +388,8 @@ pub enum GameError {
   SceneAlreadyExists(SceneID),
   #[error("The Scene '{0:?}' wasn't found")]
   SceneNotFound(SceneID),
+  #[error("The scene {0:?} has no checkpoint named {1:?}")]
+  SceneCheckpointNotFound(SceneID, String),
   #[error("The scene {0:?} is in use (by combat, probably).")]
   SceneInUse(SceneID),
   #[error("The identifier {0:?} is too long.")]
@@ -277,6 +406,8 @@ pub enum GameError {
   NoAbility(AbilityID),
   #[error("Creatures must be supplied when starting a combat.")]
   CombatMustHaveCreatures,
+  #[error("Creatures {1:?} are not in the scene {0:?}.")]
+  CreaturesNotInScene(SceneID, Vec<CreatureID>),
   #[error("RerollInitiative can only be invoked at the beginning of a round.")]
   MustRerollAtStartOfRound,
   #[error("The creature with ID {0:?} does not have the ability {1:?}")]
@@ -293,24 +424,58 @@ pub enum GameError {
   CreatureOutOfRange(CreatureID),
   #[error("Point {0:?} is out of range.")]
   PointOutOfRange(Point3),
+  #[error("There's no line of sight to point {0:?}.")]
+  PointNotVisible(Point3),
+  #[error("There's no open space at or next to {0:?} to teleport to.")]
+  NoRoomToTeleport(Point3),
+  #[error("Point {1:?} is outside the grid bounds of scene {0:?}.")]
+  OutsideGridBounds(SceneID, Point3),
+  #[error("{0:?} is not a legal target for this ability.")]
+  IllegalTarget(DecidedTarget),
   #[error("There's a bug in the program: {0}")]
   BuggyProgram(String),
   #[error("There is currently no combat.")]
   NotInCombat,
+  #[error("{0:?} is not a valid http(s) URL for a portrait.")]
+  InvalidPortraitURL(String),
+  #[error("{0:?} is not a valid http(s) URL for an icon.")]
+  InvalidIconURL(String),
+  #[error("Creature {0:?} can't be targeted because it has total cover.")]
+  TargetHasTotalCover(CreatureID),
+  #[error("There is no combat number {0} in these logs.")]
+  CombatNotFound(usize),
+  #[error("Combat replays of version {0} are not supported.")]
+  UnsupportedReplayVersion(u32),
   #[error("Creature {0:?} is already in combat.")]
   AlreadyInCombat(CreatureID),
   #[error("Creature {0:?} cannot be moved.")]
   CannotMove(CreatureID),
   #[error("Creature {0:?} cannot act.")]
   CannotAct(CreatureID),
+  #[error("It's not {0:?}'s turn.")]
+  NotYourTurn(CreatureID),
+  #[error("An effect is nested too deeply.")]
+  EffectTooDeep,
+  #[error("The ability {0:?} isn't a reaction that a creature can use on itself.")]
+  CannotReadyAbility(AbilityID),
   #[error("A path can't be found.")]
   NoPathFound,
+  #[error("There's no open space near {0:?} to summon a creature.")]
+  NoRoomToSummon(CreatureID),
+  #[error("{0:?} can only summon a creature for some rounds in combat.")]
+  SummonNeedsCombat(CreatureID),
   #[error("Path {0} already exists")]
   FolderAlreadyExists(FolderPath),
   #[error("Can't step from {0:?} to {1:?}")]
   StepTooBig(Point3, Point3),
+  #[error("Can't step from {0:?} to {1:?}: it's not open terrain, or it's occupied.")]
+  StepBlocked(Point3, Point3),
+  #[error("Creature {0:?} doesn't have enough movement left.")]
+  NotEnoughMovement(CreatureID),
   #[error("Not enough energy: {0:?}")]
   NotEnoughEnergy(Energy),
+  #[error("Creature {0:?} has no uses of ability {1:?} left until it rests.")]
+  AbilityDepleted(CreatureID, AbilityID),
   #[error("Player ID {0:?} is already registered.")]
   PlayerAlreadyExists(PlayerID),
   #[error("Player ID {0:?} was not found.")]
@@ -328,6 +493,21 @@ pub enum GameError {
   #[error("Notes can't be linked or unlinked. '{0}' / '{1}'")]
   CannotLinkNotes(FolderPath, String),
 
+  #[error("Can't undo the last change to creature {0}: {1}")]
+  CannotUndoCreature(CreatureID, String),
+  #[error("No point in the game's history has content hash {0}")]
+  ContentHashNotFound(String),
+  #[error("Pending command {0} was not found.")]
+  PendingCommandNotFound(PendingCommandID),
+  #[error("Nothing can be done until the creatures in the reaction window have reacted.")]
+  WaitingForReactions,
+  #[error("There's no reaction window open.")]
+  NoReactionWindow,
+  #[error("Creature {0:?} can't react to the action in the reaction window.")]
+  CannotReact(CreatureID),
+  #[error("Invalid stat block: {0}")]
+  InvalidStatBlock(String),
+
   #[error("No module source found")]
   NoModuleSource,
 
@@ -364,6 +544,49 @@ impl Point3 {
   }
 }
 
+/// One of the eight directions a creature can step in on the grid. North is towards negative y,
+/// i.e. the top of the map.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub enum Direction {
+  North,
+  NorthEast,
+  East,
+  SouthEast,
+  South,
+  SouthWest,
+  West,
+  NorthWest,
+}
+
+impl Direction {
+  pub const ALL: [Direction; 8] = [
+    Self::North,
+    Self::NorthEast,
+    Self::East,
+    Self::SouthEast,
+    Self::South,
+    Self::SouthWest,
+    Self::West,
+    Self::NorthWest,
+  ];
+
+  /// The point one cell away from `pt` in this direction.
+  pub fn step_from(self, pt: Point3) -> Point3 {
+    use Direction::*;
+    let (dx, dy) = match self {
+      North => (0, -1),
+      NorthEast => (1, -1),
+      East => (1, 0),
+      SouthEast => (1, 1),
+      South => (0, 1),
+      SouthWest => (-1, 1),
+      West => (-1, 0),
+      NorthWest => (-1, -1),
+    };
+    Point3::from_quantities(pt.x + i64cm(dx * 100), pt.y + i64cm(dy * 100), pt.z)
+  }
+}
+
 impl ::std::fmt::Display for Point3 {
   fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
     write!(
@@ -412,6 +635,81 @@ impl<'de> Deserialize<'de> for Point3 {
   }
 }
 
+/// A set of points on the 1-meter grid, packed into a bitset over their bounding box. This is much
+/// smaller than the list of points when the set is big and dense, like a creature's movement
+/// options.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct CompactPoints {
+  /// The corner of the bounding box with the smallest coordinates.
+  pub origin: Point3,
+  /// The number of cells in the bounding box along x, y and z.
+  pub size: (u32, u32, u32),
+  /// One bit for each cell of the bounding box, set if the cell is in the set. Cells are ordered
+  /// by x, then y, then z, and cell `i` is bit `i % 8` of `bits[i / 8]`.
+  pub bits: Vec<u8>,
+}
+
+impl CompactPoints {
+  pub fn from_points(points: &[Point3]) -> CompactPoints {
+    let cells: Vec<(i64, i64, i64)> = points
+      .iter()
+      .map(|pt| (pt.x.get::<meter>(), pt.y.get::<meter>(), pt.z.get::<meter>()))
+      .collect();
+    let (mut low, mut high) = match cells.first() {
+      Some(&cell) => (cell, cell),
+      None => return CompactPoints { origin: Point3::new(0, 0, 0), size: (0, 0, 0), bits: vec![] },
+    };
+    for &(x, y, z) in &cells {
+      low = (low.0.min(x), low.1.min(y), low.2.min(z));
+      high = (high.0.max(x), high.1.max(y), high.2.max(z));
+    }
+    let span = |low: i64, high: i64| (high - low + 1) as u32;
+    let size = (span(low.0, high.0), span(low.1, high.1), span(low.2, high.2));
+    let mut bits = vec![0u8; (size.0 as usize * size.1 as usize * size.2 as usize).div_ceil(8)];
+    for &(x, y, z) in &cells {
+      let idx = ((x - low.0) * i64::from(size.1) + (y - low.1)) * i64::from(size.2) + (z - low.2);
+      bits[idx as usize / 8] |= 1 << (idx % 8);
+    }
+    let origin = Point3::from_quantities(i64meter(low.0), i64meter(low.1), i64meter(low.2));
+    CompactPoints { origin, size, bits }
+  }
+
+  /// Unpack the points, sorted.
+  pub fn to_points(&self) -> Vec<Point3> {
+    let (size_x, size_y, size_z) = self.size;
+    let mut points = vec![];
+    let mut idx = 0;
+    for x in 0..size_x {
+      for y in 0..size_y {
+        for z in 0..size_z {
+          if self.bits[idx / 8] & (1 << (idx % 8)) != 0 {
+            points.push(Point3::from_quantities(
+              self.origin.x + i64meter(x),
+              self.origin.y + i64meter(y),
+              self.origin.z + i64meter(z),
+            ));
+          }
+          idx += 1;
+        }
+      }
+    }
+    points
+  }
+}
+
+/// Where an AABB is placed relative to the point it's placed at.
+#[derive(
+  Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, TS,
+)]
+pub enum AABBAnchor {
+  /// The point is the AABB's corner with the smallest coordinates.
+  #[default]
+  TopLeft,
+  /// The point is the AABB's middle cell. When the AABB is an even number of cells wide, it
+  /// extends one cell further in the negative direction.
+  Center,
+}
+
 /// An axis-aligned bounding box.
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize, TS)]
 pub struct AABB {
@@ -421,15 +719,30 @@ pub struct AABB {
   pub y: u32units::Length,
   #[ts(type = "number")]
   pub z: u32units::Length,
+  #[serde(default)]
+  pub anchor: AABBAnchor,
 }
 
 impl AABB {
-  /// Get the "maximum" point of the AABB (aka the top-right point) relative to a fixed point.
+  /// Get the "minimum" point of the AABB (aka the top-left point) when it's placed at a point.
+  pub fn get_min(&self, pt: Point3) -> Point3 {
+    match self.anchor {
+      AABBAnchor::TopLeft => pt,
+      AABBAnchor::Center => {
+        let half = |size: u32units::Length| i64meter(size.get::<meter>() / 2);
+        Point3::from_quantities(pt.x - half(self.x), pt.y - half(self.y), pt.z - half(self.z))
+      }
+    }
+  }
+
+  /// Get the "maximum" point of the AABB (aka the top-right point) when it's placed at a point.
+  /// This is just past the AABB, like the end of a range.
   pub fn get_max(&self, pt: Point3) -> Point3 {
+    let min = self.get_min(pt);
     Point3::from_quantities(
-      pt.x + i64cm(self.x.get::<centimeter>()),
-      pt.y + i64cm(self.y.get::<centimeter>()),
-      pt.z + i64cm(self.z.get::<centimeter>()),
+      min.x + i64cm(self.x.get::<centimeter>()),
+      min.y + i64cm(self.y.get::<centimeter>()),
+      min.z + i64cm(self.z.get::<centimeter>()),
     )
   }
 }
@@ -442,6 +755,20 @@ pub enum Dice {
   BestOf(u8, Box<Dice>),
 }
 
+impl ::std::fmt::Display for Dice {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      Dice::Expr { num, size } => write!(f, "{num}d{size}"),
+      Dice::Flat { value } => write!(f, "{value}"),
+      Dice::Plus(l, r) => match **r {
+        Dice::Flat { value } if value < 0 => write!(f, "{l}{value}"),
+        _ => write!(f, "{l}+{r}"),
+      },
+      Dice::BestOf(count, dice) => write!(f, "best of {count}x({dice})"),
+    }
+  }
+}
+
 /// Potential targets for an ability.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
 pub enum PotentialTargets {
@@ -449,6 +776,145 @@ pub enum PotentialTargets {
   Points(Vec<Point3>),
 }
 
+impl PotentialTargets {
+  /// Whether `target`, decided on by `actor`, is one of these potential targets.
+  pub fn allows(&self, actor: CreatureID, target: &DecidedTarget) -> bool {
+    match (self, target) {
+      (PotentialTargets::CreatureIDs(cids), DecidedTarget::Creature(cid)) => cids.contains(cid),
+      (PotentialTargets::CreatureIDs(cids), DecidedTarget::Creatures(targets)) => {
+        targets.iter().all(|cid| cids.contains(cid))
+      }
+      (PotentialTargets::CreatureIDs(cids), DecidedTarget::Actor) => cids.contains(&actor),
+      (PotentialTargets::Points(pts), DecidedTarget::Point(pt)) => pts.contains(pt),
+      _ => false,
+    }
+  }
+}
+
+/// The smallest, largest and average results of rolling some `Dice`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS)]
+pub struct RollRange {
+  pub min: i32,
+  pub max: i32,
+  pub average: f64,
+}
+
+/// What an ability is predicted to do to one of its targets. Damage and healing are the ranges of
+/// what would be rolled, before they're limited by the target's current and maximum HP.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TS)]
+pub struct EffectPreview {
+  pub damage: Option<RollRange>,
+  pub healing: Option<RollRange>,
+  pub energy: Energy,
+  pub conditions: Vec<AppliedCondition>,
+  /// How well the target is protected by terrain from the ability.
+  #[serde(default)]
+  pub cover: Cover,
+}
+
+/// How much of a target is shielded by blocking terrain. Creatures with total cover can't be
+/// targeted at all.
+#[derive(
+  Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, TS,
+)]
+pub enum Cover {
+  #[default]
+  None,
+  Half,
+  ThreeQuarters,
+  Total,
+}
+
+impl Cover {
+  /// How much harder cover makes it for an attack roll to hit.
+  pub fn defense_bonus(&self) -> u8 {
+    match self {
+      Cover::None | Cover::Total => 0,
+      Cover::Half => 2,
+      Cover::ThreeQuarters => 5,
+    }
+  }
+}
+
+/// A compact record of what a creature did during one turn of combat.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct TurnSummary {
+  /// Where the creature started and finished moving, if it moved at all.
+  pub moved: Option<(Point3, Point3)>,
+  /// The abilities the creature used, in the order it used them.
+  pub abilities: Vec<AbilityID>,
+  /// Damage dealt to other creatures by the creature's abilities.
+  pub damage_dealt: HP,
+  /// Damage the creature took from any source.
+  pub damage_taken: HP,
+}
+
+/// What's at a single cell of a scene, for inspecting it.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct CellInfo {
+  /// Whether the cell is open terrain.
+  pub open: bool,
+  /// The creatures taking up the cell, including large creatures that only cover part of it.
+  pub creatures: Vec<CreatureID>,
+  /// The volume conditions covering the cell.
+  pub volume_conditions: Vec<ConditionID>,
+}
+
+/// Everything needed to draw a scene, so that it can be fetched in a single request. See
+/// `GameExt::scene_render_state`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct SceneRenderState {
+  /// The creatures in the scene along with their positions, sorted by creature ID.
+  pub creatures: Vec<(CreatureID, Point3)>,
+  #[serde(with = "terrain_serde")]
+  #[ts(type = "Terrain")]
+  pub terrain: Terrain,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[ts(type = "TerrainCosts")]
+  pub terrain_costs: TerrainCosts,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[ts(type = "SceneVolumeConditions")]
+  pub volume_conditions: HashMap<ConditionID, VolumeCondition>,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[ts(type = "Highlights")]
+  pub highlights: HashMap<Point3, (Color, Visibility)>,
+}
+
+/// The order to list creatures in. See `Game::list_creatures_paged`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub enum CreatureSort {
+  #[default]
+  Name,
+  /// By the path of the folder the creature is in, then by name. Creatures that aren't in any
+  /// folder come first.
+  Folder,
+}
+
+/// Which part of a creature's turn an ability uses up, and so when during combat it can be used.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub enum ActionKind {
+  #[default]
+  Action,
+  BonusAction,
+  /// Reactions can be used during other creatures' turns.
+  Reaction,
+}
+
+/// How long a creature rests for. See `GMCommand::Rest`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub enum RestKind {
+  Short,
+  Long,
+}
+
+impl RestKind {
+  /// Whether this rest brings back the uses of abilities that recharge on a `recharge` rest. A
+  /// long rest recharges everything a short rest does.
+  pub fn recharges(self, recharge: RestKind) -> bool {
+    self == RestKind::Long || recharge == RestKind::Short
+  }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
 pub struct Ability {
   pub id: AbilityID,
@@ -456,6 +922,80 @@ pub struct Ability {
   pub cost: Energy,
   pub action: Action,
   pub usable_ooc: bool,
+  #[serde(default)]
+  pub action_kind: ActionKind,
+  /// If set, the ability only has its effect on creatures it hits. Only used by abilities that
+  /// affect creatures, and never rolled against the creature using the ability.
+  #[serde(default)]
+  pub attack_roll: Option<AttackRoll>,
+  /// How many times a creature can use the ability before it has to rest, and which kind of rest
+  /// brings the uses back. `None` means the ability can be used any number of times.
+  #[serde(default)]
+  pub uses_per_rest: Option<(u32, RestKind)>,
+}
+
+/// A roll which must meet or beat a target's defense for an ability to affect it. See
+/// `Condition::ProvideDefense`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct AttackRoll {
+  pub dice: Dice,
+  /// What happens to a target that the attack misses, if anything.
+  #[serde(default)]
+  pub miss_effect: Option<CreatureEffect>,
+}
+
+impl Ability {
+  /// Whether a creature can ready this ability, to be used on itself when it's attacked.
+  pub fn can_be_readied(&self) -> bool {
+    self.action_kind == ActionKind::Reaction
+      && matches!(self.action, Action::Creature { target: CreatureTarget::Actor, .. })
+  }
+
+  /// The kinds of ability this is, going by everything it can do to the creatures it targets,
+  /// whether its attack hits or misses. Abilities that don't target creatures have none.
+  pub fn target_categories(&self) -> Vec<TargetCategory> {
+    let effect = match self.action {
+      Action::Creature { ref effect, .. } => effect,
+      Action::SceneVolume { .. } => return vec![],
+    };
+    let mut categories = effect.target_categories();
+    if let Some(miss_effect) = self.attack_roll.as_ref().and_then(|a| a.miss_effect.as_ref()) {
+      categories.extend(miss_effect.target_categories());
+      categories.sort();
+      categories.dedup();
+    }
+    categories
+  }
+
+  /// A human-readable summary of what this ability does, e.g.
+  /// "Fireball (8 energy): deals 3 damage".
+  pub fn describe(&self) -> String {
+    format!("{} ({} energy): {}", self.name, self.cost.0, self.action.describe_effect())
+  }
+
+  /// A summary of this ability for choosing between abilities.
+  pub fn description(&self) -> AbilityDescription {
+    AbilityDescription {
+      name: self.name.clone(),
+      range: self.action.range(),
+      targeting: self.action.describe_target(),
+      effect: self.action.describe_effect(),
+    }
+  }
+}
+
+/// What an ability does, in words, e.g. for an ability picker.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub struct AbilityDescription {
+  pub name: String,
+  /// How far away the ability reaches, if that doesn't depend on the creature using it. Melee
+  /// abilities reach as far as the creature does.
+  #[ts(type = "number | null")]
+  pub range: Option<u32units::Length>,
+  /// What the ability can be used on, e.g. "a creature within 5m".
+  pub targeting: String,
+  /// What the ability does, e.g. "deals 3 damage".
+  pub effect: String,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -464,6 +1004,12 @@ pub struct AbilityCreation {
   pub cost: Energy,
   pub action: Action,
   pub usable_ooc: bool,
+  #[serde(default)]
+  pub action_kind: ActionKind,
+  #[serde(default)]
+  pub attack_roll: Option<AttackRoll>,
+  #[serde(default)]
+  pub uses_per_rest: Option<(u32, RestKind)>,
 }
 
 impl DeriveKey for Ability {
@@ -480,6 +1026,66 @@ pub enum Action {
   // Multi(Vec<(String, Action)>),
 }
 
+impl Action {
+  /// A human-readable description of what this action does, e.g. "deals 3 damage".
+  pub fn describe_effect(&self) -> String {
+    match self {
+      Action::Creature { effect, .. } => effect.describe(),
+      Action::SceneVolume {
+        effect: SceneEffect::CreateVolumeCondition { duration, condition }, ..
+      } => format!("creates an area that applies {}{}", condition.describe(), duration.describe()),
+      Action::SceneVolume { effect: SceneEffect::Summon { duration, .. }, .. } => {
+        format!("summons a creature{}", duration.describe())
+      }
+      Action::SceneVolume { effect: SceneEffect::Teleport, .. } => "teleports the user".to_string(),
+    }
+  }
+
+  /// A human-readable description of what this action can target, e.g. "a creature within 5m".
+  pub fn describe_target(&self) -> String {
+    let meters = |distance: &u32units::Length| distance.get::<centimeter>() as f64 / 100.0;
+    match self {
+      Action::Creature { target, .. } => match target {
+        CreatureTarget::Melee => "a creature within reach".to_string(),
+        CreatureTarget::Range(range) => format!("a creature within {}m", meters(range)),
+        CreatureTarget::Actor => "the user".to_string(),
+        CreatureTarget::LineFromActor { distance } => {
+          format!("every creature in a {}m line", meters(distance))
+        }
+        CreatureTarget::ConeFromActor { length, .. } => {
+          format!("every creature in a {}m cone", meters(length))
+        }
+        CreatureTarget::SomeCreaturesInVolumeInRange { maximum, range, .. } => {
+          format!("up to {} creatures in an area within {}m", maximum, meters(range))
+        }
+        CreatureTarget::AllCreaturesInVolumeInRange { range, .. } => {
+          format!("every creature in an area within {}m", meters(range))
+        }
+      },
+      Action::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => {
+        format!("an area within {}m", meters(range))
+      }
+    }
+  }
+
+  /// How far away this action can be targeted. None for melee and self-targeted actions.
+  pub fn range(&self) -> Option<u32units::Length> {
+    match *self {
+      Action::Creature { target: CreatureTarget::Melee | CreatureTarget::Actor, .. } => None,
+      Action::Creature { target: CreatureTarget::Range(range), .. } => Some(range),
+      Action::Creature { target: CreatureTarget::LineFromActor { distance }, .. } => Some(distance),
+      Action::Creature { target: CreatureTarget::ConeFromActor { length, .. }, .. } => Some(length),
+      Action::Creature {
+        target:
+          CreatureTarget::SomeCreaturesInVolumeInRange { range, .. }
+          | CreatureTarget::AllCreaturesInVolumeInRange { range, .. },
+        ..
+      }
+      | Action::SceneVolume { target: SceneTarget::RangedVolume { range, .. }, .. } => Some(range),
+    }
+  }
+}
+
 /// A target specifier for actions that ultimately affect creatures.
 /// This doesn't mean that the target *specifier* is always a `CreatureID`, but rather that
 /// ultimately the target is resolved into one or more creatures which `CreatureEffect`s will be
@@ -497,6 +1103,13 @@ pub enum CreatureTarget {
     #[ts(type = "number")]
     distance: u32units::Length,
   },
+  /// A cone from an actor, like a dragon's breath, which is always a fixed length. It's pointed at
+  /// the targeted point and affects every creature in it other than the actor.
+  ConeFromActor {
+    #[ts(type = "number")]
+    length: u32units::Length,
+    angle_degrees: u16,
+  },
   // LineFromActorToCreature{ distance: u32units::Length },
   SomeCreaturesInVolumeInRange {
     volume: Volume,
@@ -527,6 +1140,12 @@ pub enum SceneTarget {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
 pub enum SceneEffect {
   CreateVolumeCondition { duration: Duration, condition: Condition },
+  /// Create a copy of the `template` creature on open terrain next to the caster. The summoned
+  /// creature joins combat if the caster is in combat, and is removed when the duration expires.
+  Summon { template: CreatureID, duration: Duration },
+  /// Move the caster onto the targeted point, or onto the closest open cell next to it if the
+  /// caster doesn't fit there. The targeted point must be in the caster's line of sight.
+  Teleport,
   // Another example of a SceneEffect would be DestroyTerrain or BuildTerrain
 }
 
@@ -551,6 +1170,116 @@ pub enum CreatureEffect {
   Damage(Dice),
   MultiEffect(Vec<CreatureEffect>),
   GenerateEnergy(Energy),
+  /// Extra movement for the rest of the creature's turn, e.g. a dash. This is applied as an
+  /// `ExtraMovement` condition that expires at the end of the creature's turn.
+  GrantMovement(#[ts(type = "number")] u32units::Length),
+  /// Move one of the target's conditions onto the creature using the ability, or the other way
+  /// around if `to_target` is set. The condition keeps the duration it had left. Nothing happens if
+  /// there's no matching condition to move, or if the receiver is immune to it.
+  TransferCondition {
+    which: ConditionSelector,
+    #[serde(default)]
+    to_target: bool,
+  },
+}
+
+impl CreatureEffect {
+  /// Whether this effect nests `MultiEffect`s and applied `RecurringEffect`s more than `limit`
+  /// levels deep. This only looks `limit` levels down, so it's safe on absurdly deep effects.
+  pub fn nested_deeper_than(&self, limit: usize) -> bool {
+    if limit == 0 {
+      return true;
+    }
+    match self {
+      CreatureEffect::MultiEffect(effects) => {
+        effects.iter().any(|e| e.nested_deeper_than(limit - 1))
+      }
+      CreatureEffect::ApplyCondition(_, Condition::RecurringEffect(effect)) => {
+        effect.nested_deeper_than(limit - 1)
+      }
+      _ => false,
+    }
+  }
+
+  /// This effect with all of its damage rolled with `dice` instead.
+  pub fn with_damage_dice(&self, dice: &Dice) -> CreatureEffect {
+    match self {
+      CreatureEffect::Damage(_) => CreatureEffect::Damage(dice.clone()),
+      CreatureEffect::MultiEffect(effects) => {
+        CreatureEffect::MultiEffect(effects.iter().map(|e| e.with_damage_dice(dice)).collect())
+      }
+      other => other.clone(),
+    }
+  }
+
+  /// Every condition transfer in this effect, as `(which, to_target)`. These need to know who is
+  /// using the ability, so they're applied separately from the rest of the effect.
+  pub fn condition_transfers(&self) -> Vec<(&ConditionSelector, bool)> {
+    match self {
+      CreatureEffect::TransferCondition { which, to_target } => vec![(which, *to_target)],
+      CreatureEffect::MultiEffect(effects) => {
+        effects.iter().flat_map(|e| e.condition_transfers()).collect()
+      }
+      _ => vec![],
+    }
+  }
+
+  /// The kinds of ability that this effect makes an ability count as, sorted.
+  pub fn target_categories(&self) -> Vec<TargetCategory> {
+    let mut categories = match self {
+      CreatureEffect::Heal(_) => vec![TargetCategory::Healing],
+      CreatureEffect::Damage(_) => vec![TargetCategory::Damage],
+      CreatureEffect::ApplyCondition(..) | CreatureEffect::TransferCondition { .. } => {
+        vec![TargetCategory::Conditions]
+      }
+      CreatureEffect::MultiEffect(effects) => {
+        effects.iter().flat_map(|e| e.target_categories()).collect()
+      }
+      CreatureEffect::GenerateEnergy(_) | CreatureEffect::GrantMovement(_) => vec![],
+    };
+    categories.sort();
+    categories.dedup();
+    categories
+  }
+
+  /// Whether any part of this effect deals damage, which makes an ability with it an attack.
+  pub fn deals_damage(&self) -> bool {
+    match self {
+      CreatureEffect::Damage(_) => true,
+      CreatureEffect::MultiEffect(effects) => effects.iter().any(|e| e.deals_damage()),
+      _ => false,
+    }
+  }
+
+  /// A human-readable summary of this effect, e.g.
+  /// "deals 2d6 damage and applies Incapacitated for 3 rounds".
+  pub fn describe(&self) -> String {
+    match self {
+      CreatureEffect::ApplyCondition(duration, condition) => {
+        format!("applies {}{}", condition.describe(), duration.describe())
+      }
+      CreatureEffect::Heal(dice) => format!("heals {dice} HP"),
+      CreatureEffect::Damage(dice) => format!("deals {dice} damage"),
+      CreatureEffect::GenerateEnergy(energy) => format!("generates {} energy", energy.0),
+      CreatureEffect::GrantMovement(distance) => {
+        format!("grants {}m of movement", distance.get::<centimeter>() as f64 / 100.0)
+      }
+      CreatureEffect::TransferCondition { which, to_target: false } => {
+        format!("moves {} from the target to the user", which.describe())
+      }
+      CreatureEffect::TransferCondition { which, to_target: true } => {
+        format!("moves {} from the user to the target", which.describe())
+      }
+      CreatureEffect::MultiEffect(effects) => {
+        let descriptions: Vec<String> = effects.iter().map(|e| e.describe()).collect();
+        match descriptions.split_last() {
+          None => "does nothing".to_string(),
+          Some((last, [])) => last.clone(),
+          Some((last, init)) => format!("{} and {}", init.join(", "), last),
+        }
+      }
+    }
+  }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -560,14 +1289,93 @@ pub enum Condition {
   Incapacitated,
   AddDamageBuff(HP),
   DoubleMaxMovement,
+  /// Adds to the creature's speed.
+  ExtraMovement(#[ts(type = "number")] u32units::Length),
+  /// Movement costs this many times as much, dividing the creature's speed by the factor.
+  Slowed(u8),
+  /// Movement costs this many times less, multiplying the creature's speed by the factor.
+  Hastened(u8),
+  /// Damage dealt to the creature is reduced by this much.
+  ReduceDamage(HP),
+  /// Attack rolls against the creature must be at least this high to hit. Only the highest
+  /// defense a creature has counts.
+  ProvideDefense(u8),
   // Make an ability temporarily available to a creature.
   ActivateAbility(AbilityID),
+  // The creature was summoned, and will be removed from the game when this condition expires.
+  Summoned,
 }
 
 impl Condition {
   pub fn apply(&self, duration: Duration) -> AppliedCondition {
     AppliedCondition { remaining: duration, condition: self.clone() }
   }
+
+  pub fn describe(&self) -> String {
+    match self {
+      Condition::RecurringEffect(effect) => {
+        format!("a recurring effect that {}", effect.describe())
+      }
+      Condition::Dead => "Dead".to_string(),
+      Condition::Incapacitated => "Incapacitated".to_string(),
+      Condition::AddDamageBuff(hp) => format!("a +{} damage buff", hp.0),
+      Condition::DoubleMaxMovement => "double movement".to_string(),
+      Condition::ExtraMovement(distance) => {
+        format!("{}m of extra movement", distance.get::<centimeter>() as f64 / 100.0)
+      }
+      Condition::Slowed(factor) => format!("slowed by a factor of {factor}"),
+      Condition::Hastened(factor) => format!("hastened by a factor of {factor}"),
+      Condition::ReduceDamage(hp) => format!("{} damage reduction", hp.0),
+      Condition::ProvideDefense(defense) => format!("{} defense", defense),
+      Condition::ActivateAbility(_) => "an extra ability".to_string(),
+      Condition::Summoned => "Summoned".to_string(),
+    }
+  }
+}
+
+/// A kind of ability that some creatures can't be targeted by, e.g. undead that can't be healed.
+/// See `Creature::untargetable_by`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, TS)]
+pub enum TargetCategory {
+  Healing,
+  Damage,
+  /// Applying or moving conditions.
+  Conditions,
+}
+
+/// How a creature gets around. See `Creature::movement_mode`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub enum MovementMode {
+  /// Walks across the level it's on.
+  #[default]
+  Ground,
+  /// Flies or climbs, so it can also move up and down.
+  Flying,
+}
+
+/// Picks out which of a creature's conditions an effect works on.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
+pub enum ConditionSelector {
+  /// Any recurring effect, like poison.
+  AnyRecurringEffect,
+  /// Only conditions exactly like this one.
+  Exactly(Condition),
+}
+
+impl ConditionSelector {
+  pub fn matches(&self, condition: &Condition) -> bool {
+    match self {
+      ConditionSelector::AnyRecurringEffect => matches!(condition, Condition::RecurringEffect(_)),
+      ConditionSelector::Exactly(selected) => selected == condition,
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      ConditionSelector::AnyRecurringEffect => "a recurring effect".to_string(),
+      ConditionSelector::Exactly(condition) => condition.describe(),
+    }
+  }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -592,6 +1400,15 @@ pub enum Volume {
   // An Axis-Aligned Bounding Box, origin at top-left,
   // with x going east, y going south, and z going up.
   AABB(AABB),
+  /// A cone with its apex at the origin, pointing along `direction` (a relative offset, like
+  /// `Line`'s vector) and reaching out to `length`. Cells within half of `angle_degrees` of the
+  /// direction are inside it.
+  Cone {
+    direction: Point3,
+    #[ts(type = "number")]
+    length: u32units::Length,
+    angle_degrees: u16,
+  },
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, TS)]
@@ -669,6 +1486,10 @@ pub struct Creature {
   pub name: String,
   #[ts(type = "number")]
   pub speed: u32units::Length,
+  /// How far away this creature can attack in melee. This is also the area it threatens.
+  #[serde(default = "standard_reach")]
+  #[ts(type = "number")]
+  pub reach: u32units::Length,
   // Things like "max_energy" (and maybe eventually things like "defense_score", if we have that
   // concept), should not be a a fixed number stored on the Creature. Instead, they should be
   // based on Conditions such as "ProvideMaxEnergy(Dice)" or "ProvideDefense(Dice)", and then the
@@ -677,21 +1498,48 @@ pub struct Creature {
   pub cur_energy: Energy,
   #[ts(type = "Record<AbilityID, AbilityStatus>")]
   pub abilities: IndexedHashMap<AbilityStatus>,
+  /// Damage dice that this creature rolls for some abilities instead of the abilities' own.
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "Record<AbilityID, Dice>")]
+  pub ability_dice: HashMap<AbilityID, Dice>,
+  /// How many times the creature has used each of its limited abilities since it last rested.
+  /// See `Ability::uses_per_rest`.
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "Record<AbilityID, number>")]
+  pub ability_uses: HashMap<AbilityID, u32>,
   pub class: ClassID,
   pub max_health: HP,
   pub cur_health: HP,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureConditions")]
   pub conditions: HashMap<ConditionID, AppliedCondition>,
+  /// Conditions that can't be applied to this creature.
+  #[serde(default)]
+  pub immunities: Vec<Condition>,
+  /// Kinds of ability that can't target this creature at all. It isn't offered as a target for
+  /// them, and volumes of them pass it by.
+  #[serde(default)]
+  pub untargetable_by: Vec<TargetCategory>,
+  /// Whether the creature can only move across the level it's on, or up and down as well.
+  #[serde(default)]
+  pub movement_mode: MovementMode,
+  /// A reaction ability that this creature will use on itself the next time it's attacked.
+  #[serde(default)]
+  pub readied_reaction: Option<AbilityID>,
   pub note: String,
   #[serde(default)]
   pub bio: String,
   pub portrait_url: String,
   #[serde(default)]
   pub icon_url: String,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureAttributes")]
   pub attributes: HashMap<AttrID, SkillLevel>,
   pub initiative: Dice,
   pub size: AABB,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[serde(default)]
   #[ts(type = "CreatureInventory")]
   pub inventory: Inventory,
@@ -703,13 +1551,20 @@ impl Creature {
       id: sc.id,
       name: sc.name,
       speed: sc.speed,
+      reach: sc.reach,
       max_energy: sc.max_energy,
       cur_energy: sc.cur_energy,
       abilities: sc.abilities,
+      ability_dice: sc.ability_dice,
+      ability_uses: sc.ability_uses,
       class: sc.class,
       max_health: sc.max_health,
       cur_health: sc.cur_health,
       conditions: sc.conditions,
+      immunities: sc.immunities,
+      untargetable_by: sc.untargetable_by,
+      movement_mode: sc.movement_mode,
+      readied_reaction: sc.readied_reaction,
       note: sc.note,
       bio: sc.bio,
       portrait_url: sc.portrait_url,
@@ -741,8 +1596,18 @@ pub struct Combat {
   pub creatures: nonempty::NonEmptyWithCursor<(CreatureID, i16)>,
   #[ts(type = "number")]
   pub movement_used: u32units::Length,
+  /// The cells the current creature has moved through this turn, in order, starting with the one
+  /// it started moving from.
+  #[serde(default)]
+  pub movement_trail: Vec<Point3>,
+  /// The current round, starting from 1. A new round starts whenever the turn order wraps back
+  /// around to the top.
+  #[serde(default = "first_round")]
+  pub round: u32,
 }
 
+fn first_round() -> u32 { 1 }
+
 impl DeriveKey for Creature {
   type KeyType = CreatureID;
   fn derive_key(&self) -> CreatureID { self.id }
@@ -772,6 +1637,14 @@ pub struct Game {
   // goes.
   #[serde(default)]
   pub active_scene: Option<SceneID>,
+  #[serde(default)]
+  pub settings: GameSettings,
+  /// Player commands which are waiting for the GM to approve or deny them, oldest first.
+  #[serde(default)]
+  pub pending_commands: Vec<PendingCommand>,
+  /// An action that's being held so the creatures it targets can react to it first.
+  #[serde(default)]
+  pub reaction_window: Option<ReactionWindow>,
 }
 
 impl Game {
@@ -779,6 +1652,44 @@ impl Game {
     self.scenes.get(&id).ok_or_else(|| GameError::SceneNotFound(id))
   }
 
+  /// Every creature in a scene along with its position, sorted by creature ID.
+  pub fn scene_creatures(&self, id: SceneID) -> Result<Vec<(CreatureID, Point3)>, GameError> {
+    let scene = self.get_scene(id)?;
+    let mut creatures: Vec<(CreatureID, Point3)> =
+      scene.creatures.iter().map(|(cid, &(pos, _))| (*cid, pos)).collect();
+    creatures.sort();
+    Ok(creatures)
+  }
+
+  /// Every ability in the game with a description of it, sorted by name.
+  pub fn list_abilities(&self) -> Vec<(AbilityID, AbilityDescription)> {
+    let mut abilities: Vec<(AbilityID, AbilityDescription)> =
+      self.abilities.iter().map(|ability| (ability.id, ability.description())).collect();
+    abilities.sort_by(|(id1, desc1), (id2, desc2)| (&desc1.name, id1).cmp(&(&desc2.name, id2)));
+    abilities
+  }
+
+  /// Up to `limit` creatures, skipping the first `offset`, in the given order. Ties are broken by
+  /// creature ID, so the order is the same every time and each creature is on exactly one page.
+  pub fn list_creatures_paged(
+    &self, offset: usize, limit: usize, sort: CreatureSort,
+  ) -> Vec<CreatureID> {
+    let folders: HashMap<CreatureID, &FolderPath> = self
+      .campaign
+      .walk_paths(&FolderPath::root())
+      .filter_map(|path| Some((path, self.campaign.get(path).ok()?)))
+      .flat_map(|(path, folder)| folder.creatures.iter().map(move |cid| (*cid, path)))
+      .collect();
+    let mut creatures: Vec<&Creature> = self.creatures.values().collect();
+    match sort {
+      CreatureSort::Name => creatures.sort_by(|c1, c2| (&c1.name, c1.id).cmp(&(&c2.name, c2.id))),
+      CreatureSort::Folder => creatures.sort_by(|c1, c2| {
+        (folders.get(&c1.id), &c1.name, c1.id).cmp(&(folders.get(&c2.id), &c2.name, c2.id))
+      }),
+    }
+    creatures.into_iter().skip(offset).take(limit).map(|c| c.id).collect()
+  }
+
   pub fn from_serialized_game(sg: SerializedGame) -> Game {
     let creatures: IndexedHashMap<Creature> = sg
       .creatures
@@ -796,15 +1707,153 @@ impl Game {
       campaign: sg.campaign,
       players: sg.players,
       active_scene: sg.active_scene,
+      settings: sg.settings,
+      pending_commands: vec![],
+      reaction_window: sg.reaction_window,
     };
   }
 }
 
+/// Game-wide rules that the GM can change during play.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct GameSettings {
+  #[serde(default)]
+  pub vetting_policy: VettingPolicy,
+  /// How many seconds a pending command may wait for the GM before it's automatically denied. If
+  /// None, pending commands wait forever.
+  #[serde(default)]
+  pub pending_command_timeout: Option<u32>,
+  /// Derives the modifier used in attribute checks from a creature's attribute score, e.g. to let
+  /// Skilled creatures check as if they were Expert. Scores that aren't listed use their own rank
+  /// (`SkillLevel::to_ord`).
+  #[serde(default)]
+  pub attribute_modifiers: Vec<(SkillLevel, i8)>,
+  /// Take creatures that no player controls off the map (and out of combat) as soon as they die.
+  /// They stay in the campaign, so the GM can still bring them back.
+  #[serde(default)]
+  pub auto_remove_dead: bool,
+  #[serde(default)]
+  pub rest_policy: RestPolicy,
+  /// Kill a creature outright when a single hit deals more than its remaining health plus this
+  /// much, even if damage reduction would have kept it alive. If None, creatures only die by
+  /// running out of health.
+  #[serde(default)]
+  pub massive_damage_threshold: Option<HP>,
+  /// How many seconds the targets of an attack get to react to it before it lands. If None,
+  /// attacks land right away, and only readied reactions are used.
+  #[serde(default)]
+  pub reaction_window: Option<u32>,
+}
+
+impl GameSettings {
+  pub fn attribute_modifier(&self, score: SkillLevel) -> i8 {
+    self
+      .attribute_modifiers
+      .iter()
+      .find(|(level, _)| *level == score)
+      .map(|(_, modifier)| *modifier)
+      .unwrap_or_else(|| score.to_ord())
+  }
+}
+
+/// Which player commands must be approved by the GM before they take effect. Chat and notes are
+/// never held for approval.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub enum VettingPolicy {
+  #[default]
+  Never,
+  /// Hold every action: moving, using abilities and ending turns.
+  AllActions,
+  /// Only hold abilities that may affect creatures which aren't controlled by any player.
+  NPCTargetingActions,
+}
+
+/// How much health and energy creatures get back from each kind of rest. By default a short rest
+/// gives back half, and a long rest everything.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct RestPolicy {
+  pub short: RestRecovery,
+  pub long: RestRecovery,
+}
+
+impl Default for RestPolicy {
+  fn default() -> RestPolicy {
+    RestPolicy {
+      short: RestRecovery { health_percent: 50, energy_percent: 50 },
+      long: RestRecovery { health_percent: 100, energy_percent: 100 },
+    }
+  }
+}
+
+impl RestPolicy {
+  pub fn recovery(&self, kind: RestKind) -> RestRecovery {
+    match kind {
+      RestKind::Short => self.short,
+      RestKind::Long => self.long,
+    }
+  }
+}
+
+/// The share of a creature's maximum health and energy that a rest gives back, in percent.
+/// Anything over 100 counts as 100.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct RestRecovery {
+  pub health_percent: u8,
+  pub energy_percent: u8,
+}
+
+impl RestRecovery {
+  pub fn health(&self, max: HP) -> HP { HP(percent_of(max.0, self.health_percent)) }
+
+  pub fn energy(&self, max: Energy) -> Energy { Energy(percent_of(max.0, self.energy_percent)) }
+}
+
+fn percent_of(max: u8, percent: u8) -> u8 {
+  // This can't overflow, since the percentage is capped at 100.
+  (u16::from(max) * u16::from(percent.min(100)) / 100) as u8
+}
+
+/// A player command that is being held until the GM approves or denies it.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct PendingCommand {
+  pub id: PendingCommandID,
+  pub player_id: PlayerID,
+  pub command: PlayerCommand,
+  /// When the command was submitted, in seconds since the Unix epoch.
+  #[ts(type = "number")]
+  pub submitted_at: u64,
+}
+
+/// An action that's waiting for the creatures it attacks to react before it's carried out.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct ReactionWindow {
+  pub scene_id: SceneID,
+  pub creature_id: CreatureID,
+  pub ability_id: AbilityID,
+  pub target: DecidedTarget,
+  pub in_combat: bool,
+  /// The creatures that may still react, sorted by ID. The action is carried out as soon as this
+  /// is empty.
+  pub reactors: Vec<CreatureID>,
+  /// When the window was opened, in seconds since the Unix epoch.
+  #[ts(type = "number")]
+  pub opened_at: u64,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
 pub struct Player {
   pub player_id: PlayerID,
   pub scene: Option<SceneID>,
+  /// The creatures the player controls. Several players can share control of the same creature,
+  /// and any of them can issue commands for it.
+  #[serde(serialize_with = "serialize_sorted_set")]
   pub creatures: HashSet<CreatureID>,
+  /// The name to show for the player in chat and elsewhere. Empty means to use the `player_id`.
+  #[serde(default)]
+  pub display_name: String,
+  /// The color to show the player's name in. Empty means the default color.
+  #[serde(default)]
+  pub color: Color,
 }
 
 impl DeriveKey for Player {
@@ -814,7 +1863,22 @@ impl DeriveKey for Player {
 
 impl Player {
   pub fn new(name: PlayerID) -> Player {
-    Player { player_id: name, scene: None, creatures: HashSet::new() }
+    Player {
+      player_id: name,
+      scene: None,
+      creatures: HashSet::new(),
+      display_name: String::new(),
+      color: String::new(),
+    }
+  }
+
+  /// The player's display name, falling back to their ID.
+  pub fn name(&self) -> &str {
+    if self.display_name.is_empty() {
+      &self.player_id.0
+    } else {
+      &self.display_name
+    }
   }
 }
 
@@ -822,16 +1886,25 @@ impl Player {
 pub struct Scene {
   pub id: SceneID,
   pub name: String,
+  #[serde(with = "terrain_serde")]
   #[ts(type = "Terrain")]
-  pub terrain: Vec<Point3>,
+  pub terrain: Terrain,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "TerrainCosts")]
+  pub terrain_costs: TerrainCosts,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "Highlights")]
   pub highlights: HashMap<Point3, (Color, Visibility)>,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "Annotations")]
   pub annotations: HashMap<Point3, (String, Visibility)>,
 
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[serde(default)]
   #[ts(type = "SceneHotspots")]
   pub scene_hotspots: HashMap<Point3, SceneID>,
+  #[serde(serialize_with = "serialize_sorted_set")]
   #[serde(default)]
   #[ts(type = "RelatedScenes")]
   pub related_scenes: HashSet<SceneID>,
@@ -843,13 +1916,17 @@ pub struct Scene {
   pub background_image_offset: Option<(i32, i32)>,
   pub background_image_scale: (f64, f64),
 
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "SceneCreatures")]
   pub creatures: HashMap<CreatureID, (Point3, Visibility)>,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "SceneAttributeChecks")]
   pub attribute_checks: HashMap<String, AttributeCheck>,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[serde(default)]
   #[ts(type = "SceneInventory")]
   pub inventory: Inventory,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[serde(default)]
   #[ts(type = "SceneVolumeConditions")]
   pub volume_conditions: HashMap<ConditionID, VolumeCondition>,
@@ -859,6 +1936,41 @@ pub struct Scene {
   #[serde(default)]
   #[ts(type = "SceneFocusedCreatures")]
   pub focused_creatures: Vec<CreatureID>,
+
+  /// The initiative order of the last combat in this scene, if it was stopped with
+  /// `preserve_initiative`. Used by the next `StartCombat` in this scene instead of re-rolling.
+  #[serde(default)]
+  pub preserved_initiative: Vec<(CreatureID, i16)>,
+
+  /// Named snapshots of the positions and vitals of the creatures in this scene, which can be
+  /// restored with `RestoreScene`.
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "Record<string, SceneCheckpoint>")]
+  pub checkpoints: HashMap<String, SceneCheckpoint>,
+
+  /// The nominal extent of the scene's grid as two opposite corners (inclusive), so that editors
+  /// can show it before any terrain has been painted. Terrain can't be painted outside of it.
+  #[serde(default)]
+  pub grid_bounds: Option<(Point3, Point3)>,
+}
+
+/// The state of a scene's creatures as recorded by `SnapshotScene`.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize, TS)]
+pub struct SceneCheckpoint {
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[ts(type = "Record<CreatureID, CreatureCheckpoint>")]
+  pub creatures: HashMap<CreatureID, CreatureCheckpoint>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
+pub struct CreatureCheckpoint {
+  pub pos: Point3,
+  pub cur_health: HP,
+  pub cur_energy: Energy,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[ts(type = "CreatureConditions")]
+  pub conditions: HashMap<ConditionID, AppliedCondition>,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, TS)]
@@ -884,14 +1996,19 @@ impl DeriveKey for Scene {
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
 #[ts(rename = "FolderNode")]
 pub struct Folder {
+  #[serde(serialize_with = "serialize_sorted_set")]
   pub scenes: HashSet<SceneID>,
+  #[serde(serialize_with = "serialize_sorted_set")]
   pub creatures: HashSet<CreatureID>,
   #[ts(type = "Record<string, Note>")]
   pub notes: IndexedHashMap<Note>,
+  #[serde(serialize_with = "serialize_sorted_set")]
   #[serde(default)]
   pub items: HashSet<ItemID>,
+  #[serde(serialize_with = "serialize_sorted_set")]
   #[serde(default)]
   pub abilities: HashSet<AbilityID>,
+  #[serde(serialize_with = "serialize_sorted_set")]
   #[serde(default)]
   pub classes: HashSet<ClassID>,
 }
@@ -904,6 +2021,57 @@ impl Folder {
 pub struct ChangedGame {
   pub game: Game,
   pub logs: Vec<GameLog>,
+  /// The current combat creature's movement budget after the change, if there's a combat.
+  #[serde(default)]
+  pub movement: Option<CombatMovement>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TS, Serialize, Deserialize)]
+pub struct CombatMovement {
+  #[ts(type = "number")]
+  pub movement_used: u32units::Length,
+  #[ts(type = "number")]
+  pub movement_left: u32units::Length,
+}
+
+/// An overview of a combat, for showing the initiative order.
+#[derive(Debug, Clone, Eq, PartialEq, TS, Serialize, Deserialize)]
+pub struct CombatSummary {
+  pub scene_id: SceneID,
+  pub current_creature: CreatureID,
+  /// Every combatant, in initiative order.
+  pub combatants: Vec<Combatant>,
+  /// Where the current creature has moved this turn. See `Combat::movement_trail`.
+  pub movement_trail: Vec<Point3>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, TS, Serialize, Deserialize)]
+pub struct Combatant {
+  pub creature_id: CreatureID,
+  pub name: String,
+  /// The initiative the creature rolled when it joined the combat (or was last rerolled/changed).
+  pub initiative: i16,
+}
+
+/// A dangling reference or orphaned object found by `GameExt::integrity_check`.
+#[derive(Debug, Clone, Eq, PartialEq, TS, Serialize, Deserialize)]
+pub enum IntegrityIssue {
+  /// A scene contains a creature that doesn't exist.
+  MissingSceneCreature { scene_id: SceneID, creature_id: CreatureID },
+  /// The combat takes place in a scene that doesn't exist.
+  MissingCombatScene { scene_id: SceneID },
+  /// The combat includes a creature that doesn't exist.
+  MissingCombatant { creature_id: CreatureID },
+  /// A folder lists an object that doesn't exist.
+  MissingFolderItem { path: FolderPath, item_id: FolderItemID },
+  /// A player controls a creature that doesn't exist.
+  MissingPlayerCreature { player_id: PlayerID, creature_id: CreatureID },
+  /// A player is in a scene that doesn't exist.
+  MissingPlayerScene { player_id: PlayerID, scene_id: SceneID },
+  /// A creature isn't in any folder, so the GM can't find it in the campaign.
+  UnfiledCreature { creature_id: CreatureID },
+  /// A scene isn't in any folder, so the GM can't find it in the campaign.
+  UnfiledScene { scene_id: SceneID },
 }
 
 /// Serde Serializer helpers
@@ -916,6 +2084,7 @@ pub struct SerializedGame {
   pub current_combat: Option<Combat>,
   #[ts(type = "GameAbilities")]
   pub abilities: IndexedHashMap<Ability>,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "GameCreatures")]
   pub creatures: HashMap<CreatureID, SerializedCreature>,
   #[ts(type = "GameClasses")]
@@ -934,6 +2103,12 @@ pub struct SerializedGame {
   // goes.
   #[serde(default)]
   pub active_scene: Option<SceneID>,
+  #[serde(default)]
+  pub settings: GameSettings,
+  // Pending commands are left out, since players may only see their own. See
+  // `RPIGameRequest::ListPendingCommands`.
+  #[serde(default)]
+  pub reaction_window: Option<ReactionWindow>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, TS)]
@@ -952,15 +2127,37 @@ pub struct SerializedCreature {
   pub portrait_url: String,
   #[serde(default)]
   pub icon_url: String,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureAttributes")]
   pub attributes: HashMap<AttrID, SkillLevel>,
   pub initiative: Dice,
   pub size: AABB,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[serde(default)]
   #[ts(type = "CreatureInventory")]
   pub inventory: Inventory,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureConditions")]
   pub conditions: HashMap<ConditionID, AppliedCondition>,
+  #[serde(default)]
+  pub immunities: Vec<Condition>,
+  #[serde(default)]
+  pub untargetable_by: Vec<TargetCategory>,
+  #[serde(default)]
+  pub movement_mode: MovementMode,
+  #[serde(default)]
+  pub readied_reaction: Option<AbilityID>,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "Record<AbilityID, Dice>")]
+  pub ability_dice: HashMap<AbilityID, Dice>,
+  #[serde(serialize_with = "serialize_sorted_map")]
+  #[serde(default)]
+  #[ts(type = "Record<AbilityID, number>")]
+  pub ability_uses: HashMap<AbilityID, u32>,
+  #[serde(default = "standard_reach")]
+  #[ts(type = "number")]
+  pub reach: u32units::Length,
 
   // overridden fields
   #[ts(type = "Record<AbilityID, AbilityStatus>")]
@@ -969,8 +2166,10 @@ pub struct SerializedCreature {
   pub speed: u32units::Length,
 
   // synthesized fields
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureConditions")]
   pub own_conditions: HashMap<ConditionID, AppliedCondition>,
+  #[serde(serialize_with = "serialize_sorted_map")]
   #[ts(type = "CreatureConditions")]
   pub volume_conditions: HashMap<ConditionID, AppliedCondition>,
   pub can_act: bool,
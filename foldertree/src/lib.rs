@@ -373,6 +373,9 @@ impl<'a, T: Serialize> Serialize for ChildrenSerializer<'a, T> {
     let children = self.tree.get_children(self.path).map_err(|e| {
       S::Error::custom(format!("BUG: couldn't find child while serializing: {:?}", e))
     })?;
+    // Sort the children so that equal trees always serialize identically.
+    let mut children: Vec<&String> = children.iter().collect();
+    children.sort();
     let mut map = serializer.serialize_map(Some(children.len()))?;
     for child in children {
       let full_path = self.path.child(child.to_string());
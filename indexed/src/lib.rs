@@ -3,6 +3,9 @@ extern crate serde;
 
 use std::{collections::HashMap, fmt, hash, iter::FromIterator};
 
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+
 #[cfg(feature = "serde")]
 use serde::de;
 #[cfg(feature = "serde")]
@@ -45,17 +48,19 @@ where
   fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> { self.data.fmt(f) }
 }
 
+/// Entries are serialized in key order, so equal maps always serialize identically.
 #[cfg(feature = "serde")]
 impl<V> ser::Serialize for IndexedHashMap<V>
 where
   V: DeriveKey + ser::Serialize,
-  <V as DeriveKey>::KeyType: ser::Serialize,
+  <V as DeriveKey>::KeyType: ser::Serialize + Ord,
 {
   fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
   where
     S: ser::Serializer,
   {
-    self.data.serialize(s)
+    let sorted: BTreeMap<_, _> = self.data.iter().collect();
+    ser::Serialize::serialize(&sorted, s)
   }
 }
 
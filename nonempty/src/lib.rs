@@ -164,6 +164,14 @@ impl<T> NonEmptyWithCursor<T> {
   /// ```
   ///
   /// ```
+  /// use nonempty::NonEmptyWithCursor;
+  /// let mut ne = NonEmptyWithCursor::new_with_rest(1, vec![2, 3]);
+  /// ne.set_cursor(2);
+  /// assert_eq!(ne.remove(2).unwrap(), 3);
+  /// assert_eq!(ne.get_cursor(), 0); // Cursor wrapped around to the first element
+  /// ```
+  ///
+  /// ```
   /// use nonempty::{NonEmptyWithCursor, Error};
   /// let mut ne = NonEmptyWithCursor::new(1);
   /// assert_eq!(ne.remove(0), Err(Error::RemoveLastElement))
@@ -172,6 +180,9 @@ impl<T> NonEmptyWithCursor<T> {
     let r = self.data.remove(index)?;
     if index < self.cursor {
       self.cursor -= 1;
+    } else if self.cursor >= self.data.len() {
+      // The current element was the last one, so the cursor wraps around to the start.
+      self.cursor = 0;
     }
     Ok(r)
   }
@@ -155,8 +155,8 @@ export async function requestMove(creature_id: T.CreatureID) {
   if (scene) {
     let scene_id = scene.id;
     const result = await sendRequest(
-      { t: "MovementOptions", scene_id, creature_id },
-      T.arrayOfPoint3,
+      { t: "MovementOptions", scene_id, creature_id, compact: true },
+      T.decodeCompactPoints,
     );
     getState().displayMovementOptions(result, creature_id);
   } else {
@@ -183,6 +183,23 @@ export function moveCreature(creature_id: T.CreatureID, destination: T.Point3) {
   }
 }
 
+// Change the name and color the current player is shown with.
+export function setPlayerDisplay(display_name: string, color: T.Color) {
+  sendPlayerCommand({ t: "SetDisplay", display_name, color });
+}
+
+// Move one of the player's creatures a single cell in some direction.
+export function stepCreature(creature_id: T.CreatureID, direction: T.Direction) {
+  getState().clearMovementOptions();
+  sendPlayerCommand({ t: "StepDirection", creature_id, direction });
+}
+
+// Respond to the action waiting in the game's reaction window with one of the creature's
+// reactions, or pass with null.
+export function react(creature_id: T.CreatureID, ability_id: T.AbilityID | null) {
+  sendPlayerCommand({ t: "React", creature_id, ability_id });
+}
+
 export function setCreaturePos(creature_id: T.CreatureID, pos: T.Point3) {
   getState().clearMovementOptions();
   const scene = getState().getFocusedScene();
@@ -196,8 +213,16 @@ export function moveCombatCreature(destination: T.Point3) {
   sendGMCommand({ t: "PathCurrentCombatCreature", destination });
 }
 
+// List the player commands waiting for GM approval. Players only get their own.
+export async function listPendingCommands(): Promise<T.PendingCommand[]> {
+  return sendRequest({ t: "ListPendingCommands" }, Z.array(T.decodePendingCommand));
+}
+
 export async function requestCombatMovement() {
-  const options = await sendRequest({ t: "CombatMovementOptions" }, T.arrayOfPoint3);
+  const options = await sendRequest(
+    { t: "CombatMovementOptions", compact: true },
+    T.decodeCompactPoints,
+  );
   getState().displayMovementOptions(options);
 }
 
@@ -411,6 +436,93 @@ export async function fetchAbilityTargets(
   };
 }
 
+// Predict what an ability would do to each of its targets, without performing it.
+export async function previewAbility(
+  scene_id: T.SceneID,
+  creature_id: T.CreatureID,
+  ability_id: T.AbilityID,
+  target: T.DecidedTarget,
+): Promise<Array<[T.CreatureID, T.EffectPreview]>> {
+  return sendRequest(
+    { t: "PreviewAbility", scene_id, creature_id, ability_id, target },
+    Z.array(Z.tuple([Z.string(), T.decodeEffectPreview])),
+  );
+}
+
+// Find what an arbitrary volume placed at a point would cover, e.g. to preview an area.
+export async function fetchVolumeContents(
+  scene_id: T.SceneID,
+  point: T.Point3,
+  volume: T.Volume,
+): Promise<{ points: Array<T.Point3>; creatures: Array<T.CreatureID> }> {
+  const result = await sendRequest(
+    { t: "CreaturesAndTerrainInVolume", scene_id, point, volume },
+    Z.tuple([Z.array(Z.string()), Z.array(T.decodePoint3)]),
+  );
+  return {
+    creatures: result[0],
+    points: result[1],
+  };
+}
+
+// Summarize what a creature did on its most recent completed turn.
+export async function fetchLastTurnSummary(creature_id: T.CreatureID): Promise<T.TurnSummary> {
+  return sendRequest({ t: "LastTurnSummary", creature_id }, T.decodeTurnSummary);
+}
+
+// Get the current combat's initiative order, with each combatant's initiative value.
+export async function fetchCombatSummary(): Promise<T.CombatSummary> {
+  return sendRequest({ t: "CombatSummary" }, T.decodeCombatSummary);
+}
+
+// Get the most recent combat, split up into rounds and turns.
+export async function fetchCombatTimeline(): Promise<T.RoundSummary[]> {
+  return sendRequest({ t: "CombatTimeline" }, Z.array(T.decodeRoundSummary));
+}
+
+// List every creature in a scene along with its position.
+export async function fetchSceneCreatures(
+  scene_id: T.SceneID,
+): Promise<Array<[T.CreatureID, T.Point3]>> {
+  return sendRequest(
+    { t: "SceneCreatures", scene_id },
+    Z.array(Z.tuple([Z.string(), T.decodePoint3])),
+  );
+}
+
+// Get everything needed to draw a scene in one request: creatures and their positions, terrain,
+// volume conditions, and highlights. Players only get what they're allowed to see.
+export async function fetchSceneRenderState(scene_id: T.SceneID): Promise<T.SceneRenderState> {
+  return sendRequest({ t: "SceneRenderState", scene_id }, T.decodeSceneRenderState);
+}
+
+// Find out what's at a single cell of a scene: terrain, creatures, and volume conditions.
+export async function fetchCellInfo(scene_id: T.SceneID, point: T.Point3): Promise<T.CellInfo> {
+  return sendRequest({ t: "CellInfo", scene_id, point }, T.decodeCellInfo);
+}
+
+// List every ability in the game along with descriptions of them, sorted by name.
+export async function fetchAbilities(): Promise<Array<[T.AbilityID, T.AbilityDescription]>> {
+  return sendRequest(
+    { t: "ListAbilities" },
+    Z.array(Z.tuple([Z.string(), T.decodeAbilityDescription])),
+  );
+}
+
+// Get a page of the game's creatures for the roster, in an order that's the same every time.
+export async function fetchCreaturePage(
+  offset: number,
+  limit: number,
+  sort: T.CreatureSort,
+): Promise<T.CreatureID[]> {
+  return sendRequest({ t: "ListCreatures", offset, limit, sort }, Z.array(Z.string()));
+}
+
+// Get the game as it was right after the log at some index was applied.
+export async function fetchGameAtIndex(index: T.GameIndex): Promise<T.Game> {
+  return sendRequest({ t: "GMGameAtIndex", index }, T.decodeGame);
+}
+
 export async function createGame(name: string): Promise<T.GameID> {
   const result = await ptfetch("/g/create", {
     method: "POST",
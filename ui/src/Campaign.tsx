@@ -334,6 +334,9 @@ function FolderMenu({ path }: { path: T.FolderPath }) {
                   },
                 },
                 usable_ooc: false,
+                action_kind: "Action",
+                attack_roll: null,
+                uses_per_rest: null,
               },
             });
             const createAbilityLog = result.find(log => log.t === "CreateAbility");
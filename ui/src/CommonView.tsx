@@ -437,8 +437,14 @@ export function conditionIcon(cond: T.Condition): string {
   if (cond === "Dead") return "💀";
   if (cond === "Incapacitated") return "😞";
   if (cond === "DoubleMaxMovement") return "🏃";
+  if (cond === "Summoned") return "✨";
   if ("RecurringEffect" in cond) return "🔁";
   if ("AddDamageBuff" in cond) return "😈";
+  if ("ExtraMovement" in cond) return "🏃";
+  if ("Slowed" in cond) return "🐌";
+  if ("Hastened" in cond) return "⚡";
+  if ("ReduceDamage" in cond) return "🛡️";
+  if ("ProvideDefense" in cond) return "🧱";
   if ("ActivateAbility" in cond) {
     return "Ability Activated: " + cond.ActivateAbility;
   }
@@ -557,6 +563,7 @@ export function Combat({ card, initiative }: CombatProps): JSX.Element {
     }) as Array<[T.Creature, number]>;
   });
   const currentPosition = M.useState(s => s.getCombat()?.creatures.cursor);
+  const players = M.useState(s => s.game.players);
   if (!creaturesWithInit) return <div>No combat</div>;
 
   const Card = card ? card : CreatureCard;
@@ -564,6 +571,10 @@ export function Combat({ card, initiative }: CombatProps): JSX.Element {
     <Segment.Group>
       {creaturesWithInit.map(([creature, init], index) => {
         const show_init = initiative ? initiative(creature.id, init) : null;
+        const controllers = players
+          .valueSeq()
+          .filter(p => p.creatures.includes(creature.id))
+          .toArray();
         return (
           <Segment.Group key={creature.id} horizontal={true}>
             <Segment
@@ -583,6 +594,17 @@ export function Combat({ card, initiative }: CombatProps): JSX.Element {
               <div>{show_init}</div>
             </Segment>
             <Card creature={creature} />
+            {controllers.length > 0
+              && (
+                <Segment compact={true}>
+                  {controllers.map((controller, i) => (
+                    <span key={controller.player_id}>
+                      {i > 0 && ", "}
+                      <PlayerName playerId={controller.player_id} />
+                    </span>
+                  ))}
+                </Segment>
+              )}
           </Segment.Group>
         );
       })}
@@ -732,6 +754,24 @@ export function ErrorModal() {
   }
 }
 
+export function TurnAlertModal() {
+  const creature = M.useState(s => s.turnAlert ? s.getCreature(s.turnAlert) : undefined);
+  const clearTurnAlert = () => M.getState().clearTurnAlert();
+  if (creature) {
+    return (
+      <Modal dimmer="inverted" open={true} onClose={clearTurnAlert}>
+        <Modal.Header>Your turn</Modal.Header>
+        <Modal.Content>
+          <div>It's {creature.name}'s turn!</div>
+          <Button onClick={clearTurnAlert}>Ok</Button>
+        </Modal.Content>
+      </Modal>
+    );
+  } else {
+    return null;
+  }
+}
+
 interface TheLayoutProps {
   tabs: Array<JSX.Element>;
   bottom_left?: JSX.Element;
@@ -798,6 +838,7 @@ export function TheLayout(props: TheLayoutProps) {
         ? wideView()
         : narrowView(window_size.width)}
       <ErrorModal />
+      <TurnAlertModal />
     </div>
   );
 
@@ -1164,14 +1205,26 @@ export function GenericChat(props: GenericChatProps): JSX.Element {
 
 export function ChatLog({ log }: { log: T.GameLog }) {
   if (log.t === "ChatFromPlayer" || log.t === "ChatFromGM") {
-    const sender = log.t === "ChatFromPlayer" ? log.player_id : "GM";
+    const sender = log.t === "ChatFromPlayer"
+      ? <PlayerName playerId={log.player_id} />
+      : <strong>GM</strong>;
     const message = log.message;
     return (
       <span>
         &lt;
-        <strong>{sender}</strong>
+        {sender}
         &gt; {message}
       </span>
     );
   }
 }
+
+/** A player's display name, in their color. */
+export function PlayerName({ playerId }: { playerId: T.PlayerID }) {
+  const player = M.useState(s => s.game.players.get(playerId));
+  return (
+    <strong style={player?.color ? { color: player.color } : undefined}>
+      {player?.display_name || playerId}
+    </strong>
+  );
+}
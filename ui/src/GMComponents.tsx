@@ -240,9 +240,16 @@ function GMCombatHeader() {
         >
           {sceneName}
         </a>
-        <Button onClick={() => A.sendGMCommand({ t: "StopCombat" })}>
+        <Button
+          onClick={() => A.sendGMCommand({ t: "StopCombat", preserve_initiative: false })}
+        >
           Stop combat
         </Button>
+        <Button
+          onClick={() => A.sendGMCommand({ t: "StopCombat", preserve_initiative: true })}
+        >
+          Pause combat
+        </Button>
       </div>
     </Segment>
   );
@@ -388,7 +395,7 @@ export function CreateCreature(props: GMCreateCreatureProps) {
       bio: "",
       portrait_url: "",
       initiative: { Expr: { num: 1, size: 20 } },
-      size: { x: 1, y: 1, z: 1 },
+      size: { x: 1, y: 1, z: 1, anchor: "TopLeft" },
       icon_url: "",
     };
 
@@ -580,7 +587,7 @@ function EditCreatureForm(props: EditCreatureProps) {
       note,
       bio,
       initiative: Dice.parse(initiativeString),
-      size: { x: size, y: size, z: size },
+      size: { x: size, y: size, z: size, anchor: "TopLeft" },
       icon_url: iconUrl,
       max_health: Number(maxHealth),
       cur_health: Number(curHealth),
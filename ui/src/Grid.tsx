@@ -267,6 +267,7 @@ function TargetTiles() {
           "SomeCreaturesInVolumeInRange" in creatureTarget
           || "AllCreaturesInVolumeInRange" in creatureTarget
           || "LineFromActor" in creatureTarget
+          || "ConeFromActor" in creatureTarget
         )
       ) {
         // we good
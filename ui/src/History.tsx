@@ -66,6 +66,24 @@ export function GameLog(props: {
     case "SetPlayerScene": {
       return <div>Moved {log.player_id} to a scene</div>;
     }
+    case "SetPlayerDisplay":
+      return <div>Changed how {log.player_id} is displayed</div>;
+    case "EditGameSettings":
+      return <div>Changed the game settings</div>;
+    case "AddPendingCommand":
+      return <div>{log.pending.player_id} is waiting for approval</div>;
+    case "ResolvePendingCommand":
+      return <div>{log.approved ? "Approved" : "Denied"} a player's command</div>;
+    case "PendingCommandExpired":
+      return <div>A player's command expired without approval</div>;
+    case "OpenReactionWindow":
+      return <div>Waiting for reactions</div>;
+    case "Reacted": {
+      const creature = creatures.get(log.creature_id);
+      return <div>{creature ? creature.name : "A creature"} reacted</div>;
+    }
+    case "CloseReactionWindow":
+      return <div>Done waiting for reactions</div>;
     case "ChatFromGM":
       return <div>&lt;GM&gt;&nbsp;{log.message}</div>;
     case "ChatFromPlayer": {
@@ -105,6 +123,20 @@ export function GameLog(props: {
         </div>
       );
     }
+    case "LinkFolderItem": {
+      return (
+        <div>
+          Added folder item to {T.folderPathToString(log.path)}
+        </div>
+      );
+    }
+    case "UnlinkFolderItem": {
+      return (
+        <div>
+          Removed folder item from {T.folderPathToString(log.path)}
+        </div>
+      );
+    }
     case "RenameFolderItem": {
       return (
         <div>
@@ -165,6 +197,14 @@ export function GameLog(props: {
     case "RemoveCreatureFromScene": {
       return <div>Removed a creature from a scene</div>;
     }
+    case "CreatureEnteredScene": {
+      const creature = creatures.get(log.creature_id);
+      return <div>{creature ? creature.name : "A creature"} entered a scene</div>;
+    }
+    case "CreatureLeftScene": {
+      const creature = creatures.get(log.creature_id);
+      return <div>{creature ? creature.name : "A creature"} left a scene</div>;
+    }
     case "AddSceneChallenge": {
       return (
         <div>
@@ -187,6 +227,10 @@ export function GameLog(props: {
     }
     case "EditSceneTerrain":
       return <div>Edited a scene's terrain</div>;
+    case "EditSceneTerrainCosts":
+      return <div>Edited a scene's difficult terrain</div>;
+    case "SetSceneGridBounds":
+      return <div>Changed a scene's grid bounds</div>;
     case "EditSceneHighlights": {
       return <div>Edited a scene's highlights</div>;
     }
@@ -199,6 +243,12 @@ export function GameLog(props: {
     case "EditSceneSceneHotspots": {
       return <div>Edited a scene's hotspots that linked to scenes</div>;
     }
+    case "SnapshotScene": {
+      return <div>Saved scene checkpoint {log.name}</div>;
+    }
+    case "RestoreScene": {
+      return <div>Restored scene checkpoint {log.name}</div>;
+    }
     case "SetCreaturePos": {
       return <div>Set a creature position to {log.pos.toString()}</div>;
     }
@@ -207,6 +257,25 @@ export function GameLog(props: {
       const msg = last ? last.toString() : "Nowhere";
       return <div>Creature followed a path to {msg}</div>;
     }
+    case "CreatureUsedAbility": {
+      const creature = creatures.get(log.creature_id);
+      return <div>{creature ? creature.name : "A creature"} used an ability</div>;
+    }
+    case "AttackRolled": {
+      const creature = creatures.get(log.creature_id);
+      const target = creatures.get(log.target_id);
+      return (
+        <div>
+          {creature ? creature.name : "A creature"} rolled {log.roll} against{" "}
+          {target ? target.name : "a creature"}'s defense of {log.defense}:{" "}
+          {log.hit ? "hit" : "miss"}
+        </div>
+      );
+    }
+    case "TurnBegan": {
+      const creature = creatures.get(log.creature_id);
+      return <div>It's {creature ? creature.name : "a creature"}'s turn</div>;
+    }
     case "CreateCreature": {
       return <div>Created a creature {log.creature.name}</div>;
     }
@@ -216,8 +285,13 @@ export function GameLog(props: {
     case "EditCreature": {
       return <div>Edited a creature {log.creature.name}</div>;
     }
-    case "StartCombat":
-      return <div>Started combat</div>;
+    case "StartCombat": {
+      const rolls = log.combatants.map(([creatureId, initiative]) => {
+        const creature = creatures.get(creatureId);
+        return `${creature ? creature.name : "A creature"} (${initiative})`;
+      });
+      return <div>Started combat: {rolls.join(", ")}</div>;
+    }
     case "AddCreatureToCombat": {
       return <div>Added a creature to combat</div>;
     }
@@ -323,6 +397,18 @@ export function CreatureLog(props: { creatureId: T.CreatureID; log: T.CreatureLo
     case "RemoveCondition": {
       return <div>{creature_name} lost a condition.</div>;
     }
+    case "ReadyReaction": {
+      if (log.ability_id) {
+        return <div>{creature_name} readied a reaction.</div>;
+      }
+      return <div>{creature_name} no longer has a readied reaction.</div>;
+    }
+    case "UseAbility": {
+      return <div>{creature_name} used up a use of an ability.</div>;
+    }
+    case "RechargeAbilities": {
+      return <div>{creature_name} rested and recharged their abilities.</div>;
+    }
   }
   M.assertNever(log);
 }
@@ -323,10 +323,17 @@ const defaultGrid = {
 interface PlayerState {
   playerId: T.PlayerID | undefined;
   setPlayerId: (id: T.PlayerID | undefined) => void;
+  // A creature controlled by this player whose turn just began, until the player dismisses it.
+  turnAlert: T.CreatureID | undefined;
+  setTurnAlert: (cid: T.CreatureID) => void;
+  clearTurnAlert: () => void;
 }
 const playerSlice: Slice<PlayerState> = set => ({
   playerId: undefined,
   setPlayerId: playerId => set(() => ({ playerId })),
+  turnAlert: undefined,
+  setTurnAlert: turnAlert => set(() => ({ turnAlert })),
+  clearTurnAlert: () => set(() => ({ turnAlert: undefined })),
 });
 
 interface ErrorState {
@@ -5,29 +5,41 @@ import type {
   AABB,
   Ability,
   AbilityCreation,
+  AbilityDescription,
   AbilityID,
   AbilityStatus,
   Action,
+  ActionKind,
   AppliedCondition,
+  AttackRoll,
   AttributeCheck,
   AttrID,
+  CellInfo,
   ChangedGame,
   Class,
   ClassCreation,
   ClassID,
   Combat,
   CombatLog,
+  CombatSummary,
+  Combatant,
   Condition,
   ConditionID,
+  ConditionSelector,
+  Cover,
+  CreatureCheckpoint,
   CreatureCreation,
   CreatureData,
   CreatureEffect,
   CreatureID,
   CreatureLog,
+  CreatureSort,
   CreatureTarget,
   DecidedTarget,
   Dice,
+  Direction,
   Duration,
+  EffectPreview,
   Energy,
   FolderItemID,
   FolderNode,
@@ -40,6 +52,7 @@ import type {
   GameLog,
   GameMetadata,
   GameProfile,
+  GameSettings,
   GMCommand,
   HP,
   InventoryOwner,
@@ -47,21 +60,35 @@ import type {
   Item,
   ItemID,
   ModuleSource,
+  MovementMode,
   Note,
+  PendingCommand,
   Player,
   PlayerCommand,
   PlayerID,
   PotentialTargets,
+  ReactionWindow,
+  RestKind,
+  RestPolicy,
+  RestRecovery,
   Role,
+  RollRange,
+  RoundSummary,
+  RoundTurn,
   RPIGameRequest,
   Scene,
+  SceneCheckpoint,
   SceneCreation,
   SceneEffect,
   SceneID,
+  SceneRenderState,
   SceneTarget,
   SkillLevel,
+  TargetCategory,
   TileSystem,
+  TurnSummary,
   UserID,
+  VettingPolicy,
   Visibility,
   Volume,
   VolumeCondition,
@@ -70,29 +97,41 @@ export {
   AABB,
   Ability,
   AbilityCreation,
+  AbilityDescription,
   AbilityID,
   AbilityStatus,
   Action,
+  ActionKind,
   AppliedCondition,
+  AttackRoll,
   AttributeCheck,
   AttrID,
+  CellInfo,
   ChangedGame,
   Class,
   ClassCreation,
   ClassID,
   Combat,
   CombatLog,
+  CombatSummary,
+  Combatant,
   Condition,
   ConditionID,
+  ConditionSelector,
+  Cover,
+  CreatureCheckpoint,
   CreatureCreation,
   CreatureData,
   CreatureEffect,
   CreatureID,
   CreatureLog,
+  CreatureSort,
   CreatureTarget,
   DecidedTarget,
   Dice,
+  Direction,
   Duration,
+  EffectPreview,
   Energy,
   FolderItemID,
   FolderNode,
@@ -105,6 +144,7 @@ export {
   GameLog,
   GameMetadata,
   GameProfile,
+  GameSettings,
   GMCommand,
   HP,
   InventoryOwner,
@@ -112,21 +152,35 @@ export {
   Item,
   ItemID,
   ModuleSource,
+  MovementMode,
   Note,
+  PendingCommand,
   Player,
   PlayerCommand,
   PlayerID,
   PotentialTargets,
+  ReactionWindow,
+  RestKind,
+  RestPolicy,
+  RestRecovery,
   Role,
+  RollRange,
+  RoundSummary,
+  RoundTurn,
   RPIGameRequest,
   Scene,
+  SceneCheckpoint,
   SceneCreation,
   SceneEffect,
   SceneID,
+  SceneRenderState,
   SceneTarget,
   SkillLevel,
+  TargetCategory,
   TileSystem,
+  TurnSummary,
   UserID,
+  VettingPolicy,
   Visibility,
   Volume,
   VolumeCondition,
@@ -141,6 +195,7 @@ export type Distance = number;
 // Various types that aren't the obvious types that ts-rs would generate for a
 // Rust type, mostly because we use immutablejs for a bunch of stuff.
 export type Terrain = Set<Point3>;
+export type TerrainCosts = Map<Point3, number>;
 export type SceneHotspots = Map<Point3, SceneID>;
 export type Highlights = Map<Point3, [Color, Visibility]>;
 export type Annotations = Map<Point3, [string, Visibility]>;
@@ -215,6 +270,27 @@ export function parsePoint3(str: string): Point3 {
 export const decodePoint3 = Z.string().transform(parsePoint3);
 export const arrayOfPoint3 = Z.array(decodePoint3);
 
+// Decode a `CompactPoints` bitset into the points it contains.
+export const decodeCompactPoints: Decoder<Point3[]> = Z.object({
+  origin: decodePoint3,
+  size: Z.tuple([Z.number(), Z.number(), Z.number()]),
+  bits: Z.array(Z.number()),
+}).transform(({ origin, size: [sizeX, sizeY, sizeZ], bits }) => {
+  const points: Point3[] = [];
+  let idx = 0;
+  for (let x = 0; x < sizeX; x++) {
+    for (let y = 0; y < sizeY; y++) {
+      for (let z = 0; z < sizeZ; z++) {
+        if (bits[idx >> 3] & (1 << (idx & 7))) {
+          points.push(new Point3(origin.x + x * 100, origin.y + y * 100, origin.z + z * 100));
+        }
+        idx++;
+      }
+    }
+  }
+  return points;
+});
+
 export const decodePotentialTargets: Decoder<PotentialTargets> = Z.union([
   Z.object({ CreatureIDs: Z.array(Z.string()) }),
   Z.object({ Points: Z.array(decodePoint3) }),
@@ -234,20 +310,51 @@ const decodeDuration: Decoder<Duration> = Z.union([
   Z.object({ Rounds: Z.number() }),
 ]);
 
+const decodeConditionSelector: Decoder<ConditionSelector> = Z.union([
+  Z.literal("AnyRecurringEffect"),
+  Z.object({ Exactly: Z.lazy(() => decodeCondition) }),
+]);
+
+const decodeTransferCondition = Z.object({
+  TransferCondition: Z.object({
+    which: decodeConditionSelector,
+    to_target: Z.boolean().default(false),
+  }),
+});
+
 const decodeEffect: Decoder<CreatureEffect> = Z.union([
   Z.object({ ApplyCondition: Z.tuple([decodeDuration, Z.lazy(() => decodeCondition)]) }),
   Z.object({ Damage: decodeDice }),
   Z.object({ GenerateEnergy: Z.number() }),
+  Z.object({ GrantMovement: Z.number() }),
   Z.object({ Heal: decodeDice }),
   Z.object({ MultiEffect: Z.array(Z.lazy(() => decodeEffect)) }),
+  decodeTransferCondition,
+]);
+
+const decodeTargetCategory: Decoder<TargetCategory> = Z.union([
+  Z.literal("Healing"),
+  Z.literal("Damage"),
+  Z.literal("Conditions"),
+]);
+
+const decodeMovementMode: Decoder<MovementMode> = Z.union([
+  Z.literal("Ground"),
+  Z.literal("Flying"),
 ]);
 
 const decodeCondition: Decoder<Condition> = Z.union([
   Z.literal("Dead"),
   Z.literal("DoubleMaxMovement"),
   Z.literal("Incapacitated"),
+  Z.literal("Summoned"),
   Z.object({ ActivateAbility: Z.string() }),
+  Z.object({ ExtraMovement: Z.number() }),
+  Z.object({ Slowed: Z.number() }),
+  Z.object({ Hastened: Z.number() }),
   Z.object({ RecurringEffect: decodeEffect }),
+  Z.object({ ReduceDamage: Z.number() }),
+  Z.object({ ProvideDefense: Z.number() }),
 ]);
 
 const decodeAppliedCondition: Decoder<AppliedCondition> = Z.object({
@@ -255,6 +362,53 @@ const decodeAppliedCondition: Decoder<AppliedCondition> = Z.object({
   condition: decodeCondition,
 });
 
+const decodeRollRange: Decoder<RollRange> = Z.object({
+  min: Z.number(),
+  max: Z.number(),
+  average: Z.number(),
+});
+
+const decodeCover: Decoder<Cover> = Z.union([
+  Z.literal("None"),
+  Z.literal("Half"),
+  Z.literal("ThreeQuarters"),
+  Z.literal("Total"),
+]);
+
+export const decodeEffectPreview: Decoder<EffectPreview> = Z.object({
+  damage: decodeRollRange.nullable(),
+  healing: decodeRollRange.nullable(),
+  energy: Z.number(),
+  conditions: Z.array(decodeAppliedCondition),
+  cover: decodeCover.default("None"),
+});
+
+export const decodeTurnSummary: Decoder<TurnSummary> = Z.object({
+  moved: Z.tuple([decodePoint3, decodePoint3]).nullable(),
+  abilities: Z.array(Z.string()),
+  damage_dealt: Z.number(),
+  damage_taken: Z.number(),
+});
+
+export const decodeCellInfo: Decoder<CellInfo> = Z.object({
+  open: Z.boolean(),
+  creatures: Z.array(Z.string()),
+  volume_conditions: Z.array(Z.string()),
+});
+
+export const decodeCombatant: Decoder<Combatant> = Z.object({
+  creature_id: Z.string(),
+  name: Z.string(),
+  initiative: Z.number(),
+});
+
+export const decodeCombatSummary: Decoder<CombatSummary> = Z.object({
+  scene_id: Z.string(),
+  current_creature: Z.string(),
+  combatants: Z.array(decodeCombatant),
+  movement_trail: Z.array(decodePoint3),
+});
+
 export const decodeSkillLevel: Decoder<SkillLevel> = Z.union([
   Z.literal("Inept"),
   Z.literal("Unskilled"),
@@ -272,15 +426,19 @@ const decodeAABB: Decoder<AABB> = Z.object({
   x: Z.number(),
   y: Z.number(),
   z: Z.number(),
+  anchor: Z.union([Z.literal("TopLeft"), Z.literal("Center")]).default("TopLeft"),
 });
 
 const decodeCommonCreatureData = {
   id: Z.string(),
   name: Z.string(),
   speed: Z.number(),
+  reach: Z.number().default(150),
   max_energy: Z.number(),
   cur_energy: Z.number(),
   abilities: Z.record(decodeAbilityStatus),
+  ability_dice: Z.record(decodeDice).default({}),
+  ability_uses: Z.record(Z.number()).default({}),
   class: Z.string(),
   max_health: Z.number(),
   cur_health: Z.number(),
@@ -292,6 +450,10 @@ const decodeCommonCreatureData = {
   initiative: decodeDice,
   inventory: Z.record(Z.number()).transform<Creature["inventory"]>(Map),
   conditions: Z.record(decodeAppliedCondition).transform<Creature["own_conditions"]>(Map),
+  immunities: Z.array(decodeCondition).default([]),
+  untargetable_by: Z.array(decodeTargetCategory).default([]),
+  movement_mode: decodeMovementMode.default("Ground"),
+  readied_reaction: Z.string().nullable().default(null),
   size: decodeAABB,
 };
 
@@ -341,6 +503,9 @@ const decodeVolume: Decoder<Volume> = Z.union([
   Z.object({ Line: Z.object({ vector: decodePoint3 }) }),
   Z.object({ VerticalCylinder: Z.object({ radius: Z.number(), height: Z.number() }) }),
   Z.object({ AABB: decodeAABB }),
+  Z.object({
+    Cone: Z.object({ direction: decodePoint3, length: Z.number(), angle_degrees: Z.number() }),
+  }),
 ]);
 
 export const decodeVolumeCondition: Decoder<VolumeCondition> = Z.object({
@@ -350,17 +515,37 @@ export const decodeVolumeCondition: Decoder<VolumeCondition> = Z.object({
   condition: decodeCondition,
 });
 
-const decodeTerrain: Decoder<Terrain> = decodeSet(decodePoint3);
+// Terrain is usually sent as runs of open cells stepping 1 meter along the y axis, but the
+// plain list of points is still accepted.
+const decodeTerrain: Decoder<Terrain> = Z.union([
+  decodeSet(decodePoint3),
+  Z.object({ runs: Z.array(Z.tuple([decodePoint3, Z.number()])) }).transform(({ runs }) =>
+    Set(runs.flatMap(([start, len]) =>
+      Array.from({ length: len }, (_, i) => new Point3(start.x, start.y + i * 100, start.z))
+    ))
+  ),
+]);
+const decodeTerrainCosts: Decoder<TerrainCosts> = decodeIMap(decodePoint3, Z.number());
 const decodeHighlights: Decoder<Highlights> = decodeIMap(
   decodePoint3,
   Z.tuple([Z.string(), decodeVisibility]),
 );
 const decodeAnnotations: Decoder<Annotations> = decodeHighlights;
 
+const decodeSceneCheckpoint: Decoder<SceneCheckpoint> = Z.object({
+  creatures: Z.record(Z.object({
+    pos: decodePoint3,
+    cur_health: Z.number(),
+    cur_energy: Z.number(),
+    conditions: Z.record(decodeAppliedCondition).transform<CreatureCheckpoint["conditions"]>(Map),
+  })),
+});
+
 export const decodeScene: Decoder<Scene> = Z.object({
   id: Z.string(),
   name: Z.string(),
   terrain: decodeTerrain,
+  terrain_costs: decodeTerrainCosts.default({}),
   highlights: decodeHighlights,
   annotations: decodeAnnotations,
   scene_hotspots: decodeIMap(decodePoint3, Z.string()),
@@ -373,6 +558,18 @@ export const decodeScene: Decoder<Scene> = Z.object({
   background_image_scale: Z.tuple([Z.number(), Z.number()]),
   volume_conditions: Z.record(decodeVolumeCondition).transform<Scene["volume_conditions"]>(Map),
   focused_creatures: Z.array(Z.string()).transform<Scene["focused_creatures"]>(List),
+  preserved_initiative: Z.array(Z.tuple([Z.string(), Z.number()])).default([]),
+  checkpoints: Z.record(decodeSceneCheckpoint).default({}),
+  grid_bounds: Z.tuple([decodePoint3, decodePoint3]).nullable().default(null),
+});
+
+export const decodeSceneRenderState: Decoder<SceneRenderState> = Z.object({
+  creatures: Z.array(Z.tuple([Z.string(), decodePoint3])),
+  terrain: decodeTerrain,
+  terrain_costs: decodeTerrainCosts,
+  volume_conditions: Z.record(decodeVolumeCondition)
+    .transform<SceneRenderState["volume_conditions"]>(Map),
+  highlights: decodeHighlights,
 });
 
 const decodeFolderItemID: Decoder<FolderItemID> = Z.union([
@@ -419,6 +616,8 @@ const decodePlayer: Decoder<Player> = Z.object({
   player_id: Z.string(),
   scene: Z.string().nullable(),
   creatures: Z.array(Z.string()),
+  display_name: Z.string().default(""),
+  color: Z.string().default(""),
 });
 
 export const decodeClass: Decoder<Class> = Z.object({
@@ -451,6 +650,8 @@ const decodeCombat: Decoder<Combat> = Z.object({
   scene: Z.string(),
   creatures: decodeNonEmpty(Z.tuple([Z.string(), Z.number()])),
   movement_used: Z.number(),
+  movement_trail: Z.array(decodePoint3).default([]),
+  round: Z.number().default(1),
 });
 
 const decodeFolderNode: Decoder<FolderNode> = Z.object({
@@ -485,6 +686,7 @@ const decodeCreatureTarget: Decoder<CreatureTarget> = Z.union([
     }),
   }),
   Z.object({ LineFromActor: Z.object({ distance: Z.number() }) }),
+  Z.object({ ConeFromActor: Z.object({ length: Z.number(), angle_degrees: Z.number() }) }),
 ]);
 
 export const decodeSceneTarget: Decoder<SceneTarget> =
@@ -498,25 +700,52 @@ const decodeCreatureEffect: Decoder<CreatureEffect> = Z.union([
   Z.object({ Damage: decodeDice }),
   // GenerateEnergy should be dice, not number...
   Z.object({ GenerateEnergy: Z.number() }),
+  Z.object({ GrantMovement: Z.number() }),
   Z.object({ MultiEffect: Z.array(Z.lazy(() => decodeCreatureEffect)) }),
+  decodeTransferCondition,
 ]);
-const decodeSceneEffect: Decoder<SceneEffect> = // Z.union([
+const decodeSceneEffect: Decoder<SceneEffect> = Z.union([
   Z.object({
     CreateVolumeCondition: Z.object({ duration: decodeDuration, condition: decodeCondition }),
-  });
-// ]);
+  }),
+  Z.object({ Summon: Z.object({ template: Z.string(), duration: decodeDuration }) }),
+  Z.literal("Teleport"),
+]);
 
 export const decodeAction: Decoder<Action> = Z.union([
   Z.object({ Creature: Z.object({ effect: decodeCreatureEffect, target: decodeCreatureTarget }) }),
   Z.object({ SceneVolume: Z.object({ effect: decodeSceneEffect, target: decodeSceneTarget }) }),
 ]);
 
+const decodeActionKind: Decoder<ActionKind> = Z.union([
+  Z.literal("Action"),
+  Z.literal("BonusAction"),
+  Z.literal("Reaction"),
+]);
+
+const decodeRestKind: Decoder<RestKind> = Z.union([Z.literal("Short"), Z.literal("Long")]);
+
+const decodeAttackRoll: Decoder<AttackRoll> = Z.object({
+  dice: decodeDice,
+  miss_effect: decodeCreatureEffect.nullable().default(null),
+});
+
 export const decodeAbility: Decoder<Ability> = Z.object({
   name: Z.string(),
   id: Z.string(),
   action: decodeAction,
   cost: Z.number(),
   usable_ooc: Z.boolean(),
+  action_kind: decodeActionKind.default("Action"),
+  attack_roll: decodeAttackRoll.nullable().default(null),
+  uses_per_rest: Z.tuple([Z.number(), decodeRestKind]).nullable().default(null),
+});
+
+export const decodeAbilityDescription: Decoder<AbilityDescription> = Z.object({
+  name: Z.string(),
+  range: Z.number().nullable(),
+  targeting: Z.string(),
+  effect: Z.string(),
 });
 
 const decodeAbilityCreation: Decoder<AbilityCreation> = Z.object({
@@ -524,11 +753,104 @@ const decodeAbilityCreation: Decoder<AbilityCreation> = Z.object({
   action: decodeAction,
   cost: Z.number(),
   usable_ooc: Z.boolean(),
+  action_kind: decodeActionKind.default("Action"),
+  attack_roll: decodeAttackRoll.nullable().default(null),
+  uses_per_rest: Z.tuple([Z.number(), decodeRestKind]).nullable().default(null),
+});
+
+const decodeDecidedTarget: Decoder<DecidedTarget> = Z.union([
+  Z.literal("Actor"),
+  Z.object({ Creature: Z.string() }),
+  Z.object({ Creatures: Z.array(Z.string()) }),
+  Z.object({ Point: decodePoint3 }),
+]);
+
+const decodeDirection: Decoder<Direction> = Z.union([
+  Z.literal("North"),
+  Z.literal("NorthEast"),
+  Z.literal("East"),
+  Z.literal("SouthEast"),
+  Z.literal("South"),
+  Z.literal("SouthWest"),
+  Z.literal("West"),
+  Z.literal("NorthWest"),
+]);
+
+const decodePlayerCommand: Decoder<PlayerCommand> = Z.discriminatedUnion("t", [
+  Z.object({ t: Z.literal("ChatFromPlayer"), message: Z.string() }),
+  Z.object({ t: Z.literal("CreateNote"), path: decodeFolderPath, note: decodeNote }),
+  Z.object({
+    t: Z.literal("EditNote"),
+    path: decodeFolderPath,
+    original_name: Z.string(),
+    note: decodeNote,
+  }),
+  Z.object({ t: Z.literal("SetDisplay"), display_name: Z.string(), color: Z.string() }),
+  Z.object({ t: Z.literal("PathCreature"), creature_id: Z.string(), destination: decodePoint3 }),
+  Z.object({ t: Z.literal("StepDirection"), creature_id: Z.string(), direction: decodeDirection }),
+  Z.object({ t: Z.literal("CombatAct"), ability_id: Z.string(), target: decodeDecidedTarget }),
+  Z.object({ t: Z.literal("PathCurrentCombatCreature"), destination: decodePoint3 }),
+  Z.object({ t: Z.literal("EndTurn") }),
+  Z.object({
+    t: Z.literal("React"),
+    creature_id: Z.string(),
+    ability_id: Z.string().nullable(),
+  }),
+]);
+
+export const decodePendingCommand: Decoder<PendingCommand> = Z.object({
+  id: Z.string(),
+  player_id: Z.string(),
+  command: decodePlayerCommand,
+  submitted_at: Z.number(),
+});
+
+const decodeReactionWindow: Decoder<ReactionWindow> = Z.object({
+  scene_id: Z.string(),
+  creature_id: Z.string(),
+  ability_id: Z.string(),
+  target: decodeDecidedTarget,
+  in_combat: Z.boolean(),
+  reactors: Z.array(Z.string()),
+  opened_at: Z.number(),
+});
+
+const decodeVettingPolicy: Decoder<VettingPolicy> = Z.union([
+  Z.literal("Never"),
+  Z.literal("AllActions"),
+  Z.literal("NPCTargetingActions"),
+]);
+
+const decodeRestRecovery: Decoder<RestRecovery> = Z.object({
+  health_percent: Z.number(),
+  energy_percent: Z.number(),
+});
+
+const defaultRestPolicy: RestPolicy = {
+  short: { health_percent: 50, energy_percent: 50 },
+  long: { health_percent: 100, energy_percent: 100 },
+};
+
+const decodeRestPolicy: Decoder<RestPolicy> = Z.object({
+  short: decodeRestRecovery,
+  long: decodeRestRecovery,
+});
+
+const decodeGameSettings: Decoder<GameSettings> = Z.object({
+  vetting_policy: decodeVettingPolicy.default("Never"),
+  pending_command_timeout: Z.number().nullable().default(null),
+  attribute_modifiers: Z.array(Z.tuple([decodeSkillLevel, Z.number()])).default([]),
+  auto_remove_dead: Z.boolean().default(false),
+  rest_policy: decodeRestPolicy.default(defaultRestPolicy),
+  massive_damage_threshold: Z.number().nullable().default(null),
+  reaction_window: Z.number().nullable().default(null),
 });
 
 const decodeTileSystem: Decoder<TileSystem> = Z.union([
   Z.literal("Realistic"),
   Z.literal("DnD"),
+  Z.literal("AlternatingDiagonal"),
+  Z.literal("Orthogonal"),
 ]);
 
 export const decodeGame: Decoder<Game> = Z.object({
@@ -542,6 +864,16 @@ export const decodeGame: Decoder<Game> = Z.object({
   players: Z.record(decodePlayer).transform<Game["players"]>(Map),
   tile_system: decodeTileSystem,
   active_scene: Z.string().nullable(),
+  settings: decodeGameSettings.default({
+    vetting_policy: "Never",
+    pending_command_timeout: null,
+    attribute_modifiers: [],
+    auto_remove_dead: false,
+    rest_policy: defaultRestPolicy,
+    massive_damage_threshold: null,
+    reaction_window: null,
+  }),
+  reaction_window: decodeReactionWindow.nullable().default(null),
 });
 
 const decodeCreatureLog: Decoder<CreatureLog> = Z.discriminatedUnion("t", [
@@ -557,6 +889,9 @@ const decodeCreatureLog: Decoder<CreatureLog> = Z.discriminatedUnion("t", [
   }),
   Z.object({ t: Z.literal("DecrementConditionRemaining"), id: Z.string() }),
   Z.object({ t: Z.literal("RemoveCondition"), id: Z.string() }),
+  Z.object({ t: Z.literal("ReadyReaction"), ability_id: Z.string().nullable() }),
+  Z.object({ t: Z.literal("UseAbility"), ability_id: Z.string() }),
+  Z.object({ t: Z.literal("RechargeAbilities"), ability_ids: Z.array(Z.string()) }),
 ]);
 
 const decodeCombatLog: Decoder<CombatLog> = Z.discriminatedUnion("t", [
@@ -576,7 +911,7 @@ const decodeCombatLog: Decoder<CombatLog> = Z.discriminatedUnion("t", [
 ]);
 
 export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
-  Z.object({ t: Z.literal("StopCombat") }),
+  Z.object({ t: Z.literal("StopCombat"), preserve_initiative: Z.boolean().default(false) }),
   Z.object({ t: Z.literal("SetActiveScene"), id: Z.string() }),
   Z.object({ t: Z.literal("RegisterPlayer"), id: Z.string() }),
   Z.object({ t: Z.literal("UnregisterPlayer"), id: Z.string() }),
@@ -595,6 +930,19 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     player_id: Z.string(),
     scene_id: Z.string().nullable(),
   }),
+  Z.object({
+    t: Z.literal("SetPlayerDisplay"),
+    player_id: Z.string(),
+    display_name: Z.string(),
+    color: Z.string(),
+  }),
+  Z.object({ t: Z.literal("EditGameSettings"), settings: decodeGameSettings }),
+  Z.object({ t: Z.literal("AddPendingCommand"), pending: decodePendingCommand }),
+  Z.object({ t: Z.literal("ResolvePendingCommand"), id: Z.string(), approved: Z.boolean() }),
+  Z.object({ t: Z.literal("PendingCommandExpired"), id: Z.string() }),
+  Z.object({ t: Z.literal("OpenReactionWindow"), window: decodeReactionWindow }),
+  Z.object({ t: Z.literal("Reacted"), creature_id: Z.string() }),
+  Z.object({ t: Z.literal("CloseReactionWindow") }),
   Z.object({ t: Z.literal("ChatFromGM"), message: Z.string() }),
   Z.object({ t: Z.literal("ChatFromPlayer"), player_id: Z.string(), message: Z.string() }),
   Z.object({
@@ -609,6 +957,16 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     path: decodeFolderPath,
     item_id: decodeFolderItemID,
   }),
+  Z.object({
+    t: Z.literal("LinkFolderItem"),
+    path: decodeFolderPath,
+    item_id: decodeFolderItemID,
+  }),
+  Z.object({
+    t: Z.literal("UnlinkFolderItem"),
+    path: decodeFolderPath,
+    item_id: decodeFolderItemID,
+  }),
   Z.object({
     t: Z.literal("RenameFolderItem"),
     path: decodeFolderPath,
@@ -679,6 +1037,12 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     scene_id: Z.string(),
     creature_id: Z.string(),
   }),
+  Z.object({
+    t: Z.literal("CreatureEnteredScene"),
+    scene_id: Z.string(),
+    creature_id: Z.string(),
+  }),
+  Z.object({ t: Z.literal("CreatureLeftScene"), scene_id: Z.string(), creature_id: Z.string() }),
   Z.object({
     t: Z.literal("AddSceneChallenge"),
     scene_id: Z.string(),
@@ -697,6 +1061,16 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     condition_id: Z.string(),
   }),
   Z.object({ t: Z.literal("EditSceneTerrain"), scene_id: Z.string(), terrain: decodeTerrain }),
+  Z.object({
+    t: Z.literal("EditSceneTerrainCosts"),
+    scene_id: Z.string(),
+    costs: decodeTerrainCosts,
+  }),
+  Z.object({
+    t: Z.literal("SetSceneGridBounds"),
+    scene_id: Z.string(),
+    bounds: Z.tuple([decodePoint3, decodePoint3]).nullable(),
+  }),
   Z.object({
     t: Z.literal("EditSceneHighlights"),
     scene_id: Z.string(),
@@ -717,6 +1091,13 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     scene_id: Z.string(),
     scene_hotspots: decodeIMap(decodePoint3, Z.string()),
   }),
+  Z.object({
+    t: Z.literal("SnapshotScene"),
+    scene_id: Z.string(),
+    name: Z.string(),
+    checkpoint: decodeSceneCheckpoint,
+  }),
+  Z.object({ t: Z.literal("RestoreScene"), scene_id: Z.string(), name: Z.string() }),
   Z.object({
     t: Z.literal("SetCreaturePos"),
     scene_id: Z.string(),
@@ -729,6 +1110,23 @@ export const decodeGameLog: Decoder<GameLog> = Z.discriminatedUnion("t", [
     creature_id: Z.string(),
     path: Z.array(decodePoint3),
   }),
+  Z.object({
+    t: Z.literal("CreatureUsedAbility"),
+    scene_id: Z.string(),
+    creature_id: Z.string(),
+    ability_id: Z.string(),
+    target: decodeDecidedTarget,
+  }),
+  Z.object({
+    t: Z.literal("AttackRolled"),
+    scene_id: Z.string(),
+    creature_id: Z.string(),
+    target_id: Z.string(),
+    roll: Z.number(),
+    defense: Z.number(),
+    hit: Z.boolean(),
+  }),
+  Z.object({ t: Z.literal("TurnBegan"), creature_id: Z.string() }),
   Z.object({ t: Z.literal("CreateClass"), path: decodeFolderPath, class: decodeClass }),
   Z.object({ t: Z.literal("EditClass"), class: decodeClass }),
   Z.object({ t: Z.literal("CreateAbility"), path: decodeFolderPath, ability: decodeAbility }),
@@ -781,6 +1179,19 @@ if (typeof window !== "undefined") (window as any).decodeGameLog = decodeGameLog
 export const decodeChangedGame: Decoder<ChangedGame> = Z.object({
   game: decodeGame, // RADIX: I think this is wrong, it should actually be decodeGameData or smth.
   logs: Z.array(decodeGameLog),
+  movement: Z.object({ movement_used: Z.number(), movement_left: Z.number() }).nullable().default(
+    null,
+  ),
+});
+
+export const decodeRoundTurn: Decoder<RoundTurn> = Z.object({
+  creature_id: Z.string(),
+  logs: Z.array(decodeGameLog),
+});
+
+export const decodeRoundSummary: Decoder<RoundSummary> = Z.object({
+  round: Z.number(),
+  turns: Z.array(decodeRoundTurn),
 });
 
 export function decodeRustResult<T, E>(
@@ -803,6 +1214,7 @@ export function decodeRustResult<T, E>(
 
 export const decodeGameMetadata: Decoder<GameMetadata> = Z.object({
   name: Z.string(),
+  content_hash: Z.string().nullable().default(null),
 });
 
 export const decodeRole: Decoder<Role> = Z.union([
@@ -867,6 +1279,10 @@ export function encodeRPIGameRequest(request: RPIGameRequest): object {
     return { ...request, command: encodeGMCommand(request.command) };
   } else if (request.t === "PlayerCommand") {
     return { ...request, command: encodePlayerCommand(request.command) };
+  } else if (request.t === "PreviewAbility") {
+    return { ...request, target: encodeDecidedTarget(request.target) };
+  } else if (request.t === "CreaturesAndTerrainInVolume") {
+    return { ...request, point: encodePoint3(request.point), volume: encodeVolume(request.volume) };
   } else {
     return request;
   }
@@ -913,6 +1329,16 @@ export function encodeGMCommand(cmd: GMCommand): object {
       return { ...cmd, path: encodeFolderPath(cmd.path) };
     case "EditSceneTerrain":
       return { ...cmd, terrain: cmd.terrain.map(encodePoint3) };
+    case "EditSceneTerrainCosts":
+      return {
+        ...cmd,
+        costs: cmd.costs.mapEntries(([point, cost]) => [encodePoint3(point), cost]).toJS(),
+      };
+    case "SetSceneGridBounds":
+      return {
+        ...cmd,
+        bounds: cmd.bounds && [encodePoint3(cmd.bounds[0]), encodePoint3(cmd.bounds[1])],
+      };
     case "EditSceneHighlights":
       return {
         ...cmd,
@@ -953,6 +1379,13 @@ export function encodeFolderPath(path: FolderPath): string {
   }
 }
 
+function encodeVolume(volume: Volume): object {
+  if ("Line" in volume) {
+    return { Line: { vector: encodePoint3(volume.Line.vector) } };
+  }
+  return volume;
+}
+
 function encodeDecidedTarget(dt: DecidedTarget): object | string {
   if (typeof dt !== "string" && "Point" in dt) {
     return { Point: encodePoint3(dt.Point) };
@@ -53,7 +53,9 @@ export function Players() {
 
           return (
             <Table.Row key={playa.id}>
-              <Table.Cell>{playa.id}</Table.Cell>
+              <Table.Cell>
+                <CV.PlayerName playerId={playa.id} />
+              </Table.Cell>
               <Table.Cell>
                 <List>
                   {playa.creatures.map((creature) => {
@@ -24,7 +24,7 @@ test("random junk", () => {
     exAttrCheck,
   );
   const gameLogTests: [any, T.GameLog][] = [
-    [{ t: "StopCombat" }, { t: "StopCombat" }],
+    [{ t: "StopCombat" }, { t: "StopCombat", preserve_initiative: false }],
     [
       { t: "StartCombat", scene_id: "coolScene", combatants: [["coolCreature", 5]] },
       { t: "StartCombat", scene_id: "coolScene", combatants: [["coolCreature", 5]] },
@@ -93,6 +93,8 @@ test("random junk", () => {
     focused_creatures: List(),
     scene_hotspots: Map(),
     related_scenes: Set(),
+    checkpoints: {},
+    grid_bounds: null,
   };
   assertEq(T.decodeScene.parse(sceneJSON), exScene);
 });
@@ -149,7 +151,7 @@ describe("PTTypes", () => {
       initiative: {
         BestOf: [2, { Plus: [{ Expr: { num: 1, size: 20 } }, { Flat: { value: 4 } }] }],
       },
-      size: { x: 1, y: 1, z: 1 },
+      size: { x: 1, y: 1, z: 1, anchor: "TopLeft" },
       inventory: {},
       bio: "",
       can_act: true,
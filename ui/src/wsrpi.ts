@@ -13,7 +13,8 @@ export const WEBSOCKETS_ENABLED = typeof import.meta.env.VITE_WEBSOCKET_URL !==
 const _requests: Record<string, (o: any) => void> = {};
 let webSocket: WebSocket | undefined;
 let timeout: NodeJS.Timeout;
-const IDLE_TIMEOUT = 10 * 60;
+// The server tells us its idle timeout when we request a websocket; this is just the fallback.
+let idleTimeout = 10 * 60;
 
 export function sendWSRequest<T>(request: RPIGameRequest, parser: T.Decoder<T>): Promise<T> {
   let id = crypto.randomUUID();
@@ -44,11 +45,12 @@ export function connect(gameId: string, mode: T.Role) {
   // the connection, so we do all of our async work in this inner function.
   async function theAsyncFunction() {
     // We need to request a websocket token, which allows the server to validate our credentials.
-    let { token } = await A.ptfetch(
+    let { token, idle_timeout } = await A.ptfetch(
       `/request-websocket/${gameId}/${mode}`,
       {},
-      Z.object({ token: Z.string() }),
+      Z.object({ token: Z.string(), idle_timeout: Z.number().optional() }),
     );
+    if (idle_timeout) idleTimeout = idle_timeout;
     webSocket = new WebSocket(import.meta.env.VITE_WEBSOCKET_URL + `/ws/${gameId}/${token}`);
     if (window) (window as any).arpeggioSocket = webSocket;
 
@@ -97,7 +99,7 @@ function resetTimeout() {
     console.log("Idle timeout, shutting down webSocket", webSocket);
     webSocket?.close();
     console.log("what's the state?", webSocket?.readyState);
-  }, IDLE_TIMEOUT * 1000);
+  }, idleTimeout * 1000);
 }
 
 function handleWSEvent(event: MessageEvent<string>) {
@@ -120,6 +122,14 @@ function handleWSEvent(event: MessageEvent<string>) {
       let { game, logs } = decoder.parse(parsed);
       M.getState().refresh(game);
       M.getState().addLogs(logs);
+      // Let players know when it's the turn of a creature they control.
+      const playerId = M.getState().playerId;
+      const controlled = playerId ? game.players.get(playerId)?.creatures ?? [] : [];
+      for (const [, log] of logs) {
+        if (log.t === "TurnBegan" && controlled.includes(log.creature_id)) {
+          M.getState().setTurnAlert(log.creature_id);
+        }
+      }
     } else {
       console.info("Got an unexpected message from the server:", parsed);
     }
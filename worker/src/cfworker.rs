@@ -196,7 +196,7 @@ async fn list_games(_req: Request, env: Env, user_id: UserID) -> Result<Response
             profile_name: r.profile_name,
             role: r.role,
           },
-          GameMetadata { name: r.name },
+          GameMetadata { name: r.name, ..Default::default() },
         )
       })
       .collect::<Vec<_>>(),
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, num::NonZeroUsize, rc::Rc};
 
 use anyhow::{anyhow, Context};
 use arpeggio::types::PlayerID;
@@ -12,7 +12,11 @@ use worker::{
 };
 
 use crate::{
-  anyhow_str, durablestorage::GameStorage, images::CFImageService, rust_error, storage, wsrpi,
+  anyhow_str,
+  durablestorage::{GameStorage, HistoryConfig},
+  images::CFImageService,
+  storage,
+  wsrpi::{self, SessionConfig},
 };
 
 #[durable_object]
@@ -22,6 +26,10 @@ pub struct ArpeggioGame {
   sessions: Sessions,
   ws_tokens: HashMap<Uuid, WSUser>,
   metadata: Option<GameMetadata>,
+  /// Passed on to every websocket session. Its idle timeout is also returned to clients when they
+  /// request a websocket, so they know when they'll need to reconnect.
+  session_config: SessionConfig,
+  history_config: HistoryConfig,
   env: Env,
 }
 
@@ -42,27 +50,44 @@ impl DurableObject for ArpeggioGame {
       sessions: Rc::new(RefCell::new(vec![])),
       ws_tokens: HashMap::new(),
       metadata: None,
+      session_config: SessionConfig {
+        idle_timeout: idle_timeout_from_env(&env),
+        admin_commands: admin_commands_from_env(&env),
+      },
+      history_config: history_config_from_env(&env),
       env,
     }
   }
 
   #[tracing::instrument(name = "DO", skip(self, req))]
   async fn fetch(&mut self, req: Request) -> Result<Response> {
-    crate::domigrations::migrate(self.state.storage()).await.map_err(rust_error)?;
+    // Don't let errors escape the DO: the calling Worker should always get a response back, so
+    // turn them into a 500 here (see the notes on error-handling in lib.rs).
+    match self.handle_fetch(req).await {
+      Ok(response) => Ok(response),
+      Err(e) => {
+        error!(event = "do-fetch-error", ?e);
+        Response::error(format!("{e:?}"), 500)
+      }
+    }
+  }
+}
+
+impl ArpeggioGame {
+  async fn handle_fetch(&mut self, req: Request) -> anyhow::Result<Response> {
+    crate::domigrations::migrate(self.state.storage()).await?;
     let game_storage = match self.game_storage {
       Some(ref game_storage) => game_storage.clone(),
       None => {
-        let storage = GameStorage::load(self.state.clone()).await.map_err(rust_error)?;
+        let storage = GameStorage::load(self.state.clone(), self.history_config).await?;
         let rc_storage = Rc::new(storage);
         self.game_storage = Some(rc_storage.clone());
         rc_storage
       }
     };
-    self.route(req, game_storage).await.map_err(rust_error)
+    self.route(req, game_storage).await
   }
-}
 
-impl ArpeggioGame {
   async fn route(
     &mut self, req: Request, game_storage: Rc<GameStorage>,
   ) -> anyhow::Result<Response> {
@@ -82,7 +107,9 @@ impl ArpeggioGame {
         let player_id: PlayerID = PlayerID(player_id.to_string());
         info!(event = "request-websocket", ?player_id);
         self.ws_tokens.insert(token, WSUser { role, player_id });
-        Response::from_json(&json!({"token": token})).map_err(anyhow_str)
+        let idle_timeout = self.session_config.idle_timeout;
+        Response::from_json(&json!({"token": token, "idle_timeout": idle_timeout}))
+          .map_err(anyhow_str)
       }
       ["ws", game_id, ws_token] => {
         let ws_token: Uuid = ws_token.parse()?;
@@ -130,6 +157,7 @@ impl ArpeggioGame {
             self.sessions.clone(),
             ws_user,
             metadata,
+            self.session_config,
           );
           wasm_bindgen_futures::spawn_local(async move {
             session.run().await;
@@ -167,6 +195,42 @@ impl ArpeggioGame {
   }
 }
 
+/// Read the websocket idle timeout from the `WS_IDLE_TIMEOUT_SECS` var, falling back to
+/// [`wsrpi::DEFAULT_IDLE_TIMEOUT`] if it's missing or unparseable.
+fn idle_timeout_from_env(env: &Env) -> u32 {
+  let var = match env.var("WS_IDLE_TIMEOUT_SECS") {
+    Ok(var) => var.to_string(),
+    Err(_) => return wsrpi::DEFAULT_IDLE_TIMEOUT,
+  };
+  match var.parse() {
+    Ok(0) | Err(_) => {
+      error!(event = "bad-idle-timeout", var);
+      wsrpi::DEFAULT_IDLE_TIMEOUT
+    }
+    Ok(secs) => secs,
+  }
+}
+
+/// Read the `HistoryConfig` from the `RECENT_LOGS_SIZE` var, using the defaults for anything
+/// that's missing or unparseable. Keeping no recent logs would leave clients with none to show,
+/// so 0 is rejected too.
+fn history_config_from_env(env: &Env) -> HistoryConfig {
+  let mut config = HistoryConfig::default();
+  if let Ok(var) = env.var("RECENT_LOGS_SIZE") {
+    let var = var.to_string();
+    match var.parse::<NonZeroUsize>() {
+      Ok(recent_logs) => config.recent_logs = recent_logs.get(),
+      Err(_) => error!(event = "bad-recent-logs-size", var),
+    }
+  }
+  config
+}
+
+/// Admin commands are only allowed when the `ENABLE_ADMIN_COMMANDS` var is "true".
+fn admin_commands_from_env(env: &Env) -> bool {
+  env.var("ENABLE_ADMIN_COMMANDS").map(|var| var.to_string() == "true").unwrap_or(false)
+}
+
 async fn dump_storage(state: &State) -> anyhow::Result<Response> {
   // TODO: STREAM!
   let mut result = HashMap::new();
@@ -6,17 +6,25 @@ use std::{
 
 use anyhow::anyhow;
 use tracing::{info, warn};
-use worker::{ListOptions, State};
+use worker::{js_sys, wasm_bindgen::JsValue, ListOptions, State};
 
 use arpeggio::{
-  game::GameExt,
-  types::{ChangedGame, Game, GameLog},
+  game::{apply_history_log, game_at_index, CombatTimelineRecorder},
+  types::{ChangedGame, Game, GameLog, RoundSummary},
 };
 use arptypes::multitenant::{GameIndex, ImageType, InvitationID};
 
 use crate::anyhow_str;
 
 type RecentGameLogs = VecDeque<(GameIndex, GameLog)>;
+
+/// The most keys a Durable Object will write in a single `putMultiple` call. Each call is atomic.
+const MAX_KEYS_PER_PUT: usize = 128;
+
+/// The key holding how many logs have been committed. Logs at or past this index were left behind
+/// by a command that failed partway through storing them, and are ignored.
+const COMMITTED_LOGS_KEY: &str = "committed-logs";
+
 /// The state of the game.
 ///
 /// The cool thing that Durable Objects give us is that we can keep the Game in memory, just loading
@@ -32,9 +40,28 @@ pub struct GameStorage {
   next_log_idx: Cell<usize>,
   cached_game: Rc<RefCell<Game>>,
   recent_logs: Rc<RefCell<RecentGameLogs>>,
+  combat_timeline: Rc<RefCell<CombatTimelineRecorder>>,
+  history_config: HistoryConfig,
 }
 
-const RECENT_LOGS_SIZE: usize = 100;
+/// The result of replaying a snapshot's stored logs; see `replay_logs`.
+struct ReplayedLogs {
+  game: Game,
+  recent_logs: RecentGameLogs,
+  next_log_idx: usize,
+  combat_timeline: CombatTimelineRecorder,
+}
+
+/// Limits on how much game history is kept in memory.
+#[derive(Clone, Copy, Debug)]
+pub struct HistoryConfig {
+  /// How many of the most recent logs are kept around to send to clients.
+  pub recent_logs: usize,
+}
+
+impl Default for HistoryConfig {
+  fn default() -> Self { HistoryConfig { recent_logs: 100 } }
+}
 
 /// ## Storage in Durable Objects
 /// DO gives us a KV store, where the size of values is pretty significantly limited (128kB). It
@@ -44,33 +71,44 @@ const RECENT_LOGS_SIZE: usize = 100;
 impl GameStorage {
   pub fn game(&self) -> Game { self.cached_game.borrow().clone() }
   pub fn recent_logs(&self) -> RecentGameLogs { self.recent_logs.borrow().clone() }
+  pub fn combat_timeline(&self) -> Vec<RoundSummary> {
+    self.combat_timeline.borrow().rounds().to_vec()
+  }
 
-  pub async fn load(state: Rc<State>) -> anyhow::Result<Self> {
+  pub async fn load(state: Rc<State>, history_config: HistoryConfig) -> anyhow::Result<Self> {
     // TODO: support muiltple snapshots? Or maybe just wait until SQLite support exists...
-    let (game, recent_logs) =
-      match Self::get_key_state::<String>(&state, "snapshot-0-chunk-0").await? {
-        Some(game_str) => {
-          let game = serde_json::from_str(&game_str)?;
-          Self::load_logs(state.clone(), game).await?
-        }
-        None => {
-          info!(event = "new-game");
-          let default_game = Default::default();
-          state
-            .storage()
-            .put("snapshot-0-chunk-0", serde_json::to_string(&default_game)?)
-            .await
-            .map_err(anyhow_str)?;
-          (default_game, VecDeque::new())
-        }
-      };
-    let next_log_idx = recent_logs.iter().last().map(|l| l.0.log_idx + 1).unwrap_or(0);
+    let replayed = match Self::get_key_state::<String>(&state, "snapshot-0-chunk-0").await? {
+      Some(game_str) => {
+        let game = serde_json::from_str(&game_str)?;
+        Self::load_logs(state.clone(), game, history_config).await?
+      }
+      None => {
+        info!(event = "new-game");
+        let default_game = Default::default();
+        state
+          .storage()
+          .put("snapshot-0-chunk-0", serde_json::to_string(&default_game)?)
+          .await
+          .map_err(anyhow_str)?;
+        replay_logs(default_game, vec![], history_config)?
+      }
+    };
+    // Games stored before the commit marker existed have every log committed.
+    if Self::committed_log_count(&state).await?.is_none() {
+      state
+        .storage()
+        .put(COMMITTED_LOGS_KEY, replayed.next_log_idx.to_string())
+        .await
+        .map_err(anyhow_str)?;
+    }
     let game_storage = Self {
       state,
       current_snapshot_idx: Cell::new(0),
-      next_log_idx: Cell::new(next_log_idx),
-      cached_game: Rc::new(RefCell::new(game)),
-      recent_logs: Rc::new(RefCell::new(recent_logs)),
+      next_log_idx: Cell::new(replayed.next_log_idx),
+      cached_game: Rc::new(RefCell::new(replayed.game)),
+      recent_logs: Rc::new(RefCell::new(replayed.recent_logs)),
+      combat_timeline: Rc::new(RefCell::new(replayed.combat_timeline)),
+      history_config,
     };
     Ok(game_storage)
   }
@@ -89,18 +127,49 @@ impl GameStorage {
     Self::get_key_state(&self.state, key).await
   }
 
+  /// Load the stored logs and replay them on top of `game`; see `replay_logs`.
+  async fn load_logs(
+    state: Rc<State>, game: Game, history_config: HistoryConfig,
+  ) -> anyhow::Result<ReplayedLogs> {
+    replay_logs(game, Self::list_logs(&state).await?, history_config)
+  }
+
+  /// Load the snapshot and all the logs that have been applied to it, for operations that need to
+  /// replay history.
+  pub async fn history(&self) -> anyhow::Result<(Game, Vec<GameLog>)> {
+    let snapshot: String =
+      self.get_key("snapshot-0-chunk-0").await?.ok_or_else(|| anyhow!("No game snapshot"))?;
+    let snapshot = serde_json::from_str(&snapshot)?;
+    let logs = Self::list_logs(&self.state).await?.into_iter().map(|(_, log)| log).collect();
+    Ok((snapshot, logs))
+  }
+
+  /// Load the game as it was at `index` (see `game_at_index`), for looking back at the game's
+  /// history. This doesn't touch the current game.
+  pub async fn load_game_at(&self, index: GameIndex) -> anyhow::Result<Game> {
+    let (snapshot, logs) = self.history().await?;
+    Ok(game_at_index(&snapshot, &logs, index)?)
+  }
+
+  async fn committed_log_count(state: &State) -> anyhow::Result<Option<usize>> {
+    let count: Option<String> = Self::get_key_state(state, COMMITTED_LOGS_KEY).await?;
+    Ok(count.map(|count| count.parse()).transpose()?)
+  }
+
+  /// List the committed logs; see `COMMITTED_LOGS_KEY`.
   /// log keys are like "log-{snapshot_idx}-idx-{log_idx}"
-  async fn load_logs(state: Rc<State>, mut game: Game) -> anyhow::Result<(Game, RecentGameLogs)> {
+  async fn list_logs(state: &State) -> anyhow::Result<Vec<(usize, GameLog)>> {
     // Here's another super annoying deficiency of the DO "list" API: it doesn't return an iterator,
     // but the entire result set all at once as a javascript Map! So, we have to manually do
     // batching to avoid loading too much stuff into memory at once.
 
+    let committed = Self::committed_log_count(state).await?;
     let storage = state.storage();
     let list_options = ListOptions::new().prefix("log-");
     let items = storage.list_with_options(list_options).await.map_err(anyhow_str)?;
-    let mut recent_logs = VecDeque::new();
+    let mut logs = vec![];
     if items.size() == 0 {
-      return Ok((game, recent_logs));
+      return Ok(logs);
     }
     info!(event = "loading-logs", num = items.size());
     for key in items.keys() {
@@ -109,58 +178,91 @@ impl GameStorage {
       let value: String = serde_wasm_bindgen::from_value(value).map_err(anyhow_str)?;
       let key: String = serde_wasm_bindgen::from_value(key).map_err(anyhow_str)?;
       info!(event = "found-log", ?key);
-      if let ["log", _, "idx", log_idx_str] = key.split('-').collect::<Vec<_>>()[..] {
+      if let Some(log_idx) = parse_log_key(&key)? {
         let log: GameLog = serde_json::from_str(&value).map_err(|e| {
           anyhow!("Failed parsing GameLog as JSON:\ncontent: {value:?}\nerror: {e:?}")
         })?;
-        game = game.apply_log(&log)?;
-        let log_idx = log_idx_str.parse()?;
-        if recent_logs.len() >= RECENT_LOGS_SIZE {
-          recent_logs.pop_front();
-        }
-        recent_logs.push_back((GameIndex { game_idx: 0, log_idx }, log));
+        logs.push((log_idx, log));
       } else {
         warn!(event = "unknown-log-key", ?key);
       }
     }
-
-    Ok((game, recent_logs))
+    Ok(committed_logs(logs, committed))
   }
 
   /// Upate Game storage with changes from a changed_game. Updates the locally cached Game as well
   /// as writing new logs to storage.
+  ///
+  /// This is all-or-nothing: the new log count is written in the same `putMultiple` call as the last
+  /// of the logs, so the command only counts as stored once that call succeeds. If any call fails,
+  /// the logs that did get written are ignored (and overwritten by the next command), and the
+  /// cached game is left alone.
   pub async fn store_game(
     &self, changed_game: ChangedGame,
   ) -> anyhow::Result<Vec<(GameIndex, GameLog)>> {
-    // NOTE: Durable Objects support "write coalescing", where you can run multiple PUT operations
-    // without awaiting and they will be merged into one "transaction". Here's the thing: I'm not
-    // sure this works with the Rust bindings. But we should try something like that. There is also
-    // a method called "transaction" which takes a closure to do this explicitly, but that's not
-    // exposed in the Rust workers API yet.
-
     // we could probably use something more compact than JSON...
 
     // TODO: We could occasionally produce a new snapshot... or just wait for SQLite
+    let first_log_idx = self.next_log_idx.get();
+    let mut entries = vec![];
     let mut logs_with_indices = vec![];
-    for log in changed_game.logs {
-      let serialized_log = serde_json::to_string(&log)?;
-      let key =
-        format!("log-{:09}-idx-{:09}", self.current_snapshot_idx.get(), self.next_log_idx.get());
-      info!(event = "storing-log", ?key);
-      self.state.storage().put(&key, serialized_log).await.map_err(anyhow_str)?;
-      logs_with_indices.push((GameIndex { game_idx: 0, log_idx: self.next_log_idx.get() }, log));
-
-      self.next_log_idx.set(self.next_log_idx.get() + 1);
+    for (log_idx, log) in (first_log_idx..).zip(changed_game.logs) {
+      let key = log_key(self.current_snapshot_idx.get(), log_idx);
+      entries.push((key, serde_json::to_string(&log)?));
+      logs_with_indices.push((GameIndex { game_idx: 0, log_idx }, log));
+    }
+    let batches = log_batches(entries, first_log_idx + logs_with_indices.len());
+    if let Err(e) = self.put_batches(batches).await {
+      warn!(event = "store-logs-failed", error = ?e);
+      return Err(e);
+    }
+
+    self.next_log_idx.set(first_log_idx + logs_with_indices.len());
+    let previous_game = self.cached_game.replace(changed_game.game);
+    {
+      let mut recent_logs = self.recent_logs.borrow_mut();
+      recent_logs.extend(logs_with_indices.iter().cloned());
+      let drain_to = recent_logs.len().saturating_sub(self.history_config.recent_logs);
+      recent_logs.drain(0..drain_to);
     }
-    *self.cached_game.borrow_mut() = changed_game.game;
-    let mut recent_logs = self.recent_logs.borrow_mut();
-    recent_logs.extend(logs_with_indices.iter().cloned());
-    let drain_to = recent_logs.len().saturating_sub(RECENT_LOGS_SIZE);
-    recent_logs.drain(0..drain_to);
+    let logs: Vec<GameLog> = logs_with_indices.iter().map(|(_, log)| log.clone()).collect();
+    self.record_timeline(previous_game, &logs).await?;
 
     Ok(logs_with_indices)
   }
 
+  /// Bring the combat timeline up to date with logs that were just applied to `game`.
+  async fn record_timeline(&self, mut game: Game, logs: &[GameLog]) -> anyhow::Result<()> {
+    if logs.iter().any(|log| matches!(log, GameLog::Rollback { .. })) {
+      // Undoing part of the timeline needs the history being rolled back, which we only have in
+      // storage. Rollbacks are rare enough that it's fine to rebuild it from there.
+      let (snapshot, history) = self.history().await?;
+      let timeline = CombatTimelineRecorder::from_history(&snapshot, &history)?;
+      *self.combat_timeline.borrow_mut() = timeline;
+      return Ok(());
+    }
+    let mut timeline = self.combat_timeline.borrow_mut();
+    for log in logs {
+      game.apply_log_mut(log)?;
+      timeline.record(&game, log);
+    }
+    Ok(())
+  }
+
+  /// Write each batch from `log_batches` with one `putMultiple` call, stopping at the first failure.
+  async fn put_batches(&self, batches: Vec<Vec<(String, String)>>) -> anyhow::Result<()> {
+    for batch in batches {
+      let values = js_sys::Object::new();
+      for (key, value) in &batch {
+        js_sys::Reflect::set(&values, &JsValue::from_str(key), &JsValue::from_str(value))
+          .map_err(anyhow_str)?;
+      }
+      info!(event = "storing-logs", first_key = batch[0].0.as_str(), count = batch.len());
+      self.state.storage().put_multiple_raw(values).await.map_err(anyhow_str)?;
+    }
+    Ok(())
+  }
+
   pub async fn create_invitation(&self) -> anyhow::Result<InvitationID> {
     let invitation_id = InvitationID::gen();
 
@@ -195,3 +297,137 @@ impl GameStorage {
     Ok(())
   }
 }
+
+fn log_key(snapshot_idx: usize, log_idx: usize) -> String {
+  format!("log-{snapshot_idx:09}-idx-{log_idx:09}")
+}
+
+/// Get the log index out of a key made by `log_key`, or None if it isn't a log key.
+fn parse_log_key(key: &str) -> anyhow::Result<Option<usize>> {
+  match key.split('-').collect::<Vec<_>>()[..] {
+    ["log", _, "idx", log_idx_str] => Ok(Some(log_idx_str.parse()?)),
+    _ => Ok(None),
+  }
+}
+
+/// Split a command's serialized logs into `putMultiple`-sized batches, with the new committed log
+/// count at the very end so that it's written in the same call as the last of the logs.
+fn log_batches(mut entries: Vec<(String, String)>, committed: usize) -> Vec<Vec<(String, String)>> {
+  entries.push((COMMITTED_LOGS_KEY.to_string(), committed.to_string()));
+  entries.chunks(MAX_KEYS_PER_PUT).map(|batch| batch.to_vec()).collect()
+}
+
+/// Drop the logs that were never committed. Storage without a committed count predates it, so all
+/// of its logs count.
+fn committed_logs(logs: Vec<(usize, GameLog)>, committed: Option<usize>) -> Vec<(usize, GameLog)> {
+  match committed {
+    Some(committed) => logs.into_iter().filter(|(log_idx, _)| *log_idx < committed).collect(),
+    None => logs,
+  }
+}
+
+/// Replay a snapshot's stored logs on top of it. Returns the resulting game, the most recent logs
+/// to keep in memory, the index that the next log should be stored at, and the timeline of the
+/// latest combat. The next index comes from all the stored logs, not just the recent ones, so it's
+/// right however few are kept.
+fn replay_logs(
+  mut game: Game, logs: Vec<(usize, GameLog)>, history_config: HistoryConfig,
+) -> anyhow::Result<ReplayedLogs> {
+  let next_log_idx = logs.iter().map(|(log_idx, _)| log_idx + 1).max().unwrap_or(0);
+  let (indices, logs): (Vec<usize>, Vec<GameLog>) = logs.into_iter().unzip();
+  let snapshot = game.clone();
+  let mut combat_timeline = CombatTimelineRecorder::default();
+  for (idx, log) in logs.iter().enumerate() {
+    apply_history_log(&snapshot, &logs[..idx], &mut game, log)?;
+    combat_timeline.record_history(&snapshot, &logs[..idx], &game, log)?;
+  }
+  let keep_from = logs.len().saturating_sub(history_config.recent_logs);
+  let recent_logs = indices
+    .into_iter()
+    .zip(logs)
+    .skip(keep_from)
+    .map(|(log_idx, log)| (GameIndex { game_idx: 0, log_idx }, log))
+    .collect();
+  Ok(ReplayedLogs { game, recent_logs, next_log_idx, combat_timeline })
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+
+  use arpeggio::types::PlayerID;
+
+  use super::*;
+
+  fn register(name: &str) -> GameLog {
+    GameLog::RegisterPlayer { id: PlayerID(name.to_string()) }
+  }
+
+  #[test]
+  fn replay_keeps_only_recent_logs() {
+    let logs = vec![(0, register("alice")), (1, register("bob")), (2, register("carol"))];
+    let config = HistoryConfig { recent_logs: 2 };
+    let replayed = replay_logs(Game::default(), logs, config).unwrap();
+    assert_eq!(replayed.game.players.len(), 3);
+    let recent_indices: Vec<usize> =
+      replayed.recent_logs.iter().map(|(idx, _)| idx.log_idx).collect();
+    assert_eq!(recent_indices, vec![1, 2]);
+    assert_eq!(replayed.next_log_idx, 3);
+  }
+
+  #[test]
+  fn next_log_idx_ignores_the_recent_logs_limit() {
+    let logs = vec![(0, register("alice")), (1, register("bob"))];
+    let config = HistoryConfig { recent_logs: 0 };
+    let replayed = replay_logs(Game::default(), logs, config).unwrap();
+    assert!(replayed.recent_logs.is_empty());
+    assert_eq!(replayed.next_log_idx, 2);
+    let replayed = replay_logs(Game::default(), vec![], config).unwrap();
+    assert_eq!(replayed.next_log_idx, 0);
+  }
+
+  /// Read logs out of a fake storage the same way `GameStorage::list_logs` does.
+  fn stored_logs(storage: &BTreeMap<String, String>) -> Vec<(usize, GameLog)> {
+    let committed = storage.get(COMMITTED_LOGS_KEY).map(|count| count.parse().unwrap());
+    let logs = storage
+      .iter()
+      .filter_map(|(key, value)| {
+        let log_idx = parse_log_key(key).unwrap()?;
+        Some((log_idx, serde_json::from_str(value).unwrap()))
+      })
+      .collect();
+    committed_logs(logs, committed)
+  }
+
+  #[test]
+  fn logs_from_a_failed_store_are_ignored() {
+    let entries = |range: std::ops::Range<usize>| -> Vec<(String, String)> {
+      range
+        .map(|log_idx| {
+          let log = register(&format!("player{log_idx}"));
+          (log_key(0, log_idx), serde_json::to_string(&log).unwrap())
+        })
+        .collect()
+    };
+    let mut storage = BTreeMap::new();
+    for batch in log_batches(entries(0..2), 2) {
+      storage.extend(batch);
+    }
+
+    // This command takes two `putMultiple` calls, and only the first one goes through.
+    let batches = log_batches(entries(2..MAX_KEYS_PER_PUT + 2), MAX_KEYS_PER_PUT + 2);
+    assert_eq!(batches.len(), 2);
+    storage.extend(batches[0].clone());
+
+    let logs = stored_logs(&storage);
+    let indices: Vec<usize> = logs.iter().map(|(log_idx, _)| *log_idx).collect();
+    assert_eq!(indices, vec![0, 1]);
+    let replayed = replay_logs(Game::default(), logs, HistoryConfig::default()).unwrap();
+    assert_eq!(replayed.game.players.len(), 2);
+    assert_eq!(replayed.next_log_idx, 2);
+
+    // Once the whole command is written, all of its logs count.
+    storage.extend(batches[1].clone());
+    assert_eq!(stored_logs(&storage).len(), MAX_KEYS_PER_PUT + 2);
+  }
+}
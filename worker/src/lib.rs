@@ -31,9 +31,17 @@ mod wsrpi;
 // waiting Worker. I'll have to see what the behavior is in actual production; maybe this is just a
 // behavior of the local dev environment.
 
+// To keep that from happening as much as possible, the DO's `fetch` turns every Err into a 500
+// response instead of returning it. Panics can't be handled the same way: wasm32 builds abort on
+// panic, so there's no unwinding for `catch_unwind` to catch. The best we can do for those is log
+// them, both to the console and through tracing so they show up alongside everything else.
+
 #[event(start)]
 fn start() {
-  console_error_panic_hook::set_once();
+  std::panic::set_hook(Box::new(|info| {
+    console_error_panic_hook::hook(info);
+    tracing::error!(event = "panic", %info);
+  }));
   let fmt_layer = tracing_subscriber::fmt::layer()
     .json()
     .with_ansi(false) // Only partially supported across JavaScript runtimes
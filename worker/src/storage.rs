@@ -22,7 +22,10 @@ pub async fn list_all_games(env: &Env) -> worker::Result<Vec<(GameID, GameMetada
   // TODO: pagination
   let statement = db.prepare("SELECT game_id, name FROM game_metadata LIMIT 1000");
   let games: Vec<GameMetadataTable> = statement.all().await?.results()?;
-  let games = games.into_iter().map(|gmt| (gmt.game_id, GameMetadata { name: gmt.name })).collect();
+  let games = games
+    .into_iter()
+    .map(|gmt| (gmt.game_id, GameMetadata { name: gmt.name, ..Default::default() }))
+    .collect();
   return Ok(games);
 
   #[derive(Deserialize)]
@@ -32,6 +35,15 @@ pub async fn list_all_games(env: &Env) -> worker::Result<Vec<(GameID, GameMetada
   }
 }
 
+#[derive(Deserialize)]
+struct GameInfoRow {
+  user_id: UserID,
+  game_id: GameID,
+  profile_name: PlayerID,
+  role: String,
+  name: String,
+}
+
 pub async fn list_games_with_names(env: &Env, user_id: UserID) -> worker::Result<Vec<GameInfo>> {
   let db = env.d1("DB")?;
   let statement = db.prepare(
@@ -40,8 +52,20 @@ pub async fn list_games_with_names(env: &Env, user_id: UserID) -> worker::Result
     WHERE UG.game_id = meta.game_id AND user_id = ?",
   );
   let statement = statement.bind(&[user_id.to_string().into()])?;
-  let game_infos: Vec<GameInfo> = statement.all().await?.results()?;
-  Ok(game_infos)
+  let rows: Vec<GameInfoRow> = statement.all().await?.results()?;
+  rows
+    .into_iter()
+    .map(|row| {
+      let role = parse_role(&row.role, &row.user_id, &row.game_id)?;
+      Ok(GameInfo {
+        user_id: row.user_id,
+        game_id: row.game_id,
+        profile_name: row.profile_name,
+        role,
+        name: row.name,
+      })
+    })
+    .collect()
 }
 
 pub async fn get_game_metadata(env: &Env, game_id: GameID) -> worker::Result<Option<GameMetadata>> {
@@ -52,7 +76,6 @@ pub async fn get_game_metadata(env: &Env, game_id: GameID) -> worker::Result<Opt
   Ok(meta)
 }
 
-#[derive(Deserialize)]
 pub struct GameInfo {
   pub user_id: UserID,
   pub game_id: GameID,
@@ -66,12 +89,22 @@ pub async fn create_game(
 ) -> worker::Result<()> {
   create_profile(env, game_id, user_id, PlayerID("GM".to_string()), Role::GM).await?;
   let db = env.d1("DB")?;
-  let statement = db.prepare("INSERT INTO game_metadata (game_id, name) VALUES (?, ?)");
+  let statement = db.prepare(
+    "INSERT INTO game_metadata (game_id, name) VALUES (?, ?) ON CONFLICT (game_id) DO NOTHING",
+  );
   let statement = statement.bind(&[game_id.to_string().into(), name.into()])?;
   statement.run().await?;
   Ok(())
 }
 
+#[derive(Deserialize)]
+struct GameProfileRow {
+  user_id: UserID,
+  game_id: GameID,
+  profile_name: PlayerID,
+  role: String,
+}
+
 pub async fn check_game_access(
   env: &Env, user_id: UserID, game_id: GameID, role: Role,
 ) -> worker::Result<Option<GameProfile>> {
@@ -83,15 +116,37 @@ pub async fn check_game_access(
     game_id.to_string().into(),
     role.to_string().into(),
   ])?;
-  statement.first(None).await
+  let row: Option<GameProfileRow> = statement.first(None).await?;
+  row
+    .map(|row| {
+      let role = parse_role(&row.role, &row.user_id, &row.game_id)?;
+      Ok(GameProfile {
+        user_id: row.user_id,
+        game_id: row.game_id,
+        profile_name: row.profile_name,
+        role,
+      })
+    })
+    .transpose()
+}
+
+/// Roles are stored as plain strings in `user_games`, so check them explicitly rather than letting
+/// a bad value fail somewhere inside deserialization.
+fn parse_role(role: &str, user_id: &UserID, game_id: &GameID) -> worker::Result<Role> {
+  let row = format!("user_games row for user {} in game {game_id}", user_id.0);
+  Role::parse_stored(role, &row).map_err(|e| worker::Error::RustError(e.to_string()))
 }
 
+/// Associate a user with a game. This is idempotent: if the user already has a profile with this
+/// role in the game (see the `user_game_role_unique` index), the existing profile is kept as-is.
 pub async fn create_profile(
   env: &Env, game_id: GameID, user_id: UserID, profile_name: PlayerID, role: Role,
 ) -> worker::Result<()> {
   let db = env.d1("DB")?;
-  let statement =
-    db.prepare("INSERT INTO user_games (user_id, game_id, profile_name, role) VALUES (?, ?, ?, ?)");
+  let statement = db.prepare(
+    "INSERT INTO user_games (user_id, game_id, profile_name, role) VALUES (?, ?, ?, ?)
+    ON CONFLICT (user_id, game_id, role) DO NOTHING",
+  );
   let statement = statement.bind(&[
     user_id.to_string().into(),
     game_id.to_string().into(),
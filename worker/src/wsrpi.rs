@@ -3,16 +3,16 @@ use std::{cell::RefCell, rc::Rc};
 use anyhow::anyhow;
 use futures_util::stream::StreamExt;
 use gloo_timers::callback::Timeout;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
 use tracing::{error, info};
-use worker::{WebSocket, WebsocketEvent};
+use worker::{wasm_bindgen_futures, Date, WebSocket, WebsocketEvent};
 
 use arpeggio::{
-  game::GameExt,
-  types::{ChangedGame, GMCommand, GameError, RPIGame},
+  game::{content_hash_hex, find_content_hash, rollback_to, GameExt},
+  types::{ChangedGame, CompactPoints, GMCommand, Game, GameError, Point3, RPIGame},
 };
-use arptypes::multitenant::{GameAndMetadata, GameMetadata, RPIGameRequest, Role};
+use arptypes::multitenant::{GameAndMetadata, GameIndex, GameMetadata, RPIGameRequest, Role};
 
 use crate::{
   anyhow_str,
@@ -21,7 +21,18 @@ use crate::{
   images::CFImageService,
 };
 
-const IDLE_TIMEOUT: u32 = 10 * 60;
+/// How long (in seconds) a websocket may sit idle before we close it, unless the deployment
+/// configures something else with the `WS_IDLE_TIMEOUT_SECS` var.
+pub const DEFAULT_IDLE_TIMEOUT: u32 = 10 * 60;
+
+/// Settings the deployment can change for every websocket session.
+#[derive(Clone, Copy, Debug)]
+pub struct SessionConfig {
+  /// Seconds a websocket may be idle before we close it.
+  pub idle_timeout: u32,
+  /// Whether GMs may use admin commands like `GMCommand::ApplyCreatureLog`.
+  pub admin_commands: bool,
+}
 
 /// A representation of a request received from a websocket. It has an ID so we can send a response
 /// and the client can match them up.
@@ -38,6 +49,10 @@ pub struct GameSession {
   sessions: Sessions,
   ws_user: WSUser,
   timeout: RefCell<Timeout>,
+  config: SessionConfig,
+  /// Wakes us up to carry out an action held for reactions once its window runs out, in case
+  /// nobody sends a request before then.
+  reaction_timeout: RefCell<Option<Timeout>>,
   // We will want this to be Rc<RefCell<GameMetadata>> once we start supporting renaming games
   metadata: GameMetadata,
 }
@@ -45,9 +60,9 @@ pub struct GameSession {
 impl GameSession {
   pub fn new(
     image_service: CFImageService, game_storage: Rc<GameStorage>, socket: WebSocket,
-    sessions: Sessions, ws_user: WSUser, metadata: GameMetadata,
+    sessions: Sessions, ws_user: WSUser, metadata: GameMetadata, config: SessionConfig,
   ) -> Self {
-    let timeout = mk_timeout(socket.clone(), ws_user.clone());
+    let timeout = mk_timeout(socket.clone(), ws_user.clone(), config.idle_timeout);
     Self {
       image_service,
       game_storage,
@@ -55,6 +70,8 @@ impl GameSession {
       sessions,
       ws_user,
       timeout: RefCell::new(timeout),
+      config,
+      reaction_timeout: RefCell::new(None),
       metadata,
     }
   }
@@ -92,7 +109,8 @@ impl GameSession {
   }
 
   fn reset_timeout(&self) {
-    let new_timeout = mk_timeout(self.socket.clone(), self.ws_user.clone());
+    let new_timeout =
+      mk_timeout(self.socket.clone(), self.ws_user.clone(), self.config.idle_timeout);
     let old_timeout = self.timeout.replace(new_timeout);
     info!(event="refresh-timeout", ?self.ws_user);
     old_timeout.cancel();
@@ -106,8 +124,8 @@ impl GameSession {
     let changed_game = {
       let game = self.game_storage.game();
       if !game.players.contains_key(&self.ws_user.player_id) {
-        let changed_game = game
-          .perform_gm_command(GMCommand::RegisterPlayer { id: self.ws_user.player_id.clone() })?;
+        let register = GMCommand::RegisterPlayer { id: self.ws_user.player_id.clone() };
+        let changed_game = game.perform_gm_command_at(register, now_secs())?;
         Some(changed_game)
       } else {
         None
@@ -122,40 +140,46 @@ impl GameSession {
   pub async fn handle_event(&self, event: WebsocketEvent) -> anyhow::Result<bool> {
     match event {
       WebsocketEvent::Message(msg) => {
-        if let Some(text) = msg.text() {
+        let (codec, data) = if let Some(text) = msg.text() {
           info!(event = "handle-event", text);
-          let request: serde_json::Result<WSRequest> = serde_json::from_str(&text);
-          match request {
-            Ok(request) => {
-              let request_id = request.id.clone();
-              info!(event = "handling-request", ?request);
-              let response = self.handle_request(request).await;
-              // console_log!("Handled request: {response:?}");
-              match response {
-                Ok(result) => self.send(&json!({"id": request_id, "payload": &result}))?,
-                Err(e) => {
-                  error!(event = "error-handling-request", ?e);
-                  self.send(&json!({"id": request_id, "error": format!("{e:?}")}))?
-                }
+          (Codec::Json, text.into_bytes())
+        } else if let Some(bytes) = msg.bytes() {
+          info!(event = "handle-event", len = bytes.len());
+          (Codec::MessagePack, bytes)
+        } else {
+          return Ok(false);
+        };
+        let request: anyhow::Result<WSRequest> = codec.decode(&data);
+        match request {
+          Ok(request) => {
+            let request_id = request.id.clone();
+            info!(event = "handling-request", ?request);
+            let response = self.handle_request(request).await;
+            // console_log!("Handled request: {response:?}");
+            match response {
+              Ok(result) => {
+                self.send_with(codec, &json!({"id": request_id, "payload": &result}))?
               }
-            }
-            Err(e) => {
-              // This is a little involved because we try to send the request ID back with the error
-              // response, so we have to retry parsing it as a Value.
-              let error_response = json!({"error": format!("Couldn't parse as a WSRequest: {e}")});
-              let mut error_response = error_response.as_object().unwrap().clone();
-              if let Ok(value) =
-                serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&text)
-              {
-                error_response.insert(
-                  "id".to_string(),
-                  value.get("id").unwrap_or(&serde_json::Value::Null).clone(),
-                );
+              Err(e) => {
+                error!(event = "error-handling-request", ?e);
+                self.send_with(codec, &json!({"id": request_id, "error": format!("{e:?}")}))?
               }
-              error!(event = "error-response", ?error_response);
-              self.send(&error_response)?;
             }
           }
+          Err(e) => {
+            // This is a little involved because we try to send the request ID back with the error
+            // response, so we have to retry parsing it as a Value.
+            let error_response = json!({"error": format!("Couldn't parse as a WSRequest: {e}")});
+            let mut error_response = error_response.as_object().unwrap().clone();
+            if let Ok(value) = codec.decode::<serde_json::Map<String, serde_json::Value>>(&data) {
+              error_response.insert(
+                "id".to_string(),
+                value.get("id").unwrap_or(&serde_json::Value::Null).clone(),
+              );
+            }
+            error!(event = "error-response", ?error_response);
+            self.send_with(codec, &error_response)?;
+          }
         }
       }
       WebsocketEvent::Close(close_event) => {
@@ -170,6 +194,8 @@ impl GameSession {
   async fn handle_request(&self, request: WSRequest) -> anyhow::Result<serde_json::Value> {
     // TODO: we should not need to load the game on every operation; we should instead just store an
     // Arc<RefCell(?)<Game>>  in-memory in the durable object.
+    self.expire_timeouts().await?;
+    check_admin_command(self.ws_user.role, &request.request, self.config)?;
     let game = self.game_storage.game();
     use RPIGameRequest::*;
     match (self.ws_user.role, request.request) {
@@ -178,28 +204,56 @@ impl GameSession {
         // gets information about the current scene. This is going to be a big change, though.
 
         let rpi_game = RPIGame(&game);
+        let metadata =
+          GameMetadata { content_hash: Some(content_hash(&game)?), ..self.metadata.clone() };
+        let controlled_creatures = match self.ws_user.role {
+          Role::GM => vec![],
+          Role::Player => game.controlled_creatures(&self.ws_user.player_id)?,
+        };
         let result = GameAndMetadata {
           game: rpi_game.serialize_game()?,
-          metadata: self.metadata.clone(),
+          metadata,
           logs: self.game_storage.recent_logs(),
+          controlled_creatures,
         };
         Ok(serde_json::to_value(result)?)
       }
       (Role::Player, PlayerCommand { command }) => {
-        let changed_game = game.perform_player_command(self.ws_user.player_id.clone(), command);
+        let changed_game =
+          game.perform_player_command(self.ws_user.player_id.clone(), command, now_secs());
         self.change_game(changed_game).await
       }
+      (Role::GM, ListPendingCommands) => Ok(serde_json::to_value(game.list_pending(None))?),
+      (Role::Player, ListPendingCommands) => {
+        Ok(serde_json::to_value(game.list_pending(Some(&self.ws_user.player_id)))?)
+      }
       (Role::GM, GMCommand { command }) => {
-        let changed_game = game.perform_gm_command(command);
+        let changed_game = match command {
+          arpeggio::types::GMCommand::UndoCreature { creature_id } => {
+            let (snapshot, history) = self.game_storage.history().await?;
+            game.undo_creature(&snapshot, &history, creature_id)
+          }
+          arpeggio::types::GMCommand::Rollback { snapshot_index, log_index } => {
+            let (snapshot, history) = self.game_storage.history().await?;
+            let index = GameIndex { game_idx: snapshot_index, log_idx: log_index };
+            rollback_to(&snapshot, &history, index)
+          }
+          arpeggio::types::GMCommand::RollbackToHash { content_hash } => {
+            let (snapshot, history) = self.game_storage.history().await?;
+            find_content_hash(&snapshot, &history, &content_hash)
+              .and_then(|index| rollback_to(&snapshot, &history, index))
+          }
+          command => game.perform_gm_command_at(command, now_secs()),
+        };
         self.change_game(changed_game).await
       }
-      (_, MovementOptions { scene_id, creature_id }) => {
+      (_, MovementOptions { scene_id, creature_id, compact }) => {
         let options = game.get_movement_options(scene_id, creature_id)?;
-        Ok(serde_json::to_value(options)?)
+        movement_options_value(options, compact)
       }
-      (_, CombatMovementOptions) => {
+      (_, CombatMovementOptions { compact }) => {
         let options = game.get_combat()?.current_movement_options()?;
-        Ok(serde_json::to_value(options)?)
+        movement_options_value(options, compact)
       }
       (_, TargetOptions { scene_id, creature_id, ability_id }) => {
         let options = game.get_target_options(scene_id, creature_id, ability_id)?;
@@ -210,7 +264,45 @@ impl GameSession {
         let result = game.preview_volume_targets(scene, creature_id, ability_id, point)?;
         Ok(serde_json::to_value(result)?)
       }
+      (_, PreviewAbility { scene_id, creature_id, ability_id, target }) => {
+        let scene = game.get_scene(scene_id)?;
+        let result = game.preview_ability(scene, creature_id, ability_id, target)?;
+        Ok(serde_json::to_value(result)?)
+      }
+      (_, CreaturesAndTerrainInVolume { scene_id, point, volume }) => {
+        let scene = game.get_scene(scene_id)?;
+        let result = game.creatures_and_terrain_in_volume(scene, point, volume)?;
+        Ok(serde_json::to_value(result)?)
+      }
+      (_, LastTurnSummary { creature_id }) => {
+        let (_, history) = self.game_storage.history().await?;
+        Ok(serde_json::to_value(game.last_turn_summary(&history, creature_id)?)?)
+      }
+      (_, CombatSummary) => Ok(serde_json::to_value(game.get_combat()?.summary()?)?),
+      (_, CombatTimeline) => Ok(serde_json::to_value(self.game_storage.combat_timeline())?),
+      (_, SceneCreatures { scene_id }) => {
+        Ok(serde_json::to_value(game.scene_creatures(scene_id)?)?)
+      }
+      (_, SceneRenderState { scene_id }) => {
+        let player_id = match self.ws_user.role {
+          Role::GM => None,
+          Role::Player => Some(&self.ws_user.player_id),
+        };
+        Ok(serde_json::to_value(game.scene_render_state(scene_id, player_id)?)?)
+      }
+      (_, CellInfo { scene_id, point }) => {
+        Ok(serde_json::to_value(game.cell_info(scene_id, point)?)?)
+      }
+      (_, ListAbilities) => Ok(serde_json::to_value(game.list_abilities())?),
+      (_, ListCreatures { offset, limit, sort }) => {
+        Ok(serde_json::to_value(game.list_creatures_paged(offset, limit, sort))?)
+      }
 
+      (Role::GM, GMIntegrityCheck) => Ok(serde_json::to_value(game.integrity_check())?),
+      (Role::GM, GMGameAtIndex { index }) => {
+        let historical = self.game_storage.load_game_at(index).await?;
+        Ok(serde_json::to_value(RPIGame(&historical).serialize_game()?)?)
+      }
       (Role::GM, GMGenerateInvitation) => {
         let invitation_id = self.game_storage.create_invitation().await?;
         Ok(serde_json::to_value(invitation_id)?)
@@ -243,17 +335,43 @@ impl GameSession {
     }
   }
 
+  /// Deny any pending player commands that the GM didn't get to in time, and carry out any action
+  /// whose reaction window has run out.
+  async fn expire_timeouts(&self) -> anyhow::Result<()> {
+    let changed_game = self.game_storage.game().expire_pending_commands(now_secs())?;
+    if !changed_game.logs.is_empty() {
+      self.change_game(Ok(changed_game)).await?;
+    }
+    expire_reaction_window(&self.game_storage, &self.sessions).await
+  }
+
+  /// If the game is holding an action for reactions, set a timer to carry it out when the window
+  /// runs out. This replaces any timer we set before.
+  fn schedule_reaction_timeout(&self, game: &Game) {
+    let delay_ms = match reaction_timeout_ms(game, now_secs()) {
+      Some(delay_ms) => delay_ms,
+      None => return,
+    };
+    let game_storage = self.game_storage.clone();
+    let sessions = self.sessions.clone();
+    let timer = Timeout::new(delay_ms, move || {
+      wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = expire_reaction_window(&game_storage, &sessions).await {
+          error!(event = "reaction-timeout-error", ?e);
+        }
+      });
+    });
+    self.reaction_timeout.replace(Some(timer));
+  }
+
   async fn change_game(
     &self, changed_game: Result<ChangedGame, GameError>,
   ) -> anyhow::Result<serde_json::Value> {
     let changed_game = changed_game.map_err(|e| format!("{e:?}"));
     let result = match changed_game {
       Ok(changed_game) => {
-        let logs_with_indices = self.game_storage.store_game(changed_game.clone()).await?;
-        let rpi_game = RPIGame(&changed_game.game);
-        let game = rpi_game.serialize_game()?;
-        self
-          .broadcast(&json!({"t": "refresh_game", "game": game, "logs": logs_with_indices}))?;
+        store_and_broadcast(&self.game_storage, &self.sessions, &changed_game).await?;
+        self.schedule_reaction_timeout(&changed_game.game);
         Ok(changed_game.logs)
       }
       Err(e) => Err(format!("{e:?}")),
@@ -261,31 +379,221 @@ impl GameSession {
     Ok(serde_json::to_value(result)?)
   }
 
-  fn broadcast<T: Serialize>(&self, value: &T) -> anyhow::Result<()> {
-    let s = serde_json::to_string::<T>(value)?;
-    let mut sessions = self.sessions.borrow_mut();
-    info!(event = "broadcast", num_clients = sessions.len());
-    sessions.retain_mut(|socket| match socket.send_with_str(s.clone()) {
-      Ok(_) => true,
-      Err(e) => {
-        error!(event = "broadcast-error", ?e);
-        false
-      }
-    });
-    Ok(())
+  fn send_with<T: Serialize>(&self, codec: Codec, value: &T) -> anyhow::Result<()> {
+    let result = match codec {
+      Codec::Json => self.socket.send_with_str(serde_json::to_string::<T>(value)?),
+      Codec::MessagePack => self.socket.send_with_bytes(rmp_serde::to_vec_named(value)?),
+    };
+    result.map_err(|e| anyhow!(format!("{e:?}")))
   }
+}
 
-  fn send<T: Serialize>(&self, value: &T) -> anyhow::Result<()> {
-    let s = serde_json::to_string::<T>(value)?;
-    self.socket.send_with_str(s).map_err(|e| anyhow!(format!("{e:?}")))
+/// How a websocket frame is encoded. Text frames are JSON and binary frames are MessagePack; a
+/// response is always sent with the same codec as its request. Broadcasts to all sessions (e.g.
+/// `refresh_game`) are always JSON text frames.
+#[derive(Clone, Copy, Debug)]
+enum Codec {
+  Json,
+  MessagePack,
+}
+
+impl Codec {
+  fn decode<T: DeserializeOwned>(self, data: &[u8]) -> anyhow::Result<T> {
+    Ok(match self {
+      Codec::Json => serde_json::from_slice(data)?,
+      Codec::MessagePack => rmp_serde::from_slice(data)?,
+    })
   }
 }
 
-fn mk_timeout(socket: WebSocket, ws_user: WSUser) -> Timeout {
-  Timeout::new(IDLE_TIMEOUT * 1000, move || {
+/// Store a changed game and send it to every session.
+async fn store_and_broadcast(
+  game_storage: &GameStorage, sessions: &Sessions, changed_game: &ChangedGame,
+) -> anyhow::Result<()> {
+  let logs_with_indices = game_storage.store_game(changed_game.clone()).await?;
+  let rpi_game = RPIGame(&changed_game.game);
+  let game = rpi_game.serialize_game()?;
+  broadcast(
+    sessions,
+    &json!({
+      "t": "refresh_game",
+      "game": game,
+      "logs": logs_with_indices,
+      "content_hash": content_hash(&changed_game.game)?,
+      "movement": changed_game.movement,
+    }),
+  )
+}
+
+fn broadcast<T: Serialize>(sessions: &Sessions, value: &T) -> anyhow::Result<()> {
+  let s = serde_json::to_string::<T>(value)?;
+  let mut sessions = sessions.borrow_mut();
+  info!(event = "broadcast", num_clients = sessions.len());
+  sessions.retain_mut(|socket| match socket.send_with_str(s.clone()) {
+    Ok(_) => true,
+    Err(e) => {
+      error!(event = "broadcast-error", ?e);
+      false
+    }
+  });
+  Ok(())
+}
+
+/// Carry out the action in the game's reaction window if the window has run out.
+async fn expire_reaction_window(
+  game_storage: &GameStorage, sessions: &Sessions,
+) -> anyhow::Result<()> {
+  let changed_game = game_storage.game().expire_reaction_window(now_secs())?;
+  if !changed_game.logs.is_empty() {
+    store_and_broadcast(game_storage, sessions, &changed_game).await?;
+  }
+  Ok(())
+}
+
+/// How many milliseconds after `now` the game's reaction window runs out, or None if the game isn't
+/// holding an action for reactions. A window that has already run out is due right away, as is one
+/// the GM stopped waiting on by turning reaction windows off.
+fn reaction_timeout_ms(game: &Game, now: u64) -> Option<u32> {
+  let deadline = match (&game.reaction_window, game.settings.reaction_window) {
+    (Some(window), Some(timeout)) => window.opened_at.saturating_add(u64::from(timeout)),
+    (Some(_), None) => now,
+    (None, _) => return None,
+  };
+  let delay_ms = deadline.saturating_sub(now).saturating_mul(1000);
+  Some(u32::try_from(delay_ms).unwrap_or(u32::MAX))
+}
+
+/// Reject admin commands like `GMCommand::ApplyCreatureLog` unless the server has enabled them.
+/// Everything else is left for `handle_request` to authorize by role.
+fn check_admin_command(
+  role: Role, request: &RPIGameRequest, config: SessionConfig,
+) -> anyhow::Result<()> {
+  match (role, request) {
+    (Role::GM, RPIGameRequest::GMCommand { command: GMCommand::ApplyCreatureLog { .. } })
+      if !config.admin_commands =>
+    {
+      Err(anyhow!("Admin commands aren't enabled on this server."))
+    }
+    _ => Ok(()),
+  }
+}
+
+/// Hex-encode the game's content hash; a u64 doesn't survive a trip through a JS number.
+fn content_hash(game: &Game) -> anyhow::Result<String> {
+  Ok(content_hash_hex(game)?)
+}
+
+fn movement_options_value(
+  options: Vec<Point3>, compact: bool,
+) -> anyhow::Result<serde_json::Value> {
+  if compact {
+    Ok(serde_json::to_value(CompactPoints::from_points(&options))?)
+  } else {
+    Ok(serde_json::to_value(options)?)
+  }
+}
+
+fn mk_timeout(socket: WebSocket, ws_user: WSUser, idle_timeout: u32) -> Timeout {
+  Timeout::new(idle_timeout * 1000, move || {
     info!(event = "idle-close", ?ws_user);
     if let Err(e) = socket.close(Some(4000), Some("idle timeout")) {
       error!(event = "timeout-close-error", ?e);
     }
   })
 }
+
+fn now_secs() -> u64 { Date::now().as_millis() / 1000 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use arpeggio::types::{
+    AbilityID, CreatureID, CreatureLog, DecidedTarget, ReactionWindow, SceneID, HP,
+  };
+
+  #[test]
+  fn messagepack_round_trip() {
+    // A client encodes its request the same way the worker encodes responses.
+    let request = json!({
+      "id": "req-1",
+      "request": {"t": "GMGameAtIndex", "index": {"game_idx": 2, "log_idx": 5}},
+    });
+    let data = rmp_serde::to_vec_named(&request).unwrap();
+    let decoded: WSRequest = Codec::MessagePack.decode(&data).unwrap();
+    assert_eq!(decoded.id, "req-1");
+    assert!(matches!(
+      decoded.request,
+      RPIGameRequest::GMGameAtIndex { index } if index == GameIndex { game_idx: 2, log_idx: 5 }
+    ));
+
+    let response = json!({"id": "req-1", "payload": {"logs": [1, 2, 3], "name": "Test"}});
+    let data = rmp_serde::to_vec_named(&response).unwrap();
+    assert_eq!(Codec::MessagePack.decode::<serde_json::Value>(&data).unwrap(), response);
+    // JSON frames still decode the same requests.
+    let decoded: WSRequest = Codec::Json.decode(request.to_string().as_bytes()).unwrap();
+    assert_eq!(decoded.id, "req-1");
+  }
+
+  fn t_config(admin_commands: bool) -> SessionConfig {
+    SessionConfig { idle_timeout: DEFAULT_IDLE_TIMEOUT, admin_commands }
+  }
+
+  fn t_admin_request() -> RPIGameRequest {
+    let log = CreatureLog::Damage { hp: HP(3), rolls: vec![3] };
+    let command = GMCommand::ApplyCreatureLog { creature_id: CreatureID::gen(), log };
+    RPIGameRequest::GMCommand { command }
+  }
+
+  #[test]
+  fn admin_commands_need_config() {
+    let request = t_admin_request();
+    assert!(check_admin_command(Role::GM, &request, t_config(false)).is_err());
+    assert!(check_admin_command(Role::GM, &request, t_config(true)).is_ok());
+  }
+
+  #[test]
+  fn other_commands_ignore_admin_config() {
+    let command = GMCommand::StopCombat { preserve_initiative: false };
+    let request = RPIGameRequest::GMCommand { command };
+    assert!(check_admin_command(Role::GM, &request, t_config(false)).is_ok());
+    assert!(check_admin_command(Role::GM, &RPIGameRequest::GMGetGame, t_config(false)).is_ok());
+  }
+
+  fn t_reaction_game(opened_at: u64, timeout: Option<u32>) -> Game {
+    let mut game = Game::default();
+    game.settings.reaction_window = timeout;
+    game.reaction_window = Some(ReactionWindow {
+      scene_id: SceneID::gen(),
+      creature_id: CreatureID::gen(),
+      ability_id: AbilityID::gen(),
+      target: DecidedTarget::Actor,
+      in_combat: false,
+      reactors: vec![CreatureID::gen()],
+      opened_at,
+    });
+    game
+  }
+
+  #[test]
+  fn reaction_timeout_waits_for_open_windows() {
+    let game = t_reaction_game(100, Some(5));
+    assert_eq!(reaction_timeout_ms(&game, 100), Some(5000));
+    assert_eq!(reaction_timeout_ms(&game, 103), Some(2000));
+  }
+
+  #[test]
+  fn reaction_timeout_is_due_for_expired_windows() {
+    let game = t_reaction_game(100, Some(5));
+    assert_eq!(reaction_timeout_ms(&game, 105), Some(0));
+    assert_eq!(reaction_timeout_ms(&game, 500), Some(0));
+    // Reaction windows were turned off while this one was open.
+    assert_eq!(reaction_timeout_ms(&t_reaction_game(100, None), 100), Some(0));
+  }
+
+  #[test]
+  fn no_reaction_timeout_without_a_window() {
+    let mut game = Game::default();
+    game.settings.reaction_window = Some(5);
+    assert_eq!(reaction_timeout_ms(&game, 100), None);
+  }
+}